@@ -0,0 +1,152 @@
+//! Times searching a large file for a fixed string, comparing:
+//!
+//! - reading it through a buffer and mapping it into memory
+//! - searching the whole text, each line, and the text as it streams in
+//! - splitting it into records, buffered and mapped
+//! - matching without case by lowercasing each line and by folding, on lines
+//!   that are all ASCII and on lines that are not
+//! - the fixed string finder and the standard library's `str::matches`
+//!
+//! Run with `cargo bench`.
+
+use minigrep::memmem::Finder;
+use minigrep::mmap::Mmap;
+use minigrep::MmapChoice;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::time::{Duration, Instant};
+
+const LINES: usize = 1_000_000;
+const ROUNDS: u32 = 5;
+
+fn main() {
+  let path = std::env::temp_dir().join(format!("minigrep-bench-{}", std::process::id()));
+  let mut file = File::create(&path).unwrap();
+  for i in 0..LINES {
+    writeln!(
+      file,
+      "line {} of a rather large file, safe, fast, productive.",
+      i
+    )
+    .unwrap();
+  }
+  drop(file);
+
+  let buffered = time(|| {
+    let contents = fs::read_to_string(&path).unwrap();
+    minigrep::search("99999", &contents).len()
+  });
+  let mapped = time(|| {
+    let file = File::open(&path).unwrap();
+    let map = unsafe { Mmap::map(&file) }.unwrap();
+    let contents = std::str::from_utf8(&map).unwrap();
+    minigrep::search("99999", contents).len()
+  });
+  let contents = fs::read_to_string(&path).unwrap();
+  let per_line = time(|| {
+    contents
+      .lines()
+      .filter(|line| line.contains("99999"))
+      .count()
+  });
+  let whole = time(|| minigrep::search("99999", &contents).len());
+  let config = minigrep::Config::builder()
+    .query("99999")
+    .path(&path.display().to_string())
+    .count(true)
+    .build()
+    .unwrap();
+  let streamed = time(|| {
+    let reader = BufReader::new(File::open(&path).unwrap());
+    minigrep::search_input(&config, reader, None, &mut io::sink()).unwrap()
+  });
+  // Every record is read when none can be skipped, from the map or the
+  // read buffer alike
+  let records = |mmap| {
+    minigrep::Config::builder()
+      .query("99999")
+      .path(&path.display().to_string())
+      .invert_match(true)
+      .count(true)
+      .mmap(mmap)
+      .build()
+      .unwrap()
+  };
+  let all_buffered = records(MmapChoice::Never);
+  let all_mapped = records(MmapChoice::Always);
+  let records_buffered = time(|| {
+    let summary = minigrep::run_with(&all_buffered, &mut io::sink()).unwrap();
+    summary.stats.matched_lines
+  });
+  let records_mapped = time(|| {
+    let summary = minigrep::run_with(&all_mapped, &mut io::sink()).unwrap();
+    summary.stats.matched_lines
+  });
+  let lowercased = time(|| {
+    contents
+      .lines()
+      .filter(|line| line.to_lowercase().contains("safe, fast, productive!"))
+      .count()
+  });
+  let folded = time(|| minigrep::count_case_insensitive("SAFE, FAST, PRODUCTIVE!", &contents));
+  // Lines that are not all ASCII are folded into a copy, and fully rather
+  // than only lowercased
+  let accented = contents.replace("safe", "sáfe");
+  let lowercased_accented = time(|| {
+    accented
+      .lines()
+      .filter(|line| line.to_lowercase().contains("sáfe, fast, productive!"))
+      .count()
+  });
+  let folded_accented =
+    time(|| minigrep::count_case_insensitive("SÁFE, FAST, PRODUCTIVE!", &accented));
+  let finder = Finder::new(b"99999");
+  let memmem = time(|| finder.find_iter(contents.as_bytes()).count());
+  let std = time(|| contents.matches("99999").count());
+  fs::remove_file(&path).unwrap();
+
+  assert_eq!(
+    buffered.1, mapped.1,
+    "both backends must find the same lines"
+  );
+  println!("read buffer: {:?} per search", buffered.0);
+  println!("memory map:  {:?} per search", mapped.0);
+  assert_eq!(per_line.1, whole.1, "both scans must find the same lines");
+  assert_eq!(
+    per_line.1, streamed.1,
+    "both scans must find the same lines"
+  );
+  println!("each line:   {:?} per search", per_line.0);
+  println!("whole text:  {:?} per search", whole.0);
+  println!("streamed:    {:?} per search", streamed.0);
+  assert_eq!(
+    records_buffered.1, records_mapped.1,
+    "both backends must read the same records"
+  );
+  println!("records, read buffer: {:?} per search", records_buffered.0);
+  println!("records, memory map:  {:?} per search", records_mapped.0);
+  assert_eq!(lowercased.1, folded.1, "both must ignore case the same way");
+  println!("lowercased:  {:?} per search", lowercased.0);
+  println!("folded:      {:?} per search", folded.0);
+  assert_eq!(
+    lowercased_accented.1, folded_accented.1,
+    "both must ignore case the same way"
+  );
+  println!(
+    "lowercased, not ASCII: {:?} per search",
+    lowercased_accented.0
+  );
+  println!("folded, not ASCII:     {:?} per search", folded_accented.0);
+  assert_eq!(memmem.1, std.1, "both finders must find the same matches");
+  println!("memmem:      {:?} per scan", memmem.0);
+  println!("str::matches {:?} per scan", std.0);
+}
+
+fn time<F: Fn() -> usize>(search: F) -> (Duration, usize) {
+  let found = search();
+  let start = Instant::now();
+  for _ in 0..ROUNDS {
+    assert_eq!(found, search());
+  }
+  (start.elapsed() / ROUNDS, found)
+}
@@ -0,0 +1,67 @@
+/* The C interface to minigrep's matcher, from the `ffi` feature. */
+
+#ifndef MINIGREP_H
+#define MINIGREP_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define MINIGREP_OK 0
+/* The query or the text is not UTF-8. */
+#define MINIGREP_INVALID_UTF8 1
+/* The query is not a valid regular expression. */
+#define MINIGREP_INVALID_PATTERN 2
+#define MINIGREP_NULL_ARGUMENT 3
+
+/* How to search, where all false is what minigrep does by default. */
+typedef struct minigrep_options {
+  bool ignore_case;
+  bool regex;
+  bool word_regexp;
+  bool invert_match;
+} minigrep_options;
+
+/* Where a match is in its line, in bytes. */
+typedef struct minigrep_span {
+  size_t start;
+  size_t end;
+} minigrep_span;
+
+/* A matching line, without its line ending. `line` points into the searched
+ * text, which must outlive the results. */
+typedef struct minigrep_match {
+  const char *line;
+  size_t line_len;
+  size_t line_number;
+  /* Where the line starts within the searched text. */
+  size_t byte_offset;
+  const minigrep_span *spans;
+  size_t spans_len;
+} minigrep_match;
+
+typedef struct minigrep_results {
+  minigrep_match *matches;
+  size_t len;
+} minigrep_results;
+
+/* Searches the `contents_len` bytes at `contents` for the query, writing the
+ * matching lines to `results`. `options` may be NULL for the defaults.
+ * Returns MINIGREP_OK, or why nothing was searched, in which case `results`
+ * is left empty. */
+int minigrep_search(const char *query, size_t query_len, const char *contents,
+                    size_t contents_len, const minigrep_options *options,
+                    minigrep_results *results);
+
+/* Frees what minigrep_search wrote to `results` and empties it, so that
+ * freeing it twice is harmless. */
+void minigrep_free_results(minigrep_results *results);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,128 @@
+//! Finding any of several fixed strings in a single pass.
+//!
+//! The strings are put in a trie whose states also link to the longest
+//! suffix of themselves that is in the trie, so that the haystack can be
+//! read one byte at a time without ever going back, whatever the number of
+//! strings.
+
+use std::ops::Range;
+
+const ROOT: usize = 0;
+
+/// An Aho-Corasick automaton over a set of fixed strings.
+#[derive(Debug, Clone)]
+pub struct AhoCorasick {
+  states: Vec<State>,
+  // The length of each string, to find where a match starts from its end
+  lengths: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+struct State {
+  // Sorted by byte
+  next: Vec<(u8, usize)>,
+  fail: usize,
+  // The strings that end in this state, including those ending in the states
+  // it fails to
+  matches: Vec<usize>,
+}
+
+impl State {
+  fn next(&self, byte: u8) -> Option<usize> {
+    self
+      .next
+      .binary_search_by_key(&byte, |&(b, _)| b)
+      .ok()
+      .map(|i| self.next[i].1)
+  }
+}
+
+impl AhoCorasick {
+  pub fn new<S: AsRef<[u8]>>(strings: &[S]) -> AhoCorasick {
+    let mut states = vec![State::default()];
+    for (i, string) in strings.iter().enumerate() {
+      let mut current = ROOT;
+      for &byte in string.as_ref() {
+        current = match states[current].next(byte) {
+          Some(next) => next,
+          None => {
+            let next = states.len();
+            states.push(State::default());
+            let edges = &mut states[current].next;
+            let at = edges.partition_point(|&(b, _)| b < byte);
+            edges.insert(at, (byte, next));
+            next
+          }
+        };
+      }
+      states[current].matches.push(i);
+    }
+
+    // Breadth first, so that the state a state fails to is always done
+    let mut queue = std::collections::VecDeque::new();
+    queue.push_back(ROOT);
+    while let Some(current) = queue.pop_front() {
+      for i in 0..states[current].next.len() {
+        let (byte, next) = states[current].next[i];
+        let fail = if current == ROOT {
+          ROOT
+        } else {
+          let mut fail = states[current].fail;
+          loop {
+            if let Some(target) = states[fail].next(byte) {
+              break target;
+            }
+            if fail == ROOT {
+              break ROOT;
+            }
+            fail = states[fail].fail;
+          }
+        };
+        states[next].fail = fail;
+        let inherited = states[fail].matches.clone();
+        states[next].matches.extend(inherited);
+        queue.push_back(next);
+      }
+    }
+
+    AhoCorasick {
+      states,
+      lengths: strings.iter().map(|s| s.as_ref().len()).collect(),
+    }
+  }
+
+  /// Calls `found` with the index of the string and where it is, for every
+  /// occurrence of every string in `haystack`, overlapping ones included.
+  /// Occurrences come in the order they end in.
+  pub fn find_overlapping<F: FnMut(usize, Range<usize>)>(&self, haystack: &[u8], mut found: F) {
+    let mut current = ROOT;
+    for (i, &byte) in haystack.iter().enumerate() {
+      current = loop {
+        if let Some(next) = self.states[current].next(byte) {
+          break next;
+        }
+        if current == ROOT {
+          break ROOT;
+        }
+        current = self.states[current].fail;
+      };
+      for &string in &self.states[current].matches {
+        found(string, i + 1 - self.lengths[string]..i + 1);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_every_occurrence() {
+    let set = AhoCorasick::new(&["he", "she", "his", "hers"]);
+    let mut found = Vec::new();
+    set.find_overlapping(b"ushers and his", |i, span| found.push((i, span)));
+
+    assert_eq!(vec![(1, 1..4), (0, 2..4), (3, 2..6), (2, 11..14)], found);
+  }
+}
@@ -0,0 +1,344 @@
+//! Reading the files inside zip and tar archives, as searched by
+//! `--search-archives`.
+//!
+//! Tar archives may be gzip compressed. Zip entries may be stored or
+//! deflated; entries that are encrypted or use any other compression method
+//! are skipped, as are zip64 archives.
+
+use crate::gzip::GzDecoder;
+use std::convert::TryFrom;
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+use std::path::Path;
+
+const BLOCK_LEN: u64 = 512;
+const ZIP_LOCAL_HEADER: u32 = 0x0403_4b50;
+const ZIP_CENTRAL_HEADER: u32 = 0x0201_4b50;
+const ZIP_END_OF_DIRECTORY: u32 = 0x0605_4b50;
+// The end of directory record and the longest comment it can have
+const ZIP_MAX_TAIL: u64 = 22 + 0xffff;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Archive {
+  Zip,
+  Tar,
+  TarGz,
+}
+
+impl Archive {
+  /// Recognizes an archive by its extension.
+  pub fn for_path(path: &Path) -> Option<Archive> {
+    let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+    if name.ends_with(".zip") {
+      Some(Archive::Zip)
+    } else if name.ends_with(".tar") {
+      Some(Archive::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+      Some(Archive::TarGz)
+    } else {
+      None
+    }
+  }
+}
+
+/// Calls `visit` with the path and contents of each regular file in the
+/// archive, in the order they are stored.
+pub fn for_each_entry<R, F>(archive: Archive, reader: R, mut visit: F) -> io::Result<()>
+where
+  R: BufRead + Seek,
+  F: FnMut(&str, &mut dyn BufRead) -> io::Result<()>,
+{
+  match archive {
+    Archive::Zip => zip_entries(reader, &mut visit),
+    Archive::Tar => tar_entries(reader, &mut visit),
+    Archive::TarGz => tar_entries(GzDecoder::new(reader), &mut visit),
+  }
+}
+
+fn tar_entries<R, F>(mut reader: R, visit: &mut F) -> io::Result<()>
+where
+  R: BufRead,
+  F: FnMut(&str, &mut dyn BufRead) -> io::Result<()>,
+{
+  let mut header = [0; BLOCK_LEN as usize];
+  // Set by a GNU or pax header for the entry that follows it
+  let mut long_name: Option<String> = None;
+  loop {
+    if !read_block(&mut reader, &mut header)? || header.iter().all(|&b| b == 0) {
+      return Ok(());
+    }
+    let size = parse_octal(&header[124..136])?;
+    let mut data = (&mut reader).take(size);
+    match header[156] {
+      b'L' => long_name = Some(read_name(&mut data)?),
+      b'x' => long_name = pax_path(&mut data)?.or(long_name),
+      b'0' | b'\0' | b'7' => {
+        let name = long_name.take().unwrap_or_else(|| header_name(&header));
+        visit(&name, &mut data)?;
+      }
+      // Directories, links and the like
+      _ => long_name = None,
+    }
+    // Skip whatever the visitor left and the padding to the next block
+    let padding = (BLOCK_LEN - size % BLOCK_LEN) % BLOCK_LEN;
+    io::copy(&mut data, &mut io::sink())?;
+    io::copy(&mut (&mut reader).take(padding), &mut io::sink())?;
+  }
+}
+
+// Returns `false` at the end of the input
+fn read_block<R: Read>(reader: &mut R, block: &mut [u8]) -> io::Result<bool> {
+  let mut filled = 0;
+  while filled < block.len() {
+    match reader.read(&mut block[filled..])? {
+      0 if filled == 0 => return Ok(false),
+      0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+      n => filled += n,
+    }
+  }
+  Ok(true)
+}
+
+fn parse_octal(field: &[u8]) -> io::Result<u64> {
+  let digits = String::from_utf8_lossy(field);
+  let digits = digits.trim_matches(|c: char| c == '\0' || c == ' ');
+  if digits.is_empty() {
+    return Ok(0);
+  }
+  u64::from_str_radix(digits, 8).map_err(|_| invalid("invalid tar header"))
+}
+
+fn nul_terminated(bytes: &[u8]) -> String {
+  let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+  String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+// Names longer than 100 bytes are split between a prefix and the name in
+// the ustar format
+fn header_name(header: &[u8]) -> String {
+  let name = nul_terminated(&header[..100]);
+  let prefix = nul_terminated(&header[345..500]);
+  if &header[257..262] == b"ustar" && !prefix.is_empty() {
+    format!("{}/{}", prefix, name)
+  } else {
+    name
+  }
+}
+
+fn read_name<R: Read>(data: &mut R) -> io::Result<String> {
+  let mut bytes = Vec::new();
+  data.read_to_end(&mut bytes)?;
+  Ok(nul_terminated(&bytes))
+}
+
+// Pax headers are records of the form `<length> <key>=<value>\n`
+fn pax_path<R: Read>(data: &mut R) -> io::Result<Option<String>> {
+  let mut records = String::new();
+  data.read_to_string(&mut records)?;
+  Ok(records.lines().find_map(|record| {
+    let (_, pair) = record.split_once(' ')?;
+    pair.strip_prefix("path=").map(String::from)
+  }))
+}
+
+struct ZipEntry {
+  name: String,
+  method: u16,
+  encrypted: bool,
+  compressed_size: u64,
+  header_offset: u64,
+}
+
+fn zip_entries<R, F>(mut reader: R, visit: &mut F) -> io::Result<()>
+where
+  R: BufRead + Seek,
+  F: FnMut(&str, &mut dyn BufRead) -> io::Result<()>,
+{
+  for entry in zip_directory(&mut reader)? {
+    if entry.name.ends_with('/') || entry.encrypted || !matches!(entry.method, 0 | 8) {
+      continue;
+    }
+    reader.seek(SeekFrom::Start(entry.header_offset))?;
+    let mut header = [0; 30];
+    reader.read_exact(&mut header)?;
+    if u32_at(&header, 0) != ZIP_LOCAL_HEADER {
+      return Err(invalid("corrupt zip entry"));
+    }
+    let skip = u16_at(&header, 26) as i64 + u16_at(&header, 28) as i64;
+    reader.seek(SeekFrom::Current(skip))?;
+    let data = (&mut reader).take(entry.compressed_size);
+    if entry.method == 0 {
+      visit(&entry.name, &mut { data })?;
+    } else {
+      visit(&entry.name, &mut GzDecoder::raw(data))?;
+    }
+  }
+  Ok(())
+}
+
+// Reads the central directory at the end of the archive
+fn zip_directory<R: Read + Seek>(reader: &mut R) -> io::Result<Vec<ZipEntry>> {
+  let len = reader.seek(SeekFrom::End(0))?;
+  let tail_len = len.min(ZIP_MAX_TAIL);
+  reader.seek(SeekFrom::Start(len - tail_len))?;
+  let mut tail = Vec::with_capacity(tail_len as usize);
+  reader.take(tail_len).read_to_end(&mut tail)?;
+  let end = (0..tail.len().saturating_sub(21))
+    .rev()
+    .find(|&i| u32_at(&tail, i) == ZIP_END_OF_DIRECTORY)
+    .ok_or_else(|| invalid("not a zip archive"))?;
+  let count = u16_at(&tail, end + 10);
+  let size = u32_at(&tail, end + 12);
+  let offset = u32_at(&tail, end + 16);
+  if count == 0xffff || size == u32::MAX || offset == u32::MAX {
+    return Err(invalid("zip64 archives are not supported"));
+  }
+
+  reader.seek(SeekFrom::Start(offset as u64))?;
+  let mut directory = vec![0; size as usize];
+  reader.read_exact(&mut directory)?;
+  let mut entries = Vec::with_capacity(count as usize);
+  let mut at = 0;
+  for _ in 0..count {
+    if directory.len() < at + 46 || u32_at(&directory, at) != ZIP_CENTRAL_HEADER {
+      return Err(invalid("corrupt zip directory"));
+    }
+    let name_len = u16_at(&directory, at + 28) as usize;
+    let extra_len = u16_at(&directory, at + 30) as usize;
+    let comment_len = u16_at(&directory, at + 32) as usize;
+    let name = directory
+      .get(at + 46..at + 46 + name_len)
+      .ok_or_else(|| invalid("corrupt zip directory"))?;
+    entries.push(ZipEntry {
+      name: String::from_utf8_lossy(name).into_owned(),
+      method: u16_at(&directory, at + 10),
+      encrypted: u16_at(&directory, at + 8) & 1 != 0,
+      compressed_size: u32_at(&directory, at + 20) as u64,
+      header_offset: u32_at(&directory, at + 42) as u64,
+    });
+    at += 46 + name_len + extra_len + comment_len;
+  }
+  Ok(entries)
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+  u16::from_le_bytes(<[u8; 2]>::try_from(&bytes[at..at + 2]).unwrap())
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+  u32::from_le_bytes(<[u8; 4]>::try_from(&bytes[at..at + 4]).unwrap())
+}
+
+fn invalid(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Cursor;
+
+  fn tar_header(name: &str, size: usize, kind: u8) -> Vec<u8> {
+    let mut header = vec![0; BLOCK_LEN as usize];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    let size = format!("{:011o}", size);
+    header[124..135].copy_from_slice(size.as_bytes());
+    header[156] = kind;
+    header
+  }
+
+  fn tar(entries: &[(&str, &str)]) -> Vec<u8> {
+    let mut tar = Vec::new();
+    for (name, contents) in entries {
+      tar.extend(tar_header(name, contents.len(), b'0'));
+      tar.extend(contents.as_bytes());
+      tar.resize(tar.len().div_ceil(512) * 512, 0);
+    }
+    tar.resize(tar.len() + 1024, 0);
+    tar
+  }
+
+  // A zip archive holding `entries` without compression
+  fn zip(entries: &[(&str, &str)]) -> Vec<u8> {
+    let mut zip = Vec::new();
+    let mut directory = Vec::new();
+    for (name, contents) in entries {
+      let offset = zip.len() as u32;
+      let len = (contents.len() as u32).to_le_bytes();
+      let name_len = (name.len() as u16).to_le_bytes();
+      zip.extend(&ZIP_LOCAL_HEADER.to_le_bytes());
+      zip.extend(&[0; 14]);
+      zip.extend(&len);
+      zip.extend(&len);
+      zip.extend(&name_len);
+      zip.extend(&[0, 0]);
+      zip.extend(name.as_bytes());
+      zip.extend(contents.as_bytes());
+
+      directory.extend(&ZIP_CENTRAL_HEADER.to_le_bytes());
+      directory.extend(&[0; 16]);
+      directory.extend(&len);
+      directory.extend(&len);
+      directory.extend(&name_len);
+      directory.extend(&[0; 12]);
+      directory.extend(&offset.to_le_bytes());
+      directory.extend(name.as_bytes());
+    }
+    let offset = (zip.len() as u32).to_le_bytes();
+    let count = (entries.len() as u16).to_le_bytes();
+    zip.extend(&directory);
+    zip.extend(&ZIP_END_OF_DIRECTORY.to_le_bytes());
+    zip.extend(&[0; 4]);
+    zip.extend(&count);
+    zip.extend(&count);
+    zip.extend(&(directory.len() as u32).to_le_bytes());
+    zip.extend(&offset);
+    zip.extend(&[0, 0]);
+    zip
+  }
+
+  fn contents(archive: Archive, bytes: Vec<u8>) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    for_each_entry(archive, Cursor::new(bytes), |name, data| {
+      let mut text = String::new();
+      data.read_to_string(&mut text)?;
+      found.push((name.to_string(), text));
+      Ok(())
+    })
+    .unwrap();
+    found
+  }
+
+  #[test]
+  fn reads_tar_entries() {
+    let mut bytes = tar(&[("poem.txt", "a frog\n"), ("src/main.rs", "fn main() {}\n")]);
+    assert_eq!(
+      vec![
+        ("poem.txt".to_string(), "a frog\n".to_string()),
+        ("src/main.rs".to_string(), "fn main() {}\n".to_string())
+      ],
+      contents(Archive::Tar, bytes.clone())
+    );
+
+    // A GNU long name applies to the entry after it
+    let long = "a/".repeat(60) + "frog.txt";
+    let mut named = tar_header("././@LongLink", long.len() + 1, b'L');
+    named.extend(long.as_bytes());
+    named.resize(2 * 512, 0);
+    named.append(&mut bytes);
+    assert_eq!(long, contents(Archive::Tar, named)[0].0);
+  }
+
+  #[test]
+  fn reads_zip_entries() {
+    let bytes = zip(&[("dir/", ""), ("dir/poem.txt", "a frog\n")]);
+    assert_eq!(
+      vec![("dir/poem.txt".to_string(), "a frog\n".to_string())],
+      contents(Archive::Zip, bytes)
+    );
+    assert_eq!(
+      Some(Archive::TarGz),
+      Archive::for_path(Path::new("src.TAR.GZ"))
+    );
+    assert_eq!(None, Archive::for_path(Path::new("notes.gz")));
+  }
+}
@@ -0,0 +1,455 @@
+//! The options minigrep understands, and how command line arguments are
+//! split into them.
+//!
+//! Each option is defined once in `OPTIONS`. Long options may have their
+//! value attached with `=` or given as the next argument, short options may
+//! be bundled as in `-inC2`, and everything after `--` is a query or a file
+//! even when it starts with `-`.
+
+use crate::error::MinigrepError;
+
+/// An option of the command line.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OptionSpec {
+  /// The name after `--`, which is also what the option is known by once
+  /// parsed.
+  pub long: &'static str,
+  pub short: Option<char>,
+  /// Other long names that mean the same, left out of the help.
+  pub aliases: &'static [&'static str],
+  pub value: Value,
+  pub help: &'static str,
+}
+
+/// Whether an option takes a value, and what the value is called in help.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Value {
+  None,
+  /// Attached or the next argument.
+  Required(&'static str),
+  /// Only ever attached with `=`, as in `--color=never`.
+  Optional(&'static str),
+}
+
+const fn flag(long: &'static str, short: Option<char>, help: &'static str) -> OptionSpec {
+  OptionSpec {
+    long,
+    short,
+    aliases: &[],
+    value: Value::None,
+    help,
+  }
+}
+
+const fn valued(
+  long: &'static str,
+  short: Option<char>,
+  value: &'static str,
+  help: &'static str,
+) -> OptionSpec {
+  OptionSpec {
+    long,
+    short,
+    aliases: &[],
+    value: Value::Required(value),
+    help,
+  }
+}
+
+/// Every option, in the order the help lists them.
+#[rustfmt::skip]
+pub static OPTIONS: &[OptionSpec] = &[
+  valued("regexp", Some('e'), "PATTERN", "Search for PATTERN, which may be given several times"),
+  valued("file", Some('f'), "FILE", "Search for each pattern in FILE, one per line"),
+  flag("ignore-case", Some('i'), "Match regardless of case"),
+  flag("smart-case", Some('S'), "Ignore case unless a query has an uppercase letter"),
+  OptionSpec {
+    aliases: &["no-ignore-case"],
+    ..flag("case-sensitive", None, "Match case exactly, which is the default")
+  },
+  valued("case-fold", None, "MODE", "How case is ignored: simple, full or turkic"),
+  flag("ignore-accents", None, "Match letters whatever their accents"),
+  flag("recursive", Some('r'), "Search directories and everything below them"),
+  flag("extended-regexp", Some('E'), "Read queries as regular expressions"),
+  flag("fixed-strings", Some('F'), "Read queries as fixed strings, which is the default"),
+  flag("multiline", Some('U'), "Let matches span several lines"),
+  flag("crlf", None, "Treat \\r\\n as a line ending in regular expressions"),
+  flag("paragraph", None, "Match paragraphs separated by blank lines instead of lines"),
+  valued("record-separator", None, "STRING", "Match records separated by STRING instead of lines"),
+  flag("null-data", None, "Match records separated by NUL bytes instead of lines"),
+  flag("csv", None, "Match the rows of CSV or TSV inputs, past their header"),
+  flag("json-input", None, "Match the string values of JSON or NDJSON inputs"),
+  valued("path", None, "PATH", "Only match --json-input strings under PATH, like items[].name"),
+  flag("json-pointers", None, "Print the JSON pointer of each matching string, not its document"),
+  valued("column", None, "NAME", "Only match in the column of --csv rows with this name or number"),
+  flag("fuzzy", None, "Match the characters of the query in order, with anything between"),
+  flag("sort-by-score", None, "Print fuzzy matches best first"),
+  flag("line-number", Some('n'), "Print the line number of each line"),
+  flag("count", Some('c'), "Print how many lines match in each input"),
+  flag("count-matches", None, "Print how many times the patterns occur in each input"),
+  flag("total", None, "Follow the counts with their sum"),
+  flag("invert-match", Some('v'), "Print the lines that do not match"),
+  flag("word-regexp", Some('w'), "Only match whole words"),
+  flag("line-regexp", Some('x'), "Only match whole lines"),
+  OptionSpec {
+    aliases: &["silent"],
+    ..flag("quiet", Some('q'), "Print nothing and stop at the first match")
+  },
+  flag("no-messages", Some('s'), "Say nothing about files that cannot be read"),
+  flag("files-with-matches", Some('l'), "Print only the names of inputs that match"),
+  flag("files-without-match", Some('L'), "Print only the names of inputs that don't match"),
+  flag("only-matching", Some('o'), "Print only the matching parts of lines"),
+  flag("byte-offset", Some('b'), "Print the byte offset of each line"),
+  flag("with-filename", Some('H'), "Print the file name of each line, even for one file"),
+  flag("no-filename", Some('h'), "Never print file names before lines"),
+  valued("after-context", Some('A'), "N", "Print N lines after each match"),
+  valued("before-context", Some('B'), "N", "Print N lines before each match"),
+  valued("context", Some('C'), "N", "Print N lines before and after each match"),
+  OptionSpec {
+    aliases: &["passthrough"],
+    ..flag("passthru", None, "Print every line, highlighting the ones that match")
+  },
+  valued("context-separator", None, "SEP", "Print SEP between groups of context"),
+  flag("no-context-separator", None, "Print nothing between groups of context"),
+  valued("max-count", Some('m'), "N", "Stop reading an input after N matching lines"),
+  valued("max-columns", Some('M'), "N", "Print at most N characters of a line, around its first match"),
+  flag("max-columns-preview", None, "Print the start of lines cut by --max-columns instead"),
+  flag("trim", None, "Print lines without the whitespace they start with"),
+  valued("max-results", None, "N", "Stop the whole search after N matching lines"),
+  OptionSpec {
+    aliases: &["colour"],
+    value: Value::Optional("WHEN"),
+    ..flag("color", None, "Highlight matches: auto, the default for WHEN, always or never")
+  },
+  valued("colors", None, "PART:STYLE", "Style a part of the output, such as match:red"),
+  valued("hyperlink-format", None, "FORMAT", "Link file names: default, vscode, none or a template"),
+  flag("json", None, "Print a JSON object for each matching line"),
+  flag("json-lines", None, "Print ripgrep's JSON messages for each input and the search"),
+  valued("format", None, "FORMAT", "Print results as text, json, json-lines, csv, tsv or vimgrep"),
+  flag("vimgrep", None, "Print a file:line:column:text line for each match"),
+  valued("output", None, "FILE", "Write the results to FILE"),
+  flag("null", Some('0'), "End file names with a NUL byte"),
+  valued("replace", None, "TEMPLATE", "Print matches replaced by TEMPLATE, where $1 is a group"),
+  OptionSpec {
+    value: Value::Optional("SUFFIX"),
+    ..flag("in-place", None, "Write replacements back, keeping a backup with SUFFIX")
+  },
+  flag("show-pattern", None, "Print which pattern matched each line"),
+  valued("comment-prefix", None, "PREFIX", "Skip lines of pattern files starting with PREFIX"),
+  valued("include", None, "GLOB", "Only search files matching GLOB"),
+  valued("exclude", None, "GLOB", "Skip files and directories matching GLOB"),
+  valued("type", None, "TYPE", "Only search files of TYPE, such as rust"),
+  valued("type-not", None, "TYPE", "Skip files of TYPE"),
+  valued("type-add", None, "NAME:GLOBS", "Define a file type by its globs"),
+  flag("no-ignore", None, "Search files that ignore files of any kind leave out"),
+  flag("ignore", None, "Honor ignore files, which is the default"),
+  flag("no-ignore-vcs", None, "Search files that .gitignore files leave out"),
+  flag("no-ignore-dot", None, "Search files that .ignore and .minigrepignore files leave out"),
+  flag("git", None, "With -r, only search files tracked by Git"),
+  flag("hidden", None, "Search hidden files and directories"),
+  flag("no-hidden", None, "Skip hidden files and directories, which is the default"),
+  flag("follow", None, "Follow symbolic links while searching recursively"),
+  valued("max-depth", None, "N", "Search at most N directories deep"),
+  valued("newer-than", None, "TIME", "Only search files modified since TIME, like 2d or 2024-01-01"),
+  valued("older-than", None, "TIME", "Only search files last modified before TIME"),
+  valued("sort", None, "KEY", "With -r, search files by KEY: path, mtime or size"),
+  valued("sortr", None, "KEY", "Like --sort, in reverse order"),
+  valued("max-filesize", None, "SIZE", "Skip files larger than SIZE, such as 10M"),
+  flag("no-config", None, "Ignore config files"),
+  valued("threads", Some('j'), "N", "Search with N threads, 0 picking one per CPU"),
+  flag("unordered", None, "Print results from files as they are done, not in order"),
+  flag("mmap", None, "Always map files into memory to search them"),
+  flag("no-mmap", None, "Never map files into memory"),
+  OptionSpec {
+    aliases: &["binary"],
+    ..flag("text", Some('a'), "Search binary files as text, escaping what is not printable")
+  },
+  valued("encoding", None, "ENCODING", "Read input as ENCODING, such as utf-16le"),
+  valued("pre", None, "COMMAND", "Search what COMMAND prints for each file"),
+  valued("pre-glob", None, "GLOB", "Only run the --pre command for files matching GLOB"),
+  flag("search-zip", Some('z'), "Search inside gzip compressed files"),
+  flag("search-archives", None, "Search the files inside zip and tar archives"),
+  flag("stats", None, "Print statistics once the search is done"),
+  flag("per-file", None, "With --stats, also print statistics for each file"),
+  flag("verbose", None, "Log which files are searched and skipped, more when repeated"),
+  valued("log-level", None, "LEVEL", "Log up to LEVEL: error, warn, info, debug or trace"),
+  flag("watch", None, "Search files again whenever they change"),
+  flag("tail", None, "Keep reading files as they grow, like tail -F"),
+  flag("tui", None, "Refine the query interactively"),
+  flag("no-pager", None, "Never page the results, even when they fill the terminal"),
+  flag("help", None, "Print this help and exit"),
+  flag("version", Some('V'), "Print the version and exit"),
+];
+
+/// The option called `long`, or one of its aliases.
+pub fn find_long(long: &str) -> Option<&'static OptionSpec> {
+  OPTIONS
+    .iter()
+    .find(|spec| spec.long == long || spec.aliases.contains(&long))
+}
+
+pub fn find_short(short: char) -> Option<&'static OptionSpec> {
+  OPTIONS.iter().find(|spec| spec.short == Some(short))
+}
+
+#[rustfmt::skip]
+const EXAMPLES: &[(&str, &str)] = &[
+  ("frog poem.txt", "Print the lines of poem.txt containing frog"),
+  ("-rin todo src", "Search everything in src for todo in any case, with line numbers"),
+  ("-E -e '^fn \\w+' --type rust .", "Search Rust files for a regular expression"),
+  ("-c error - < log.txt", "Count the lines of standard input containing error"),
+];
+
+/// The usage line shown along with command line errors.
+pub fn usage(programname: &str) -> String {
+  format!(
+    "Usage: {} [OPTION]... <query> [<file>...]\nTry `{} --help` for more information.",
+    programname, programname
+  )
+}
+
+/// What `--help` prints: the usage, every option in `OPTIONS` with its
+/// description, and a few examples.
+pub fn help(programname: &str) -> String {
+  let options: Vec<(String, &str)> = OPTIONS
+    .iter()
+    .map(|spec| {
+      let short = match spec.short {
+        Some(short) => format!("-{}, ", short),
+        None => "    ".to_string(),
+      };
+      let value = match spec.value {
+        Value::None => String::new(),
+        Value::Required(name) => format!(" {}", name),
+        Value::Optional(name) => format!("[={}]", name),
+      };
+      (format!("{}--{}{}", short, spec.long, value), spec.help)
+    })
+    .collect();
+  let width = options
+    .iter()
+    .map(|(names, _)| names.len())
+    .max()
+    .unwrap_or(0);
+
+  let mut help = format!(
+    "Search files for lines matching a query.\n\n\
+     Usage: {0} [OPTION]... <query> [<file>...]\n       \
+     {0} [OPTION]... -e PATTERN... [<file>...]\n       \
+     {0} completions bash|zsh|fish|powershell\n\n\
+     With no file, or when a file is -, standard input is searched. The last\n\
+     form, with nothing else after it, prints a tab completion script for the\n\
+     shell.\n\n\
+     Queries are fixed strings unless -E is given. Unlike GNU grep, there are\n\
+     no basic regular expressions, so `a.c` only matches itself by default.\n\n\
+     Options:\n",
+    programname
+  );
+  for (names, description) in options {
+    help.push_str(&format!(
+      "  {:width$}  {}\n",
+      names,
+      description,
+      width = width
+    ));
+  }
+  help.push_str("\nExamples:\n");
+  for (args, description) in EXAMPLES {
+    help.push_str(&format!(
+      "  {} {}\n      {}\n",
+      programname, args, description
+    ));
+  }
+  help
+}
+
+/// What `--version` prints.
+pub fn version() -> String {
+  format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+/// An argument once it is told apart from the others.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Arg {
+  Option {
+    spec: &'static OptionSpec,
+    /// The option as it was written, such as `-m` or `--max-count`, for
+    /// error messages.
+    name: String,
+    /// Missing for an option that needs a value only when there are no
+    /// arguments left.
+    value: Option<String>,
+  },
+  /// A query or a file.
+  Positional(String),
+}
+
+/// Splits arguments into options and positional arguments.
+pub struct Parser<I> {
+  args: I,
+  // What is left of a bundle of short options
+  shorts: Vec<char>,
+  // Past `--`
+  positional_only: bool,
+}
+
+impl<I: Iterator<Item = String>> Parser<I> {
+  pub fn new(args: I) -> Parser<I> {
+    Parser {
+      args,
+      shorts: Vec::new(),
+      positional_only: false,
+    }
+  }
+
+  fn short(&mut self) -> Result<Arg, MinigrepError> {
+    let short = self.shorts.remove(0);
+    let name = format!("-{}", short);
+    let spec = find_short(short).ok_or_else(|| MinigrepError::UnknownOption(name.clone()))?;
+    let value = match spec.value {
+      Value::None | Value::Optional(_) => None,
+      // The value is either the rest of the bundle (`-A3`) or the next
+      // argument (`-A 3`)
+      Value::Required(_) if self.shorts.is_empty() => self.args.next(),
+      Value::Required(_) => Some(self.shorts.drain(..).collect()),
+    };
+    Ok(Arg::Option { spec, name, value })
+  }
+}
+
+impl<I: Iterator<Item = String>> Iterator for Parser<I> {
+  type Item = Result<Arg, MinigrepError>;
+
+  fn next(&mut self) -> Option<Result<Arg, MinigrepError>> {
+    if !self.shorts.is_empty() {
+      return Some(self.short());
+    }
+    let arg = self.args.next()?;
+    if self.positional_only || arg == "-" || !arg.starts_with('-') {
+      return Some(Ok(Arg::Positional(arg)));
+    }
+    if arg == "--" {
+      self.positional_only = true;
+      return self.next();
+    }
+    let long = match arg.strip_prefix("--") {
+      Some(long) => long,
+      None => {
+        self.shorts = arg.chars().skip(1).collect();
+        return Some(self.short());
+      }
+    };
+    let (long, attached) = match long.find('=') {
+      Some(i) => (&long[..i], Some(long[i + 1..].to_string())),
+      None => (long, None),
+    };
+    let name = format!("--{}", long);
+    let spec = match find_long(long) {
+      Some(spec) => spec,
+      None => return Some(Err(MinigrepError::UnknownOption(name))),
+    };
+    let value = match spec.value {
+      Value::None if attached.is_some() => {
+        return Some(Err(MinigrepError::UnexpectedValue { option: name }))
+      }
+      Value::None | Value::Optional(_) => attached,
+      Value::Required(_) => attached.or_else(|| self.args.next()),
+    };
+    Some(Ok(Arg::Option { spec, name, value }))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(args: &[&str]) -> Vec<(String, Option<String>)> {
+    Parser::new(args.iter().map(|arg| arg.to_string()))
+      .map(|arg| match arg.unwrap() {
+        Arg::Option { spec, value, .. } => (spec.long.to_string(), value),
+        Arg::Positional(arg) => (arg, None),
+      })
+      .collect()
+  }
+
+  fn pair(name: &str, value: Option<&str>) -> (String, Option<String>) {
+    (name.to_string(), value.map(str::to_string))
+  }
+
+  #[test]
+  fn splits_arguments() {
+    assert_eq!(
+      vec![
+        pair("ignore-case", None),
+        pair("line-number", None),
+        pair("context", Some("2")),
+        pair("max-count", Some("3")),
+        pair("color", None),
+        pair("quiet", None),
+        pair("frog", None),
+        pair("-", None),
+        pair("-v", None),
+        pair("--count", None),
+      ],
+      parse(&[
+        "-inC2",
+        "--max-count",
+        "3",
+        "--color",
+        "--silent",
+        "frog",
+        "-",
+        "--",
+        "-v",
+        "--count"
+      ])
+    );
+    assert_eq!(vec![pair("regexp", Some("-x"))], parse(&["--regexp=-x"]));
+    assert_eq!(
+      vec![pair("in-place", None), pair("a", None)],
+      parse(&["--in-place", "a"])
+    );
+    assert_eq!(vec![pair("after-context", None)], parse(&["-A"]));
+
+    let error = |args: &[&str]| {
+      Parser::new(args.iter().map(|arg| arg.to_string()))
+        .find_map(Result::err)
+        .map(|e| e.to_string())
+    };
+    assert_eq!(Some("Unsupported option `-y`".to_string()), error(&["-iy"]));
+    assert_eq!(
+      Some("Unsupported option `--frog`".to_string()),
+      error(&["--frog"])
+    );
+    assert_eq!(
+      Some("Unexpected value for `--count`".to_string()),
+      error(&["--count=2"])
+    );
+  }
+
+  #[test]
+  fn helps_with_every_option() {
+    let help = help("minigrep");
+    for spec in OPTIONS {
+      assert!(help.contains(&format!("--{}", spec.long)), "{}", spec.long);
+    }
+    assert!(help.contains("\n  -A, --after-context N "));
+    assert!(help.contains("\n      --color[=WHEN] "));
+    assert!(version().starts_with("minigrep 0."));
+  }
+
+  #[test]
+  fn names_are_unique() {
+    for (i, spec) in OPTIONS.iter().enumerate() {
+      for other in &OPTIONS[i + 1..] {
+        assert_ne!(spec.long, other.long);
+        assert!(
+          spec.short.is_none() || spec.short != other.short,
+          "{}",
+          spec.long
+        );
+        assert!(!other.aliases.contains(&spec.long) && !spec.aliases.contains(&other.long));
+      }
+    }
+  }
+}
@@ -0,0 +1,322 @@
+//! Searching from async code, behind the `async` feature, for services that
+//! already run an executor and must not block it on reading files.
+//!
+//! Files are walked on threads of their own and searched on a few shared
+//! ones, and the futures here only wait for them to finish. That makes them
+//! work the same under any executor, tokio's included, without depending on
+//! one. What is dropped before it is done stops the threads working for it.
+
+use crate::error::MinigrepError;
+use crate::{search_file, walker, Config, FileMatch};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+// What a thread has sent so far, and who is waiting for more
+struct Shared<T> {
+  items: VecDeque<T>,
+  // Set once the sending thread is done, or has panicked
+  closed: bool,
+  // Set once nobody is waiting for what is sent anymore
+  dropped: bool,
+  waker: Option<Waker>,
+}
+
+type Queue<T> = Arc<Mutex<Shared<T>>>;
+
+struct Sender<T>(Queue<T>);
+
+impl<T> Sender<T> {
+  // Whether anyone still waits for more
+  fn send(&self, item: T) -> bool {
+    let mut shared = self.0.lock().unwrap();
+    shared.items.push_back(item);
+    if let Some(waker) = shared.waker.take() {
+      waker.wake();
+    }
+    !shared.dropped
+  }
+
+  fn wanted(&self) -> bool {
+    !self.0.lock().unwrap().dropped
+  }
+}
+
+impl<T> Drop for Sender<T> {
+  fn drop(&mut self) {
+    // Still reached when the thread panics, which must not leave anyone
+    // waiting forever
+    let mut shared = match self.0.lock() {
+      Ok(shared) => shared,
+      Err(poisoned) => poisoned.into_inner(),
+    };
+    shared.closed = true;
+    if let Some(waker) = shared.waker.take() {
+      waker.wake();
+    }
+  }
+}
+
+fn queue<T>() -> (Sender<T>, Queue<T>) {
+  let shared = Arc::new(Mutex::new(Shared {
+    items: VecDeque::new(),
+    closed: false,
+    dropped: false,
+    waker: None,
+  }));
+  (Sender(shared.clone()), shared)
+}
+
+// Tells the sending thread it can stop
+fn drop_queue<T>(queue: &Queue<T>) {
+  if let Ok(mut shared) = queue.lock() {
+    shared.dropped = true;
+    shared.items.clear();
+  }
+}
+
+// The next item, or none once the thread is done
+fn poll_next<T>(queue: &Queue<T>, cx: &mut Context) -> Poll<Option<T>> {
+  let mut shared = queue.lock().unwrap();
+  match shared.items.pop_front() {
+    Some(item) => Poll::Ready(Some(item)),
+    None if shared.closed => Poll::Ready(None),
+    None => {
+      shared.waker = Some(cx.waker().clone());
+      Poll::Pending
+    }
+  }
+}
+
+type Search = Result<Vec<FileMatch>, MinigrepError>;
+
+/// The matches in a file, found on another thread while they are being
+/// awaited.
+pub struct Background {
+  path: PathBuf,
+  queue: Queue<Search>,
+}
+
+impl Drop for Background {
+  fn drop(&mut self) {
+    drop_queue(&self.queue);
+  }
+}
+
+impl Future for Background {
+  type Output = Search;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Search> {
+    match poll_next(&self.queue, cx) {
+      Poll::Ready(Some(found)) => Poll::Ready(found),
+      // The search panicked before it was done
+      Poll::Ready(None) => Poll::Ready(Err(MinigrepError::Io {
+        path: self.path.clone(),
+        source: io::Error::other("the search panicked"),
+      })),
+      Poll::Pending => Poll::Pending,
+    }
+  }
+}
+
+/// Searches the file at `path` like `search_file`, without blocking. Every
+/// call shares the same few threads, one for each core.
+pub fn search_file_async(config: Arc<Config>, path: PathBuf) -> Background {
+  static POOL: OnceLock<Pool> = OnceLock::new();
+  let pool = POOL.get_or_init(|| Pool::new(thread::available_parallelism().map_or(1, |n| n.get())));
+  pool.search(config, path)
+}
+
+// A fixed number of threads searching the files handed to them, so that a
+// large tree is not searched a thread a file. They stop once dropped.
+struct Pool {
+  jobs: mpsc::Sender<(Arc<Config>, PathBuf, Sender<Search>)>,
+}
+
+impl Pool {
+  fn new(threads: usize) -> Pool {
+    let (jobs, queue) = mpsc::channel::<(Arc<Config>, PathBuf, Sender<Search>)>();
+    let queue = Arc::new(Mutex::new(queue));
+    for _ in 0..threads {
+      let queue = queue.clone();
+      thread::spawn(move || loop {
+        // The lock is only held while waiting for the next file
+        let (config, path, sender) = match queue.lock().unwrap().recv() {
+          Ok(job) => job,
+          Err(_) => break,
+        };
+        // Searches no longer awaited are not worth starting, and one that
+        // panics leaves the thread to the next, closing its queue
+        if sender.wanted() {
+          let found = panic::catch_unwind(AssertUnwindSafe(|| search_file(&config, &path)));
+          if let Ok(found) = found {
+            sender.send(found);
+          }
+        }
+      });
+    }
+    Pool { jobs }
+  }
+
+  fn search(&self, config: Arc<Config>, path: PathBuf) -> Background {
+    let (sender, queue) = queue();
+    // Should the threads be gone all the same, the queue is closed for the
+    // search to tell
+    let _ = self.jobs.send((config, path.clone(), sender));
+    Background { path, queue }
+  }
+}
+
+/// The files below a root that `config` searches, found on another thread
+/// while they are being awaited.
+pub struct Paths {
+  queue: Queue<Result<PathBuf, MinigrepError>>,
+}
+
+impl Drop for Paths {
+  fn drop(&mut self) {
+    drop_queue(&self.queue);
+  }
+}
+
+impl Paths {
+  /// The next file, or none once every one has been found.
+  pub fn next_path(&mut self) -> impl Future<Output = Option<Result<PathBuf, MinigrepError>>> + '_ {
+    NextPath { paths: self }
+  }
+}
+
+struct NextPath<'a> {
+  paths: &'a mut Paths,
+}
+
+impl Future for NextPath<'_> {
+  type Output = Option<Result<PathBuf, MinigrepError>>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+    poll_next(&self.paths.queue, cx)
+  }
+}
+
+/// Walks `root` the way `search_dir` does, without blocking.
+pub fn walk_async(config: &Config, root: &Path) -> Paths {
+  let walker = walker(config, root);
+  let root = root.to_path_buf();
+  let (sender, queue) = queue();
+  thread::spawn(move || {
+    for path in walker {
+      let path = path.map_err(|e| MinigrepError::Io {
+        path: root.clone(),
+        source: e,
+      });
+      let failed = path.is_err();
+      // Nothing is left to walk for once `Paths` is dropped
+      if !sender.send(path) || failed {
+        break;
+      }
+    }
+  });
+  Paths { queue }
+}
+
+/// Searches every file below `root` like `search_dir`, several at once and
+/// without blocking. Results come in the order the files were found.
+pub async fn search_dir_async(
+  config: Arc<Config>,
+  root: PathBuf,
+) -> Result<Vec<FileMatch>, MinigrepError> {
+  let at_once = match config.threads {
+    0 => thread::available_parallelism().map_or(1, |n| n.get()),
+    n => n,
+  };
+  let mut paths = walk_async(&config, &root);
+  let pool = Pool::new(at_once);
+  let mut searching = VecDeque::new();
+  let mut results = Vec::new();
+  while let Some(path) = paths.next_path().await {
+    if searching.len() == at_once {
+      results.extend(searching.pop_front().unwrap().await?);
+    }
+    searching.push_back(pool.search(config.clone(), path?));
+  }
+  for search in searching {
+    results.extend(search.await?);
+  }
+  Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::Fixture;
+  use std::task::Wake;
+  use std::thread::Thread;
+
+  struct Unpark(Thread);
+
+  impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+      self.0.unpark();
+    }
+  }
+
+  fn block_on<F: Future>(future: F) -> F::Output {
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+    loop {
+      match future.as_mut().poll(&mut cx) {
+        Poll::Ready(output) => return output,
+        Poll::Pending => thread::park(),
+      }
+    }
+  }
+
+  #[test]
+  fn searches_without_blocking() {
+    let root = Fixture::new(
+      "async",
+      &[("a.txt", "frog\ntoad\n"), ("pond/b.txt", "no\na frog\n")],
+    );
+    let config = Arc::new(
+      Config::builder()
+        .query("frog")
+        .path(&root.path().display().to_string())
+        .threads(1)
+        .build()
+        .unwrap(),
+    );
+
+    let found = block_on(search_dir_async(config.clone(), root.path().to_path_buf())).unwrap();
+    let found: Vec<(PathBuf, usize)> = found.into_iter().map(|m| (m.path, m.line_number)).collect();
+    assert_eq!(
+      vec![(root.join("a.txt"), 1), (root.join("pond/b.txt"), 2)],
+      found
+    );
+    assert!(block_on(search_file_async(config, root.join("missing"))).is_err());
+  }
+
+  #[test]
+  fn fails_a_search_that_panicked() {
+    let (sender, queue) = queue::<Search>();
+    drop(sender);
+    let path = PathBuf::from("a.txt");
+    let search = Background { path, queue };
+    assert!(matches!(block_on(search), Err(MinigrepError::Io { .. })));
+  }
+
+  #[test]
+  fn tells_the_walker_once_dropped() {
+    let (sender, queue) = queue();
+    let paths = Paths { queue };
+    assert!(sender.send(Ok(PathBuf::from("a.txt"))));
+    drop(paths);
+    assert!(!sender.send(Ok(PathBuf::from("b.txt"))));
+  }
+}
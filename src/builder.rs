@@ -0,0 +1,707 @@
+use crate::csv::Column;
+use crate::edit::InPlace;
+use crate::encoding::Encoding;
+use crate::fold::CaseFold;
+use crate::hyperlink::HyperlinkFormat;
+use crate::json_input::Selector;
+use crate::limit::ResultLimit;
+use crate::log::Level;
+use crate::preprocess::Preprocessor;
+use crate::records::Records;
+use crate::replace::Template;
+use crate::types::FileTypes;
+use crate::walk::Sort;
+use crate::{
+  compile_patterns, has_uppercase, literal_set, read_patterns, ColorChoice, Config, MinigrepError,
+  MmapChoice, OutputFormat, Syntax, DEFAULT_COMMENT_PREFIX,
+};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Builds a `Config` in code, with the same defaults and checks as the
+/// command line.
+///
+/// ```
+/// let config = minigrep::Config::builder()
+///   .query("frog")
+///   .path("poem.txt")
+///   .ignore_case(true)
+///   .build()
+///   .unwrap();
+/// assert_eq!(vec!["poem.txt".to_string()], config.filenames);
+/// ```
+pub struct ConfigBuilder {
+  config: Config,
+  queries: Vec<String>,
+  pattern_files: Vec<String>,
+  comment_prefix: Option<String>,
+  syntax: Syntax,
+  smart_case: bool,
+  include: Vec<String>,
+  exclude: Vec<String>,
+  types: Vec<String>,
+  types_not: Vec<String>,
+  type_definitions: Vec<String>,
+  colors: Vec<String>,
+  pre_globs: Vec<String>,
+}
+
+impl ConfigBuilder {
+  pub fn new() -> ConfigBuilder {
+    ConfigBuilder {
+      config: Config::default(),
+      queries: Vec::new(),
+      pattern_files: Vec::new(),
+      comment_prefix: Some(DEFAULT_COMMENT_PREFIX.to_string()),
+      syntax: Syntax::Literal,
+      smart_case: false,
+      include: Vec::new(),
+      exclude: Vec::new(),
+      types: Vec::new(),
+      types_not: Vec::new(),
+      type_definitions: Vec::new(),
+      colors: Vec::new(),
+      pre_globs: Vec::new(),
+    }
+  }
+
+  /// Adds a pattern; a line matches when any of them does.
+  pub fn query(mut self, query: &str) -> ConfigBuilder {
+    self.queries.push(query.to_string());
+    self
+  }
+
+  /// Adds the patterns in the file at `path`, one a line, read once the
+  /// config is built.
+  pub fn pattern_file(mut self, path: &str) -> ConfigBuilder {
+    self.pattern_files.push(path.to_string());
+    self
+  }
+
+  /// What starts the comment lines skipped in pattern files, or `None` to
+  /// keep every line.
+  pub fn comment_prefix(mut self, prefix: Option<&str>) -> ConfigBuilder {
+    self.comment_prefix = prefix.map(str::to_string);
+    self
+  }
+
+  /// Adds a file or directory to search, `-` being standard input.
+  pub fn path(mut self, path: &str) -> ConfigBuilder {
+    self.config.filenames.push(path.to_string());
+    self
+  }
+
+  /// Treat every query as a regular expression.
+  pub fn regex(mut self, yes: bool) -> ConfigBuilder {
+    self.syntax = if yes { Syntax::Regex } else { Syntax::Literal };
+    self
+  }
+
+  pub fn syntax(mut self, syntax: Syntax) -> ConfigBuilder {
+    self.syntax = syntax;
+    self
+  }
+
+  pub fn ignore_case(mut self, yes: bool) -> ConfigBuilder {
+    self.config.case_sensitive = !yes;
+    self.smart_case = false;
+    self
+  }
+
+  /// How case is folded when it is ignored.
+  pub fn case_fold(mut self, mode: CaseFold) -> ConfigBuilder {
+    self.config.case_fold = mode;
+    self
+  }
+
+  /// Match letters whatever their accents.
+  pub fn ignore_accents(mut self, yes: bool) -> ConfigBuilder {
+    self.config.ignore_accents = yes;
+    self
+  }
+
+  /// Ignore case unless a query has an uppercase letter.
+  pub fn smart_case(mut self, yes: bool) -> ConfigBuilder {
+    self.smart_case = yes;
+    self
+  }
+
+  pub fn recursive(mut self, yes: bool) -> ConfigBuilder {
+    self.config.recursive = yes;
+    self
+  }
+
+  /// Prefix the results with the input they are from, or never do, rather
+  /// than only when there are several.
+  pub fn with_filename(mut self, yes: bool) -> ConfigBuilder {
+    self.config.with_filename = Some(yes);
+    self
+  }
+
+  pub fn line_number(mut self, yes: bool) -> ConfigBuilder {
+    self.config.line_number = yes;
+    self
+  }
+
+  /// Lines of context to show before and after each match.
+  pub fn context(mut self, before: usize, after: usize) -> ConfigBuilder {
+    self.config.before_context = before;
+    self.config.after_context = after;
+    self
+  }
+
+  /// What goes between groups of context that are not next to each other,
+  /// or nothing at all.
+  pub fn context_separator(mut self, separator: Option<&str>) -> ConfigBuilder {
+    self.config.context_separator = separator.map(str::to_string);
+    self
+  }
+
+  pub fn count(mut self, yes: bool) -> ConfigBuilder {
+    self.config.count = yes;
+    self
+  }
+
+  /// Count every occurrence rather than matching lines.
+  pub fn count_matches(mut self, yes: bool) -> ConfigBuilder {
+    self.config.count_matches = yes;
+    self
+  }
+
+  /// Follow the counts with their sum.
+  pub fn total(mut self, yes: bool) -> ConfigBuilder {
+    self.config.total = yes;
+    self
+  }
+
+  pub fn invert_match(mut self, yes: bool) -> ConfigBuilder {
+    self.config.invert_match = yes;
+    self
+  }
+
+  pub fn color(mut self, color: ColorChoice) -> ConfigBuilder {
+    self.config.color = color;
+    self
+  }
+
+  /// Sets the style of one part of the output, as in `match:bold red`.
+  pub fn colors(mut self, spec: &str) -> ConfigBuilder {
+    self.colors.push(spec.to_string());
+    self
+  }
+
+  /// Link the file names in colored output to where `format` says.
+  pub fn hyperlink_format(mut self, format: Option<HyperlinkFormat>) -> ConfigBuilder {
+    self.config.hyperlink_format = format;
+    self
+  }
+
+  /// Write the results to a file instead of standard output.
+  pub fn output(mut self, path: &str) -> ConfigBuilder {
+    self.config.output = Some(PathBuf::from(path));
+    self
+  }
+
+  pub fn json(mut self, yes: bool) -> ConfigBuilder {
+    self.config.format = if yes {
+      OutputFormat::Json
+    } else {
+      OutputFormat::Text
+    };
+    self
+  }
+
+  pub fn format(mut self, format: OutputFormat) -> ConfigBuilder {
+    self.config.format = format;
+    self
+  }
+
+  pub fn word_regexp(mut self, yes: bool) -> ConfigBuilder {
+    self.config.word_regexp = yes;
+    self
+  }
+
+  pub fn line_regexp(mut self, yes: bool) -> ConfigBuilder {
+    self.config.line_regexp = yes;
+    self
+  }
+
+  pub fn show_pattern(mut self, yes: bool) -> ConfigBuilder {
+    self.config.show_pattern = yes;
+    self
+  }
+
+  /// Only search files matching this glob.
+  pub fn include(mut self, glob: &str) -> ConfigBuilder {
+    self.include.push(glob.to_string());
+    self
+  }
+
+  /// Skip files and directories matching this glob.
+  pub fn exclude(mut self, glob: &str) -> ConfigBuilder {
+    self.exclude.push(glob.to_string());
+    self
+  }
+
+  /// Only search files of this type, such as `rust`.
+  pub fn file_type(mut self, name: &str) -> ConfigBuilder {
+    self.types.push(name.to_string());
+    self
+  }
+
+  /// Skip files of this type.
+  pub fn file_type_not(mut self, name: &str) -> ConfigBuilder {
+    self.types_not.push(name.to_string());
+    self
+  }
+
+  /// Defines or extends a file type, as in `web:*.html,*.css`.
+  pub fn file_type_add(mut self, definition: &str) -> ConfigBuilder {
+    self.type_definitions.push(definition.to_string());
+    self
+  }
+
+  /// Search hidden files and directories while searching recursively.
+  pub fn hidden(mut self, yes: bool) -> ConfigBuilder {
+    self.config.path_filter.hidden(yes);
+    self
+  }
+
+  pub fn git_ignore(mut self, yes: bool) -> ConfigBuilder {
+    self.config.git_ignore = yes;
+    self
+  }
+
+  /// Honor `.ignore` and `.minigrepignore` files while searching
+  /// recursively.
+  pub fn dot_ignore(mut self, yes: bool) -> ConfigBuilder {
+    self.config.dot_ignore = yes;
+    self
+  }
+
+  /// Only search files tracked by Git while searching recursively.
+  pub fn git_tracked(mut self, yes: bool) -> ConfigBuilder {
+    self.config.git_tracked = yes;
+    self
+  }
+
+  /// Follow symbolic links while searching recursively.
+  pub fn follow(mut self, yes: bool) -> ConfigBuilder {
+    self.config.follow = yes;
+    self
+  }
+
+  /// How many directories deep to search recursively, `0` searching only
+  /// the paths given.
+  pub fn max_depth(mut self, depth: Option<usize>) -> ConfigBuilder {
+    self.config.max_depth = depth;
+    self
+  }
+
+  /// Only search the files found recursively that were modified after
+  /// `time`.
+  pub fn newer_than(mut self, time: SystemTime) -> ConfigBuilder {
+    self.config.newer_than = Some(time);
+    self
+  }
+
+  /// Only search the files found recursively that were modified before
+  /// `time`.
+  pub fn older_than(mut self, time: SystemTime) -> ConfigBuilder {
+    self.config.older_than = Some(time);
+    self
+  }
+
+  /// The order to search files in while searching recursively.
+  pub fn sort(mut self, sort: Option<Sort>) -> ConfigBuilder {
+    self.config.sort = sort;
+    self
+  }
+
+  /// Skip files larger than `bytes`.
+  pub fn max_filesize(mut self, bytes: Option<u64>) -> ConfigBuilder {
+    self.config.max_filesize = bytes;
+    self
+  }
+
+  /// How many files to search at once, `0` to pick based on the CPU count.
+  pub fn threads(mut self, threads: usize) -> ConfigBuilder {
+    self.config.threads = threads;
+    self
+  }
+
+  /// Print each file's results as soon as they are ready, rather than in
+  /// the order the files were found.
+  pub fn unordered(mut self, yes: bool) -> ConfigBuilder {
+    self.config.unordered = yes;
+    self
+  }
+
+  pub fn mmap(mut self, mmap: MmapChoice) -> ConfigBuilder {
+    self.config.mmap = mmap;
+    self
+  }
+
+  /// Search binary files as text, printing what is not printable escaped.
+  pub fn binary(mut self, yes: bool) -> ConfigBuilder {
+    self.config.binary = yes;
+    self
+  }
+
+  pub fn encoding(mut self, encoding: Encoding) -> ConfigBuilder {
+    self.config.encoding = encoding;
+    self
+  }
+
+  /// Search what `command` prints for each file instead of the file.
+  pub fn pre(mut self, command: &str) -> ConfigBuilder {
+    self.config.preprocessor = Some(Preprocessor::new(command));
+    self
+  }
+
+  /// Only run the `pre` command for files matching this glob or another
+  /// one given.
+  pub fn pre_glob(mut self, glob: &str) -> ConfigBuilder {
+    self.pre_globs.push(glob.to_string());
+    self
+  }
+
+  pub fn max_count(mut self, max: usize) -> ConfigBuilder {
+    self.config.max_count = Some(max);
+    self
+  }
+
+  /// Print at most `max` characters of each line, around its first match.
+  pub fn max_columns(mut self, max: usize) -> ConfigBuilder {
+    self.config.max_columns = Some(max);
+    self
+  }
+
+  /// Print the start of lines cut by `max_columns` rather than their first
+  /// match.
+  pub fn max_columns_preview(mut self, yes: bool) -> ConfigBuilder {
+    self.config.max_columns_preview = yes;
+    self
+  }
+
+  /// Print lines without their leading whitespace.
+  pub fn trim(mut self, yes: bool) -> ConfigBuilder {
+    self.config.trim = yes;
+    self
+  }
+
+  /// Print every line, highlighting the matches in the ones that match.
+  pub fn passthru(mut self, yes: bool) -> ConfigBuilder {
+    self.config.passthru = yes;
+    self
+  }
+
+  /// Stop the whole search after `max` matching lines across every file.
+  pub fn max_results(mut self, max: usize) -> ConfigBuilder {
+    self.config.max_results = Some(ResultLimit::new(max));
+    self
+  }
+
+  pub fn quiet(mut self, yes: bool) -> ConfigBuilder {
+    self.config.quiet = yes;
+    self
+  }
+
+  /// Say nothing about inputs that cannot be read.
+  pub fn no_messages(mut self, yes: bool) -> ConfigBuilder {
+    self.config.no_messages = yes;
+    self
+  }
+
+  /// Call `on_error` with each input that cannot be searched, rather than
+  /// skipping it without a word.
+  pub fn on_error<F>(mut self, on_error: F) -> ConfigBuilder
+  where
+    F: Fn(&Path, &io::Error) + Send + Sync + 'static,
+  {
+    self.config.on_error = Some(Box::new(on_error));
+    self
+  }
+
+  pub fn files_with_matches(mut self, yes: bool) -> ConfigBuilder {
+    self.config.files_with_matches = yes;
+    self
+  }
+
+  pub fn files_without_match(mut self, yes: bool) -> ConfigBuilder {
+    self.config.files_without_match = yes;
+    self
+  }
+
+  pub fn only_matching(mut self, yes: bool) -> ConfigBuilder {
+    self.config.only_matching = yes;
+    self
+  }
+
+  pub fn byte_offset(mut self, yes: bool) -> ConfigBuilder {
+    self.config.byte_offset = yes;
+    self
+  }
+
+  pub fn null(mut self, yes: bool) -> ConfigBuilder {
+    self.config.null = yes;
+    self
+  }
+
+  pub fn replace(mut self, template: &str) -> ConfigBuilder {
+    self.config.replace = Some(Template::new(template));
+    self
+  }
+
+  pub fn in_place(mut self, in_place: InPlace) -> ConfigBuilder {
+    self.config.in_place = Some(in_place);
+    self
+  }
+
+  /// Let matches span several lines.
+  pub fn multiline(mut self, yes: bool) -> ConfigBuilder {
+    self.config.multiline = yes;
+    self
+  }
+
+  /// Let `$` match before `\r\n` line endings.
+  pub fn crlf(mut self, yes: bool) -> ConfigBuilder {
+    self.config.crlf = yes;
+    self
+  }
+
+  /// Match and print records split by `records` rather than lines.
+  pub fn records(mut self, records: Records) -> ConfigBuilder {
+    self.config.records = records;
+    self
+  }
+
+  /// Match only in `column` of each row, which needs `Records::Csv`.
+  pub fn column(mut self, column: Column) -> ConfigBuilder {
+    self.config.column = Some(column);
+    self
+  }
+
+  /// Match the string values of JSON documents, one an input or one a
+  /// line, and print the documents that match.
+  pub fn json_input(mut self, yes: bool) -> ConfigBuilder {
+    self.config.json_input = yes;
+    self
+  }
+
+  /// Only match the strings under what `selector` selects.
+  pub fn json_path(mut self, selector: Selector) -> ConfigBuilder {
+    self.config.json_path = Some(selector);
+    self
+  }
+
+  /// Print the JSON pointer of each string that matches, rather than its
+  /// document.
+  pub fn json_pointers(mut self, yes: bool) -> ConfigBuilder {
+    self.config.json_pointers = yes;
+    self
+  }
+
+  /// Decompress gzip inputs, recognized by a `.gz` extension or their
+  /// contents.
+  pub fn search_zip(mut self, yes: bool) -> ConfigBuilder {
+    self.config.search_zip = yes;
+    self
+  }
+
+  /// Search the files inside `.zip`, `.tar` and `.tar.gz` archives.
+  pub fn search_archives(mut self, yes: bool) -> ConfigBuilder {
+    self.config.search_archives = yes;
+    self
+  }
+
+  /// Collect statistics for printing once the search is done.
+  pub fn stats(mut self, yes: bool) -> ConfigBuilder {
+    self.config.stats = yes;
+    self
+  }
+
+  /// Collect statistics for each file as well, which needs `stats`.
+  pub fn per_file(mut self, yes: bool) -> ConfigBuilder {
+    self.config.per_file = yes;
+    self
+  }
+
+  /// What to log about the search, once passed to `log::set_level`.
+  pub fn log_level(mut self, level: Option<Level>) -> ConfigBuilder {
+    self.config.log_level = level;
+    self
+  }
+
+  /// Keep reading files as they grow, when passed to `tail`.
+  pub fn tail(mut self, yes: bool) -> ConfigBuilder {
+    self.config.tail = yes;
+    self
+  }
+
+  /// Keep searching files again as they change, when passed to `watch`.
+  pub fn watch(mut self, yes: bool) -> ConfigBuilder {
+    self.config.watch = yes;
+    self
+  }
+
+  /// Refine the query interactively, when passed to `tui::run`, which
+  /// needs no query to start with.
+  pub fn tui(mut self, yes: bool) -> ConfigBuilder {
+    self.config.tui = yes;
+    self
+  }
+
+  /// Order the matching lines of each input by fuzzy score, best first.
+  pub fn sort_by_score(mut self, yes: bool) -> ConfigBuilder {
+    self.config.sort_by_score = yes;
+    self
+  }
+
+  /// Checks the options and compiles the patterns and globs.
+  pub fn build(self) -> Result<Config, MinigrepError> {
+    let mut config = self.config;
+    let mut queries = self.queries;
+    for path in &self.pattern_files {
+      match fs::read_to_string(path) {
+        Ok(contents) => queries.extend(read_patterns(&contents, self.comment_prefix.as_deref())),
+        Err(e) => {
+          return Err(MinigrepError::Io {
+            path: PathBuf::from(path),
+            source: e,
+          })
+        }
+      }
+    }
+    // A pattern file may have none, matching nothing, as with grep
+    if queries.is_empty() && self.pattern_files.is_empty() && !config.tui {
+      return Err(MinigrepError::MissingQuery);
+    }
+    if config.filenames.is_empty() {
+      return Err(MinigrepError::MissingFile);
+    }
+    for glob in &self.include {
+      config.path_filter.include(glob)?;
+    }
+    for glob in &self.exclude {
+      config.path_filter.exclude(glob)?;
+    }
+    if let Some(pre) = &mut config.preprocessor {
+      for glob in &self.pre_globs {
+        pre.glob(glob)?;
+      }
+    }
+    let mut types = FileTypes::new();
+    for definition in &self.type_definitions {
+      types.add(definition)?;
+    }
+    types.apply(&mut config.path_filter, &self.types, &self.types_not)?;
+    if config.output.is_some() && config.color == ColorChoice::Auto {
+      config.color = ColorChoice::Never;
+    }
+    for spec in &self.colors {
+      config.theme.set(spec)?;
+    }
+    if self.smart_case {
+      config.case_sensitive = has_uppercase(&queries, self.syntax);
+    }
+    config.syntax = self.syntax;
+    config.patterns = compile_patterns(
+      queries,
+      self.syntax,
+      config.case_sensitive,
+      config.crlf,
+      config.ignore_accents,
+    )?;
+    let exact = config.case_sensitive && !config.ignore_accents;
+    config.literal_set = literal_set(&config.patterns, exact);
+    config.validate()?;
+    Ok(config)
+  }
+}
+
+impl Default for ConfigBuilder {
+  fn default() -> ConfigBuilder {
+    ConfigBuilder::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::Fixture;
+  use crate::Pattern;
+
+  #[test]
+  fn builds_configs() {
+    let config = ConfigBuilder::new()
+      .query("fr.g")
+      .regex(true)
+      .path("src/")
+      .ignore_case(true)
+      .exclude("target/**")
+      .build()
+      .unwrap();
+
+    assert!(!config.case_sensitive);
+    match &config.patterns[0] {
+      Pattern::Regex(re) => assert!(re.is_match("FROG")),
+      _ => panic!("expected a regex"),
+    }
+    assert!(!config
+      .path_filter
+      .is_match(std::path::Path::new("target/a.rs")));
+  }
+
+  #[test]
+  fn validates_at_build_time() {
+    let builder = || ConfigBuilder::new().path("poem.txt");
+
+    assert!(matches!(
+      builder().build(),
+      Err(MinigrepError::MissingQuery)
+    ));
+    assert!(matches!(
+      builder().query("(").regex(true).build(),
+      Err(MinigrepError::InvalidPattern { .. })
+    ));
+    assert!(matches!(
+      builder().query("frog").include("[a").build(),
+      Err(MinigrepError::InvalidGlob(_))
+    ));
+    let in_place = InPlace {
+      backup_suffix: None,
+    };
+    assert!(matches!(
+      builder().query("frog").in_place(in_place).build(),
+      Err(MinigrepError::IncompatibleOptions(_))
+    ));
+  }
+
+  #[test]
+  fn reads_pattern_files_and_waits_for_a_query_in_the_tui() {
+    let fixture = Fixture::new("builder-patterns", &[]);
+    let path = fixture.write("patterns.txt", "# frogs\nfrog\n\ntoad\n");
+    let config = ConfigBuilder::new()
+      .pattern_file(path.to_str().unwrap())
+      .path("poem.txt")
+      .build()
+      .unwrap();
+
+    let literals = |config: &Config| -> Vec<String> {
+      config
+        .patterns
+        .iter()
+        .map(|pattern| match pattern {
+          Pattern::Literal(literal) => literal.clone(),
+          _ => panic!("expected a literal"),
+        })
+        .collect()
+    };
+    assert_eq!(vec!["frog", "toad"], literals(&config));
+    let config = ConfigBuilder::new().tui(true).path(".").build().unwrap();
+    assert!(config.patterns.is_empty());
+  }
+}
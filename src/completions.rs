@@ -0,0 +1,191 @@
+//! Tab completion scripts for `minigrep completions SHELL`, written from
+//! `args::OPTIONS` so that they know every option the parser does.
+//!
+//! Options complete with their descriptions where the shell shows them, and
+//! the values of options taking a `FILE` complete to paths.
+
+use crate::args::{OptionSpec, Value, OPTIONS};
+
+const NAME: &str = env!("CARGO_PKG_NAME");
+
+/// A shell that completion scripts can be written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+  Bash,
+  Zsh,
+  Fish,
+  PowerShell,
+}
+
+impl Shell {
+  pub fn from_name(name: &str) -> Option<Shell> {
+    match name {
+      "bash" => Some(Shell::Bash),
+      "zsh" => Some(Shell::Zsh),
+      "fish" => Some(Shell::Fish),
+      "powershell" => Some(Shell::PowerShell),
+      _ => None,
+    }
+  }
+
+  /// The completion script, to be sourced or installed where the shell
+  /// looks for completions.
+  pub fn script(self) -> String {
+    match self {
+      Shell::Bash => bash(),
+      Shell::Zsh => zsh(),
+      Shell::Fish => fish(),
+      Shell::PowerShell => powershell(),
+    }
+  }
+}
+
+fn takes_path(spec: &OptionSpec) -> bool {
+  spec.value == Value::Required("FILE")
+}
+
+// Both names of an option, as they are written on the command line
+fn names(spec: &OptionSpec) -> impl Iterator<Item = String> {
+  let short = spec.short.map(|short| format!("-{}", short));
+  short.into_iter().chain(Some(format!("--{}", spec.long)))
+}
+
+fn bash() -> String {
+  let matching = |predicate: fn(&OptionSpec) -> bool| {
+    OPTIONS
+      .iter()
+      .filter(|spec| predicate(spec))
+      .flat_map(names)
+      .collect::<Vec<_>>()
+      .join("|")
+  };
+  let words: Vec<String> = OPTIONS.iter().flat_map(names).collect();
+  format!(
+    "_{name}() {{
+    local cur=\"${{COMP_WORDS[COMP_CWORD]}}\"
+    local prev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"
+    case \"$prev\" in
+        {paths})
+            COMPREPLY=($(compgen -f -- \"$cur\"))
+            return
+            ;;
+        {values})
+            COMPREPLY=()
+            return
+            ;;
+    esac
+    if [[ \"$cur\" == -* ]]; then
+        COMPREPLY=($(compgen -W \"{words}\" -- \"$cur\"))
+    else
+        COMPREPLY=($(compgen -f -- \"$cur\"))
+    fi
+}}
+complete -o filenames -F _{name} {name}
+",
+    name = NAME,
+    paths = matching(takes_path),
+    values = matching(|spec| matches!(spec.value, Value::Required(_)) && !takes_path(spec)),
+    words = words.join(" "),
+  )
+}
+
+fn zsh() -> String {
+  let mut script = format!("#compdef {}\n\n_arguments -s -S \\\n", NAME);
+  for spec in OPTIONS {
+    let help = spec
+      .help
+      .replace('\'', "'\\''")
+      .replace('[', "\\[")
+      .replace(']', "\\]");
+    let action = if takes_path(spec) { "_files" } else { " " };
+    let (suffix, value) = match spec.value {
+      Value::None => ("", String::new()),
+      Value::Required(name) => ("=", format!(":{}:{}", name, action)),
+      Value::Optional(name) => ("=-", format!("::{}:{}", name, action)),
+    };
+    let names = match spec.short {
+      // `-A3` and `-A 3` are both fine for short options
+      Some(short) if spec.value == Value::None => format!("{{-{},--{}}}", short, spec.long),
+      Some(short) => format!("{{-{}+,--{}{}}}", short, spec.long, suffix),
+      None => format!("--{}{}", spec.long, suffix),
+    };
+    script.push_str(&format!("  '*'{}'[{}]{}' \\\n", names, help, value));
+  }
+  script.push_str("  '1:query: ' \\\n  '*:file:_files'\n");
+  script
+}
+
+fn fish() -> String {
+  let mut script = String::new();
+  for spec in OPTIONS {
+    script.push_str(&format!("complete -c {}", NAME));
+    if let Some(short) = spec.short {
+      script.push_str(&format!(" -s {}", short));
+    }
+    script.push_str(&format!(" -l {}", spec.long));
+    match spec.value {
+      Value::Required(_) if takes_path(spec) => script.push_str(" -r -F"),
+      Value::Required(_) => script.push_str(" -x"),
+      Value::None | Value::Optional(_) => {}
+    }
+    script.push_str(&format!(" -d '{}'\n", spec.help.replace('\'', "\\'")));
+  }
+  script
+}
+
+fn powershell() -> String {
+  let mut results = String::new();
+  for spec in OPTIONS {
+    let help = spec.help.replace('\'', "''");
+    for name in names(spec) {
+      results.push_str(&format!(
+        "        [CompletionResult]::new('{0}', '{0}', [CompletionResultType]::ParameterName, '{1}')\n",
+        name, help
+      ));
+    }
+  }
+  format!(
+    "using namespace System.Management.Automation
+
+Register-ArgumentCompleter -Native -CommandName '{name}' -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+    # Anything but an option falls back to completing paths
+    if ($wordToComplete -notlike '-*') {{
+        return
+    }}
+    @(
+{results}    ) | Where-Object {{ $_.CompletionText -like \"$wordToComplete*\" }}
+}}
+",
+    name = NAME,
+    results = results,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn completes_every_option() {
+    for shell in &["bash", "zsh", "fish", "powershell"] {
+      let script = Shell::from_name(shell).unwrap().script();
+      for spec in OPTIONS {
+        assert!(
+          script.contains(&format!("--{}", spec.long))
+            || script.contains(&format!("-l {} ", spec.long)),
+          "{} {}",
+          shell,
+          spec.long
+        );
+      }
+    }
+    assert!(bash().contains("\n        -f|--file|--output)\n"));
+    assert!(
+      zsh().contains("\n  '*'{-A+,--after-context=}'[Print N lines after each match]:N: ' \\\n")
+    );
+    assert!(fish().contains("\ncomplete -c minigrep -s f -l file -r -F -d 'Search for each pattern in FILE, one per line'\n"));
+    assert!(fish().contains(" -d 'Print only the names of inputs that don\\'t match'\n"));
+    assert_eq!(None, Shell::from_name("tcsh"));
+  }
+}
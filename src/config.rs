@@ -0,0 +1,259 @@
+use crate::args::{self, Arg};
+use crate::error::MinigrepError;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the files that default options are read from.
+pub const RC_FILENAME: &str = ".minigreprc";
+
+// What the config file of a project may set, by long name: how matches are
+// found and shown and which files are searched. Anything that runs a
+// command, writes a file, reads patterns or never ends is left to whoever
+// runs the search, since a cloned repository is not to be trusted.
+const PROJECT_OPTIONS: &[&str] = &[
+  "ignore-case",
+  "smart-case",
+  "case-sensitive",
+  "case-fold",
+  "ignore-accents",
+  "word-regexp",
+  "line-number",
+  "byte-offset",
+  "with-filename",
+  "no-filename",
+  "after-context",
+  "before-context",
+  "context",
+  "context-separator",
+  "no-context-separator",
+  "max-columns",
+  "max-columns-preview",
+  "trim",
+  "color",
+  "colors",
+  "include",
+  "exclude",
+  "type",
+  "type-not",
+  "type-add",
+  "no-ignore",
+  "ignore",
+  "no-ignore-vcs",
+  "no-ignore-dot",
+  "hidden",
+  "no-hidden",
+  "max-depth",
+  "max-filesize",
+  "sort",
+  "sortr",
+  "threads",
+  "no-config",
+];
+
+/// A config file holds one command line option per line, such as
+/// `--ignore-case` or `--exclude=target/**`. Blank lines and lines starting
+/// with `#` are skipped.
+///
+/// Colors may also be set as `colors.<part> = "<style>"`, which stands for
+/// `--colors=<part>:<style>`.
+pub fn parse(contents: &str) -> Result<Vec<String>, String> {
+  let mut args = Vec::new();
+  for (i, line) in contents.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    if let Some((part, style)) = line
+      .strip_prefix("colors.")
+      .and_then(|setting| setting.split_once('='))
+    {
+      let style = style.trim().trim_matches('"');
+      args.push(format!("--colors={}:{}", part.trim(), style));
+      continue;
+    }
+    if !line.starts_with('-') {
+      return Err(format!(
+        "line {}: expected an option, found `{}`",
+        i + 1,
+        line
+      ));
+    }
+    args.push(line.to_string());
+  }
+  Ok(args)
+}
+
+/// Reads the options in the config file at `path`, if there is one.
+pub fn load(path: &Path) -> Result<Vec<String>, MinigrepError> {
+  match fs::read_to_string(path) {
+    Ok(contents) => parse(&contents).map_err(|message| MinigrepError::ConfigFile {
+      path: path.to_path_buf(),
+      message,
+    }),
+    Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+    Err(e) => Err(MinigrepError::Io {
+      path: path.to_path_buf(),
+      source: e,
+    }),
+  }
+}
+
+/// Reads the options in the config file of a project at `path`, if there is
+/// one, which may only set those in `PROJECT_OPTIONS`.
+pub fn load_project(path: &Path) -> Result<Vec<String>, MinigrepError> {
+  let options = load(path)?;
+  for arg in args::Parser::new(options.iter().cloned()) {
+    let name = match arg {
+      Ok(Arg::Option { spec, .. }) if PROJECT_OPTIONS.contains(&spec.long) => continue,
+      Ok(Arg::Option { name, .. }) => name,
+      Ok(Arg::Positional(arg)) => arg,
+      Err(e) => e.to_string(),
+    };
+    return Err(MinigrepError::ConfigFile {
+      path: path.to_path_buf(),
+      message: format!("`{}` cannot be set in the config file of a project", name),
+    });
+  }
+  Ok(options)
+}
+
+/// Whether the command line `args` leave config files to be read, which
+/// they don't when `--no-config` is given as an option rather than as a
+/// pattern, a file or the value of another option.
+pub fn wanted(args: &[String]) -> bool {
+  !args::Parser::new(args.iter().cloned())
+    .any(|arg| matches!(arg, Ok(Arg::Option { spec, .. }) if spec.long == "no-config"))
+}
+
+/// The user's config file, in the home directory.
+pub fn user_path() -> Option<PathBuf> {
+  let home = env::var_os("HOME").filter(|home| !home.is_empty())?;
+  Some(Path::new(&home).join(RC_FILENAME))
+}
+
+/// The project's config file, at the root of the enclosing git repository
+/// or else in the current directory.
+pub fn project_path() -> Option<PathBuf> {
+  let cwd = env::current_dir().ok()?;
+  let project = cwd
+    .ancestors()
+    .find(|dir| dir.join(".git").exists())
+    .unwrap_or(&cwd)
+    .join(RC_FILENAME);
+  Some(project)
+}
+
+/// Options implied by the environment, which take precedence over config
+/// files.
+///
+/// `CASE_INSENSITIVE` is deprecated in favor of `-i` and `--case-sensitive`.
+/// It may be set to `true` or `false` (or `1`/`0`, `yes`/`no`, `on`/`off`);
+/// any other value still turns case sensitivity off, as it used to.
+pub fn env_args() -> Vec<String> {
+  let mut args = Vec::new();
+  if let Ok(value) = env::var("CASE_INSENSITIVE") {
+    eprintln!(
+      "warning: CASE_INSENSITIVE is deprecated, use -i/--ignore-case or \
+       --case-sensitive instead"
+    );
+    let flag = match parse_bool(&value) {
+      Some(false) => "--case-sensitive",
+      _ => "--ignore-case",
+    };
+    args.push(flag.to_string());
+  }
+  args
+}
+
+/// Reads a boolean from an environment variable, ignoring case.
+pub fn parse_bool(value: &str) -> Option<bool> {
+  match value.trim().to_ascii_lowercase().as_str() {
+    "1" | "true" | "yes" | "on" => Some(true),
+    "0" | "false" | "no" | "off" => Some(false),
+    _ => None,
+  }
+}
+
+/// The options from every config file, the user's then the project's, to
+/// be parsed ahead of the command line so that later sources override
+/// earlier ones.
+pub fn file_args() -> Result<Vec<String>, MinigrepError> {
+  let user = user_path();
+  let mut args = match &user {
+    Some(path) => load(path)?,
+    None => Vec::new(),
+  };
+  // The user's own home is no project, even when it holds a repository
+  if let Some(path) = project_path().filter(|path| Some(path) != user.as_ref()) {
+    args.extend(load_project(&path)?);
+  }
+  Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::Fixture;
+
+  #[test]
+  fn parses_one_option_per_line() {
+    let contents = "\
+# defaults
+--ignore-case
+
+  --exclude=target/**
+colors.match = \"bold red\"
+";
+
+    assert_eq!(
+      Ok(vec![
+        "--ignore-case".to_string(),
+        "--exclude=target/**".to_string(),
+        "--colors=match:bold red".to_string()
+      ]),
+      parse(contents)
+    );
+    assert!(parse("--color=never\nfrog\n").is_err());
+  }
+
+  #[test]
+  fn keeps_projects_to_safe_options() {
+    let dir = Fixture::new("rc", &[]);
+    let load =
+      |contents: &str| load_project(&dir.write(RC_FILENAME, contents)).map_err(|e| e.to_string());
+
+    assert_eq!(
+      Ok(vec!["-iC2".to_string(), "--exclude=target/**".to_string()]),
+      load("-iC2\n--exclude=target/**\n")
+    );
+    let pre = load("--ignore-case\n--pre=./x.sh\n");
+    let in_place = load("-n\n--in-place\n");
+    let pattern_file = load("-ifpatterns.txt\n");
+
+    assert!(pre
+      .unwrap_err()
+      .ends_with("`--pre` cannot be set in the config file of a project"));
+    assert!(in_place.unwrap_err().contains("`--in-place`"));
+    assert!(pattern_file.unwrap_err().contains("`-f`"));
+  }
+
+  #[test]
+  fn finds_no_config_among_options_only() {
+    let wanted =
+      |args: &[&str]| wanted(&args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>());
+    assert!(!wanted(&["--no-config", "foo"]));
+    assert!(!wanted(&["-i", "foo", "--no-config"]));
+    assert!(wanted(&["-e", "--no-config", "t.txt"]));
+    assert!(wanted(&["--regexp=--no-config"]));
+    assert!(wanted(&["foo", "--", "--no-config"]));
+  }
+
+  #[test]
+  fn parses_booleans() {
+    assert_eq!(Some(true), parse_bool("TRUE"));
+    assert_eq!(Some(false), parse_bool(" off "));
+    assert_eq!(None, parse_bool(""));
+  }
+}
@@ -0,0 +1,274 @@
+//! Writing results as CSV or TSV rows, for `--format csv` and `--format tsv`,
+//! and reading the rows of CSV and TSV inputs, for `--csv`.
+//!
+//! Both quote fields the way RFC 4180 does, so that spreadsheets and CSV
+//! readers given the right delimiter get back exactly what was matched.
+//! Inputs are read the same way, with the first row taken as the header,
+//! which tells whether fields are separated by commas or tabs.
+
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::Range;
+
+/// The column names, written once before any rows.
+pub const HEADER: [&str; 5] = ["file", "line", "column", "match", "text"];
+
+/// Writes one row, quoting each field that holds the delimiter, a quote or a
+/// line break.
+pub fn write_row<W: Write>(out: &mut W, fields: &[&str], delimiter: char) -> io::Result<()> {
+  for (i, field) in fields.iter().enumerate() {
+    if i > 0 {
+      write!(out, "{}", delimiter)?;
+    }
+    if field.contains([delimiter, '"', '\n', '\r']) {
+      write!(out, "\"{}\"", field.replace('"', "\"\""))?;
+    } else {
+      out.write_all(field.as_bytes())?;
+    }
+  }
+  // RFC 4180 ends rows with CRLF, but every reader accepts a bare LF
+  writeln!(out)
+}
+
+/// The column `--column` matches in, by its name in the header or its
+/// place, counting from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+  Index(usize),
+  Name(String),
+}
+
+impl Column {
+  /// Reads a number as a place, and anything else as a name.
+  pub fn parse(spec: &str) -> Column {
+    match spec.parse::<usize>() {
+      Ok(n) if n > 0 => Column::Index(n - 1),
+      _ => Column::Name(spec.to_string()),
+    }
+  }
+}
+
+impl fmt::Display for Column {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Column::Index(i) => write!(f, "{}", i + 1),
+      Column::Name(name) => write!(f, "{}", name),
+    }
+  }
+}
+
+/// Picks out what is matched in each row of one input: the field in the
+/// column, or the whole row when there is no column.
+pub struct Rows<'a> {
+  column: Option<&'a Column>,
+  layout: Cell<Layout>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+  // The next row is the header
+  Header,
+  Column { delimiter: char, index: usize },
+  WholeRow,
+  // No column in the header has the name
+  Missing,
+}
+
+impl Rows<'_> {
+  pub fn new(column: Option<&Column>) -> Rows<'_> {
+    Rows {
+      column,
+      layout: Cell::new(Layout::Header),
+    }
+  }
+
+  /// What is matched in `row`, which is nothing for the header.
+  pub fn field(&self, row: &str) -> Option<Field> {
+    match self.layout.get() {
+      Layout::Header => {
+        self.layout.set(self.read_header(row));
+        None
+      }
+      Layout::Column { delimiter, index } => {
+        Some(fields(row, delimiter).nth(index).unwrap_or(Field {
+          range: row.len()..row.len(),
+          quoted: false,
+        }))
+      }
+      Layout::WholeRow => Some(Field {
+        range: 0..row.len(),
+        quoted: false,
+      }),
+      Layout::Missing => None,
+    }
+  }
+
+  /// The column no field of the header was named, once the header is read.
+  pub fn missing(&self) -> Option<&Column> {
+    self.column.filter(|_| self.layout.get() == Layout::Missing)
+  }
+
+  fn read_header(&self, header: &str) -> Layout {
+    let delimiter = delimiter_of(header);
+    let index = match self.column {
+      None => return Layout::WholeRow,
+      Some(Column::Index(index)) => Some(*index),
+      Some(Column::Name(name)) => {
+        fields(header, delimiter).position(|field| field.text(header) == name.as_str())
+      }
+    };
+    match index {
+      Some(index) => Layout::Column { delimiter, index },
+      None => Layout::Missing,
+    }
+  }
+}
+
+/// Where a field is in its row, without the quotes around it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+  range: Range<usize>,
+  quoted: bool,
+}
+
+impl Field {
+  /// What the field holds, with doubled quotes read as one.
+  pub fn text<'r>(&self, row: &'r str) -> Cow<'r, str> {
+    let raw = &row[self.range.clone()];
+    if self.quoted && raw.contains("\"\"") {
+      Cow::Owned(raw.replace("\"\"", "\""))
+    } else {
+      Cow::Borrowed(raw)
+    }
+  }
+
+  /// Where `span` of the field's `text` is in `row`.
+  pub fn span_in(&self, row: &str, span: Range<usize>) -> Range<usize> {
+    let raw = &row[self.range.clone()];
+    let offset = |at: usize| {
+      if !self.quoted {
+        return at;
+      }
+      // Every quote before `at` was written twice
+      let quotes = raw.replace("\"\"", "\"")[..at].matches('"').count();
+      at + quotes
+    };
+    self.range.start + offset(span.start)..self.range.start + offset(span.end)
+  }
+}
+
+// The fields of `row`, none of which are split by a delimiter or quote in
+// quotes
+fn fields(row: &str, delimiter: char) -> impl Iterator<Item = Field> + '_ {
+  let mut start = Some(0);
+  std::iter::from_fn(move || {
+    let at = start?;
+    let rest = &row[at..];
+    let (field, end) = match rest.strip_prefix('"') {
+      Some(quoted) => {
+        // Up to the closing quote, or the end of a field left open
+        let len = closing_quote(quoted).unwrap_or(quoted.len());
+        // Anything between the closing quote and the delimiter is dropped
+        let after = (len + 2).min(rest.len());
+        let end = rest[after..].find(delimiter).map(|i| after + i);
+        (
+          Field {
+            range: at + 1..at + 1 + len,
+            quoted: true,
+          },
+          end,
+        )
+      }
+      None => {
+        let end = rest.find(delimiter);
+        (
+          Field {
+            range: at..at + end.unwrap_or(rest.len()),
+            quoted: false,
+          },
+          end,
+        )
+      }
+    };
+    start = end.map(|end| at + end + delimiter.len_utf8());
+    Some(field)
+  })
+}
+
+// Where the quote that ends a quoted field is, once the opening one is
+// left out
+fn closing_quote(quoted: &str) -> Option<usize> {
+  let bytes = quoted.as_bytes();
+  let mut i = 0;
+  while i < bytes.len() {
+    if bytes[i] == b'"' {
+      if bytes.get(i + 1) != Some(&b'"') {
+        return Some(i);
+      }
+      i += 1;
+    }
+    i += 1;
+  }
+  None
+}
+
+// Tabs when the header has more of them than commas, outside of quotes
+fn delimiter_of(header: &str) -> char {
+  let (mut commas, mut tabs, mut quoted) = (0, 0, false);
+  for c in header.chars() {
+    match c {
+      '"' => quoted = !quoted,
+      ',' if !quoted => commas += 1,
+      '\t' if !quoted => tabs += 1,
+      _ => {}
+    }
+  }
+  if tabs > commas {
+    '\t'
+  } else {
+    ','
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn quotes_fields_when_needed() {
+    let mut out = Vec::new();
+    write_row(&mut out, &["a.txt", "3", "say \"hi\", then", "x\ty"], ',').unwrap();
+    write_row(&mut out, &["a.txt", "x\ty", "plain"], '\t').unwrap();
+
+    assert_eq!(
+      "a.txt,3,\"say \"\"hi\"\", then\",x\ty\na.txt\t\"x\ty\"\tplain\n",
+      String::from_utf8(out).unwrap()
+    );
+  }
+
+  #[test]
+  fn finds_columns_by_name_or_place() {
+    let name = Column::parse("note");
+    let rows = Rows::new(Some(&name));
+    assert_eq!(None, rows.field("id,\"note\",when"));
+    let row = "7,\"a \"\"frog\"\", here\",today";
+    let field = rows.field(row).unwrap();
+    assert_eq!("a \"frog\", here", field.text(row));
+    assert_eq!(7..11, field.span_in(row, 3..7));
+    assert_eq!("", rows.field("8").unwrap().text("8"));
+    assert_eq!(None, rows.missing());
+
+    let place = Column::parse("2");
+    let rows = Rows::new(Some(&place));
+    rows.field("id\tnote");
+    assert_eq!("b,c", rows.field("a\tb,c").unwrap().text("a\tb,c"));
+
+    let missing = Column::parse("size");
+    let rows = Rows::new(Some(&missing));
+    rows.field("id,note");
+    assert_eq!(None, rows.field("1,frog"));
+    assert_eq!(Some(&missing), rows.missing());
+  }
+}
@@ -0,0 +1,460 @@
+//! The text of PDF and docx documents, searched in place of their bytes when
+//! minigrep is built with the `documents` feature.
+//!
+//! Only what most documents need is understood. From a PDF, the strings
+//! shown between `BT` and `ET` in its content streams are taken, which may
+//! be stored or deflated, with their bytes read as Latin-1; fonts with their
+//! own encodings come out garbled. From a docx, the text of
+//! `word/document.xml` is taken, with a line for each paragraph.
+
+use crate::archive::{self, Archive};
+use crate::gzip::GzDecoder;
+use std::io::{self, BufRead, Read, Seek};
+use std::path::Path;
+
+const CHUNK_LEN: usize = 32 * 1024;
+// A space is shown for a `TJ` adjustment wider than this, in thousandths of
+// a text space unit
+const WORD_GAP: f64 = 200.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Document {
+  Pdf,
+  Docx,
+}
+
+impl Document {
+  /// Recognizes a document by its extension.
+  pub fn for_path(path: &Path) -> Option<Document> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+      "pdf" => Some(Document::Pdf),
+      "docx" => Some(Document::Docx),
+      _ => None,
+    }
+  }
+}
+
+/// Hands the text of the document in `reader` to `search`.
+pub fn with_text<R, T, F>(document: Document, mut reader: R, search: F) -> io::Result<T>
+where
+  R: BufRead + Seek,
+  F: FnOnce(&mut dyn BufRead) -> io::Result<T>,
+{
+  match document {
+    Document::Pdf => {
+      let mut bytes = Vec::new();
+      reader.read_to_end(&mut bytes)?;
+      search(&mut pdf_text(&bytes).as_bytes())
+    }
+    Document::Docx => {
+      let mut search = Some(search);
+      let mut found = None;
+      archive::for_each_entry(Archive::Zip, reader, |name, entry| {
+        if name == "word/document.xml" {
+          if let Some(search) = search.take() {
+            found = Some(search(&mut XmlText::new(entry))?);
+          }
+        }
+        Ok(())
+      })?;
+      match (found, search) {
+        (Some(found), _) => Ok(found),
+        // A document without any text
+        (None, Some(search)) => search(&mut io::empty()),
+        (None, None) => unreachable!("the search only fails through `?`"),
+      }
+    }
+  }
+}
+
+/// The text shown by the content streams of a PDF, a line for each text
+/// object or move to a new line.
+pub fn pdf_text(pdf: &[u8]) -> String {
+  let mut text = String::new();
+  let mut at = 0;
+  while let Some(start) = find(&pdf[at..], b"stream").map(|i| at + i) {
+    at = start + b"stream".len();
+    if pdf[..start].ends_with(b"end") {
+      continue;
+    }
+    let data_start = match pdf[at..] {
+      [b'\r', b'\n', ..] => at + 2,
+      [b'\n', ..] | [b'\r', ..] => at + 1,
+      _ => continue,
+    };
+    let data_end = match find(&pdf[data_start..], b"endstream") {
+      Some(len) => data_start + len,
+      None => break,
+    };
+    at = data_end;
+    // The dictionary right before the stream says how it is encoded
+    let dictionary = &pdf[rfind(&pdf[..start], b"obj").unwrap_or(0)..start];
+    let data = &pdf[data_start..data_end];
+    if find(dictionary, b"/FlateDecode").is_some() {
+      let mut inflated = Vec::new();
+      // Past the two byte zlib header, which says nothing that matters here
+      let deflated = data.get(2..).unwrap_or_default();
+      if GzDecoder::raw(deflated).read_to_end(&mut inflated).is_ok() {
+        show_text(&inflated, &mut text);
+      }
+    } else if find(dictionary, b"/Filter").is_none() {
+      show_text(data, &mut text);
+    }
+  }
+  text
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+  String(Vec<u8>),
+  Number(f64),
+  ArrayStart,
+  ArrayEnd,
+  Operator(Vec<u8>),
+}
+
+// Appends the text a content stream shows to `text`
+fn show_text(content: &[u8], text: &mut String) {
+  let mut operands = Vec::new();
+  let mut in_array = false;
+  let tokens = Tokens {
+    bytes: content,
+    at: 0,
+  };
+  let new_line = |text: &mut String| {
+    if !text.is_empty() && !text.ends_with('\n') {
+      text.push('\n');
+    }
+  };
+  for token in tokens {
+    match token {
+      Token::ArrayStart => in_array = true,
+      Token::ArrayEnd => in_array = false,
+      Token::Operator(operator) => {
+        match operator.as_slice() {
+          b"T*" | b"'" | b"\"" | b"ET" => new_line(text),
+          b"Td" | b"TD" => match operands.as_slice() {
+            [.., Token::Number(_), Token::Number(y)] if *y != 0.0 => new_line(text),
+            _ => {}
+          },
+          _ => {}
+        }
+        if matches!(operator.as_slice(), b"Tj" | b"TJ" | b"'" | b"\"") {
+          for operand in &operands {
+            match operand {
+              Token::String(bytes) => text.extend(bytes.iter().map(|&b| b as char)),
+              Token::Number(gap) if *gap < -WORD_GAP && !text.ends_with(' ') => text.push(' '),
+              _ => {}
+            }
+          }
+        }
+        operands.clear();
+      }
+      // Only what a `TJ` array holds and the last two numbers before an
+      // operator matter
+      Token::Number(_) if !in_array && operands.len() >= 2 => {
+        operands.remove(0);
+        operands.push(token);
+      }
+      token => operands.push(token),
+    }
+  }
+  new_line(text);
+}
+
+struct Tokens<'a> {
+  bytes: &'a [u8],
+  at: usize,
+}
+
+impl Iterator for Tokens<'_> {
+  type Item = Token;
+
+  fn next(&mut self) -> Option<Token> {
+    loop {
+      let &byte = self.bytes.get(self.at)?;
+      self.at += 1;
+      match byte {
+        b'%' => {
+          while self.bytes.get(self.at).is_some_and(|&b| b != b'\n') {
+            self.at += 1;
+          }
+        }
+        b'(' => return Some(Token::String(self.literal_string())),
+        b'<' if self.bytes.get(self.at) == Some(&b'<') => self.at += 1,
+        b'>' if self.bytes.get(self.at) == Some(&b'>') => self.at += 1,
+        b'<' => return Some(Token::String(self.hex_string())),
+        b'[' => return Some(Token::ArrayStart),
+        b']' => return Some(Token::ArrayEnd),
+        b'/' | b'{' | b'}' => {
+          // Names and the rest are never shown, so they are only skipped
+          self.word();
+        }
+        b if b.is_ascii_whitespace() => {}
+        b'0'..=b'9' | b'+' | b'-' | b'.' => {
+          let start = self.at - 1;
+          self.word();
+          let word = String::from_utf8_lossy(&self.bytes[start..self.at]);
+          return Some(Token::Number(word.parse().unwrap_or(0.0)));
+        }
+        _ => {
+          let start = self.at - 1;
+          self.word();
+          return Some(Token::Operator(self.bytes[start..self.at].to_vec()));
+        }
+      }
+    }
+  }
+}
+
+impl Tokens<'_> {
+  // Moves past the rest of a name, number or operator
+  fn word(&mut self) {
+    while let Some(&b) = self.bytes.get(self.at) {
+      if b.is_ascii_whitespace() || b"()<>[]{}/%".contains(&b) {
+        break;
+      }
+      self.at += 1;
+    }
+  }
+
+  // The rest of a `(string)`, which may hold balanced parentheses
+  fn literal_string(&mut self) -> Vec<u8> {
+    let mut string = Vec::new();
+    let mut depth = 0;
+    while let Some(&b) = self.bytes.get(self.at) {
+      self.at += 1;
+      match b {
+        b'(' => depth += 1,
+        b')' if depth == 0 => break,
+        b')' => depth -= 1,
+        b'\\' => {
+          let escaped = match self.bytes.get(self.at) {
+            Some(&escaped) => escaped,
+            None => break,
+          };
+          self.at += 1;
+          match escaped {
+            b'n' => string.push(b'\n'),
+            b'r' => string.push(b'\r'),
+            b't' => string.push(b'\t'),
+            b'b' => string.push(8),
+            b'f' => string.push(12),
+            b'0'..=b'7' => {
+              let mut code = (escaped - b'0') as u32;
+              for _ in 0..2 {
+                match self.bytes.get(self.at) {
+                  Some(&digit @ b'0'..=b'7') => {
+                    code = code * 8 + (digit - b'0') as u32;
+                    self.at += 1;
+                  }
+                  _ => break,
+                }
+              }
+              string.push(code as u8);
+            }
+            // A backslash at the end of a line continues the string
+            b'\r' | b'\n' => {
+              if escaped == b'\r' && self.bytes.get(self.at) == Some(&b'\n') {
+                self.at += 1;
+              }
+            }
+            escaped => string.push(escaped),
+          }
+          continue;
+        }
+        _ => {}
+      }
+      string.push(b);
+    }
+    string
+  }
+
+  // The rest of a `<hex string>`, where a missing last digit is a zero
+  fn hex_string(&mut self) -> Vec<u8> {
+    let mut digits = Vec::new();
+    while let Some(&b) = self.bytes.get(self.at) {
+      self.at += 1;
+      match (b as char).to_digit(16) {
+        Some(digit) => digits.push(digit as u8),
+        None if b == b'>' => break,
+        None => {}
+      }
+    }
+    digits
+      .chunks(2)
+      .map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0))
+      .collect()
+  }
+}
+
+/// The text of an XML document as it is read, without its markup. Paragraph
+/// ends and breaks become newlines and tabs become tabs, as in a docx.
+pub struct XmlText<R> {
+  inner: R,
+  state: XmlState,
+  // A tag or entity read partway at the end of a chunk
+  pending: Vec<u8>,
+  output: Vec<u8>,
+  position: usize,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum XmlState {
+  Text,
+  Tag,
+  Entity,
+}
+
+impl<R: BufRead> XmlText<R> {
+  pub fn new(inner: R) -> XmlText<R> {
+    XmlText {
+      inner,
+      state: XmlState::Text,
+      pending: Vec::new(),
+      output: Vec::with_capacity(CHUNK_LEN),
+      position: 0,
+    }
+  }
+
+  // Reads until there is some output or the input ends
+  fn fill(&mut self) -> io::Result<()> {
+    self.output.clear();
+    self.position = 0;
+    while self.output.is_empty() {
+      let chunk = self.inner.fill_buf()?;
+      if chunk.is_empty() {
+        return Ok(());
+      }
+      let len = chunk.len().min(CHUNK_LEN);
+      for &b in &chunk[..len] {
+        match (self.state, b) {
+          (XmlState::Text, b'<') => self.state = XmlState::Tag,
+          (XmlState::Text, b'&') => self.state = XmlState::Entity,
+          (XmlState::Text, b) => self.output.push(b),
+          (XmlState::Tag, b'>') => {
+            self.output.extend(tag_text(&self.pending));
+            self.pending.clear();
+            self.state = XmlState::Text;
+          }
+          (XmlState::Entity, b';') => {
+            let mut c = [0; 4];
+            let c = entity(&self.pending)
+              .unwrap_or('\u{FFFD}')
+              .encode_utf8(&mut c);
+            self.output.extend(c.as_bytes());
+            self.pending.clear();
+            self.state = XmlState::Text;
+          }
+          (_, b) => self.pending.push(b),
+        }
+      }
+      self.inner.consume(len);
+    }
+    Ok(())
+  }
+}
+
+// What a tag stands for in the text
+fn tag_text(tag: &[u8]) -> &'static [u8] {
+  let name_len = tag
+    .iter()
+    .position(|&b| b.is_ascii_whitespace() || (b == b'/' && tag[0] != b'/'))
+    .unwrap_or(tag.len());
+  match &tag[..name_len] {
+    b"/w:p" | b"w:br" | b"w:cr" => b"\n",
+    b"w:tab" => b"\t",
+    _ => b"",
+  }
+}
+
+fn entity(name: &[u8]) -> Option<char> {
+  match name {
+    b"amp" => Some('&'),
+    b"lt" => Some('<'),
+    b"gt" => Some('>'),
+    b"quot" => Some('"'),
+    b"apos" => Some('\''),
+    [b'#', b'x', hex @ ..] => {
+      char::from_u32(u32::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?)
+    }
+    [b'#', decimal @ ..] => char::from_u32(std::str::from_utf8(decimal).ok()?.parse().ok()?),
+    _ => None,
+  }
+}
+
+impl<R: BufRead> Read for XmlText<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let available = self.fill_buf()?;
+    let len = available.len().min(buf.len());
+    buf[..len].copy_from_slice(&available[..len]);
+    self.consume(len);
+    Ok(len)
+  }
+}
+
+impl<R: BufRead> BufRead for XmlText<R> {
+  fn fill_buf(&mut self) -> io::Result<&[u8]> {
+    if self.position == self.output.len() {
+      self.fill()?;
+    }
+    Ok(&self.output[self.position..])
+  }
+
+  fn consume(&mut self, amount: usize) {
+    self.position = (self.position + amount).min(self.output.len());
+  }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack
+    .windows(needle.len())
+    .position(|window| window == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+  haystack
+    .windows(needle.len())
+    .rposition(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn extracts_pdf_text() {
+    let content = b"BT /F1 12 Tf 72 712 Td (Invoice \\(paid\\)) Tj 0 -14 Td [(to)-250(tal)] TJ ET";
+    // The same content deflated as a single stored block, in a zlib stream
+    let len = (content.len() as u16).to_le_bytes();
+    let nlen = (!(content.len() as u16)).to_le_bytes();
+    let deflated = [&[0x78, 0x01, 0x01][..], &len, &nlen, content, &[0, 0, 0, 0]].concat();
+    let pdf = [
+      &b"%PDF-1.4\n4 0 obj << /Length 80 >>\nstream\n"[..],
+      content,
+      b"\nendstream\nendobj\n5 0 obj << /Filter /FlateDecode >>\nstream\r\n",
+      &deflated,
+      b"\nendstream\nendobj\n6 0 obj << /Filter /DCTDecode >>\nstream\n(no) Tj\nendstream\n",
+    ]
+    .concat();
+
+    assert_eq!(
+      "Invoice (paid)\nto tal\nInvoice (paid)\nto tal\n",
+      pdf_text(&pdf)
+    );
+    assert_eq!(Some(Document::Pdf), Document::for_path(Path::new("a.PDF")));
+  }
+
+  #[test]
+  fn strips_xml_markup() {
+    let xml = "<w:body><w:p><w:r><w:t xml:space=\"preserve\">Fish &amp; chips</w:t><w:tab/>\
+      <w:t>&#163;3</w:t></w:r></w:p><w:p><w:r><w:t>mushy peas</w:t></w:r></w:p></w:body>";
+    let mut text = String::new();
+    // A tiny buffer splits tags and entities across reads
+    XmlText::new(io::BufReader::with_capacity(3, xml.as_bytes()))
+      .read_to_string(&mut text)
+      .unwrap();
+    assert_eq!("Fish & chips\t£3\nmushy peas\n", text);
+  }
+}
@@ -0,0 +1,130 @@
+use crate::{is_binary, line_matcher, match_groups, trim_line_ending, Config};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// How files are rewritten by `--in-place`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InPlace {
+  /// Keep the original file next to the new one, with this appended to its
+  /// name.
+  pub backup_suffix: Option<String>,
+}
+
+/// Applies `config.replace` to every matching line of the file at `path` and
+/// returns how many lines were replaced. Files without a match are left alone.
+///
+/// The new contents are written to a temporary file in the same directory,
+/// which then replaces the original in a single rename, so the file is never
+/// seen half written. Symbolic links are followed and the file they point to
+/// is rewritten, keeping its permissions.
+pub fn edit_file(config: &Config, path: &Path, in_place: &InPlace) -> io::Result<usize> {
+  let target = fs::canonicalize(path)?;
+  let temp = temp_path(&target);
+  let result = rewrite(config, &target, &temp, in_place);
+  if !matches!(result, Ok(count) if count > 0) {
+    // Nothing to keep, whether it failed or there was nothing to replace
+    let _ = fs::remove_file(&temp);
+  }
+  result
+}
+
+fn rewrite(config: &Config, target: &Path, temp: &Path, in_place: &InPlace) -> io::Result<usize> {
+  let template = match &config.replace {
+    Some(template) => template,
+    None => return Ok(0),
+  };
+  let permissions = fs::metadata(target)?.permissions();
+  let mut reader = BufReader::new(File::open(target)?);
+  if !config.binary && is_binary(reader.fill_buf()?) {
+    return Ok(0);
+  }
+
+  let file = OpenOptions::new().write(true).create_new(true).open(temp)?;
+  let mut writer = BufWriter::new(file);
+  let find = line_matcher(config, true);
+  let mut buffer = Vec::new();
+  let mut count = 0;
+  loop {
+    buffer.clear();
+    if reader.read_until(b'\n', &mut buffer)? == 0 {
+      break;
+    }
+    let limit_reached = config.max_count.is_some_and(|max| count >= max);
+    // Lines that are not valid UTF-8 are copied over untouched
+    let text = match std::str::from_utf8(&buffer) {
+      Ok(text) if !limit_reached => text,
+      _ => {
+        writer.write_all(&buffer)?;
+        continue;
+      }
+    };
+    let line = trim_line_ending(text);
+    match find(line).filter(|hit| !hit.spans.is_empty()) {
+      Some(hit) => {
+        count += 1;
+        writer.write_all(
+          template
+            .replace(line, &hit.spans, |span| match_groups(config, line, span))
+            .as_bytes(),
+        )?;
+        writer.write_all(&buffer[line.len()..])?;
+      }
+      None => writer.write_all(&buffer)?,
+    }
+  }
+  if count == 0 {
+    return Ok(0);
+  }
+
+  let file = writer.into_inner().map_err(|e| e.into_error())?;
+  file.sync_all()?;
+  fs::set_permissions(temp, permissions)?;
+  if let Some(suffix) = &in_place.backup_suffix {
+    let mut backup = target.as_os_str().to_owned();
+    backup.push(suffix);
+    fs::copy(target, backup)?;
+  }
+  fs::rename(temp, target)?;
+  Ok(count)
+}
+
+// Next to the target, so that renaming never crosses file systems
+fn temp_path(target: &Path) -> PathBuf {
+  let name = target.file_name().unwrap_or_default().to_string_lossy();
+  target.with_file_name(format!(".{}.minigrep-{}.tmp", name, process::id()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::Fixture;
+  use crate::{replace::Template, Pattern};
+
+  #[test]
+  fn rewrites_matching_files() {
+    let dir = Fixture::new("edit", &[]);
+    let path = dir.write("poem.txt", "a frog\r\na bog\nfrog\n");
+
+    let mut config = crate::tests::config("frog");
+    config.replace = Some(Template::new("toad"));
+    let in_place = InPlace {
+      backup_suffix: Some(".bak".to_string()),
+    };
+
+    assert_eq!(2, edit_file(&config, &path, &in_place).unwrap());
+    assert_eq!(
+      "a toad\r\na bog\ntoad\n",
+      fs::read_to_string(&path).unwrap()
+    );
+    assert_eq!(
+      "a frog\r\na bog\nfrog\n",
+      fs::read_to_string(dir.join("poem.txt.bak")).unwrap()
+    );
+
+    config.patterns = vec![Pattern::Literal("newt".to_string())];
+    assert_eq!(0, edit_file(&config, &path, &in_place).unwrap());
+    assert_eq!(2, fs::read_dir(dir.path()).unwrap().count());
+  }
+}
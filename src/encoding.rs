@@ -0,0 +1,253 @@
+use std::io::{self, BufRead, Read};
+
+/// A text encoding that input can be transcoded from before searching.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Encoding {
+  Utf8,
+  /// UTF-16 in the byte order its byte order mark gives, or little-endian
+  /// without one.
+  Utf16,
+  Utf16Le,
+  Utf16Be,
+  /// ISO-8859-1, where every byte is the code point of the same value.
+  Latin1,
+}
+
+impl Encoding {
+  /// Looks up an encoding by one of its common names, ignoring case.
+  pub fn for_label(label: &str) -> Option<Encoding> {
+    match label.trim().to_ascii_lowercase().as_str() {
+      "utf-8" | "utf8" => Some(Encoding::Utf8),
+      "utf-16" | "utf16" => Some(Encoding::Utf16),
+      "utf-16le" | "utf16le" => Some(Encoding::Utf16Le),
+      "utf-16be" | "utf16be" => Some(Encoding::Utf16Be),
+      "latin1" | "latin-1" | "iso-8859-1" | "iso8859-1" | "l1" => Some(Encoding::Latin1),
+      _ => None,
+    }
+  }
+
+  /// Transcodes all of `bytes` to UTF-8, replacing anything malformed with
+  /// `U+FFFD`.
+  pub fn decode(self, bytes: &[u8]) -> String {
+    let mut output = Vec::with_capacity(bytes.len());
+    Decoder::new(self).decode(bytes, true, &mut output);
+    // The decoder only ever produces valid UTF-8
+    String::from_utf8(output).unwrap()
+  }
+}
+
+/// Incrementally transcodes chunks of input, carrying partial characters from
+/// one chunk over to the next.
+#[derive(Debug)]
+struct Decoder {
+  encoding: Encoding,
+  pending: Vec<u8>,
+  started: bool,
+}
+
+const BOM: char = '\u{feff}';
+
+impl Decoder {
+  fn new(encoding: Encoding) -> Decoder {
+    Decoder {
+      encoding,
+      pending: Vec::new(),
+      started: false,
+    }
+  }
+
+  // Appends the UTF-8 for `input` to `output`. `last` flushes whatever
+  // incomplete character is left over.
+  fn decode(&mut self, input: &[u8], last: bool, output: &mut Vec<u8>) {
+    self.pending.extend_from_slice(input);
+    if self.encoding == Encoding::Utf16 {
+      self.encoding = match self.pending.get(..2) {
+        Some([0xfe, 0xff]) => Encoding::Utf16Be,
+        Some(_) => Encoding::Utf16Le,
+        // The order is not known until the mark could be read
+        None if !last => return,
+        None => Encoding::Utf16Le,
+      };
+    }
+    let mut text = String::new();
+    let consumed = match self.encoding {
+      Encoding::Utf8 => decode_utf8(&self.pending, last, &mut text),
+      Encoding::Utf16 | Encoding::Utf16Le => {
+        decode_utf16(&self.pending, last, u16::from_le_bytes, &mut text)
+      }
+      Encoding::Utf16Be => decode_utf16(&self.pending, last, u16::from_be_bytes, &mut text),
+      Encoding::Latin1 => {
+        text.extend(self.pending.iter().map(|&b| b as char));
+        self.pending.len()
+      }
+    };
+    self.pending.drain(..consumed);
+
+    // A byte order mark only means something at the very start
+    let mut text = text.as_str();
+    if !self.started && !text.is_empty() {
+      self.started = true;
+      text = text.strip_prefix(BOM).unwrap_or(text);
+    }
+    output.extend_from_slice(text.as_bytes());
+  }
+}
+
+// Returns how many bytes were decoded, leaving a truncated trailing sequence
+// for the next chunk unless this is the last one.
+fn decode_utf8(bytes: &[u8], last: bool, text: &mut String) -> usize {
+  let mut rest = bytes;
+  loop {
+    match std::str::from_utf8(rest) {
+      Ok(valid) => {
+        text.push_str(valid);
+        return bytes.len();
+      }
+      Err(e) => {
+        let (valid, after) = rest.split_at(e.valid_up_to());
+        text.push_str(std::str::from_utf8(valid).unwrap());
+        match e.error_len() {
+          Some(len) => {
+            text.push(char::REPLACEMENT_CHARACTER);
+            rest = &after[len..];
+          }
+          None if last => {
+            text.push(char::REPLACEMENT_CHARACTER);
+            return bytes.len();
+          }
+          None => return bytes.len() - after.len(),
+        }
+      }
+    }
+  }
+}
+
+fn decode_utf16(bytes: &[u8], last: bool, unit: fn([u8; 2]) -> u16, text: &mut String) -> usize {
+  let mut units: Vec<u16> = bytes.chunks_exact(2).map(|b| unit([b[0], b[1]])).collect();
+  let mut consumed = units.len() * 2;
+  // Keep a lone leading surrogate around until its pair arrives
+  if !last && units.last().is_some_and(|u| (0xd800..0xdc00).contains(u)) {
+    units.pop();
+    consumed -= 2;
+  }
+  text.extend(char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)));
+  if last && bytes.len() % 2 == 1 {
+    text.push(char::REPLACEMENT_CHARACTER);
+    consumed += 1;
+  }
+  consumed
+}
+
+/// Wraps a reader so that it yields UTF-8 transcoded from `encoding`.
+pub struct DecodeReader<R> {
+  inner: R,
+  decoder: Decoder,
+  output: Vec<u8>,
+  position: usize,
+  done: bool,
+}
+
+const CHUNK_LEN: usize = 8 * 1024;
+
+impl<R: Read> DecodeReader<R> {
+  pub fn new(inner: R, encoding: Encoding) -> DecodeReader<R> {
+    DecodeReader {
+      inner,
+      decoder: Decoder::new(encoding),
+      output: Vec::new(),
+      position: 0,
+      done: false,
+    }
+  }
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let available = self.fill_buf()?;
+    let len = available.len().min(buf.len());
+    buf[..len].copy_from_slice(&available[..len]);
+    self.consume(len);
+    Ok(len)
+  }
+}
+
+impl<R: Read> BufRead for DecodeReader<R> {
+  fn fill_buf(&mut self) -> io::Result<&[u8]> {
+    let mut chunk = [0; CHUNK_LEN];
+    // A chunk may hold nothing but part of a character, so keep reading
+    while self.position == self.output.len() && !self.done {
+      self.output.clear();
+      self.position = 0;
+      let len = self.inner.read(&mut chunk)?;
+      self.done = len == 0;
+      self
+        .decoder
+        .decode(&chunk[..len], self.done, &mut self.output);
+    }
+    Ok(&self.output[self.position..])
+  }
+
+  fn consume(&mut self, amount: usize) {
+    self.position = (self.position + amount).min(self.output.len());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect()
+  }
+
+  #[test]
+  fn decodes_whole_inputs() {
+    assert_eq!("café", Encoding::Latin1.decode(b"caf\xe9"));
+    assert_eq!("߿ ok", Encoding::Utf16Le.decode(&utf16le("\u{feff}߿ ok")));
+    assert_eq!("ok\u{fffd}", Encoding::Utf16Be.decode(b"\0o\0k\xd8\x3d"));
+    assert_eq!("a\u{fffd}b", Encoding::Utf8.decode(b"a\xffb"));
+    assert_eq!(Some(Encoding::Utf16Le), Encoding::for_label("UTF-16LE"));
+    assert_eq!(Some(Encoding::Utf16), Encoding::for_label("utf-16"));
+    assert_eq!(None, Encoding::for_label("ebcdic"));
+  }
+
+  #[test]
+  fn reads_the_byte_order_of_utf16_from_its_mark() {
+    let big_endian: Vec<u8> = "\u{feff}a line\n"
+      .encode_utf16()
+      .flat_map(|u| u.to_be_bytes())
+      .collect();
+    assert_eq!("a line\n", Encoding::Utf16.decode(&big_endian));
+    assert_eq!(
+      "a line\n",
+      Encoding::Utf16.decode(&utf16le("\u{feff}a line\n"))
+    );
+    assert_eq!("a line\n", Encoding::Utf16.decode(&utf16le("a line\n")));
+    assert_eq!("\u{fffd}", Encoding::Utf16.decode(b"a"));
+
+    // Even when the mark comes a byte at a time
+    let mut reader = DecodeReader::new(
+      io::Read::chain(&big_endian[..1], &big_endian[1..]),
+      Encoding::Utf16,
+    );
+    let mut decoded = String::new();
+    reader.read_to_string(&mut decoded).unwrap();
+    assert_eq!("a line\n", decoded);
+  }
+
+  #[test]
+  fn streams_characters_split_across_chunks() {
+    // Pad so that the surrogate pair straddles the first chunk boundary
+    let text = format!("{}🦀\nsafe\n", "x".repeat(CHUNK_LEN / 2 - 1));
+    let bytes = utf16le(&text);
+    let mut reader = DecodeReader::new(&bytes[..], Encoding::Utf16Le);
+    let mut decoded = String::new();
+    reader.read_to_string(&mut decoded).unwrap();
+    assert_eq!(text, decoded);
+
+    let text = format!("{}é\n", "x".repeat(CHUNK_LEN - 1));
+    let reader = DecodeReader::new(text.as_bytes(), Encoding::Utf8);
+    let lines: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
+    assert_eq!(vec![text.trim_end().to_string()], lines);
+  }
+}
@@ -0,0 +1,147 @@
+use crate::glob::GlobError;
+use crate::regex::RegexError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Everything that can go wrong while configuring or running a search.
+#[derive(Debug)]
+pub enum MinigrepError {
+  /// No query was given, neither as an argument nor through `-e` or `-f`.
+  MissingQuery,
+  /// No file was given and standard input is a terminal.
+  MissingFile,
+  UnknownOption(String),
+  MissingValue {
+    option: String,
+  },
+  InvalidValue {
+    option: String,
+    value: String,
+  },
+  /// A value was attached to an option that does not take one.
+  UnexpectedValue {
+    option: String,
+  },
+  InvalidPattern {
+    pattern: String,
+    source: RegexError,
+  },
+  InvalidGlob(GlobError),
+  /// Options that were given together but cannot work together.
+  IncompatibleOptions(&'static str),
+  /// A config file that could not be read or parsed.
+  ConfigFile {
+    path: PathBuf,
+    message: String,
+  },
+  /// An input, pattern file or directory that could not be read.
+  Io {
+    path: PathBuf,
+    source: io::Error,
+  },
+  /// Writing the results failed.
+  Output(io::Error),
+}
+
+impl MinigrepError {
+  /// Whether the command line itself was wrong, so that showing the usage
+  /// is helpful.
+  pub fn is_usage(&self) -> bool {
+    matches!(
+      self,
+      MinigrepError::MissingQuery
+        | MinigrepError::MissingFile
+        | MinigrepError::UnknownOption(_)
+        | MinigrepError::MissingValue { .. }
+        | MinigrepError::InvalidValue { .. }
+        | MinigrepError::UnexpectedValue { .. }
+        | MinigrepError::InvalidGlob(_)
+        | MinigrepError::IncompatibleOptions(_)
+    )
+  }
+
+  /// Whether the results stopped being read, as they do once `head` has
+  /// had its lines, which is no reason to complain.
+  pub fn is_broken_pipe(&self) -> bool {
+    matches!(self, MinigrepError::Output(e) if e.kind() == io::ErrorKind::BrokenPipe)
+  }
+}
+
+impl fmt::Display for MinigrepError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      MinigrepError::MissingQuery => write!(f, "Missing query"),
+      MinigrepError::MissingFile => write!(f, "Missing file to search"),
+      MinigrepError::UnknownOption(option) => write!(f, "Unsupported option `{}`", option),
+      MinigrepError::MissingValue { option } => write!(f, "Missing value for `{}`", option),
+      MinigrepError::InvalidValue { option, value } => {
+        write!(f, "Invalid value `{}` for `{}`", value, option)
+      }
+      MinigrepError::UnexpectedValue { option } => write!(f, "Unexpected value for `{}`", option),
+      MinigrepError::InvalidPattern { pattern, source } => {
+        write!(f, "Invalid pattern `{}`: {}", pattern, source)
+      }
+      MinigrepError::InvalidGlob(e) => write!(f, "{}", e),
+      MinigrepError::IncompatibleOptions(reason) => write!(f, "{}", reason),
+      MinigrepError::ConfigFile { path, message } => write!(f, "{}: {}", path.display(), message),
+      MinigrepError::Io { path, source } => write!(f, "{}: {}", path.display(), describe(source)),
+      MinigrepError::Output(e) => write!(f, "writing output: {}", e),
+    }
+  }
+}
+
+impl Error for MinigrepError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match self {
+      MinigrepError::InvalidPattern { source, .. } => Some(source),
+      MinigrepError::InvalidGlob(e) => Some(e),
+      MinigrepError::Io { source, .. } => Some(source),
+      MinigrepError::Output(e) => Some(e),
+      _ => None,
+    }
+  }
+}
+
+/// What went wrong with a file, as grep words it, without the `(os error 2)`
+/// that comes after the system's own message.
+pub fn describe(e: &io::Error) -> String {
+  let message = e.to_string();
+  match message.rfind(" (os error ") {
+    Some(end) if e.raw_os_error().is_some() => message[..end].to_string(),
+    _ => message,
+  }
+}
+
+impl From<GlobError> for MinigrepError {
+  fn from(e: GlobError) -> MinigrepError {
+    MinigrepError::InvalidGlob(e)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn describes_errors() {
+    let e = MinigrepError::InvalidValue {
+      option: "-A".to_string(),
+      value: "many".to_string(),
+    };
+    assert_eq!("Invalid value `many` for `-A`", e.to_string());
+    assert!(e.is_usage());
+
+    let e = MinigrepError::Io {
+      path: PathBuf::from("poem.txt"),
+      source: io::Error::new(io::ErrorKind::NotFound, "not found"),
+    };
+    assert_eq!("poem.txt: not found", e.to_string());
+    assert!(!e.is_usage());
+    assert!(e.source().is_some());
+    let e = io::Error::from(io::ErrorKind::NotFound);
+    assert_eq!(e.to_string(), describe(&e));
+    assert!(!describe(&io::Error::from_raw_os_error(2)).contains("os error"));
+  }
+}
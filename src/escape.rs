@@ -0,0 +1,165 @@
+//! Keeping the bytes of inputs that are not valid UTF-8, to print them as
+//! they were, or for binary inputs searched with `-a` to print them and the
+//! ones a terminal would act on as escapes such as `\x00`.
+//!
+//! Matching is done on text, so each byte that is not part of valid UTF-8
+//! is read as a character of its own, from the end of the last private use
+//! plane where no text is likely to have any. Patterns never match those
+//! but `.` does, once for every byte. Each takes four bytes in the text for
+//! the one it stands for, which `offset` accounts for.
+
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::ops::Range;
+
+// Where the characters standing for raw bytes start
+const RAW_BYTES: u32 = 0x10_FF00;
+
+/// Reads `bytes` as UTF-8, keeping every byte that is not valid as a
+/// character of its own.
+pub fn decode(bytes: &[u8]) -> Cow<'_, str> {
+  let mut rest = match std::str::from_utf8(bytes) {
+    Ok(text) => return Cow::Borrowed(text),
+    Err(_) => bytes,
+  };
+  let mut text = String::with_capacity(bytes.len() + 8);
+  while !rest.is_empty() {
+    match std::str::from_utf8(rest) {
+      Ok(valid) => {
+        text.push_str(valid);
+        break;
+      }
+      Err(e) => {
+        let (valid, invalid) = rest.split_at(e.valid_up_to());
+        // Checked for UTF-8 just now
+        text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+        let len = e.error_len().unwrap_or(invalid.len());
+        for &b in &invalid[..len] {
+          text.push(raw_byte(b));
+        }
+        rest = &invalid[len..];
+      }
+    }
+  }
+  Cow::Owned(text)
+}
+
+fn raw_byte(b: u8) -> char {
+  char::from_u32(RAW_BYTES + u32::from(b)).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// The byte `c` stands for, when it is one `decode` kept.
+pub fn kept_byte(c: char) -> Option<u8> {
+  u32::from(c)
+    .checked_sub(RAW_BYTES)
+    .filter(|&b| b <= 0xFF)
+    .map(|b| b as u8)
+}
+
+/// The bytes `text` was decoded from, undoing `decode`.
+pub fn encode(text: &str) -> Cow<'_, [u8]> {
+  if !text.chars().any(|c| kept_byte(c).is_some()) {
+    return Cow::Borrowed(text.as_bytes());
+  }
+  let mut bytes = Vec::with_capacity(text.len());
+  for c in text.chars() {
+    match kept_byte(c) {
+      Some(b) => bytes.push(b),
+      None => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+    }
+  }
+  Cow::Owned(bytes)
+}
+
+/// `text` with the bytes `decode` kept replaced by U+FFFD, for output that
+/// has to be valid UTF-8.
+pub fn lossy(text: &str) -> Cow<'_, str> {
+  if !text.chars().any(|c| kept_byte(c).is_some()) {
+    return Cow::Borrowed(text);
+  }
+  let replaced = text.chars().map(|c| match kept_byte(c) {
+    Some(_) => char::REPLACEMENT_CHARACTER,
+    None => c,
+  });
+  Cow::Owned(replaced.collect())
+}
+
+/// Where byte offset `i` of the decoded `text` is in the bytes it was
+/// decoded from.
+pub fn offset(text: &str, i: usize) -> usize {
+  let kept = text[..i]
+    .chars()
+    .filter(|&c| kept_byte(c).is_some())
+    .count();
+  i - 3 * kept
+}
+
+/// Where `span` of the decoded `text` is in the bytes it was decoded from.
+pub fn span(text: &str, span: &Range<usize>) -> Range<usize> {
+  offset(text, span.start)..offset(text, span.end)
+}
+
+/// `line` with every control character other than a tab, and every byte
+/// `decode` kept, written as an escape, and `spans` moved along with it.
+/// Returns `None` when there is nothing to escape.
+pub fn escape(line: &str, spans: &[Range<usize>]) -> Option<(String, Vec<Range<usize>>)> {
+  if !line.chars().any(needs_escape) {
+    return None;
+  }
+  let mut escaped = String::with_capacity(line.len() + 16);
+  // Where each character of `line` went in `escaped`, and where it ended
+  let mut moved = Vec::with_capacity(line.len() + 1);
+  for (i, c) in line.char_indices() {
+    moved.resize(i + 1, escaped.len());
+    let code = u32::from(c);
+    if let Some(b) = kept_byte(c) {
+      let _ = write!(escaped, "\\x{:02X}", b);
+    } else if needs_escape(c) && code < 0x80 {
+      let _ = write!(escaped, "\\x{:02X}", code);
+    } else if needs_escape(c) {
+      let _ = write!(escaped, "\\u{{{:X}}}", code);
+    } else {
+      escaped.push(c);
+    }
+  }
+  moved.resize(line.len() + 1, escaped.len());
+  let spans = spans
+    .iter()
+    .map(|span| moved[span.start]..moved[span.end])
+    .collect();
+  Some((escaped, spans))
+}
+
+fn needs_escape(c: char) -> bool {
+  (c.is_control() && c != '\t') || kept_byte(c).is_some()
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keeps_raw_bytes_and_escapes_them() {
+    assert!(matches!(decode(b"frog"), Cow::Borrowed("frog")));
+    let decoded = decode(b"\xFFfrog\x00\xC3");
+    assert_eq!(4 + 4 + 1 + 4, decoded.len());
+
+    let (escaped, spans) = escape(&decoded, &[4..8]).unwrap();
+    assert_eq!(r"\xFFfrog\x00\xC3", escaped);
+    assert_eq!(vec![4..8], spans);
+    let (escaped, _) = escape("a\u{1b}[1m\u{85}\tb", &[]).unwrap();
+    assert_eq!("a\\x1B[1m\\u{85}\tb", escaped);
+    assert_eq!(None, escape("plain\ttext", &[]));
+  }
+
+  #[test]
+  fn gives_back_the_bytes_it_kept() {
+    let decoded = decode(b"caf\xE9 foo");
+    assert_eq!(&b"caf\xE9 foo"[..], &encode(&decoded)[..]);
+    assert_eq!("caf\u{FFFD} foo", lossy(&decoded));
+    let foo = decoded.find("foo").unwrap();
+    assert_eq!(5, offset(&decoded, foo));
+    assert!(matches!(encode("café"), Cow::Borrowed(_)));
+  }
+}
@@ -0,0 +1,162 @@
+//! The messages `--json-lines` prints, one JSON object per line, in the
+//! schema of ripgrep's `--json` so that tools reading that can read these.
+//!
+//! Every input with a match gets a `begin` message, one `match` or `context`
+//! message per line printed and an `end` message with its statistics, and a
+//! `summary` message closes the search. Lines are always text, and the byte
+//! counts ripgrep keeps are left out.
+
+use crate::escape;
+use crate::json::Value;
+use crate::stats::Stats;
+use crate::Match;
+use std::path::Path;
+use std::time::Duration;
+
+pub fn begin(path: Option<&Path>) -> Value {
+  message("begin", vec![("path", path_value(path))])
+}
+
+/// A line printed for `path`, which is either `match` or `context`.
+pub fn line(kind: &str, path: Option<&Path>, m: &Match, terminator: &str) -> Value {
+  let submatches = m
+    .spans
+    .iter()
+    .filter(|span| !span.is_empty())
+    .map(|span| {
+      // Offsets count the bytes of the input, whatever is not UTF-8 in it
+      let bytes = escape::span(m.line, span);
+      object(vec![
+        ("match", text(&escape::lossy(&m.line[span.clone()]))),
+        ("start", Value::from(bytes.start)),
+        ("end", Value::from(bytes.end)),
+      ])
+    })
+    .collect();
+  message(
+    kind,
+    vec![
+      ("path", path_value(path)),
+      (
+        "lines",
+        text(&format!("{}{}", escape::lossy(m.line), terminator)),
+      ),
+      ("line_number", Value::from(m.line_number)),
+      ("absolute_offset", Value::from(m.byte_offset)),
+      ("submatches", Value::Array(submatches)),
+    ],
+  )
+}
+
+pub fn end(path: Option<&Path>, stats: &Stats) -> Value {
+  message(
+    "end",
+    vec![
+      ("path", path_value(path)),
+      ("binary_offset", Value::Null),
+      ("stats", stats_value(stats)),
+    ],
+  )
+}
+
+pub fn summary(stats: &Stats) -> Value {
+  message(
+    "summary",
+    vec![
+      ("elapsed_total", elapsed(stats.elapsed)),
+      ("stats", stats_value(stats)),
+    ],
+  )
+}
+
+fn message(kind: &str, data: Vec<(&str, Value)>) -> Value {
+  object(vec![("type", Value::from(kind)), ("data", object(data))])
+}
+
+fn object(members: Vec<(&str, Value)>) -> Value {
+  Value::Object(
+    members
+      .into_iter()
+      .map(|(name, value)| (name.to_string(), value))
+      .collect(),
+  )
+}
+
+fn text(s: &str) -> Value {
+  object(vec![("text", Value::from(s))])
+}
+
+fn path_value(path: Option<&Path>) -> Value {
+  match path {
+    Some(path) => text(&path.display().to_string()),
+    None => Value::Null,
+  }
+}
+
+fn elapsed(elapsed: Duration) -> Value {
+  object(vec![
+    ("secs", Value::Number(elapsed.as_secs() as f64)),
+    ("nanos", Value::Number(f64::from(elapsed.subsec_nanos()))),
+    (
+      "human",
+      Value::from(format!("{:.6}s", elapsed.as_secs_f64()).as_str()),
+    ),
+  ])
+}
+
+fn stats_value(stats: &Stats) -> Value {
+  object(vec![
+    ("elapsed", elapsed(stats.elapsed)),
+    ("searches", Value::from(stats.files_searched)),
+    ("searches_with_match", Value::from(stats.files_matched)),
+    ("matched_lines", Value::from(stats.matched_lines)),
+    ("matches", Value::from(stats.matches)),
+  ])
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn follows_the_ripgrep_schema() {
+    let m = Match {
+      line_number: 3,
+      byte_offset: 12,
+      line: "a frog",
+      terminator: "\n",
+      spans: vec![2..6],
+      pattern: None,
+    };
+    assert_eq!(
+      r#"{"type":"match","data":{"path":{"text":"pond.txt"},"lines":{"text":"a frog\n"},"line_number":3,"absolute_offset":12,"submatches":[{"match":{"text":"frog"},"start":2,"end":6}]}}"#,
+      line("match", Some(Path::new("pond.txt")), &m, "\n").to_string()
+    );
+    let stats = Stats {
+      files_searched: 2,
+      files_matched: 1,
+      matched_lines: 1,
+      matches: 1,
+      elapsed: Duration::from_millis(1500),
+      ..Stats::default()
+    };
+    let summary = summary(&stats);
+    let data = summary.get("data").unwrap();
+    assert_eq!(Some("summary"), summary.get("type").and_then(Value::as_str));
+    assert_eq!(
+      Some("1.500000s"),
+      data
+        .get("elapsed_total")
+        .and_then(|e| e.get("human"))
+        .and_then(Value::as_str)
+    );
+    assert_eq!(
+      Some(1),
+      data
+        .get("stats")
+        .and_then(|s| s.get("searches_with_match"))
+        .and_then(Value::as_usize)
+    );
+  }
+}
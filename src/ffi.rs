@@ -0,0 +1,228 @@
+//! A C interface to the matcher, behind the `ffi` feature, so that C and C++
+//! programs can search text they already hold. `include/minigrep.h` declares
+//! it, and the library is built as one they can link against with
+//!
+//! ```text
+//! cargo rustc --lib --release --no-default-features --features ffi --crate-type cdylib
+//! ```
+//!
+//! Results point into the searched text rather than copying it, so it must
+//! outlive them. They are freed with `minigrep_free_results`.
+
+use crate::{search_contents, Config, STDIN_FILENAME};
+use std::ops::Range;
+use std::os::raw::c_int;
+use std::{ptr, slice, str};
+
+pub const MINIGREP_OK: c_int = 0;
+/// The query or the text is not UTF-8.
+pub const MINIGREP_INVALID_UTF8: c_int = 1;
+/// The query is not a valid regular expression.
+pub const MINIGREP_INVALID_PATTERN: c_int = 2;
+pub const MINIGREP_NULL_ARGUMENT: c_int = 3;
+
+/// How to search, where all false is what minigrep does by default.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MinigrepOptions {
+  pub ignore_case: bool,
+  pub regex: bool,
+  pub word_regexp: bool,
+  pub invert_match: bool,
+}
+
+/// Where a match is in its line, in bytes.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MinigrepSpan {
+  pub start: usize,
+  pub end: usize,
+}
+
+/// A matching line, without its line ending.
+#[repr(C)]
+#[derive(Debug)]
+pub struct MinigrepMatch {
+  pub line: *const u8,
+  pub line_len: usize,
+  pub line_number: usize,
+  /// Where the line starts within the searched text.
+  pub byte_offset: usize,
+  pub spans: *const MinigrepSpan,
+  pub spans_len: usize,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct MinigrepResults {
+  pub matches: *mut MinigrepMatch,
+  pub len: usize,
+}
+
+/// Searches the `contents_len` bytes at `contents` for the query, writing
+/// the matching lines to `results`. `options` may be null for the defaults.
+/// Returns `MINIGREP_OK`, or why nothing was searched, in which case
+/// `results` is left empty.
+///
+/// # Safety
+///
+/// `query` and `contents` must point to that many readable bytes, and
+/// `results` to a `MinigrepResults` that is later passed to
+/// `minigrep_free_results`.
+#[no_mangle]
+pub unsafe extern "C" fn minigrep_search(
+  query: *const u8,
+  query_len: usize,
+  contents: *const u8,
+  contents_len: usize,
+  options: *const MinigrepOptions,
+  results: *mut MinigrepResults,
+) -> c_int {
+  if query.is_null() || contents.is_null() || results.is_null() {
+    return MINIGREP_NULL_ARGUMENT;
+  }
+  *results = MinigrepResults {
+    matches: ptr::null_mut(),
+    len: 0,
+  };
+  let options = options.as_ref().copied().unwrap_or_default();
+  let (query, contents) = match (
+    str::from_utf8(slice::from_raw_parts(query, query_len)),
+    str::from_utf8(slice::from_raw_parts(contents, contents_len)),
+  ) {
+    (Ok(query), Ok(contents)) => (query, contents),
+    _ => return MINIGREP_INVALID_UTF8,
+  };
+  // Nothing is read from it, but a config always names its inputs
+  let config = Config::builder()
+    .query(query)
+    .path(STDIN_FILENAME)
+    .ignore_case(options.ignore_case)
+    .regex(options.regex)
+    .word_regexp(options.word_regexp)
+    .invert_match(options.invert_match)
+    .build();
+  let config = match config {
+    Ok(config) => config,
+    Err(_) => return MINIGREP_INVALID_PATTERN,
+  };
+
+  let matches: Box<[MinigrepMatch]> = search_contents(&config, contents)
+    .into_iter()
+    .map(|m| {
+      let spans: Box<[MinigrepSpan]> = m.spans.into_iter().map(span).collect();
+      let spans_len = spans.len();
+      MinigrepMatch {
+        line: m.line.as_ptr(),
+        line_len: m.line.len(),
+        line_number: m.line_number,
+        byte_offset: m.byte_offset,
+        spans: Box::into_raw(spans) as *const MinigrepSpan,
+        spans_len,
+      }
+    })
+    .collect();
+  let len = matches.len();
+  *results = MinigrepResults {
+    matches: Box::into_raw(matches) as *mut MinigrepMatch,
+    len,
+  };
+  MINIGREP_OK
+}
+
+fn span(range: Range<usize>) -> MinigrepSpan {
+  MinigrepSpan {
+    start: range.start,
+    end: range.end,
+  }
+}
+
+/// Frees what `minigrep_search` wrote to `results` and empties it, so that
+/// freeing it twice is harmless.
+///
+/// # Safety
+///
+/// `results` must be null or have been filled in by `minigrep_search`.
+#[no_mangle]
+pub unsafe extern "C" fn minigrep_free_results(results: *mut MinigrepResults) {
+  let results = match results.as_mut() {
+    Some(results) if !results.matches.is_null() => results,
+    _ => return,
+  };
+  let matches = Box::from_raw(ptr::slice_from_raw_parts_mut(results.matches, results.len));
+  for m in matches.iter() {
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+      m.spans as *mut MinigrepSpan,
+      m.spans_len,
+    )));
+  }
+  drop(matches);
+  results.matches = ptr::null_mut();
+  results.len = 0;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn searches_through_the_c_interface() {
+    let contents = "Frog\nfrog pond\ntoad\n";
+    let search = |query: &str, options: Option<MinigrepOptions>| {
+      let mut results = MinigrepResults {
+        matches: ptr::null_mut(),
+        len: 0,
+      };
+      let options = options
+        .as_ref()
+        .map_or(ptr::null(), |options| options as *const _);
+      let code = unsafe {
+        minigrep_search(
+          query.as_ptr(),
+          query.len(),
+          contents.as_ptr(),
+          contents.len(),
+          options,
+          &mut results,
+        )
+      };
+      let found: Vec<(String, Vec<MinigrepSpan>)> = (0..results.len)
+        .map(|i| unsafe {
+          let m = &*results.matches.add(i);
+          let line = slice::from_raw_parts(m.line, m.line_len);
+          let spans = slice::from_raw_parts(m.spans, m.spans_len);
+          (String::from_utf8(line.to_vec()).unwrap(), spans.to_vec())
+        })
+        .collect();
+      unsafe {
+        minigrep_free_results(&mut results);
+        minigrep_free_results(&mut results);
+      }
+      (code, found)
+    };
+
+    assert_eq!(
+      (
+        MINIGREP_OK,
+        vec![(
+          "frog pond".to_string(),
+          vec![MinigrepSpan { start: 0, end: 4 }]
+        )]
+      ),
+      search("frog", None)
+    );
+    let options = MinigrepOptions {
+      ignore_case: true,
+      ..MinigrepOptions::default()
+    };
+    assert_eq!(2, search("FROG", Some(options)).1.len());
+    let options = MinigrepOptions {
+      regex: true,
+      ..MinigrepOptions::default()
+    };
+    assert_eq!(
+      (MINIGREP_INVALID_PATTERN, Vec::new()),
+      search("(", Some(options))
+    );
+  }
+}
@@ -0,0 +1,153 @@
+use crate::glob::{Glob, GlobError};
+use std::path::Path;
+
+/// Decides which paths get searched, based on `--include` and `--exclude`
+/// globs.
+///
+/// Globs without a `/` are matched against the file name only, so `*.rs`
+/// selects Rust files in any directory. Globs containing a `/` are matched
+/// against the whole path and against every trailing part of it that starts
+/// at a directory boundary, so `target/**` excludes `./target/debug/app` as
+/// well as `crates/foo/target/debug/app`.
+///
+/// Hidden files and directories, whose names start with a dot, are skipped
+/// unless `hidden` is set.
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+  include: Vec<Glob>,
+  exclude: Vec<Glob>,
+  // From `--type` and `--type-not`, which only ever apply to files
+  include_types: Vec<Glob>,
+  exclude_types: Vec<Glob>,
+  hidden: bool,
+}
+
+impl PathFilter {
+  pub fn new() -> PathFilter {
+    PathFilter::default()
+  }
+
+  pub fn include(&mut self, pattern: &str) -> Result<&mut PathFilter, GlobError> {
+    self.include.push(Glob::new(pattern)?);
+    Ok(self)
+  }
+
+  pub fn exclude(&mut self, pattern: &str) -> Result<&mut PathFilter, GlobError> {
+    self.exclude.push(Glob::new(pattern)?);
+    Ok(self)
+  }
+
+  /// Only searches files matching this glob or another type glob. Unlike
+  /// `exclude`, excluding a type never prunes directories.
+  pub fn include_type(&mut self, pattern: &str) -> Result<&mut PathFilter, GlobError> {
+    self.include_types.push(Glob::new(pattern)?);
+    Ok(self)
+  }
+
+  pub fn exclude_type(&mut self, pattern: &str) -> Result<&mut PathFilter, GlobError> {
+    self.exclude_types.push(Glob::new(pattern)?);
+    Ok(self)
+  }
+
+  /// Whether to search hidden files and directories too.
+  pub fn hidden(&mut self, yes: bool) -> &mut PathFilter {
+    self.hidden = yes;
+    self
+  }
+
+  /// Whether a path is skipped for being hidden. Only the last component
+  /// counts, so a hidden directory named on the command line is still
+  /// searched.
+  pub fn is_hidden(&self, path: &Path) -> bool {
+    !self.hidden
+      && path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+  }
+
+  /// Whether a file should be searched.
+  pub fn is_match(&self, path: &Path) -> bool {
+    let path = normalize(path);
+    let any = |globs: &[Glob]| globs.iter().any(|glob| matches(glob, &path));
+    if any(&self.exclude) || any(&self.exclude_types) {
+      return false;
+    }
+    (self.include.is_empty() || any(&self.include))
+      && (self.include_types.is_empty() || any(&self.include_types))
+  }
+
+  /// Whether a directory should be skipped entirely while walking. Include
+  /// globs only ever select files, so they never prune directories.
+  pub fn is_excluded_dir(&self, path: &Path) -> bool {
+    let path = normalize(path);
+    let with_slash = format!("{}/", path);
+    self
+      .exclude
+      .iter()
+      .any(|glob| matches(glob, &path) || matches(glob, &with_slash))
+  }
+}
+
+fn normalize(path: &Path) -> String {
+  let path = path.to_string_lossy().replace('\\', "/");
+  match path.strip_prefix("./") {
+    Some(stripped) => stripped.to_string(),
+    None => path,
+  }
+}
+
+fn matches(glob: &Glob, path: &str) -> bool {
+  if !glob.has_separator() {
+    let name = path.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+    return glob.is_match(name);
+  }
+  if glob.is_match(path) {
+    return true;
+  }
+  path
+    .char_indices()
+    .filter(|&(i, c)| c == '/' && i + 1 < path.len())
+    .any(|(i, _)| glob.is_match(&path[i + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn include_and_exclude() {
+    let mut filter = PathFilter::new();
+    filter
+      .include("*.rs")
+      .unwrap()
+      .exclude("target/**")
+      .unwrap();
+
+    assert!(filter.is_match(Path::new("./src/lib.rs")));
+    assert!(!filter.is_match(Path::new("./README.md")));
+    assert!(!filter.is_match(Path::new("./target/debug/build.rs")));
+    assert!(filter.is_excluded_dir(Path::new("./target")));
+    assert!(filter.is_excluded_dir(Path::new("crates/foo/target")));
+    assert!(!filter.is_excluded_dir(Path::new("./src")));
+  }
+
+  #[test]
+  fn skips_hidden_paths() {
+    let mut filter = PathFilter::new();
+
+    assert!(filter.is_hidden(Path::new("./src/.git")));
+    assert!(filter.is_hidden(Path::new(".env")));
+    assert!(!filter.is_hidden(Path::new("./src")));
+    assert!(!filter.is_hidden(Path::new("..")));
+    assert!(!filter.hidden(true).is_hidden(Path::new(".env")));
+  }
+
+  #[test]
+  fn empty_filter_matches_everything() {
+    let filter = PathFilter::new();
+
+    assert!(filter.is_match(Path::new("anything")));
+    assert!(!filter.is_excluded_dir(Path::new("anything")));
+  }
+}
@@ -0,0 +1,47 @@
+//! Directories of files for the tests, each removed once its test is done
+//! with it, whether it passed or not.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+pub(crate) struct Fixture(PathBuf);
+
+impl Fixture {
+  /// A directory of its own for the test called `name`, holding `files`,
+  /// given as their paths below it and their contents.
+  pub(crate) fn new(name: &str, files: &[(&str, &str)]) -> Fixture {
+    let root = std::env::temp_dir().join(format!("minigrep-{}-{}", name, process::id()));
+    // Left over by a run that was killed
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    let fixture = Fixture(root);
+    for (path, contents) in files {
+      fixture.write(path, contents);
+    }
+    fixture
+  }
+
+  pub(crate) fn path(&self) -> &Path {
+    &self.0
+  }
+
+  pub(crate) fn join<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+    self.0.join(path)
+  }
+
+  /// Writes `contents` to `path` below the directory, with the directories
+  /// it needs, and gives back where it is.
+  pub(crate) fn write<C: AsRef<[u8]>>(&self, path: &str, contents: C) -> PathBuf {
+    let path = self.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, contents).unwrap();
+    path
+  }
+}
+
+impl Drop for Fixture {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.0);
+  }
+}
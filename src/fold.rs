@@ -0,0 +1,121 @@
+//! Unicode case folding, so that text matches whatever its case, `ß` matching
+//! `SS` and `ς` matching `Σ`.
+//!
+//! The standard library only knows lower and uppercase mappings, so folding
+//! is built from those: a character folds to the lowercase form of its
+//! uppercase form. This agrees with Unicode's case folding except for the
+//! dotless `ı`, which is left alone as it is there.
+
+use crate::accents;
+use std::char::{ToLowercase, ToUppercase};
+use std::iter::FlatMap;
+
+/// Which case folding ignoring case uses, from `--case-fold`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaseFold {
+  /// Every character folds to a single one, so `ß` does not match `ss`.
+  Simple,
+  /// Characters may fold to several, so `ß` matches `ss` and `ﬁ` matches
+  /// `fi`.
+  #[default]
+  Full,
+  /// Full folding with the Turkish and Azeri dotted and dotless `i`: `I`
+  /// folds to `ı` and `İ` to `i`.
+  Turkic,
+}
+
+/// What is ignored when comparing text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Folding {
+  /// How case is folded, when it is ignored.
+  pub case: Option<CaseFold>,
+  /// Compare letters without their accents, from `--ignore-accents`.
+  pub accents: bool,
+}
+
+impl Folding {
+  /// The characters `c` is compared as, none for a combining mark once
+  /// accents are ignored.
+  pub fn fold(&self, c: char) -> Folded {
+    let c = if self.accents {
+      match accents::base(c) {
+        Some(c) => c,
+        None => return Folded::One(None),
+      }
+    } else {
+      c
+    };
+    match self.case {
+      Some(mode) => fold(c, mode),
+      None => Folded::One(Some(c)),
+    }
+  }
+}
+
+/// The characters `c` folds to.
+pub enum Folded {
+  One(Option<char>),
+  Many(FlatMap<ToUppercase, ToLowercase, fn(char) -> ToLowercase>),
+}
+
+impl Iterator for Folded {
+  type Item = char;
+
+  fn next(&mut self) -> Option<char> {
+    match self {
+      Folded::One(c) => c.take(),
+      Folded::Many(chars) => chars.next(),
+    }
+  }
+}
+
+pub fn fold(c: char, mode: CaseFold) -> Folded {
+  match (mode, c) {
+    (CaseFold::Turkic, 'I') => Folded::One(Some('ı')),
+    // Most text is ASCII, which need not go through both case mappings
+    (_, c) if c.is_ascii() => Folded::One(Some(c.to_ascii_lowercase())),
+    (CaseFold::Simple, _) => Folded::One(Some(simple(c))),
+    (CaseFold::Turkic, 'İ') => Folded::One(Some('i')),
+    (_, 'ı') => Folded::One(Some('ı')),
+    _ => Folded::Many(
+      c.to_uppercase()
+        .flat_map(char::to_lowercase as fn(char) -> ToLowercase),
+    ),
+  }
+}
+
+/// The single character `c` folds to, keeping `c` when it would fold to
+/// several.
+pub fn simple(c: char) -> char {
+  single(fold(c, CaseFold::Full))
+    .or_else(|| single(c.to_lowercase()))
+    .unwrap_or(c)
+}
+
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+  match (chars.next(), chars.next()) {
+    (Some(c), None) => Some(c),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn folded(s: &str, mode: CaseFold) -> String {
+    s.chars().flat_map(|c| fold(c, mode)).collect()
+  }
+
+  #[test]
+  fn folds_case() {
+    assert_eq!("strasse", folded("STRAßE", CaseFold::Full));
+    assert_eq!("straße", folded("STRAßE", CaseFold::Simple));
+    assert_eq!("σοφόσ", folded("ΣΟΦΌς", CaseFold::Full));
+    assert_eq!("σ", folded("ς", CaseFold::Simple));
+    assert_eq!("ıi\u{307}i", folded("ıİI", CaseFold::Full));
+    assert_eq!("ıiı", folded("ıİI", CaseFold::Turkic));
+    assert_eq!("İ", folded("İ", CaseFold::Simple));
+    assert_eq!("fi", folded("ﬁ", CaseFold::Full));
+  }
+}
@@ -0,0 +1,181 @@
+use crate::matcher::Matcher;
+use std::ops::Range;
+
+const SCORE_MATCH: i64 = 16;
+const BONUS_CONSECUTIVE: i64 = 8;
+// Matching the start of a word, such as `b` in `foo_bar` or `fooBar`
+const BONUS_BOUNDARY: i64 = 8;
+const PENALTY_GAP_START: i64 = 3;
+const PENALTY_GAP_EXTENSION: i64 = 1;
+
+/// Matches lines containing the characters of a query in order, though not
+/// necessarily next to each other, the way fzf does.
+///
+/// Each match is scored so that better ones can be told apart: matched
+/// characters that are adjacent or start a word count for more, while gaps
+/// between them count against the match.
+#[derive(Debug, Clone)]
+pub struct FuzzyMatcher {
+  source: String,
+  query: Vec<char>,
+  case_sensitive: bool,
+}
+
+/// Where a fuzzy query matched within a line, and how well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyMatch {
+  pub score: i64,
+  /// Byte ranges of the matched characters, adjacent ones merged together.
+  pub spans: Vec<Range<usize>>,
+}
+
+impl FuzzyMatcher {
+  pub fn new(query: &str, case_sensitive: bool) -> FuzzyMatcher {
+    FuzzyMatcher {
+      source: query.to_string(),
+      query: query.chars().map(|c| fold(c, case_sensitive)).collect(),
+      case_sensitive,
+    }
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.source
+  }
+
+  /// The best scoring match within `line`, if every character of the query
+  /// is found.
+  pub fn find_fuzzy(&self, line: &str) -> Option<FuzzyMatch> {
+    let chars: Vec<(usize, char)> = line
+      .char_indices()
+      .map(|(i, c)| (i, fold(c, self.case_sensitive)))
+      .collect();
+    if self.query.is_empty() {
+      return Some(FuzzyMatch {
+        score: 0,
+        spans: Vec::new(),
+      });
+    }
+
+    // Find where the leftmost occurrence ends, then walk back from there to
+    // the latest possible start, which gives the shortest window
+    let mut next = 0;
+    let end = chars.iter().position(|&(_, c)| {
+      if c == self.query[next] {
+        next += 1;
+      }
+      next == self.query.len()
+    })?;
+    let mut remaining = self.query.len();
+    let start = (0..=end).rev().find(|&i| {
+      if chars[i].1 == self.query[remaining - 1] {
+        remaining -= 1;
+      }
+      remaining == 0
+    })?;
+
+    let mut positions = Vec::with_capacity(self.query.len());
+    let mut next = 0;
+    for (i, &(_, c)) in chars.iter().enumerate().take(end + 1).skip(start) {
+      if next < self.query.len() && c == self.query[next] {
+        positions.push(i);
+        next += 1;
+      }
+    }
+
+    let original: Vec<char> = line.chars().collect();
+    let mut score = 0;
+    let mut previous: Option<usize> = None;
+    for &i in &positions {
+      score += SCORE_MATCH;
+      match previous {
+        Some(p) if p + 1 == i => score += BONUS_CONSECUTIVE,
+        Some(p) => score -= PENALTY_GAP_START + PENALTY_GAP_EXTENSION * (i - p - 2) as i64,
+        None => {}
+      }
+      if is_boundary(&original, i) {
+        score += BONUS_BOUNDARY;
+      }
+      previous = Some(i);
+    }
+
+    let mut spans: Vec<Range<usize>> = Vec::new();
+    for &i in &positions {
+      let (start, c) = (chars[i].0, original[i]);
+      let end = start + c.len_utf8();
+      match spans.last_mut() {
+        Some(last) if last.end == start => last.end = end,
+        _ => spans.push(start..end),
+      }
+    }
+    Some(FuzzyMatch { score, spans })
+  }
+
+  pub fn score(&self, line: &str) -> Option<i64> {
+    self.find_fuzzy(line).map(|m| m.score)
+  }
+}
+
+impl Matcher for FuzzyMatcher {
+  fn find(&self, line: &str) -> Option<Range<usize>> {
+    let spans = self.find_fuzzy(line)?.spans;
+    match (spans.first(), spans.last()) {
+      (Some(first), Some(last)) => Some(first.start..last.end),
+      _ => Some(0..0),
+    }
+  }
+
+  fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+    match self.find_fuzzy(line) {
+      Some(m) if m.spans.is_empty() => std::iter::once(0..0).collect(),
+      Some(m) => m.spans,
+      None => Vec::new(),
+    }
+  }
+}
+
+// Simple folding maps each character to one, which keeps positions one to
+// one with the characters of the line
+fn fold(c: char, case_sensitive: bool) -> char {
+  if case_sensitive {
+    c
+  } else {
+    crate::fold::simple(c)
+  }
+}
+
+fn is_boundary(chars: &[char], i: usize) -> bool {
+  match i.checked_sub(1).map(|p| chars[p]) {
+    None => true,
+    Some(before) => !before.is_alphanumeric() || (before.is_lowercase() && chars[i].is_uppercase()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_subsequences() {
+    let matcher = FuzzyMatcher::new("cfg", true);
+
+    let m = matcher
+      .find_fuzzy("let config = Config::new(args);")
+      .unwrap();
+    assert_eq!(vec![4..5, 7..8, 9..10], m.spans);
+    assert!(matcher.find_fuzzy("let gfc = 1;").is_none());
+    assert!(FuzzyMatcher::new("CFG", false).is_match("config"));
+  }
+
+  #[test]
+  fn prefers_tight_matches_at_word_starts() {
+    let matcher = FuzzyMatcher::new("sr", true);
+
+    let adjacent = matcher.score("src").unwrap();
+    let gapped = matcher.score("search_reader").unwrap();
+    let scattered = matcher.score("sometimes never").unwrap();
+    assert!(adjacent > gapped);
+    assert!(gapped > scattered);
+    // The shortest window is picked, so the `s` closest to `r` is used
+    assert_eq!(vec![2..4], matcher.find_fuzzy("a srsly").unwrap().spans);
+  }
+}
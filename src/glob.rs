@@ -0,0 +1,186 @@
+use std::error::Error;
+use std::fmt;
+
+/// A shell-style wildcard pattern matched against `/`-separated paths.
+///
+/// `?` matches any single character and `*` any run of characters other
+/// than `/`. `**` also crosses directory separators, and `**/` matches zero
+/// or more whole directories. Bracket classes such as `[a-z]` and `[!0-9]`
+/// are supported as well.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glob {
+  source: String,
+  tokens: Vec<Token>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobError {
+  pub pattern: String,
+  pub message: String,
+}
+
+impl fmt::Display for GlobError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "invalid glob `{}`: {}", self.pattern, self.message)
+  }
+}
+
+impl Error for GlobError {}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Char(char),
+  AnyChar,
+  Star,
+  DoubleStar,
+  // `**/`, which may also match nothing at all
+  Directories,
+  Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+  },
+}
+
+impl Glob {
+  pub fn new(pattern: &str) -> Result<Glob, GlobError> {
+    let error = |message: &str| GlobError {
+      pattern: pattern.to_string(),
+      message: message.to_string(),
+    };
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+      match chars[i] {
+        '*' if chars.get(i + 1) == Some(&'*') => {
+          i += 2;
+          if chars.get(i) == Some(&'/') {
+            i += 1;
+            tokens.push(Token::Directories);
+          } else {
+            tokens.push(Token::DoubleStar);
+          }
+          continue;
+        }
+        '*' => tokens.push(Token::Star),
+        '?' => tokens.push(Token::AnyChar),
+        '[' => {
+          let mut j = i + 1;
+          let negated = matches!(chars.get(j), Some('!') | Some('^'));
+          if negated {
+            j += 1;
+          }
+          let mut ranges = Vec::new();
+          let mut first = true;
+          loop {
+            let c = match chars.get(j) {
+              Some(&c) => c,
+              None => return Err(error("unclosed character class")),
+            };
+            if c == ']' && !first {
+              break;
+            }
+            first = false;
+            if chars.get(j + 1) == Some(&'-') && chars.get(j + 2).is_some_and(|&c| c != ']') {
+              ranges.push((c, chars[j + 2]));
+              j += 3;
+            } else {
+              ranges.push((c, c));
+              j += 1;
+            }
+          }
+          tokens.push(Token::Class { ranges, negated });
+          i = j;
+        }
+        '\\' if i + 1 < chars.len() => {
+          i += 1;
+          tokens.push(Token::Char(chars[i]));
+        }
+        c => tokens.push(Token::Char(c)),
+      }
+      i += 1;
+    }
+    Ok(Glob {
+      source: pattern.to_string(),
+      tokens,
+    })
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.source
+  }
+
+  /// Whether the pattern contains a `/`, which anchors it to whole paths
+  /// instead of plain file names.
+  pub fn has_separator(&self) -> bool {
+    self
+      .tokens
+      .iter()
+      .any(|t| matches!(t, Token::Char('/') | Token::Directories))
+  }
+
+  pub fn is_match(&self, text: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    // matched[t][p]: whether tokens[t..] matches text[p..]
+    let width = text.len() + 1;
+    let mut matched = vec![false; (self.tokens.len() + 1) * width];
+    matched[self.tokens.len() * width + text.len()] = true;
+    for t in (0..self.tokens.len()).rev() {
+      for p in (0..=text.len()).rev() {
+        let rest = |t: usize, p: usize| matched[t * width + p];
+        let c = text.get(p).copied();
+        let result = match &self.tokens[t] {
+          Token::Char(expected) => c == Some(*expected) && rest(t + 1, p + 1),
+          Token::AnyChar => c.is_some_and(|c| c != '/') && rest(t + 1, p + 1),
+          Token::Class { ranges, negated } => {
+            c.is_some_and(|c| {
+              c != '/' && ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+            }) && rest(t + 1, p + 1)
+          }
+          Token::Star => rest(t + 1, p) || (c.is_some_and(|c| c != '/') && rest(t, p + 1)),
+          Token::DoubleStar => rest(t + 1, p) || (c.is_some() && rest(t, p + 1)),
+          // Either no directories at all, or everything up to some `/`
+          Token::Directories => {
+            rest(t + 1, p) || (p..text.len()).any(|end| text[end] == '/' && rest(t + 1, end + 1))
+          }
+        };
+        matched[t * width + p] = result;
+      }
+    }
+    matched[0]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn is_match(pattern: &str, text: &str) -> bool {
+    Glob::new(pattern).unwrap().is_match(text)
+  }
+
+  #[test]
+  fn wildcards() {
+    assert!(is_match("*.rs", "lib.rs"));
+    assert!(!is_match("*.rs", "src/lib.rs"));
+    assert!(is_match("src/*.rs", "src/lib.rs"));
+    assert!(is_match("?at.[ch]", "cat.h"));
+    assert!(!is_match("?at.[!ch]", "cat.h"));
+    assert!(is_match("file\\*", "file*"));
+  }
+
+  #[test]
+  fn double_star() {
+    assert!(is_match("target/**", "target/debug/build/x.o"));
+    assert!(is_match("target/**", "target/"));
+    assert!(is_match("**/*.rs", "lib.rs"));
+    assert!(is_match("**/*.rs", "src/walk/mod.rs"));
+    assert!(is_match("src/**/mod.rs", "src/mod.rs"));
+    assert!(!is_match("src/**/mod.rs", "tests/mod.rs"));
+  }
+
+  #[test]
+  fn invalid() {
+    assert!(Glob::new("[abc").is_err());
+  }
+}
@@ -0,0 +1,518 @@
+//! Decompression of gzip streams, as searched by `-z`.
+//!
+//! Only what gzip files use is implemented: the member format of RFC 1952
+//! around DEFLATE data as described by RFC 1951. Several members one after
+//! the other, as written by `cat a.gz b.gz`, decompress to their
+//! concatenation.
+
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+
+/// Every gzip stream starts with these bytes.
+pub const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+const CHUNK_LEN: usize = 32 * 1024;
+const WINDOW_LEN: usize = 32 * 1024;
+const MAX_BITS: usize = 15;
+
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+const LENGTH_BASE: [u16; 29] = [
+  3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+  163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+  0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [u16; 30] = [
+  1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049,
+  3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+  0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// The order in which the code lengths of the code length alphabet are sent
+const CODE_LENGTH_ORDER: [usize; 19] = [
+  16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// Whether the file at `path` should be decompressed, judging by its name or
+/// else by the first bytes of its contents.
+pub fn is_gzip(path: &Path, start: &[u8]) -> bool {
+  path.extension().is_some_and(|ext| ext == "gz") || start.starts_with(&MAGIC)
+}
+
+/// Decompresses a gzip stream as it is read.
+pub struct GzDecoder<R> {
+  bits: BitReader<R>,
+  // Bare DEFLATE data, without gzip's header and trailer
+  raw: bool,
+  state: State,
+  final_block: bool,
+  // The last `WINDOW_LEN` bytes written, which back references point into
+  window: Vec<u8>,
+  window_pos: usize,
+  crc: u32,
+  size: u32,
+  output: Vec<u8>,
+  position: usize,
+}
+
+enum State {
+  Header,
+  Block,
+  Stored(usize),
+  Compressed(Huffman, Huffman),
+  Trailer,
+  Done,
+}
+
+impl<R: BufRead> GzDecoder<R> {
+  pub fn new(inner: R) -> GzDecoder<R> {
+    GzDecoder {
+      bits: BitReader {
+        inner,
+        bits: 0,
+        count: 0,
+      },
+      raw: false,
+      state: State::Header,
+      final_block: false,
+      window: vec![0; WINDOW_LEN],
+      window_pos: 0,
+      crc: 0,
+      size: 0,
+      output: Vec::with_capacity(CHUNK_LEN + 258),
+      position: 0,
+    }
+  }
+
+  /// Decompresses bare DEFLATE data, as found in zip archives.
+  pub fn raw(inner: R) -> GzDecoder<R> {
+    GzDecoder {
+      raw: true,
+      crc: !0,
+      ..GzDecoder::new(inner)
+    }
+  }
+
+  // Decompresses until there is a chunk of output or the stream ends. A copy
+  // may run past the chunk length, by at most one match.
+  fn fill(&mut self) -> io::Result<()> {
+    self.output.clear();
+    self.position = 0;
+    while self.output.len() < CHUNK_LEN {
+      match std::mem::replace(&mut self.state, State::Done) {
+        State::Header if self.raw => self.state = State::Block,
+        State::Header => {
+          self.state = if self.read_header()? {
+            State::Block
+          } else {
+            State::Done
+          };
+        }
+        State::Block => self.state = self.read_block_header()?,
+        State::Stored(0) => self.state = self.end_of_block(),
+        State::Stored(remaining) => {
+          let len = remaining.min(CHUNK_LEN - self.output.len());
+          for _ in 0..len {
+            let byte = self.bits.byte()?;
+            self.emit(byte);
+          }
+          self.state = State::Stored(remaining - len);
+        }
+        State::Compressed(literals, distances) => {
+          if self.inflate_symbol(&literals, &distances)? {
+            self.state = State::Compressed(literals, distances);
+          } else {
+            self.state = self.end_of_block();
+          }
+        }
+        State::Trailer if self.raw => self.state = State::Done,
+        State::Trailer => {
+          self.bits.align();
+          let crc = self.bits.u32_le()?;
+          let size = self.bits.u32_le()?;
+          if crc != !self.crc || size != self.size {
+            return Err(invalid("gzip checksum mismatch"));
+          }
+          self.state = State::Header;
+        }
+        State::Done => break,
+      }
+    }
+    Ok(())
+  }
+
+  // Returns `false` at the clean end of the input, where another member
+  // could have started
+  fn read_header(&mut self) -> io::Result<bool> {
+    if self.bits.inner.fill_buf()?.is_empty() {
+      return Ok(false);
+    }
+    let mut header = [0; 10];
+    for byte in header.iter_mut() {
+      *byte = self.bits.byte()?;
+    }
+    if header[..2] != MAGIC || header[2] != 8 {
+      return Err(invalid("not a gzip stream"));
+    }
+    let flags = header[3];
+    if flags & FLAG_EXTRA != 0 {
+      let len = self.bits.byte()? as usize | (self.bits.byte()? as usize) << 8;
+      for _ in 0..len {
+        self.bits.byte()?;
+      }
+    }
+    for flag in [FLAG_NAME, FLAG_COMMENT].iter() {
+      if flags & flag != 0 {
+        while self.bits.byte()? != 0 {}
+      }
+    }
+    if flags & FLAG_HCRC != 0 {
+      self.bits.byte()?;
+      self.bits.byte()?;
+    }
+    self.final_block = false;
+    self.crc = !0;
+    self.size = 0;
+    Ok(true)
+  }
+
+  fn read_block_header(&mut self) -> io::Result<State> {
+    self.final_block = self.bits.bits(1)? == 1;
+    match self.bits.bits(2)? {
+      0 => {
+        self.bits.align();
+        let len = self.bits.byte()? as u16 | (self.bits.byte()? as u16) << 8;
+        let complement = self.bits.byte()? as u16 | (self.bits.byte()? as u16) << 8;
+        if len != !complement {
+          return Err(invalid("corrupt stored block length"));
+        }
+        Ok(State::Stored(len as usize))
+      }
+      1 => {
+        let mut lengths = [0; 288 + 30];
+        for (symbol, length) in lengths.iter_mut().enumerate() {
+          *length = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            280..=287 => 8,
+            _ => 5,
+          };
+        }
+        Ok(State::Compressed(
+          Huffman::new(&lengths[..288])?,
+          Huffman::new(&lengths[288..])?,
+        ))
+      }
+      2 => self.read_dynamic_tables(),
+      _ => Err(invalid("invalid deflate block type")),
+    }
+  }
+
+  fn read_dynamic_tables(&mut self) -> io::Result<State> {
+    let literal_count = self.bits.bits(5)? as usize + 257;
+    let distance_count = self.bits.bits(5)? as usize + 1;
+    let code_length_count = self.bits.bits(4)? as usize + 4;
+    let mut code_lengths = [0; 19];
+    for &i in &CODE_LENGTH_ORDER[..code_length_count] {
+      code_lengths[i] = self.bits.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+      let (value, repeat) = match code_lengths.decode(&mut self.bits)? {
+        length @ 0..=15 => (length as u8, 1),
+        16 => match i.checked_sub(1) {
+          Some(previous) => (lengths[previous], 3 + self.bits.bits(2)? as usize),
+          None => return Err(invalid("repeated code length without a previous one")),
+        },
+        17 => (0, 3 + self.bits.bits(3)? as usize),
+        _ => (0, 11 + self.bits.bits(7)? as usize),
+      };
+      if i + repeat > lengths.len() {
+        return Err(invalid("too many code lengths"));
+      }
+      lengths[i..i + repeat].iter_mut().for_each(|l| *l = value);
+      i += repeat;
+    }
+    if lengths[256] == 0 {
+      return Err(invalid("missing end of block code"));
+    }
+    Ok(State::Compressed(
+      Huffman::new(&lengths[..literal_count])?,
+      Huffman::new(&lengths[literal_count..])?,
+    ))
+  }
+
+  // Returns `false` at the end of the block
+  fn inflate_symbol(&mut self, literals: &Huffman, distances: &Huffman) -> io::Result<bool> {
+    let symbol = literals.decode(&mut self.bits)? as usize;
+    if symbol < 256 {
+      self.emit(symbol as u8);
+      return Ok(true);
+    }
+    if symbol == 256 {
+      return Ok(false);
+    }
+    let index = symbol - 257;
+    if index >= LENGTH_BASE.len() {
+      return Err(invalid("invalid length code"));
+    }
+    let length = LENGTH_BASE[index] as usize + self.bits.bits(LENGTH_EXTRA[index])? as usize;
+    let index = distances.decode(&mut self.bits)? as usize;
+    if index >= DISTANCE_BASE.len() {
+      return Err(invalid("invalid distance code"));
+    }
+    let distance = DISTANCE_BASE[index] as usize + self.bits.bits(DISTANCE_EXTRA[index])? as usize;
+    if distance as u64 > u64::from(self.size) && self.size < WINDOW_LEN as u32 {
+      return Err(invalid("distance too far back"));
+    }
+    for _ in 0..length {
+      let byte = self.window[(self.window_pos + WINDOW_LEN - distance) % WINDOW_LEN];
+      self.emit(byte);
+    }
+    Ok(true)
+  }
+
+  fn end_of_block(&self) -> State {
+    if self.final_block {
+      State::Trailer
+    } else {
+      State::Block
+    }
+  }
+
+  fn emit(&mut self, byte: u8) {
+    self.output.push(byte);
+    self.window[self.window_pos] = byte;
+    self.window_pos = (self.window_pos + 1) % WINDOW_LEN;
+    self.crc = CRC_TABLE[((self.crc ^ byte as u32) & 0xff) as usize] ^ (self.crc >> 8);
+    self.size = self.size.wrapping_add(1);
+  }
+}
+
+impl<R: BufRead> Read for GzDecoder<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let available = self.fill_buf()?;
+    let len = available.len().min(buf.len());
+    buf[..len].copy_from_slice(&available[..len]);
+    self.consume(len);
+    Ok(len)
+  }
+}
+
+impl<R: BufRead> BufRead for GzDecoder<R> {
+  fn fill_buf(&mut self) -> io::Result<&[u8]> {
+    if self.position == self.output.len() {
+      self.fill()?;
+    }
+    Ok(&self.output[self.position..])
+  }
+
+  fn consume(&mut self, amount: usize) {
+    self.position = (self.position + amount).min(self.output.len());
+  }
+}
+
+// Reads DEFLATE's least significant bit first bit stream
+struct BitReader<R> {
+  inner: R,
+  bits: u32,
+  count: u8,
+}
+
+impl<R: BufRead> BitReader<R> {
+  fn byte(&mut self) -> io::Result<u8> {
+    if self.count >= 8 {
+      // Only reachable right after `align`, which keeps whole bytes
+      let byte = self.bits as u8;
+      self.bits >>= 8;
+      self.count -= 8;
+      return Ok(byte);
+    }
+    let byte = match self.inner.fill_buf()?.first() {
+      Some(&byte) => byte,
+      None => return Err(io::ErrorKind::UnexpectedEof.into()),
+    };
+    self.inner.consume(1);
+    Ok(byte)
+  }
+
+  fn bits(&mut self, count: u8) -> io::Result<u32> {
+    while self.count < count {
+      let byte = match self.inner.fill_buf()?.first() {
+        Some(&byte) => byte,
+        None => return Err(io::ErrorKind::UnexpectedEof.into()),
+      };
+      self.inner.consume(1);
+      self.bits |= (byte as u32) << self.count;
+      self.count += 8;
+    }
+    let value = self.bits & ((1u32 << count) - 1);
+    self.bits >>= count;
+    self.count -= count;
+    Ok(value)
+  }
+
+  // Drops the bits left in the current byte
+  fn align(&mut self) {
+    let partial = self.count % 8;
+    self.bits >>= partial;
+    self.count -= partial;
+  }
+
+  fn u32_le(&mut self) -> io::Result<u32> {
+    let mut value = 0;
+    for shift in (0..32).step_by(8) {
+      value |= (self.byte()? as u32) << shift;
+    }
+    Ok(value)
+  }
+}
+
+// A canonical Huffman code, decoded one bit at a time
+struct Huffman {
+  // How many codes there are of each length
+  counts: [u16; MAX_BITS + 1],
+  // Symbols ordered by code
+  symbols: Vec<u16>,
+}
+
+impl Huffman {
+  fn new(lengths: &[u8]) -> io::Result<Huffman> {
+    let mut counts = [0u16; MAX_BITS + 1];
+    for &length in lengths {
+      counts[length as usize] += 1;
+    }
+    counts[0] = 0;
+    // Each length doubles the codes available, minus those used up
+    let mut left = 1i32;
+    for &count in &counts[1..] {
+      left = (left << 1) - count as i32;
+      if left < 0 {
+        return Err(invalid("oversubscribed Huffman code"));
+      }
+    }
+    let mut offsets = [0u16; MAX_BITS + 2];
+    for length in 1..=MAX_BITS {
+      offsets[length + 1] = offsets[length] + counts[length];
+    }
+    let mut symbols = vec![0; offsets[MAX_BITS + 1] as usize];
+    for (symbol, &length) in lengths.iter().enumerate() {
+      if length != 0 {
+        symbols[offsets[length as usize] as usize] = symbol as u16;
+        offsets[length as usize] += 1;
+      }
+    }
+    Ok(Huffman { counts, symbols })
+  }
+
+  fn decode<R: BufRead>(&self, bits: &mut BitReader<R>) -> io::Result<u16> {
+    // Codes of each length follow on from the shorter ones, so the code read
+    // so far is looked up among those of its length
+    let mut code = 0i32;
+    let mut first = 0i32;
+    let mut index = 0i32;
+    for length in 1..=MAX_BITS {
+      code |= bits.bits(1)? as i32;
+      let count = self.counts[length] as i32;
+      if code - first < count {
+        return Ok(self.symbols[(index + code - first) as usize]);
+      }
+      index += count;
+      first = (first + count) << 1;
+      code <<= 1;
+    }
+    Err(invalid("invalid Huffman code"))
+  }
+}
+
+fn invalid(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+const fn crc_table() -> [u32; 256] {
+  let mut table = [0u32; 256];
+  let mut i = 0;
+  while i < 256 {
+    let mut crc = i as u32;
+    let mut bit = 0;
+    while bit < 8 {
+      crc = if crc & 1 != 0 {
+        0xedb8_8320 ^ (crc >> 1)
+      } else {
+        crc >> 1
+      };
+      bit += 1;
+    }
+    table[i] = crc;
+    i += 1;
+  }
+  table
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // `(0..40).map(|i| format!("{} frog\n", i))`, compressed with a dynamic
+  // Huffman code
+  const DYNAMIC: &[u8] =
+    b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\xff\x35\xcf\xb9\x0d\x80\x50\x10\x03\
+    \xd1\x9c\x2a\x28\x01\xdb\x9c\x0d\x41\x88\x44\xff\x01\x81\xe7\x47\x13\xed\
+    \x93\x77\x99\xef\xef\x7d\x26\x35\x6e\xd2\xac\xcd\xd6\xec\xcd\xd1\x9c\xcd\
+    \xc5\xf9\x42\x71\x04\x24\x24\x41\x09\x4b\x60\x42\x13\x9c\xf0\x8c\xe7\xb1\
+    \x0b\xcf\x78\xc6\x33\x9e\xf1\x8c\x67\x3c\xe3\x05\x2f\x78\x19\x8f\xe2\x05\
+    \x2f\x78\xc1\x0b\x5e\xf0\x82\xf7\x03\xd6\x0f\x6a\xce\x36\x01\x00\x00";
+  // "a frog\na bog\nfrog\n", with the fixed code
+  const FIXED: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x02\xff\x4b\x54\x48\x2b\xca\x4f\xe7\x4a\
+    \x54\x48\x02\x92\x60\x26\x00\x69\x71\xa0\xd2\x12\x00\x00\x00";
+  // "stored frog\n", not compressed at all
+  const STORED: &[u8] = b"\x1f\x8b\x08\x00\x00\x00\x00\x00\x00\xff\x01\x0c\x00\xf3\xff\x73\x74\x6f\
+    \x72\x65\x64\x20\x66\x72\x6f\x67\x0a\x32\xb1\xbb\x26\x0c\x00\x00\x00";
+
+  fn decompress(bytes: &[u8]) -> io::Result<String> {
+    let mut text = String::new();
+    GzDecoder::new(bytes).read_to_string(&mut text)?;
+    Ok(text)
+  }
+
+  #[test]
+  fn decompresses_every_block_type() {
+    let expected: String = (0..40).map(|i| format!("{} frog\n", i)).collect();
+    assert_eq!(expected, decompress(DYNAMIC).unwrap());
+    assert_eq!("a frog\na bog\nfrog\n", decompress(FIXED).unwrap());
+    assert_eq!(
+      "a frog\na bog\nfrog\nstored frog\n",
+      decompress(&[FIXED, STORED].concat()).unwrap()
+    );
+    assert!(is_gzip(Path::new("notes.txt"), STORED));
+    assert!(is_gzip(Path::new("notes.txt.gz"), b""));
+  }
+
+  #[test]
+  fn rejects_corrupt_streams() {
+    let mut corrupt = FIXED.to_vec();
+    let crc = corrupt.len() - 8;
+    corrupt[crc] ^= 1;
+    assert_eq!(
+      io::ErrorKind::InvalidData,
+      decompress(&corrupt).unwrap_err().kind()
+    );
+    assert_eq!(
+      io::ErrorKind::UnexpectedEof,
+      decompress(&FIXED[..20]).unwrap_err().kind()
+    );
+    assert!(decompress(b"frog\n").is_err());
+  }
+}
@@ -0,0 +1,319 @@
+//! Searching what is at an `http://` or `https://` URL given in place of a
+//! file, as in `minigrep ERROR https://host/logs/today.log`.
+//!
+//! Plain HTTP is spoken directly: a `GET` that follows redirects and reads a
+//! chunked or gzip encoded body as it arrives. There is no TLS in here, so
+//! `https://` URLs are fetched by `curl`, which must be installed for them.
+
+use crate::gzip::GzDecoder;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+const MAX_REDIRECTS: usize = 5;
+
+/// Whether an input names a URL rather than a file.
+pub fn is_url(name: &str) -> bool {
+  name.starts_with("http://") || name.starts_with("https://")
+}
+
+/// The body of the resource at `url`, decompressed, as it is received.
+pub fn open(url: &str) -> io::Result<Box<dyn BufRead>> {
+  if url.starts_with("https://") {
+    return curl(url);
+  }
+  let mut url = url.to_string();
+  for _ in 0..=MAX_REDIRECTS {
+    match get(&url)? {
+      Response::Body(body) => return Ok(body),
+      Response::Redirect(location) if location.starts_with('/') => {
+        let (host, _) = split(&url)?;
+        url = format!("http://{}{}", host, location);
+      }
+      Response::Redirect(location) if location.starts_with("https://") => return curl(&location),
+      Response::Redirect(location) => url = location,
+    }
+  }
+  Err(io::Error::other("too many redirects"))
+}
+
+enum Response {
+  Body(Box<dyn BufRead>),
+  Redirect(String),
+}
+
+// The host, with its port if there is one, and the path of an `http://` URL
+fn split(url: &str) -> io::Result<(&str, &str)> {
+  let rest = url
+    .strip_prefix("http://")
+    .ok_or_else(|| invalid(format!("unsupported URL `{}`", url)))?;
+  let rest = rest.split('#').next().unwrap_or(rest);
+  Ok(match rest.find('/') {
+    Some(i) => (&rest[..i], &rest[i..]),
+    None => (rest, "/"),
+  })
+}
+
+fn get(url: &str) -> io::Result<Response> {
+  let (host, path) = split(url)?;
+  let address = if host.contains(':') {
+    host.to_string()
+  } else {
+    format!("{}:80", host)
+  };
+  let request = format!(
+    "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: minigrep/{}\r\n\
+     Accept-Encoding: gzip\r\nConnection: close\r\n\r\n",
+    path,
+    host,
+    env!("CARGO_PKG_VERSION")
+  );
+  let mut stream = TcpStream::connect(address)?;
+  stream.write_all(request.as_bytes())?;
+  let mut reader = BufReader::new(stream);
+  read_response(&mut reader)?.finish(reader)
+}
+
+// The parts of a response head that tell how to read what follows
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Head {
+  status: u16,
+  chunked: bool,
+  gzip: bool,
+  location: Option<String>,
+}
+
+impl Head {
+  fn finish<R: BufRead + 'static>(self, reader: R) -> io::Result<Response> {
+    match (self.status, self.location) {
+      (300..=399, Some(location)) => return Ok(Response::Redirect(location)),
+      (200..=299, _) => {}
+      (status, _) => return Err(io::Error::other(format!("HTTP status {}", status))),
+    }
+    let body: Box<dyn BufRead> = if self.chunked {
+      Box::new(BufReader::new(Chunked::new(reader)))
+    } else {
+      Box::new(reader)
+    };
+    Ok(Response::Body(if self.gzip {
+      Box::new(GzDecoder::new(body))
+    } else {
+      body
+    }))
+  }
+}
+
+fn read_response<R: BufRead>(reader: &mut R) -> io::Result<Head> {
+  // Interim responses such as `100 Continue` come before the real one
+  loop {
+    let head = read_head(reader)?;
+    if !(100..200).contains(&head.status) {
+      return Ok(head);
+    }
+  }
+}
+
+fn read_head<R: BufRead>(reader: &mut R) -> io::Result<Head> {
+  let mut line = String::new();
+  reader.read_line(&mut line)?;
+  let status = line
+    .split_whitespace()
+    .nth(1)
+    .and_then(|status| status.parse().ok())
+    .filter(|_| line.starts_with("HTTP/"))
+    .ok_or_else(|| invalid(format!("not an HTTP response: {:?}", line.trim_end())))?;
+  let mut head = Head {
+    status,
+    ..Head::default()
+  };
+  loop {
+    line.clear();
+    if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+      return Ok(head);
+    }
+    let (name, value) = match line.split_once(':') {
+      Some((name, value)) => (name.trim(), value.trim()),
+      None => continue,
+    };
+    let has = |word: &str| {
+      value
+        .split(',')
+        .any(|part| part.trim().eq_ignore_ascii_case(word))
+    };
+    if name.eq_ignore_ascii_case("transfer-encoding") {
+      head.chunked = has("chunked");
+    } else if name.eq_ignore_ascii_case("content-encoding") {
+      head.gzip = has("gzip") || has("x-gzip");
+    } else if name.eq_ignore_ascii_case("location") {
+      head.location = Some(value.to_string());
+    }
+  }
+}
+
+fn invalid(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A body sent with `Transfer-Encoding: chunked`, read without the chunk
+/// sizes between its pieces.
+struct Chunked<R> {
+  inner: R,
+  // What is left of the current chunk, none before the first one
+  left: Option<u64>,
+  done: bool,
+}
+
+impl<R: BufRead> Chunked<R> {
+  fn new(inner: R) -> Chunked<R> {
+    Chunked {
+      inner,
+      left: None,
+      done: false,
+    }
+  }
+
+  fn next_chunk(&mut self) -> io::Result<u64> {
+    let mut line = String::new();
+    if self.left.is_some() {
+      // The line ending after the previous chunk
+      self.inner.read_line(&mut line)?;
+      line.clear();
+    }
+    self.inner.read_line(&mut line)?;
+    let size = line.trim_end().split(';').next().unwrap_or("");
+    u64::from_str_radix(size.trim(), 16)
+      .map_err(|_| invalid(format!("invalid chunk size {:?}", line.trim_end())))
+  }
+}
+
+impl<R: BufRead> Read for Chunked<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if self.done || buf.is_empty() {
+      return Ok(0);
+    }
+    if self.left.is_none_or(|left| left == 0) {
+      let size = self.next_chunk()?;
+      self.left = Some(size);
+      // The last chunk is empty, and may be followed by trailers that
+      // nobody needs
+      if size == 0 {
+        self.done = true;
+        return Ok(0);
+      }
+    }
+    let left = self.left.unwrap_or(0);
+    let len = buf.len().min(left.min(usize::MAX as u64) as usize);
+    let read = self.inner.read(&mut buf[..len])?;
+    if read == 0 {
+      return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    self.left = Some(left - read as u64);
+    Ok(read)
+  }
+}
+
+fn curl(url: &str) -> io::Result<Box<dyn BufRead>> {
+  let mut child = Command::new("curl")
+    .args([
+      "--silent",
+      "--show-error",
+      "--fail",
+      "--location",
+      "--compressed",
+    ])
+    .arg(url)
+    .stdout(Stdio::piped())
+    .spawn()
+    .map_err(|e| io::Error::new(e.kind(), format!("curl, which fetches https URLs: {}", e)))?;
+  let stdout = child.stdout.take().expect("stdout is piped");
+  Ok(Box::new(BufReader::new(Fetched { child, stdout })))
+}
+
+/// What `curl` prints, which only ends well if it exits successfully.
+struct Fetched {
+  child: Child,
+  stdout: ChildStdout,
+}
+
+impl Read for Fetched {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let read = self.stdout.read(buf)?;
+    if read == 0 && !buf.is_empty() {
+      let status = self.child.wait()?;
+      if !status.success() {
+        return Err(io::Error::other(format!("curl failed with {}", status)));
+      }
+    }
+    Ok(read)
+  }
+}
+
+impl Drop for Fetched {
+  fn drop(&mut self) {
+    // The search may stop reading early, as with `-q`
+    let _ = self.child.kill();
+    let _ = self.child.wait();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::net::TcpListener;
+  use std::thread;
+
+  #[test]
+  fn reads_chunked_responses() {
+    let response = "HTTP/1.1 100 Continue\r\n\r\n\
+                    HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                    5\r\nfrog\n\r\n6;x=y\r\npond\nt\r\n4\r\noad\n\r\n0\r\n\r\n";
+    let mut reader = response.as_bytes();
+    let head = read_response(&mut reader).unwrap();
+    assert_eq!((200, true, false), (head.status, head.chunked, head.gzip));
+    let mut body = String::new();
+    Chunked::new(reader).read_to_string(&mut body).unwrap();
+    assert_eq!("frog\npond\ntoad\n", body);
+
+    let mut reader = "HTTP/1.0 301 Moved\r\nLocation: /new\r\n\r\n".as_bytes();
+    let head = read_response(&mut reader).unwrap();
+    assert_eq!(
+      (301, Some("/new".to_string())),
+      (head.status, head.location)
+    );
+    assert!(read_response(&mut "SSH-2.0\r\n".as_bytes()).is_err());
+    assert_eq!(
+      ("example.com:8080", "/logs?day=1"),
+      split("http://example.com:8080/logs?day=1#top").unwrap()
+    );
+  }
+
+  #[test]
+  fn fetches_urls() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || {
+      for (i, stream) in listener.incoming().take(2).enumerate() {
+        let mut stream = stream.unwrap();
+        let mut request = BufReader::new(&stream);
+        let mut line = String::new();
+        while request.read_line(&mut line).unwrap() > 2 {
+          line.clear();
+        }
+        let response: &[u8] = if i == 0 {
+          b"HTTP/1.1 302 Found\r\nLocation: /today.log\r\n\r\n"
+        } else {
+          b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nfrog\ntoad\n"
+        };
+        stream.write_all(response).unwrap();
+      }
+    });
+
+    let mut body = String::new();
+    open(&format!("http://{}/logs", address))
+      .unwrap()
+      .read_to_string(&mut body)
+      .unwrap();
+    assert_eq!("frog\ntoad\n", body);
+    server.join().unwrap();
+  }
+}
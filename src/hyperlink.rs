@@ -0,0 +1,148 @@
+//! OSC 8 hyperlinks around the file names in the results, so that terminals
+//! which understand them can open a match with a click.
+//!
+//! Where a link leads is a template, as `--hyperlink-format` sets, in which
+//! `{path}` is the absolute path of the file, `{line}` and `{column}` where
+//! the match is, both starting at 1, and `{host}` the name of this machine.
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{self, Path, PathBuf};
+
+const PLACEHOLDERS: [&str; 4] = ["{path}", "{line}", "{column}", "{host}"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HyperlinkFormat {
+  template: String,
+  // Looked up once, since links are made for every line printed
+  host: String,
+}
+
+impl Default for HyperlinkFormat {
+  /// A `file://` URL with the line as its fragment.
+  fn default() -> HyperlinkFormat {
+    HyperlinkFormat::new("file://{host}{path}#{line}")
+  }
+}
+
+impl HyperlinkFormat {
+  fn new(template: &str) -> HyperlinkFormat {
+    HyperlinkFormat {
+      template: template.to_string(),
+      host: if template.contains("{host}") {
+        hostname()
+      } else {
+        String::new()
+      },
+    }
+  }
+
+  /// Reads `default`, `vscode`, `none`, which turns links off, or a
+  /// template with `{path}` in it.
+  pub fn parse(spec: &str) -> Result<Option<HyperlinkFormat>, String> {
+    let template = match spec {
+      "none" | "" => return Ok(None),
+      "default" => return Ok(Some(HyperlinkFormat::default())),
+      "vscode" => "vscode://file{path}:{line}:{column}",
+      template => template,
+    };
+    if !template.contains("{path}") {
+      return Err(format!("`{}` does not link to a {{path}}", template));
+    }
+    // Anything else in braces is most likely a typo
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+      let placeholder = match rest[start..].find('}') {
+        Some(end) => &rest[start..start + end + 1],
+        None => &rest[start..],
+      };
+      if !PLACEHOLDERS.contains(&placeholder) {
+        return Err(format!("unknown placeholder `{}`", placeholder));
+      }
+      rest = &rest[start + placeholder.len()..];
+    }
+    Ok(Some(HyperlinkFormat::new(template)))
+  }
+
+  /// Where a match in `path` leads, which must be absolute.
+  pub fn url(&self, path: &Path, line: usize, column: usize) -> String {
+    self
+      .template
+      .replace("{path}", &encode(path))
+      .replace("{line}", &line.to_string())
+      .replace("{column}", &column.to_string())
+      .replace("{host}", &self.host)
+  }
+}
+
+/// The file behind a printed path, which links can lead to, unless it is
+/// not a file of its own, as with standard input or a file in an archive.
+pub fn target(path: &Path) -> Option<PathBuf> {
+  if !path.is_file() {
+    return None;
+  }
+  path::absolute(path).ok()
+}
+
+/// `text` as a link to `url`.
+pub fn link<T: fmt::Display>(url: &str, text: T) -> String {
+  format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+// Percent-encodes everything in a path that has a meaning in URLs, or is
+// not printable
+fn encode(path: &Path) -> String {
+  let path = path.to_string_lossy();
+  let mut encoded = String::with_capacity(path.len());
+  for &b in path.as_bytes() {
+    match b {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+        encoded.push(b as char)
+      }
+      // Drive letters and separators on Windows
+      b'\\' => encoded.push('/'),
+      b':' if cfg!(windows) => encoded.push(':'),
+      _ => encoded.push_str(&format!("%{:02X}", b)),
+    }
+  }
+  // `C:/dir` becomes `/C:/dir`, as file URLs want them
+  if !encoded.starts_with('/') {
+    encoded.insert(0, '/');
+  }
+  encoded
+}
+
+fn hostname() -> String {
+  env::var("HOSTNAME")
+    .ok()
+    .or_else(|| fs::read_to_string("/proc/sys/kernel/hostname").ok())
+    .or_else(|| fs::read_to_string("/etc/hostname").ok())
+    .map(|name| name.trim().to_string())
+    .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fills_in_templates() {
+    let format = HyperlinkFormat::parse("vscode").unwrap().unwrap();
+    assert_eq!(
+      "vscode://file/home/me/my%20notes.txt:3:7",
+      format.url(Path::new("/home/me/my notes.txt"), 3, 7)
+    );
+    let format = HyperlinkFormat::parse("edit://{path}?l={line}")
+      .unwrap()
+      .unwrap();
+    assert_eq!("edit:///pond?l=1", format.url(Path::new("/pond"), 1, 1));
+    assert_eq!(None, HyperlinkFormat::parse("none").unwrap());
+    assert!(HyperlinkFormat::parse("file://{file}").is_err());
+    assert!(HyperlinkFormat::parse("file://{path}#{lin").is_err());
+    assert_eq!(
+      "\x1b]8;;file:///pond\x1b\\pond\x1b]8;;\x1b\\",
+      link("file:///pond", "pond")
+    );
+  }
+}
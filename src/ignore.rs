@@ -0,0 +1,220 @@
+use crate::glob::Glob;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The rules of a single `.gitignore` file.
+#[derive(Debug, Clone)]
+pub struct Gitignore {
+  root: PathBuf,
+  // Where `root` is below the directory of the ignore file, for one in a
+  // directory above where the search starts
+  below: PathBuf,
+  rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+  glob: Glob,
+  negated: bool,
+  dir_only: bool,
+  // Rules containing a `/` only match relative to the ignore file itself
+  anchored: bool,
+}
+
+impl Gitignore {
+  /// Parses the contents of an ignore file living in the directory `root`.
+  /// Lines that are not valid globs are skipped, like git does.
+  pub fn parse<P: AsRef<Path>>(root: P, contents: &str) -> Gitignore {
+    let rules = contents.lines().filter_map(parse_rule).collect();
+    Gitignore {
+      root: root.as_ref().to_path_buf(),
+      below: PathBuf::new(),
+      rules,
+    }
+  }
+
+  /// Loads `dir/name`, returning `None` when there is no such file.
+  pub fn from_dir(dir: &Path, name: &str) -> Option<Gitignore> {
+    let contents = fs::read_to_string(dir.join(name)).ok()?;
+    Some(Gitignore::parse(dir, &contents))
+  }
+
+  /// Loads `dir/name` for a search starting at `root`, which is `below`
+  /// inside `dir`, so that the rules apply to the paths found from `root`.
+  pub fn from_parent(dir: &Path, name: &str, root: &Path, below: &Path) -> Option<Gitignore> {
+    let mut ignore = Gitignore::from_dir(dir, name)?;
+    ignore.root = root.to_path_buf();
+    ignore.below = below.to_path_buf();
+    Some(ignore)
+  }
+
+  /// Returns `Some(true)` if `path` is ignored, `Some(false)` if a negated
+  /// rule explicitly re-includes it and `None` if no rule applies. As in git,
+  /// the last matching rule wins.
+  pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+    let relative = self.below.join(path.strip_prefix(&self.root).ok()?);
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    let name = relative.rsplit('/').next().unwrap_or("");
+    self
+      .rules
+      .iter()
+      .rev()
+      .filter(|rule| is_dir || !rule.dir_only)
+      .find(|rule| {
+        if rule.anchored {
+          rule.glob.is_match(&relative)
+        } else {
+          rule.glob.is_match(name)
+        }
+      })
+      .map(|rule| !rule.negated)
+  }
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+  // Trailing spaces are ignored unless escaped, the escaped one being left
+  // for the glob to read as a space
+  let line = line.trim_end_matches('\r');
+  let trimmed = line.trim_end_matches(' ');
+  let backslashes = trimmed.len() - trimmed.trim_end_matches('\\').len();
+  let mut line = if backslashes % 2 == 1 && trimmed.len() < line.len() {
+    &line[..trimmed.len() + 1]
+  } else {
+    trimmed
+  };
+  if line.ends_with('\\') {
+    line = &line[..line.len() - 1];
+  }
+  if line.is_empty() || line.starts_with('#') {
+    return None;
+  }
+  let (negated, line) = match line.strip_prefix('!') {
+    Some(rest) => (true, rest),
+    None => (false, line.strip_prefix('\\').unwrap_or(line)),
+  };
+  let (dir_only, line) = match line.strip_suffix('/') {
+    Some(rest) => (true, rest),
+    None => (false, line),
+  };
+  let anchored = line.contains('/');
+  let line = line.strip_prefix('/').unwrap_or(line);
+  if line.is_empty() {
+    return None;
+  }
+  Some(Rule {
+    glob: Glob::new(line).ok()?,
+    negated,
+    dir_only,
+    anchored,
+  })
+}
+
+/// The ignore files in effect for a directory: its own plus those of every
+/// directory above it, nearest first.
+#[derive(Debug, Default)]
+pub struct IgnoreStack {
+  ignore: Option<Gitignore>,
+  parent: Option<Arc<IgnoreStack>>,
+}
+
+impl IgnoreStack {
+  pub fn new() -> Arc<IgnoreStack> {
+    Arc::new(IgnoreStack::default())
+  }
+
+  /// Adds the rules of a nested directory on top of `parent`. Returns the
+  /// parent unchanged when there is nothing to add.
+  pub fn push(parent: &Arc<IgnoreStack>, ignore: Option<Gitignore>) -> Arc<IgnoreStack> {
+    match ignore {
+      Some(ignore) => Arc::new(IgnoreStack {
+        ignore: Some(ignore),
+        parent: Some(parent.clone()),
+      }),
+      None => parent.clone(),
+    }
+  }
+
+  /// Whether `path` is ignored. Rules from deeper ignore files take
+  /// precedence over the ones above them.
+  pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+    let mut stack = Some(self);
+    while let Some(current) = stack {
+      if let Some(ignored) = current
+        .ignore
+        .as_ref()
+        .and_then(|i| i.matched(path, is_dir))
+      {
+        return ignored;
+      }
+      stack = current.parent.as_deref();
+    }
+    false
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::Fixture;
+
+  #[test]
+  fn gitignore_rules() {
+    let ignore = Gitignore::parse(
+      "/repo",
+      "\
+# build output
+target/
+*.log
+!keep.log
+/docs/*.html
+",
+    );
+    let matched = |path: &str, is_dir| ignore.matched(Path::new(path), is_dir);
+
+    assert_eq!(Some(true), matched("/repo/target", true));
+    assert_eq!(None, matched("/repo/target", false));
+    assert_eq!(Some(true), matched("/repo/src/debug.log", false));
+    assert_eq!(Some(false), matched("/repo/src/keep.log", false));
+    assert_eq!(Some(true), matched("/repo/docs/index.html", false));
+    assert_eq!(None, matched("/repo/src/docs/index.html", false));
+    assert_eq!(None, matched("/elsewhere/debug.log", false));
+  }
+
+  #[test]
+  fn keeps_escaped_trailing_spaces() {
+    let ignore = Gitignore::parse("/repo", "spaced\\ \nplain  \n");
+    let matched = |path: &str| ignore.matched(Path::new(path), false);
+
+    assert_eq!(Some(true), matched("/repo/spaced "));
+    assert_eq!(None, matched("/repo/spaced"));
+    assert_eq!(Some(true), matched("/repo/plain"));
+  }
+
+  #[test]
+  fn applies_parent_rules_below_the_root() {
+    let dir = Fixture::new("ignore", &[(".gitignore", "/sub/build/\n*.log\n")]);
+    let ignore = Gitignore::from_parent(dir.path(), ".gitignore", Path::new("."), Path::new("sub"));
+    let ignore = ignore.unwrap();
+
+    assert_eq!(Some(true), ignore.matched(Path::new("./build"), true));
+    assert_eq!(
+      Some(true),
+      ignore.matched(Path::new("./a/debug.log"), false)
+    );
+    assert_eq!(None, ignore.matched(Path::new("./a/build"), true));
+  }
+
+  #[test]
+  fn nested_rules_take_precedence() {
+    let root = IgnoreStack::push(
+      &IgnoreStack::new(),
+      Some(Gitignore::parse("/repo", "*.txt")),
+    );
+    let nested = IgnoreStack::push(&root, Some(Gitignore::parse("/repo/sub", "!notes.txt")));
+
+    assert!(root.is_ignored(Path::new("/repo/sub/notes.txt"), false));
+    assert!(!nested.is_ignored(Path::new("/repo/sub/notes.txt"), false));
+    assert!(nested.is_ignored(Path::new("/repo/sub/other.txt"), false));
+  }
+}
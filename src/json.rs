@@ -0,0 +1,429 @@
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+/// A parsed JSON document.
+///
+/// Object members keep their original order so that serializing a value
+/// produces stable output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Value>),
+  Object(Vec<(String, Value)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+  pub message: String,
+  pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} at byte {}", self.message, self.position)
+  }
+}
+
+impl Error for ParseError {}
+
+impl Value {
+  pub fn parse(input: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser {
+      input: input.as_bytes(),
+      pos: 0,
+    };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    if parser.pos != parser.input.len() {
+      return Err(parser.error("trailing characters"));
+    }
+    Ok(value)
+  }
+
+  pub fn get(&self, key: &str) -> Option<&Value> {
+    match self {
+      Value::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Value::String(s) => Some(s),
+      _ => None,
+    }
+  }
+
+  pub fn as_bool(&self) -> Option<bool> {
+    match self {
+      Value::Bool(b) => Some(*b),
+      _ => None,
+    }
+  }
+
+  pub fn as_usize(&self) -> Option<usize> {
+    match self {
+      Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+      _ => None,
+    }
+  }
+
+  pub fn as_array(&self) -> Option<&[Value]> {
+    match self {
+      Value::Array(items) => Some(items),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Value::Null => f.write_str("null"),
+      Value::Bool(b) => write!(f, "{}", b),
+      Value::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+      Value::Number(n) => write!(f, "{}", n),
+      Value::String(s) => write_string(f, s),
+      Value::Array(items) => {
+        f.write_str("[")?;
+        for (i, item) in items.iter().enumerate() {
+          if i > 0 {
+            f.write_str(",")?;
+          }
+          write!(f, "{}", item)?;
+        }
+        f.write_str("]")
+      }
+      Value::Object(members) => {
+        f.write_str("{")?;
+        for (i, (key, value)) in members.iter().enumerate() {
+          if i > 0 {
+            f.write_str(",")?;
+          }
+          write_string(f, key)?;
+          write!(f, ":{}", value)?;
+        }
+        f.write_str("}")
+      }
+    }
+  }
+}
+
+impl From<&str> for Value {
+  fn from(s: &str) -> Value {
+    Value::String(s.to_string())
+  }
+}
+
+impl From<usize> for Value {
+  fn from(n: usize) -> Value {
+    Value::Number(n as f64)
+  }
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+  f.write_str("\"")?;
+  for c in s.chars() {
+    match c {
+      '"' => f.write_str("\\\"")?,
+      '\\' => f.write_str("\\\\")?,
+      '\n' => f.write_str("\\n")?,
+      '\r' => f.write_str("\\r")?,
+      '\t' => f.write_str("\\t")?,
+      c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+      c => write!(f, "{}", c)?,
+    }
+  }
+  f.write_str("\"")
+}
+
+struct Parser<'a> {
+  input: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn error(&self, message: &str) -> ParseError {
+    ParseError {
+      message: message.to_string(),
+      position: self.pos,
+    }
+  }
+
+  fn skip_whitespace(&mut self) {
+    while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.input.get(self.pos) {
+      self.pos += 1;
+    }
+  }
+
+  fn expect(&mut self, literal: &str, value: Value) -> Result<Value, ParseError> {
+    if self.input[self.pos..].starts_with(literal.as_bytes()) {
+      self.pos += literal.len();
+      Ok(value)
+    } else {
+      Err(self.error("invalid literal"))
+    }
+  }
+
+  fn parse_value(&mut self) -> Result<Value, ParseError> {
+    self.skip_whitespace();
+    match self.input.get(self.pos) {
+      None => Err(self.error("unexpected end of input")),
+      Some(b'n') => self.expect("null", Value::Null),
+      Some(b't') => self.expect("true", Value::Bool(true)),
+      Some(b'f') => self.expect("false", Value::Bool(false)),
+      Some(b'"') => Ok(Value::String(self.parse_string()?)),
+      Some(b'[') => self.parse_array(),
+      Some(b'{') => self.parse_object(),
+      Some(b'-' | b'0'..=b'9') => self.parse_number(),
+      Some(_) => Err(self.error("unexpected character")),
+    }
+  }
+
+  fn parse_number(&mut self) -> Result<Value, ParseError> {
+    let start = self.pos;
+    while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.input.get(self.pos) {
+      self.pos += 1;
+    }
+    let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+    text.parse().map(Value::Number).map_err(|_| ParseError {
+      message: "invalid number".to_string(),
+      position: start,
+    })
+  }
+
+  fn parse_string(&mut self) -> Result<String, ParseError> {
+    self.pos += 1;
+    let mut output = String::new();
+    loop {
+      let start = self.pos;
+      while let Some(&b) = self.input.get(self.pos) {
+        if b == b'"' || b == b'\\' {
+          break;
+        }
+        self.pos += 1;
+      }
+      // Input came from a &str and we only split at ASCII bytes
+      output.push_str(std::str::from_utf8(&self.input[start..self.pos]).unwrap());
+      match self.input.get(self.pos) {
+        None => return Err(self.error("unterminated string")),
+        Some(b'"') => {
+          self.pos += 1;
+          return Ok(output);
+        }
+        Some(_) => {
+          self.pos += 1;
+          let escaped = match self.input.get(self.pos) {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+              self.pos += 1;
+              let c = self.parse_unicode_escape()?;
+              output.push(c);
+              continue;
+            }
+            _ => return Err(self.error("invalid escape")),
+          };
+          self.pos += 1;
+          output.push(escaped);
+        }
+      }
+    }
+  }
+
+  fn parse_hex4(&mut self) -> Result<u32, ParseError> {
+    let digits = self
+      .input
+      .get(self.pos..self.pos + 4)
+      .and_then(|d| std::str::from_utf8(d).ok())
+      .and_then(|d| u32::from_str_radix(d, 16).ok());
+    match digits {
+      Some(n) => {
+        self.pos += 4;
+        Ok(n)
+      }
+      None => Err(self.error("invalid unicode escape")),
+    }
+  }
+
+  fn parse_unicode_escape(&mut self) -> Result<char, ParseError> {
+    let high = self.parse_hex4()?;
+    let code = if (0xd800..0xdc00).contains(&high) {
+      // Surrogate pairs are spelled as two consecutive escapes
+      if !self.input[self.pos..].starts_with(b"\\u") {
+        return Err(self.error("unpaired surrogate"));
+      }
+      self.pos += 2;
+      let low = self.parse_hex4()?;
+      if !(0xdc00..0xe000).contains(&low) {
+        return Err(self.error("unpaired surrogate"));
+      }
+      0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+    } else {
+      high
+    };
+    char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+  }
+
+  fn parse_array(&mut self) -> Result<Value, ParseError> {
+    self.pos += 1;
+    let mut items = Vec::new();
+    self.skip_whitespace();
+    if self.input.get(self.pos) == Some(&b']') {
+      self.pos += 1;
+      return Ok(Value::Array(items));
+    }
+    loop {
+      items.push(self.parse_value()?);
+      self.skip_whitespace();
+      match self.input.get(self.pos) {
+        Some(b',') => self.pos += 1,
+        Some(b']') => {
+          self.pos += 1;
+          return Ok(Value::Array(items));
+        }
+        _ => return Err(self.error("expected `,` or `]`")),
+      }
+    }
+  }
+
+  fn parse_object(&mut self) -> Result<Value, ParseError> {
+    self.pos += 1;
+    let mut members = Vec::new();
+    self.skip_whitespace();
+    if self.input.get(self.pos) == Some(&b'}') {
+      self.pos += 1;
+      return Ok(Value::Object(members));
+    }
+    loop {
+      self.skip_whitespace();
+      if self.input.get(self.pos) != Some(&b'"') {
+        return Err(self.error("expected a string key"));
+      }
+      let key = self.parse_string()?;
+      self.skip_whitespace();
+      if self.input.get(self.pos) != Some(&b':') {
+        return Err(self.error("expected `:`"));
+      }
+      self.pos += 1;
+      members.push((key, self.parse_value()?));
+      self.skip_whitespace();
+      match self.input.get(self.pos) {
+        Some(b',') => self.pos += 1,
+        Some(b'}') => {
+          self.pos += 1;
+          return Ok(Value::Object(members));
+        }
+        _ => return Err(self.error("expected `,` or `}`")),
+      }
+    }
+  }
+}
+
+/// The object printed for every matching line in `--json` mode.
+///
+/// The schema is `{"file": string|null, "line_number": number, "line":
+/// string, "matches": [{"start": number, "end": number}]}`, where match
+/// offsets are byte offsets into `line`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchRecord {
+  pub file: Option<String>,
+  pub line_number: usize,
+  pub line: String,
+  pub matches: Vec<Range<usize>>,
+}
+
+impl MatchRecord {
+  pub fn to_value(&self) -> Value {
+    let file = match &self.file {
+      Some(file) => Value::from(file.as_str()),
+      None => Value::Null,
+    };
+    let matches = self
+      .matches
+      .iter()
+      .map(|span| {
+        Value::Object(vec![
+          ("start".to_string(), Value::from(span.start)),
+          ("end".to_string(), Value::from(span.end)),
+        ])
+      })
+      .collect();
+    Value::Object(vec![
+      ("file".to_string(), file),
+      ("line_number".to_string(), Value::from(self.line_number)),
+      ("line".to_string(), Value::from(self.line.as_str())),
+      ("matches".to_string(), Value::Array(matches)),
+    ])
+  }
+
+  pub fn from_value(value: &Value) -> Option<MatchRecord> {
+    let file = match value.get("file")? {
+      Value::Null => None,
+      file => Some(file.as_str()?.to_string()),
+    };
+    let mut matches = Vec::new();
+    for span in value.get("matches")?.as_array()? {
+      matches.push(span.get("start")?.as_usize()?..span.get("end")?.as_usize()?);
+    }
+    Some(MatchRecord {
+      file,
+      line_number: value.get("line_number")?.as_usize()?,
+      line: value.get("line")?.as_str()?.to_string(),
+      matches,
+    })
+  }
+
+  pub fn parse(line: &str) -> Option<MatchRecord> {
+    MatchRecord::from_value(&Value::parse(line).ok()?)
+  }
+}
+
+impl fmt::Display for MatchRecord {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.to_value())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_and_prints_documents() {
+    let input = r#"{"a": [1, 2.5, true, null], "b": "x\"é😀"}"#;
+    let value = Value::parse(input).unwrap();
+
+    assert_eq!(Some("x\"é😀"), value.get("b").and_then(Value::as_str));
+    assert_eq!(r#"{"a":[1,2.5,true,null],"b":"x\"é😀"}"#, value.to_string());
+    assert!(Value::parse("[1,]").is_err());
+    assert!(Value::parse("{} {}").is_err());
+  }
+
+  #[test]
+  fn match_record_round_trips() {
+    let record = MatchRecord {
+      file: Some("src/lib.rs".to_string()),
+      line_number: 3,
+      line: "\tlet x = \"y\";".to_string(),
+      matches: vec![5..6, 10..11],
+    };
+    let printed = record.to_string();
+
+    assert_eq!(
+      r#"{"file":"src/lib.rs","line_number":3,"line":"\tlet x = \"y\";","matches":[{"start":5,"end":6},{"start":10,"end":11}]}"#,
+      printed
+    );
+    assert_eq!(Some(record), MatchRecord::parse(&printed));
+  }
+}
@@ -0,0 +1,269 @@
+//! Searching the string values of JSON inputs, for `--json-input`, so that
+//! neither keys nor the escapes in how a string is written can match.
+//!
+//! An input is one JSON document, or one on each line as with NDJSON. Only
+//! the strings under what `--path` selects are matched: `a.b` is member `b`
+//! of member `a`, `[]` and `*` are every item of an array or member of an
+//! object, and `[2]` is the third item, as in `items[].name`. Strings are
+//! found at any depth below what is selected.
+
+use crate::json::{ParseError, Value};
+use std::ops::Range;
+
+/// One document of an input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Document {
+  /// Where the document starts in the input.
+  pub offset: usize,
+  pub line_number: usize,
+  pub value: Value,
+}
+
+/// Reads `contents` as a single document, or as one document a line,
+/// skipping blank lines.
+pub fn documents(contents: &str) -> Result<Vec<Document>, ParseError> {
+  let whole = match Value::parse(contents) {
+    Ok(value) => {
+      let offset = contents.len() - contents.trim_start().len();
+      let line_number = 1 + contents[..offset].matches('\n').count();
+      return Ok(vec![Document {
+        offset,
+        line_number,
+        value,
+      }]);
+    }
+    Err(e) => e,
+  };
+  let mut documents = Vec::new();
+  let mut offset = 0;
+  for (i, line) in contents.split_inclusive('\n').enumerate() {
+    let start = offset + line.len() - line.trim_start().len();
+    offset += line.len();
+    if line.trim().is_empty() {
+      continue;
+    }
+    match Value::parse(line) {
+      Ok(value) => documents.push(Document {
+        offset: start,
+        line_number: i + 1,
+        value,
+      }),
+      // When not even the first line is a document, the input was meant as
+      // a single one
+      Err(_) if documents.is_empty() => return Err(whole),
+      Err(e) => {
+        return Err(ParseError {
+          message: e.message,
+          position: offset - line.len() + e.position,
+        })
+      }
+    }
+  }
+  Ok(documents)
+}
+
+/// The values of a document that are searched, from `--path`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector(Vec<Step>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+  Member(String),
+  Index(usize),
+  Each,
+}
+
+impl Step {
+  fn selects(&self, key: Key) -> bool {
+    match (self, key) {
+      (Step::Each, _) => true,
+      (Step::Member(name), Key::Member(member)) => name == member,
+      (Step::Index(index), Key::Index(i)) => *index == i,
+      _ => false,
+    }
+  }
+}
+
+impl Selector {
+  /// Reads a path like `items[].name`, which may start with `$` or `.`.
+  pub fn parse(spec: &str) -> Result<Selector, String> {
+    let invalid = || format!("`{}` is not a path like `items[].name`", spec);
+    let mut steps = Vec::new();
+    let mut rest = spec.strip_prefix('$').unwrap_or(spec);
+    while !rest.is_empty() {
+      if let Some(after) = rest.strip_prefix('[') {
+        let end = after.find(']').ok_or_else(invalid)?;
+        steps.push(match &after[..end] {
+          "" | "*" => Step::Each,
+          index => Step::Index(index.parse().map_err(|_| invalid())?),
+        });
+        rest = &after[end + 1..];
+        continue;
+      }
+      rest = rest.strip_prefix('.').unwrap_or(rest);
+      let end = rest.find(['.', '[']).unwrap_or(rest.len());
+      steps.push(match &rest[..end] {
+        "" => return Err(invalid()),
+        "*" => Step::Each,
+        name => Step::Member(name.to_string()),
+      });
+      rest = &rest[end..];
+    }
+    Ok(Selector(steps))
+  }
+}
+
+/// A document written on a single line, the way `Value` displays it.
+pub struct Compact<'v> {
+  pub line: String,
+  /// The selected strings, in the order they are written in `line`.
+  pub strings: Vec<Text<'v>>,
+}
+
+/// A string value that is searched.
+pub struct Text<'v> {
+  /// Where the string is in the document, as a JSON pointer.
+  pub pointer: String,
+  pub text: &'v str,
+  // Where the written string starts in `line`, past its opening quote
+  start: usize,
+}
+
+impl Text<'_> {
+  /// Where `span` of the text is in the compact line.
+  pub fn span_in_line(&self, span: Range<usize>) -> Range<usize> {
+    let written = escaped_span(self.text, span);
+    self.start + written.start..self.start + written.end
+  }
+}
+
+/// Where `span` of `text` is once it is written as a JSON string, quotes
+/// left out.
+pub fn escaped_span(text: &str, span: Range<usize>) -> Range<usize> {
+  escaped_len(&text[..span.start])..escaped_len(&text[..span.end])
+}
+
+fn escaped_len(text: &str) -> usize {
+  Value::from(text).to_string().len() - 2
+}
+
+/// Writes `value` on one line, gathering the strings `selector` selects, or
+/// every string without one.
+pub fn compact<'v>(value: &'v Value, selector: Option<&Selector>) -> Compact<'v> {
+  let steps = selector.map_or(&[][..], |selector| &selector.0);
+  let mut compact = Compact {
+    line: String::new(),
+    strings: Vec::new(),
+  };
+  write_value(value, Some(steps), &mut String::new(), &mut compact);
+  compact
+}
+
+#[derive(Clone, Copy)]
+enum Key<'a> {
+  Member(&'a str),
+  Index(usize),
+}
+
+// `steps` are what is left of the selector on the way down, or `None` once
+// the path has left it
+fn write_value<'v>(
+  value: &'v Value,
+  steps: Option<&[Step]>,
+  pointer: &mut String,
+  compact: &mut Compact<'v>,
+) {
+  let below = |key: Key| match steps {
+    Some([]) => Some(&[][..]),
+    Some([step, rest @ ..]) if step.selects(key) => Some(rest),
+    _ => None,
+  };
+  let depth = pointer.len();
+  match value {
+    Value::String(text) => {
+      let written = value.to_string();
+      if steps == Some(&[]) {
+        compact.strings.push(Text {
+          pointer: pointer.clone(),
+          text,
+          start: compact.line.len() + 1,
+        });
+      }
+      compact.line.push_str(&written);
+    }
+    Value::Array(items) => {
+      compact.line.push('[');
+      for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+          compact.line.push(',');
+        }
+        pointer.push_str(&format!("/{}", i));
+        write_value(item, below(Key::Index(i)), pointer, compact);
+        pointer.truncate(depth);
+      }
+      compact.line.push(']');
+    }
+    Value::Object(members) => {
+      compact.line.push('{');
+      for (i, (key, member)) in members.iter().enumerate() {
+        if i > 0 {
+          compact.line.push(',');
+        }
+        compact
+          .line
+          .push_str(&Value::from(key.as_str()).to_string());
+        compact.line.push(':');
+        pointer.push('/');
+        pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+        write_value(member, below(Key::Member(key)), pointer, compact);
+        pointer.truncate(depth);
+      }
+      compact.line.push('}');
+    }
+    Value::Null | Value::Bool(_) | Value::Number(_) => compact.line.push_str(&value.to_string()),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_the_strings_a_path_selects() {
+    let value =
+      Value::parse(r#"{"frog":"a", "items":[{"name":"a \"frog\""}, {"name":1}], "a/b":"x"}"#)
+        .unwrap();
+    let compact = compact(&value, None);
+    assert_eq!(value.to_string(), compact.line);
+    let pointers: Vec<&str> = compact.strings.iter().map(|s| s.pointer.as_str()).collect();
+    assert_eq!(vec!["/frog", "/items/0/name", "/a~1b"], pointers);
+    let frog = &compact.strings[1];
+    assert_eq!("frog", &compact.line[frog.span_in_line(3..7)]);
+
+    let selector = Selector::parse("$.items[].name").unwrap();
+    let selected = super::compact(&value, Some(&selector));
+    assert_eq!(1, selected.strings.len());
+    assert_eq!("a \"frog\"", selected.strings[0].text);
+    assert_eq!(
+      Selector(vec![Step::Index(0), Step::Each]),
+      Selector::parse("[0].*").unwrap()
+    );
+    assert!(Selector::parse("items..name").is_err());
+    assert!(Selector::parse("items[x]").is_err());
+  }
+
+  #[test]
+  fn reads_one_document_or_one_a_line() {
+    let single = documents("\n {\"a\":\n1}\n").unwrap();
+    assert_eq!((2, 2), (single[0].offset, single[0].line_number));
+    let lines = documents("{\"a\":1}\n\n\"b\"\n").unwrap();
+    assert_eq!(
+      vec![(0, 1), (9, 3)],
+      lines
+        .iter()
+        .map(|d| (d.offset, d.line_number))
+        .collect::<Vec<_>>()
+    );
+    assert_eq!(12, documents("{\"a\":1}\n[1,\n").unwrap_err().position);
+  }
+}
@@ -1,138 +1,4282 @@
-use std::env;
-use std::error::Error;
-use std::fs;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+#[cfg(feature = "cli")]
+use std::io::IsTerminal;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "parallel")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "cli")]
+use std::sync::Arc;
+#[cfg(feature = "parallel")]
+use std::sync::{mpsc, Mutex};
+#[cfg(feature = "parallel")]
+use std::thread;
+use std::time::{Instant, SystemTime};
 
-const HELP_TEXT: &str = "<query> <file> [-i]";
-const UNSUFFICIENT_ARGUMENTS: &str = "Unsufficient arguments";
-const UNSUPPORTED_OPTION: &str = "Unsupported option";
-const UNEXPECTED_ARGUMENT: &str = "Unexpected argument";
+pub mod accents;
+pub mod aho_corasick;
+pub mod archive;
+#[cfg(feature = "cli")]
+pub mod args;
+#[cfg(feature = "async")]
+pub mod background;
+pub mod builder;
+#[cfg(feature = "cli")]
+pub mod completions;
+#[cfg(feature = "cli")]
+pub mod config;
+pub mod csv;
+#[cfg(feature = "documents")]
+pub mod document;
+pub mod edit;
+pub mod encoding;
+pub mod error;
+pub mod escape;
+pub mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filter;
+#[cfg(test)]
+mod fixture;
+pub mod fold;
+pub mod fuzzy;
+pub mod glob;
+pub mod gzip;
+pub mod http;
+pub mod hyperlink;
+pub mod ignore;
+pub mod json;
+pub mod json_input;
+pub mod limit;
+pub mod log;
+pub mod matcher;
+pub mod memmem;
+pub mod mmap;
+#[cfg(feature = "cli")]
+pub mod pager;
+pub mod preprocess;
+pub mod printer;
+pub mod progress;
+pub mod records;
+pub mod regex;
+pub mod replace;
+pub mod sink;
+pub mod stats;
+#[cfg(feature = "cli")]
+pub mod tail;
+#[cfg(feature = "color")]
+pub mod terminal;
+pub mod theme;
+pub mod timespec;
+#[cfg(feature = "cli")]
+pub mod tui;
+#[cfg(feature = "parallel")]
+pub mod turns;
+pub mod types;
+pub mod walk;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "cli")]
+pub mod watch;
+
+use aho_corasick::AhoCorasick;
+use archive::Archive;
+#[cfg(feature = "cli")]
+use args::Arg;
+use builder::ConfigBuilder;
+use csv::Column;
+use edit::InPlace;
+use encoding::{DecodeReader, Encoding};
+use error::MinigrepError;
+use filter::PathFilter;
+use fold::{CaseFold, Folding};
+use fuzzy::FuzzyMatcher;
+use gzip::GzDecoder;
+use hyperlink::HyperlinkFormat;
+use json::Value;
+use json_input::Selector;
+use limit::ResultLimit;
+use matcher::{CaseInsensitiveMatcher, FoldingMatcher, LiteralMatcher, Matcher};
+use memmem::{count_byte, Finder};
+use mmap::Mmap;
+use preprocess::Preprocessor;
+use printer::Printer;
+#[cfg(feature = "parallel")]
+use progress::Progress;
+use records::{Record, Records};
+use regex::Regex;
+use replace::{Groups, Template};
+use sink::Sink;
+use stats::{FileStats, LineCounter, Stats};
+#[cfg(feature = "cli")]
+use tail::{Follow, Lines};
+use theme::Theme;
+#[cfg(feature = "parallel")]
+use turns::Turns;
+#[cfg(feature = "cli")]
+use walk::SortBy;
+use walk::{Sort, Walker};
+#[cfg(feature = "cli")]
+use watch::Watcher;
+
+const DEFAULT_COMMENT_PREFIX: &str = "#";
+pub(crate) const DEFAULT_CONTEXT_SEPARATOR: &str = "--";
+const STDIN_FILENAME: &str = "-";
+const STDIN_LABEL: &str = "(standard input)";
+const IN_PLACE_WITHOUT_REPLACE: &str = "`--in-place` needs `--replace`";
+const IN_PLACE_ENCODING: &str = "`--in-place` only supports UTF-8 input";
+const IN_PLACE_MULTILINE: &str = "`--in-place` cannot be combined with `--multiline`";
+const SORT_WITHOUT_FUZZY: &str = "`--sort-by-score` needs `--fuzzy`";
+const JSON_LINES_COUNT: &str = "`--json-lines` cannot be combined with `-c`, `-l` or `-L`";
+const PER_FILE_WITHOUT_STATS: &str = "`--per-file` needs `--stats`";
+const TOTAL_WITHOUT_COUNT: &str = "`--total` needs `-c` or `--count-matches`";
+const WATCH_STDIN: &str = "`--watch` cannot watch standard input";
+const WATCH_IN_PLACE: &str = "`--watch` cannot be combined with `--in-place`";
+const TAIL_STDIN: &str = "`--tail` cannot follow standard input";
+const TAIL_UNFINISHED: &str = "`--tail` never reaches the end of a file, so it cannot be combined \
+                               with `-c`, `--count-matches`, `-L`, `-U`, `--sort-by-score`, \
+                               `--in-place`, `--watch` or `--json-input`";
+const TUI_IN_PLACE: &str = "`--tui` cannot be combined with `--in-place`";
+const PRE_IN_PLACE: &str = "`--pre` cannot be combined with `--in-place`";
+const RECORDS_MULTILINE: &str = "records other than lines cannot be combined with `--multiline`";
+const RECORDS_IN_PLACE: &str = "records other than lines cannot be combined with `--in-place`";
+const COLUMN_WITHOUT_CSV: &str = "`--column` needs `--csv`";
+const JSON_INPUT_RECORDS: &str =
+  "`--json-input` cannot be combined with `-U`, `--in-place` or records other than lines";
+const PREVIEW_WITHOUT_MAX_COLUMNS: &str = "`--max-columns-preview` needs `--max-columns`";
+const PASSTHRU_MULTILINE: &str = "`--passthru` cannot be combined with `--multiline`";
+const AGE_WITHOUT_RECURSIVE: &str = "`--newer-than` and `--older-than` need `-r`";
+const JSON_PATH_WITHOUT_INPUT: &str = "`--path` and `--json-pointers` need `--json-input`";
+const REPLACE_MISSING_GROUP: &str =
+  "`--replace` refers to a group that no pattern has, which needs `-E`";
+
+/// Reads one pattern per line of `contents`, skipping blank lines and lines
+/// starting with `comment_prefix`.
+pub fn read_patterns(contents: &str, comment_prefix: Option<&str>) -> Vec<String> {
+  contents
+    .lines()
+    .filter(|line| !line.trim().is_empty())
+    .filter(|line| !comment_prefix.is_some_and(|prefix| line.starts_with(prefix)))
+    .map(String::from)
+    .collect()
+}
+
+/// What a line is tested against, compiled once when the arguments are parsed.
+pub enum Pattern {
+  Literal(String),
+  Regex(Regex),
+  Fuzzy(FuzzyMatcher),
+}
+
+impl Pattern {
+  pub fn as_str(&self) -> &str {
+    match self {
+      Pattern::Literal(query) => query,
+      Pattern::Regex(re) => re.as_str(),
+      Pattern::Fuzzy(fuzzy) => fuzzy.as_str(),
+    }
+  }
+}
+
+/// How queries are turned into patterns.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Syntax {
+  Literal,
+  Regex,
+  /// Characters of the query in order, with anything in between.
+  Fuzzy,
+}
+
+/// When to highlight matches with ANSI escape codes.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColorChoice {
+  /// Only when standard output is a terminal that can show colors, and
+  /// `NO_COLOR` is not set. Never without the `color` feature.
+  Auto,
+  Always,
+  Never,
+}
+
+impl ColorChoice {
+  fn enabled(self) -> bool {
+    match self {
+      #[cfg(feature = "color")]
+      ColorChoice::Auto => terminal::stdout_wants_color(),
+      #[cfg(not(feature = "color"))]
+      ColorChoice::Auto => false,
+      ColorChoice::Always => true,
+      ColorChoice::Never => false,
+    }
+  }
+}
+
+/// How results are written.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum OutputFormat {
+  Text,
+  /// One JSON object per matching line.
+  Json,
+  /// ripgrep's `--json` messages, for each input and the whole search.
+  JsonLines,
+  /// `file,line,column,match,text` rows, one per match, after a header.
+  Csv,
+  /// Like `Csv`, with tabs between the fields.
+  Tsv,
+  /// `file:line:column:text` for each match, as Vim's `:grep` reads it.
+  Vimgrep,
+}
+
+impl OutputFormat {
+  /// What separates the fields of a row, for the tabular formats.
+  pub fn delimiter(self) -> Option<char> {
+    match self {
+      OutputFormat::Csv => Some(','),
+      OutputFormat::Tsv => Some('\t'),
+      OutputFormat::Text | OutputFormat::Json | OutputFormat::JsonLines | OutputFormat::Vimgrep => {
+        None
+      }
+    }
+  }
+}
+
+/// When to search files through a memory map instead of a read buffer.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MmapChoice {
+  /// Only for regular files of at least `MMAP_THRESHOLD` bytes.
+  Auto,
+  Always,
+  Never,
+}
+
+/// Below this size, reading a file is about as fast as mapping it.
+pub const MMAP_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+impl MmapChoice {
+  fn should_map(self, file: &File) -> bool {
+    let metadata = match file.metadata() {
+      Ok(metadata) if metadata.is_file() && metadata.len() > 0 => metadata,
+      _ => return false,
+    };
+    match self {
+      MmapChoice::Auto => cfg!(unix) && metadata.len() >= MMAP_THRESHOLD,
+      MmapChoice::Always => cfg!(unix),
+      MmapChoice::Never => false,
+    }
+  }
+}
 
 pub struct Config {
-  pub query: String,
-  pub filename: String,
+  /// A line matches when any of these patterns does.
+  pub patterns: Vec<Pattern>,
+  /// `patterns` in a single automaton, when they are several fixed strings
+  /// matched case sensitively, so that each line is read just once. Build
+  /// it again with `literal_set` after changing `patterns`.
+  pub literal_set: Option<AhoCorasick>,
+  pub filenames: Vec<String>,
+  /// Whether each result is prefixed with the input it is from, from `-H`
+  /// and `-h`. `None` leaves that to `prints_filenames`.
+  pub with_filename: Option<bool>,
+  /// Name the files found below `.` without the `./` in front, as `-r`
+  /// without a file does.
+  pub strip_dot: bool,
   pub case_sensitive: bool,
+  /// How case is ignored, from `--case-fold`.
+  pub case_fold: CaseFold,
+  /// Match letters whatever their accents, from `--ignore-accents`.
+  pub ignore_accents: bool,
+  pub recursive: bool,
+  pub line_number: bool,
+  pub before_context: usize,
+  pub after_context: usize,
+  /// The line between groups of context that are not next to each other,
+  /// `None` for `--no-context-separator`.
+  pub context_separator: Option<String>,
+  pub count: bool,
+  /// Count every occurrence rather than matching lines, from
+  /// `--count-matches`.
+  pub count_matches: bool,
+  /// Follow the counts with their sum, from `--total`.
+  pub total: bool,
+  pub invert_match: bool,
+  pub color: ColorChoice,
+  /// The colors of each part of the output, from `--colors`.
+  pub theme: Theme,
+  /// Where the file names in colored output link to, from
+  /// `--hyperlink-format`. By default only terminals known to support links
+  /// get them.
+  pub hyperlink_format: Option<HyperlinkFormat>,
+  /// Write the results to this file instead of standard output, from
+  /// `--output`. Colors are off by default then.
+  pub output: Option<PathBuf>,
+  /// How results are written, from `--format` or `--json`.
+  pub format: OutputFormat,
+  pub word_regexp: bool,
+  /// Only match whole lines, from `-x`.
+  pub line_regexp: bool,
+  /// Prefix each matching line with the pattern that matched it.
+  pub show_pattern: bool,
+  /// Which files get searched, from `--include`, `--exclude` and `--hidden`.
+  pub path_filter: PathFilter,
+  /// Honor `.gitignore` files while searching recursively, unless
+  /// `--no-ignore` or `--no-ignore-vcs` is given.
+  pub git_ignore: bool,
+  /// Honor `.ignore` and `.minigrepignore` files while searching
+  /// recursively, unless `--no-ignore` or `--no-ignore-dot` is given.
+  pub dot_ignore: bool,
+  /// Only search files tracked by Git while searching recursively, from
+  /// `--git`.
+  pub git_tracked: bool,
+  /// Follow symbolic links while searching recursively, from `--follow`.
+  pub follow: bool,
+  /// How many directories deep to search recursively, from `--max-depth`.
+  /// `0` searches only the paths given.
+  pub max_depth: Option<usize>,
+  /// Only search the files below a directory modified after this, from
+  /// `--newer-than`.
+  pub newer_than: Option<SystemTime>,
+  /// Only search the files below a directory modified before this, from
+  /// `--older-than`.
+  pub older_than: Option<SystemTime>,
+  /// The order files are searched in while searching recursively, from
+  /// `--sort` and `--sortr`. Without one they are searched as they are found.
+  pub sort: Option<Sort>,
+  /// Files larger than this many bytes are skipped, from `--max-filesize`.
+  pub max_filesize: Option<u64>,
+  /// How many files to search at once, `0` to pick based on the CPU count.
+  /// Without the `parallel` feature files are always searched one at a time.
+  pub threads: usize,
+  /// Print each file's results as soon as they are ready rather than in the
+  /// order the files were found, from `--unordered`. Only threads can finish
+  /// files out of order, and `--sort` keeps them in order regardless.
+  pub unordered: bool,
+  /// Whether files are memory mapped, from `--mmap` and `--no-mmap`.
+  pub mmap: MmapChoice,
+  /// Search binary files as if they were text, from `-a`. Bytes that are
+  /// not UTF-8 are kept, and printed escaped along with control characters.
+  pub binary: bool,
+  /// What input is transcoded from before matching, from `--encoding`.
+  pub encoding: Encoding,
+  /// The command files are run through before searching, from `--pre`.
+  pub preprocessor: Option<Preprocessor>,
+  /// Stop searching a file after this many matching lines, from `-m`.
+  pub max_count: Option<usize>,
+  /// Cut printed lines down to this many characters around their first
+  /// match, from `-M`. What is matched is left alone.
+  pub max_columns: Option<usize>,
+  /// Keep the start of lines that are cut instead, from
+  /// `--max-columns-preview`.
+  pub max_columns_preview: bool,
+  /// Print lines without the spaces and tabs they start with, from
+  /// `--trim`. Columns still count from the start of the line.
+  pub trim: bool,
+  /// Print every line, the ones that do not match as context, from
+  /// `--passthru`.
+  pub passthru: bool,
+  /// Stop the whole search after this many matching lines across every
+  /// file, from `--max-results`. With threads, which lines those are depends
+  /// on which files are done first.
+  pub max_results: Option<ResultLimit>,
+  /// Print nothing and stop at the first match, from `-q`.
+  pub quiet: bool,
+  /// Say nothing about inputs that cannot be read, from `-s`.
+  pub no_messages: bool,
+  /// Told about each input that could not be searched, which is then
+  /// skipped. Nothing is said about them without it.
+  pub on_error: Option<ErrorHook>,
+  /// Only print the names of files with a match, from `-l`.
+  pub files_with_matches: bool,
+  /// Only print the names of files without any match, from `-L`.
+  pub files_without_match: bool,
+  /// Print each match on its own instead of the whole line, from `-o`.
+  pub only_matching: bool,
+  /// Prefix output with its byte offset in the input, from `-b`.
+  pub byte_offset: bool,
+  /// End file names with a NUL byte instead of `:` or a newline, from
+  /// `--null`.
+  pub null: bool,
+  /// Print matching lines with each match replaced, from `--replace`.
+  pub replace: Option<Template>,
+  /// Rewrite files with their matches replaced instead of printing them,
+  /// from `--in-place`.
+  pub in_place: Option<InPlace>,
+  /// Print the matching lines of each input best fuzzy match first, from
+  /// `--sort-by-score`.
+  pub sort_by_score: bool,
+  /// Let matches span several lines, from `-U`.
+  pub multiline: bool,
+  /// Let `$` match before a `\r\n` line ending, from `--crlf`.
+  pub crlf: bool,
+  /// What counts as a line when matching and printing, from `--paragraph`,
+  /// `--record-separator`, `--null-data` and `--csv`.
+  pub records: Records,
+  /// The column of `--csv` rows that is matched, from `--column`, rather
+  /// than the whole row.
+  pub column: Option<Column>,
+  /// Match the string values of JSON documents rather than lines, from
+  /// `--json-input`.
+  pub json_input: bool,
+  /// Only match the strings under what this selects in each document, from
+  /// `--path`.
+  pub json_path: Option<Selector>,
+  /// Print the JSON pointer of each string that matches instead of its
+  /// document, from `--json-pointers`.
+  pub json_pointers: bool,
+  /// Decompress gzip inputs before searching them, from `-z`.
+  pub search_zip: bool,
+  /// Search the files inside zip and tar archives, from `--search-archives`.
+  pub search_archives: bool,
+  /// Print statistics about the search once it is done, from `--stats`.
+  pub stats: bool,
+  /// Print the statistics of each file along with those of the whole
+  /// search, from `--per-file`.
+  pub per_file: bool,
+  /// What to log about the search, from `--verbose` and `--log-level`. It
+  /// takes effect once passed to `log::set_level`.
+  pub log_level: Option<log::Level>,
+  /// Keep searching files again as they change, from `--watch`.
+  pub watch: bool,
+  /// Keep reading files as they grow, from `--tail`.
+  pub tail: bool,
+  /// Refine the query interactively, from `--tui`.
+  pub tui: bool,
+  /// Page results that fill more than the terminal, unless `--no-pager`
+  /// was given. Only set when standard output is a terminal.
+  pub pager: bool,
+  /// How the queries were read, which the interactive mode needs to read
+  /// new ones the same way.
+  pub syntax: Syntax,
+}
+
+/// What the command line asks for.
+#[cfg(feature = "cli")]
+pub enum Parsed {
+  /// A search, as `config` says.
+  Search(Box<Config>),
+  /// The help, from `--help`, instead of searching.
+  Help,
+  /// The version, from `--version`, instead of searching.
+  Version,
+}
+
+impl Default for Config {
+  /// The defaults of the command line, with no patterns and no inputs.
+  fn default() -> Config {
+    Config {
+      patterns: Vec::new(),
+      literal_set: None,
+      filenames: Vec::new(),
+      with_filename: None,
+      strip_dot: false,
+      case_sensitive: true,
+      case_fold: CaseFold::Full,
+      ignore_accents: false,
+      recursive: false,
+      line_number: false,
+      before_context: 0,
+      after_context: 0,
+      context_separator: Some(DEFAULT_CONTEXT_SEPARATOR.to_string()),
+      count: false,
+      count_matches: false,
+      total: false,
+      invert_match: false,
+      color: ColorChoice::Auto,
+      theme: Theme::default(),
+      hyperlink_format: None,
+      output: None,
+      format: OutputFormat::Text,
+      word_regexp: false,
+      line_regexp: false,
+      show_pattern: false,
+      path_filter: PathFilter::new(),
+      git_ignore: true,
+      dot_ignore: true,
+      git_tracked: false,
+      follow: false,
+      max_depth: None,
+      newer_than: None,
+      older_than: None,
+      sort: None,
+      max_filesize: None,
+      threads: 0,
+      unordered: false,
+      mmap: MmapChoice::Auto,
+      binary: false,
+      encoding: Encoding::Utf8,
+      preprocessor: None,
+      max_count: None,
+      max_columns: None,
+      max_columns_preview: false,
+      trim: false,
+      passthru: false,
+      max_results: None,
+      quiet: false,
+      no_messages: false,
+      on_error: None,
+      files_with_matches: false,
+      files_without_match: false,
+      only_matching: false,
+      byte_offset: false,
+      null: false,
+      replace: None,
+      in_place: None,
+      sort_by_score: false,
+      multiline: false,
+      crlf: false,
+      records: Records::Lines,
+      column: None,
+      json_input: false,
+      json_path: None,
+      json_pointers: false,
+      search_zip: false,
+      search_archives: false,
+      stats: false,
+      per_file: false,
+      log_level: None,
+      watch: false,
+      tail: false,
+      tui: false,
+      pager: false,
+      syntax: Syntax::Literal,
+    }
+  }
 }
 
 impl Config {
-  pub fn new(mut args: env::Args) -> Result<Config, String> {
-    let programname = match args.next() {
-      Some(f) => f,
-      None => panic!("Unexpected error"),
-    };
-    let arguments_err = Err(format!(
-      "{prog}: {err}\nUsage: {prog} {help}",
-      prog = programname,
-      err = UNSUFFICIENT_ARGUMENTS,
-      help = HELP_TEXT
-    ));
-    let query = match args.next() {
-      Some(q) => q,
-      None => return arguments_err,
-    };
-    let filename = match args.next() {
-      Some(f) => f,
-      None => return arguments_err,
-    };
+  // Whether it only matters if an input matches at all, not which lines do
+  fn first_match_only(&self) -> bool {
+    self.quiet || self.files_with_matches || self.files_without_match
+  }
 
-    let mut case_sensitive = env::var("CASE_INSENSITIVE").is_err();
+  // Whether context is shown around the matching lines at all. With
+  // `only_matching` that is only the separators between groups, as GNU grep
+  // prints them, since context lines have no matches to print.
+  fn shows_context(&self) -> bool {
+    let counting = self.count || self.count_matches;
+    (self.before_context > 0 || self.after_context > 0)
+      && !counting
+      && !self.first_match_only()
+      && !self.sort_by_score
+  }
+
+  /// Whether results are prefixed with the input they are from, which
+  /// unless `with_filename` says otherwise they are when there may be
+  /// several: there are several operands, or `recursive` makes a directory
+  /// among them stand for all the files below it.
+  pub fn prints_filenames(&self) -> bool {
+    self.with_filename.unwrap_or_else(|| {
+      self.filenames.len() > 1
+        || self.recursive && self.filenames.iter().any(|f| Path::new(f).is_dir())
+    })
+  }
+
+  // Whether `--max-results` lines have been found, so that no more files
+  // need searching
+  fn limit_reached(&self) -> bool {
+    self.max_results.as_ref().is_some_and(ResultLimit::reached)
+  }
+
+  /// Parses command line arguments into the configuration of a search, as
+  /// `parse` does with standard input to be searched. `--help` and
+  /// `--version` configure no search and are refused.
+  #[cfg(feature = "cli")]
+  pub fn new<I: IntoIterator<Item = String>>(args: I) -> Result<Config, MinigrepError> {
+    match Config::parse(args, false)? {
+      Parsed::Search(config) => Ok(*config),
+      Parsed::Help | Parsed::Version => Err(MinigrepError::IncompatibleOptions(
+        "`--help` and `--version` do not configure a search",
+      )),
+    }
+  }
+
+  /// Parses command line arguments, starting with the program name as in
+  /// `env::args()`. Without files to search, standard input is read unless
+  /// `stdin_is_terminal` says there is a terminal there to wait on. Defaults
+  /// from config files and the environment, see `config`, are up to the
+  /// caller to put ahead of the arguments.
+  #[cfg(feature = "cli")]
+  pub fn parse<I: IntoIterator<Item = String>>(
+    args: I,
+    stdin_is_terminal: bool,
+  ) -> Result<Parsed, MinigrepError> {
+    let mut args = args.into_iter();
+    // Skip the program name
+    args.next();
+    let mut builder = ConfigBuilder::new();
+    let mut positionals = Vec::new();
+    let mut has_query = false;
+    let mut recursive = false;
+    // Like GNU grep, `-A` and `-B` win over `-C` whichever comes first
+    let mut before_context = None;
+    let mut after_context = None;
+    let mut context = None;
+    let mut hyperlink_format = None;
+    let mut verbose = 0;
+    let mut tui = false;
+    let mut no_pager = false;
+    let args = args::Parser::new(args);
     for arg in args {
-      // Parse argument options
-      let mut arg_chars = arg.chars();
-      let first_token = arg_chars.next().unwrap();
-      if first_token == '-' {
-        for option in arg_chars {
-          match option {
-            'i' => case_sensitive = false,
-            _ => {
-              return Err(format!(
-                "{prog}: {err} `{op}`\nUsage: {prog} {help}",
-                prog = programname,
-                err = UNSUPPORTED_OPTION,
-                op = option,
-                help = HELP_TEXT
-              ));
+      // Options are known by their long name whichever way they were given,
+      // with `option` as it was written
+      let (name, option, value) = match arg? {
+        Arg::Option { spec, name, value } => (spec.long, name, value),
+        Arg::Positional(arg) => {
+          positionals.push(arg);
+          continue;
+        }
+      };
+      builder = match name {
+        "recursive" => {
+          recursive = true;
+          builder
+        }
+        "extended-regexp" => builder.syntax(Syntax::Regex),
+        "fixed-strings" => builder.syntax(Syntax::Literal),
+        "fuzzy" => builder.syntax(Syntax::Fuzzy),
+        "sort-by-score" => builder.sort_by_score(true),
+        "multiline" => builder.multiline(true),
+        "crlf" => builder.crlf(true),
+        "paragraph" => builder.records(Records::Paragraphs),
+        "null-data" => builder.records(Records::Null),
+        "record-separator" => {
+          let separator = required_value(&option, value)?;
+          builder.records(Records::separator(&separator)?)
+        }
+        "csv" => builder.records(Records::Csv),
+        "column" => builder.column(Column::parse(&required_value(&option, value)?)),
+        "json-input" => builder.json_input(true),
+        "path" => {
+          let spec = required_value(&option, value)?;
+          let selector = Selector::parse(&spec).map_err(|_| MinigrepError::InvalidValue {
+            option: option.clone(),
+            value: spec,
+          })?;
+          builder.json_path(selector)
+        }
+        "json-pointers" => builder.json_pointers(true),
+        "search-zip" => builder.search_zip(true),
+        "search-archives" => builder.search_archives(true),
+        "stats" => builder.stats(true),
+        "per-file" => builder.per_file(true),
+        "verbose" => {
+          verbose += 1;
+          builder.log_level(Some(log::Level::verbose(verbose)))
+        }
+        "log-level" => {
+          let value = required_value(&option, value)?;
+          match log::Level::from_name(&value) {
+            Some(level) => builder.log_level(Some(level)),
+            None => return Err(MinigrepError::InvalidValue { option, value }),
+          }
+        }
+        "watch" => builder.watch(true),
+        "tail" => builder.tail(true),
+        "tui" => {
+          tui = true;
+          builder.tui(true)
+        }
+        "no-pager" => {
+          no_pager = true;
+          builder
+        }
+        "help" => return Ok(Parsed::Help),
+        "version" => return Ok(Parsed::Version),
+        "format" => {
+          let value = required_value(&option, value)?;
+          builder.format(match value.as_str() {
+            "text" => OutputFormat::Text,
+            "json" => OutputFormat::Json,
+            "json-lines" => OutputFormat::JsonLines,
+            "csv" => OutputFormat::Csv,
+            "tsv" => OutputFormat::Tsv,
+            "vimgrep" => OutputFormat::Vimgrep,
+            _ => return Err(MinigrepError::InvalidValue { option, value }),
+          })
+        }
+        "output" => builder.output(&required_value(&option, value)?),
+        "colors" => builder.colors(&required_value(&option, value)?),
+        "line-number" => builder.line_number(true),
+        "count" => builder.count(true),
+        "count-matches" => builder.count_matches(true),
+        "total" => builder.total(true),
+        "invert-match" => builder.invert_match(true),
+        "json" => builder.format(OutputFormat::Json),
+        "json-lines" => builder.format(OutputFormat::JsonLines),
+        "vimgrep" => builder.format(OutputFormat::Vimgrep),
+        "word-regexp" => builder.word_regexp(true),
+        "line-regexp" => builder.line_regexp(true),
+        "with-filename" => builder.with_filename(true),
+        "no-filename" => builder.with_filename(false),
+        "no-messages" => builder.no_messages(true),
+        "show-pattern" => builder.show_pattern(true),
+        "ignore-case" | "case-sensitive" => builder.ignore_case(name == "ignore-case"),
+        "smart-case" => builder.smart_case(true),
+        "case-fold" => {
+          let value = required_value(&option, value)?;
+          builder.case_fold(match value.as_str() {
+            "simple" => CaseFold::Simple,
+            "full" => CaseFold::Full,
+            "turkic" => CaseFold::Turkic,
+            _ => return Err(MinigrepError::InvalidValue { option, value }),
+          })
+        }
+        "ignore-accents" => builder.ignore_accents(true),
+        "no-ignore" | "ignore" => {
+          let ignore = name == "ignore";
+          builder.git_ignore(ignore).dot_ignore(ignore)
+        }
+        "no-ignore-vcs" => builder.git_ignore(false),
+        "no-ignore-dot" => builder.dot_ignore(false),
+        "git" => builder.git_tracked(true),
+        "follow" => builder.follow(true),
+        "hidden" => builder.hidden(true),
+        "no-hidden" => builder.hidden(false),
+        "max-depth" => builder.max_depth(Some(parse_count(&option, value)?)),
+        "newer-than" => builder.newer_than(parse_time(&option, value)?),
+        "older-than" => builder.older_than(parse_time(&option, value)?),
+        "sort" | "sortr" => {
+          let value = required_value(&option, value)?;
+          match SortBy::from_name(&value) {
+            Some(by) => builder.sort(Some(Sort {
+              by,
+              reverse: name == "sortr",
+            })),
+            None => return Err(MinigrepError::InvalidValue { option, value }),
+          }
+        }
+        "max-filesize" => builder.max_filesize(Some(parse_size(&option, value)?)),
+        "no-config" => builder,
+        "mmap" => builder.mmap(MmapChoice::Always),
+        "no-mmap" => builder.mmap(MmapChoice::Never),
+        "text" => builder.binary(true),
+        "quiet" => builder.quiet(true),
+        "only-matching" => builder.only_matching(true),
+        "byte-offset" => builder.byte_offset(true),
+        "null" => builder.null(true),
+        "files-with-matches" | "files-without-match" => {
+          let with = name == "files-with-matches";
+          builder.files_with_matches(with).files_without_match(!with)
+        }
+        "threads" => builder.threads(parse_count(&option, value)?),
+        "unordered" => builder.unordered(true),
+        "max-count" => builder.max_count(parse_count(&option, value)?),
+        "max-columns" => builder.max_columns(parse_count(&option, value)?),
+        "max-columns-preview" => builder.max_columns_preview(true),
+        "trim" => builder.trim(true),
+        "passthru" => builder.passthru(true),
+        "max-results" => builder.max_results(parse_count(&option, value)?),
+        "regexp" | "file" => {
+          has_query = true;
+          let value = required_value(&option, value)?;
+          if name == "regexp" {
+            builder.query(&value)
+          } else {
+            builder.pattern_file(&value)
+          }
+        }
+        "include" => builder.include(&required_value(&option, value)?),
+        "exclude" => builder.exclude(&required_value(&option, value)?),
+        "type" => builder.file_type(&required_value(&option, value)?),
+        "type-not" => builder.file_type_not(&required_value(&option, value)?),
+        "type-add" => builder.file_type_add(&required_value(&option, value)?),
+        "replace" => builder.replace(&required_value(&option, value)?),
+        // The backup suffix can only be attached, it is optional
+        "in-place" => builder.in_place(InPlace {
+          backup_suffix: value.filter(|suffix| !suffix.is_empty()),
+        }),
+        "comment-prefix" => {
+          let prefix = required_value(&option, value)?;
+          builder.comment_prefix(Some(prefix.as_str()).filter(|prefix| !prefix.is_empty()))
+        }
+        "encoding" => {
+          let label = required_value(&option, value)?;
+          match Encoding::for_label(&label) {
+            Some(encoding) => builder.encoding(encoding),
+            None => {
+              return Err(MinigrepError::InvalidValue {
+                option,
+                value: label,
+              });
             }
           }
         }
-      } else {
-        return Err(format!(
-          "{prog}: {err} {first_token}{rest}\nUsage: {prog} {help}",
-          prog = programname,
-          err = UNEXPECTED_ARGUMENT,
-          first_token = first_token,
-          rest = arg_chars.as_str(),
-          help = HELP_TEXT
-        ));
+        "pre" => builder.pre(&required_value(&option, value)?),
+        "hyperlink-format" => {
+          let value = required_value(&option, value)?;
+          hyperlink_format = match HyperlinkFormat::parse(&value) {
+            Ok(format) => Some(format),
+            Err(_) => return Err(MinigrepError::InvalidValue { option, value }),
+          };
+          builder
+        }
+        "pre-glob" => builder.pre_glob(&required_value(&option, value)?),
+        "color" => builder.color(match value.as_deref() {
+          // As with grep, a bare `--color` leaves it to the terminal
+          Some("auto") | None => ColorChoice::Auto,
+          Some("always") => ColorChoice::Always,
+          Some("never") => ColorChoice::Never,
+          Some(other) => {
+            return Err(MinigrepError::InvalidValue {
+              value: other.to_string(),
+              option,
+            });
+          }
+        }),
+        "after-context" | "before-context" | "context" => {
+          let n = Some(parse_count(&option, value)?);
+          match name {
+            "after-context" => after_context = n,
+            "before-context" => before_context = n,
+            _ => context = n,
+          }
+          builder
+        }
+        "context-separator" => builder.context_separator(Some(&required_value(&option, value)?)),
+        "no-context-separator" => builder.context_separator(None),
+        _ => unreachable!("`{}` is defined but never handled", option),
+      };
+    }
+
+    // Without any `-e` or `-f`, the first positional argument is the query,
+    // except in the interactive mode where it can be typed in later
+    let mut positionals = positionals.into_iter();
+    if !has_query && !tui {
+      match positionals.next() {
+        Some(query) => builder = builder.query(&query),
+        None => return Err(MinigrepError::MissingQuery),
+      }
+    }
+    let mut filenames: Vec<String> = positionals.collect();
+    let mut strip_dot = false;
+    if filenames.is_empty() && tui && stdin_is_terminal {
+      filenames.push(".".to_string());
+      recursive = true;
+    } else if filenames.is_empty() && recursive {
+      // Like grep, `-r` searches the working directory then, and names the
+      // files it finds relative to it
+      filenames.push(".".to_string());
+      strip_dot = true;
+    } else if filenames.is_empty() {
+      // Fall back to reading piped input, but never wait on a terminal
+      if stdin_is_terminal {
+        return Err(MinigrepError::MissingFile);
       }
+      filenames.push(STDIN_FILENAME.to_string());
+    }
+    for filename in &filenames {
+      builder = builder.path(filename);
     }
 
-    Ok(Config {
-      query,
-      filename,
-      case_sensitive,
-    })
+    let hyperlink_format = hyperlink_format
+      .unwrap_or_else(|| terminal::stdout_supports_hyperlinks().then(HyperlinkFormat::default));
+    let mut config = builder
+      .recursive(recursive)
+      .context(
+        before_context.or(context).unwrap_or(0),
+        after_context.or(context).unwrap_or(0),
+      )
+      .hyperlink_format(hyperlink_format)
+      .build()?;
+    config.strip_dot = strip_dot;
+    // Results that keep coming or that nobody reads are not paged
+    config.pager = !no_pager
+      && config.output.is_none()
+      && !(config.watch || config.tail || config.tui || config.quiet)
+      && io::stdout().is_terminal();
+    Ok(Parsed::Search(Box::new(config)))
+  }
+
+  /// Starts building a `Config` in code rather than from arguments.
+  pub fn builder() -> ConfigBuilder {
+    ConfigBuilder::new()
+  }
+
+  // Rejects combinations of options that cannot work together
+  fn validate(&self) -> Result<(), MinigrepError> {
+    if self.in_place.is_some() && self.replace.is_none() {
+      return Err(MinigrepError::IncompatibleOptions(IN_PLACE_WITHOUT_REPLACE));
+    }
+    if self.in_place.is_some() && self.encoding != Encoding::Utf8 {
+      return Err(MinigrepError::IncompatibleOptions(IN_PLACE_ENCODING));
+    }
+    if self.in_place.is_some() && self.multiline {
+      return Err(MinigrepError::IncompatibleOptions(IN_PLACE_MULTILINE));
+    }
+    if let Some(max) = self.replace.as_ref().map(Template::max_group) {
+      let has_group =
+        |pattern: &Pattern| matches!(pattern, Pattern::Regex(re) if re.groups() >= max);
+      if max > 0 && !self.patterns.iter().any(has_group) {
+        return Err(MinigrepError::IncompatibleOptions(REPLACE_MISSING_GROUP));
+      }
+    }
+    let fuzzy = |pattern: &Pattern| matches!(pattern, Pattern::Fuzzy(_));
+    if self.sort_by_score && !self.patterns.iter().any(fuzzy) {
+      return Err(MinigrepError::IncompatibleOptions(SORT_WITHOUT_FUZZY));
+    }
+    let counting = self.count || self.count_matches;
+    let listing = self.files_with_matches || self.files_without_match;
+    if self.format == OutputFormat::JsonLines && (counting || listing) {
+      return Err(MinigrepError::IncompatibleOptions(JSON_LINES_COUNT));
+    }
+    if self.per_file && !self.stats {
+      return Err(MinigrepError::IncompatibleOptions(PER_FILE_WITHOUT_STATS));
+    }
+    if self.total && !self.count && !self.count_matches {
+      return Err(MinigrepError::IncompatibleOptions(TOTAL_WITHOUT_COUNT));
+    }
+    if self.watch && self.filenames.iter().any(|f| f == STDIN_FILENAME) {
+      return Err(MinigrepError::IncompatibleOptions(WATCH_STDIN));
+    }
+    if self.watch && self.in_place.is_some() {
+      return Err(MinigrepError::IncompatibleOptions(WATCH_IN_PLACE));
+    }
+    if self.tail && self.filenames.iter().any(|f| f == STDIN_FILENAME) {
+      return Err(MinigrepError::IncompatibleOptions(TAIL_STDIN));
+    }
+    let unfinished = self.count
+      || self.count_matches
+      || self.files_without_match
+      || self.multiline
+      || self.sort_by_score
+      || self.in_place.is_some()
+      || self.watch
+      || self.json_input;
+    if self.tail && unfinished {
+      return Err(MinigrepError::IncompatibleOptions(TAIL_UNFINISHED));
+    }
+    if self.tui && self.in_place.is_some() {
+      return Err(MinigrepError::IncompatibleOptions(TUI_IN_PLACE));
+    }
+    if self.preprocessor.is_some() && self.in_place.is_some() {
+      return Err(MinigrepError::IncompatibleOptions(PRE_IN_PLACE));
+    }
+    if self.records != Records::Lines && self.multiline {
+      return Err(MinigrepError::IncompatibleOptions(RECORDS_MULTILINE));
+    }
+    if self.records != Records::Lines && self.in_place.is_some() {
+      return Err(MinigrepError::IncompatibleOptions(RECORDS_IN_PLACE));
+    }
+    if self.column.is_some() && self.records != Records::Csv {
+      return Err(MinigrepError::IncompatibleOptions(COLUMN_WITHOUT_CSV));
+    }
+    let whole_lines = self.multiline || self.in_place.is_some() || self.records != Records::Lines;
+    if self.json_input && whole_lines {
+      return Err(MinigrepError::IncompatibleOptions(JSON_INPUT_RECORDS));
+    }
+    if self.max_columns_preview && self.max_columns.is_none() {
+      return Err(MinigrepError::IncompatibleOptions(
+        PREVIEW_WITHOUT_MAX_COLUMNS,
+      ));
+    }
+    if (self.newer_than.is_some() || self.older_than.is_some()) && !self.recursive {
+      return Err(MinigrepError::IncompatibleOptions(AGE_WITHOUT_RECURSIVE));
+    }
+    if self.passthru && self.multiline {
+      return Err(MinigrepError::IncompatibleOptions(PASSTHRU_MULTILINE));
+    }
+    if !self.json_input && (self.json_path.is_some() || self.json_pointers) {
+      return Err(MinigrepError::IncompatibleOptions(JSON_PATH_WITHOUT_INPUT));
+    }
+    Ok(())
   }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-  let contents = fs::read_to_string(config.filename)?;
+fn compile_patterns(
+  queries: Vec<String>,
+  syntax: Syntax,
+  case_sensitive: bool,
+  crlf: bool,
+  ignore_accents: bool,
+) -> Result<Vec<Pattern>, MinigrepError> {
+  let mut patterns = Vec::with_capacity(queries.len());
+  for query in queries {
+    let pattern = match syntax {
+      Syntax::Literal => Pattern::Literal(query),
+      Syntax::Fuzzy => Pattern::Fuzzy(FuzzyMatcher::new(&query, case_sensitive)),
+      Syntax::Regex => match Regex::with_case_insensitive(&query, !case_sensitive) {
+        Ok(re) => Pattern::Regex(re.crlf(crlf).ignore_accents(ignore_accents)),
+        Err(e) => {
+          return Err(MinigrepError::InvalidPattern {
+            pattern: query,
+            source: e,
+          })
+        }
+      },
+    };
+    patterns.push(pattern);
+  }
+  Ok(patterns)
+}
 
-  let results = if config.case_sensitive {
-    search(&config.query, &contents)
-  } else {
-    search_case_insensitive(&config.query, &contents)
+/// Puts `patterns` in one automaton when there are several and they are all
+/// fixed strings matched byte for byte, with neither case nor accents
+/// ignored.
+pub fn literal_set(patterns: &[Pattern], exact: bool) -> Option<AhoCorasick> {
+  let mut strings = Vec::with_capacity(patterns.len());
+  for pattern in patterns {
+    match pattern {
+      Pattern::Literal(query) if !query.is_empty() => strings.push(query.as_str()),
+      _ => return None,
+    }
+  }
+  if strings.len() < 2 || !exact {
+    return None;
+  }
+  Some(AhoCorasick::new(&strings))
+}
+
+/// Whether any of `queries` has an uppercase letter, which makes a search
+/// with `--smart-case` case sensitive. Letters escaped in a regex, such as
+/// `\W`, are character classes and don't count.
+pub fn has_uppercase(queries: &[String], syntax: Syntax) -> bool {
+  queries.iter().any(|query| {
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+      if c == '\\' && syntax == Syntax::Regex {
+        chars.next();
+      } else if c.is_uppercase() {
+        return true;
+      }
+    }
+    false
+  })
+}
+
+#[cfg(feature = "cli")]
+fn required_value(option: &str, value: Option<String>) -> Result<String, MinigrepError> {
+  value.ok_or_else(|| MinigrepError::MissingValue {
+    option: option.to_string(),
+  })
+}
+
+// A number of bytes, which may end in `K`, `M` or `G` for kibibytes,
+// mebibytes or gibibytes
+#[cfg(feature = "cli")]
+fn parse_size(option: &str, value: Option<String>) -> Result<u64, MinigrepError> {
+  let value = required_value(option, value)?;
+  let (digits, unit) = match value.char_indices().last() {
+    Some((i, 'K')) | Some((i, 'k')) => (&value[..i], 1 << 10),
+    Some((i, 'M')) | Some((i, 'm')) => (&value[..i], 1 << 20),
+    Some((i, 'G')) | Some((i, 'g')) => (&value[..i], 1 << 30),
+    _ => (value.as_str(), 1),
   };
+  match digits.parse::<u64>().ok().and_then(|n| n.checked_mul(unit)) {
+    Some(size) => Ok(size),
+    None => Err(MinigrepError::InvalidValue {
+      option: option.to_string(),
+      value,
+    }),
+  }
+}
+
+#[cfg(feature = "cli")]
+fn parse_count(option: &str, value: Option<String>) -> Result<usize, MinigrepError> {
+  let value = required_value(option, value)?;
+  value.parse().map_err(|_| MinigrepError::InvalidValue {
+    option: option.to_string(),
+    value,
+  })
+}
+
+// An age like `2d`, counting back from now, or a date like `2024-01-01`
+#[cfg(feature = "cli")]
+fn parse_time(option: &str, value: Option<String>) -> Result<SystemTime, MinigrepError> {
+  let value = required_value(option, value)?;
+  timespec::parse(&value, SystemTime::now()).ok_or_else(|| MinigrepError::InvalidValue {
+    option: option.to_string(),
+    value,
+  })
+}
+
+/// What `Config::on_error` is: for the path of an input that could not be
+/// searched, and why.
+pub type ErrorHook = Box<dyn Fn(&Path, &io::Error) + Send + Sync>;
+
+/// The outcome of a whole search.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Summary {
+  /// Whether any line matched.
+  pub matched: bool,
+  /// How many inputs could not be searched. Each was handed to
+  /// `Config::on_error` and skipped.
+  pub errors: usize,
+  pub stats: Stats,
+  /// The statistics of every input searched, in the order they were found,
+  /// when `config.per_file` is set.
+  pub files: Vec<FileStats>,
+}
+
+/// Searches every input named in `config`.
+///
+/// With several inputs, one that cannot be read is reported and counted in
+/// the summary rather than ending the search.
+pub fn run(config: Config) -> Result<Summary, MinigrepError> {
+  let mut out = open_output(&config)?;
+  run_config(&config, &mut out)
+}
+
+/// Searches every input named in `config` like `run`, writing the results
+/// to `out` instead of where `config.output` says.
+pub fn run_with<W: Write + Send>(config: &Config, out: &mut W) -> Result<Summary, MinigrepError> {
+  run_config(config, out)
+}
+
+// Standard output, or the file named by `--output`, which is buffered since
+// nobody is watching it fill up
+fn open_output(config: &Config) -> Result<Box<dyn Write + Send>, MinigrepError> {
+  match &config.output {
+    Some(path) => match File::create(path) {
+      Ok(file) => Ok(Box::new(BufWriter::new(file))),
+      Err(e) => Err(MinigrepError::Io {
+        path: path.clone(),
+        source: e,
+      }),
+    },
+    None => Ok(Box::new(io::stdout())),
+  }
+}
+
+// Whether `path` is where the results are going, which must not be searched
+// while it is being written
+fn is_output(config: &Config, path: &Path) -> bool {
+  config.output.as_ref().is_some_and(|output| {
+    output.file_name() == path.file_name()
+      && fs::canonicalize(output)
+        .ok()
+        .is_some_and(|output| fs::canonicalize(path).ok() == Some(output))
+  })
+}
 
-  for line in results {
-    println!("{}", line);
+/// Searches every input named in `config` like `run`, then keeps
+/// searching each file again whenever it changes, printing the matching
+/// lines it did not print for the file before. Only returns if the first
+/// search fails, or the output does.
+#[cfg(feature = "cli")]
+pub fn watch(config: Config) -> Result<(), MinigrepError> {
+  watch::start();
+  let mut watcher = Watcher::new();
+  // Listing the files first means that changes made during the first
+  // search are not missed
+  watcher.changed(&config);
+  let mut out = open_output(&config)?;
+  run_config(&config, &mut out)?;
+  let with_filename = config.prints_filenames();
+  loop {
+    thread::sleep(watch::POLL_INTERVAL);
+    // Each change is searched with the whole limit
+    if let Some(limit) = &config.max_results {
+      limit.reset();
+    }
+    for path in watcher.changed(&config) {
+      if let Err(e) = search_path(
+        &config,
+        &path,
+        with_filename,
+        &mut out,
+        &mut Stats::default(),
+      ) {
+        if e.kind() == io::ErrorKind::BrokenPipe {
+          return Err(MinigrepError::Output(e));
+        }
+        report(&config, &path, &e);
+      }
+    }
+    out.flush().map_err(MinigrepError::Output)?;
   }
+}
 
+/// Searches every file named in `config`, then keeps reading each as it
+/// grows, printing the lines that match as they are written. Files that are
+/// truncated or replaced, as logs are when rotated, are searched again from
+/// the start. Only returns once no file is left to follow, as when each
+/// has had `max_count` matching lines, or once the output is closed.
+#[cfg(feature = "cli")]
+pub fn tail(config: Config) -> Result<(), MinigrepError> {
+  let mut out = open_output(&config)?;
+  if let Some(delimiter) = config.format.delimiter() {
+    csv::write_row(&mut out, &csv::HEADER, delimiter).map_err(MinigrepError::Output)?;
+  }
+  let out = Mutex::new(out);
+  let closed = Arc::new(AtomicBool::new(false));
+  let with_filename = names_inputs(&config, config.prints_filenames());
+  let paths = input_paths(&config);
+  log::info!("following {}", config.filenames.join(", "));
+  // Each file is waited on by a thread of its own
+  thread::scope(|scope| {
+    for path in &paths {
+      let (config, out, closed) = (&config, &out, &closed);
+      scope.spawn(move || {
+        let name = with_filename.then_some(path.as_path());
+        let result = Follow::open(path).and_then(|follow| {
+          let mut follow = follow.until(Arc::clone(closed));
+          let mut lines = Lines::new(out);
+          // What a file starts over with is searched anew, numbering its
+          // lines from the start again
+          loop {
+            let reader = BufReader::new(&mut follow);
+            search_decoded(config, reader, name, &mut lines, &mut Stats::default())?;
+            if !follow.restarted() {
+              return Ok(());
+            }
+          }
+        });
+        match result {
+          // Every other file stops being followed too, once no one reads
+          // what is found in them
+          Err(e) if e.kind() == io::ErrorKind::BrokenPipe => closed.store(true, Ordering::Relaxed),
+          Err(e) => report(config, path, &e),
+          Ok(()) => {}
+        }
+      });
+    }
+  });
   Ok(())
 }
 
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-  contents
-    .lines()
-    .filter(|line| line.contains(query))
-    .collect()
+// Every file `config` searches, skipping any that cannot be listed right
+// now since they may well be back on the next poll
+#[cfg(feature = "cli")]
+fn input_paths(config: &Config) -> Vec<PathBuf> {
+  let mut paths = Vec::new();
+  for filename in &config.filenames {
+    let path = Path::new(filename);
+    if filename == STDIN_FILENAME {
+      continue;
+    }
+    if !config.recursive {
+      if config.path_filter.is_match(path) {
+        paths.push(path.to_path_buf());
+      }
+      continue;
+    }
+    let walker = walker(config, path);
+    paths.extend(walker.filter_map(Result::ok));
+  }
+  paths.retain(|path| !is_output(config, path));
+  paths
 }
 
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-  let query = query.to_lowercase();
-  contents
-    .lines()
-    .filter(|line| line.to_lowercase().contains(&query))
-    .collect()
+fn run_config<W: Write + Send>(config: &Config, out: &mut W) -> Result<Summary, MinigrepError> {
+  let start = Instant::now();
+  if let Some(delimiter) = config.format.delimiter() {
+    csv::write_row(out, &csv::HEADER, delimiter).map_err(MinigrepError::Output)?;
+  }
+  if !config.recursive && config.filenames.len() == 1 {
+    let mut stats = Stats::default();
+    let path = Path::new(&config.filenames[0]);
+    if path != Path::new(STDIN_FILENAME) && !config.path_filter.is_match(path) {
+      log::debug!("skipping {}: not matching the filters", path.display());
+      stats.skipped_filtered += 1;
+      stats.elapsed = start.elapsed();
+      return Ok(Summary {
+        stats,
+        ..Summary::default()
+      });
+    }
+    let count =
+      search_path(config, path, config.prints_filenames(), out, &mut stats).map_err(|e| {
+        // Reading never breaks a pipe, so the output was closed
+        if e.kind() == io::ErrorKind::BrokenPipe {
+          return MinigrepError::Output(e);
+        }
+        MinigrepError::Io {
+          path: input_name(path).to_path_buf(),
+          source: e,
+        }
+      })?;
+    stats.elapsed = start.elapsed();
+    write_end(config, out, &stats).map_err(MinigrepError::Output)?;
+    out.flush().map_err(MinigrepError::Output)?;
+    let files = if config.per_file {
+      vec![FileStats {
+        path: path.to_path_buf(),
+        stats,
+      }]
+    } else {
+      Vec::new()
+    };
+    return Ok(Summary {
+      matched: count > 0,
+      errors: 0,
+      stats,
+      files,
+    });
+  }
+
+  search_all(config, out, start)
 }
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+// Every input `config` names, handed to `search` one at a time until it
+// returns false, and how many the filters and ignore rules left out
+fn feed<F>(config: &Config, mut search: F) -> Result<usize, MinigrepError>
+where
+  F: FnMut(PathBuf) -> bool,
+{
+  let mut filtered = 0;
+  for filename in &config.filenames {
+    // URLs are searched as they are, even with `-r`
+    if !config.recursive || filename == STDIN_FILENAME || http::is_url(filename) {
+      let path = PathBuf::from(filename);
+      if filename != STDIN_FILENAME && !config.path_filter.is_match(&path) {
+        log::debug!("skipping {}: not matching the filters", path.display());
+        filtered += 1;
+      } else if !search(path) {
+        break;
+      }
+      continue;
+    }
+    let mut walker = walker(config, Path::new(filename));
+    for path in &mut walker {
+      let path = path.map_err(|e| MinigrepError::Io {
+        path: PathBuf::from(filename),
+        source: e,
+      })?;
+      if is_output(config, &path) {
+        log::debug!("skipping {}: the results are written to it", path.display());
+        continue;
+      }
+      if !search(path) {
+        return Ok(filtered + walker.skipped());
+      }
+    }
+    filtered += walker.skipped();
+  }
+  Ok(filtered)
+}
 
-  #[test]
-  fn case_sensitive() {
-    let query = "duct";
-    let contents = "\
-Rust:
-safe, fast, productive.
-Pick three.
-Duct tape.";
+// Searches the inputs on a pool of threads
+#[cfg(feature = "parallel")]
+fn search_all<W: Write + Send>(
+  config: &Config,
+  out: &mut W,
+  start: Instant,
+) -> Result<Summary, MinigrepError> {
+  let threads = match config.threads {
+    0 => thread::available_parallelism().map_or(1, |n| n.get()),
+    n => n,
+  };
+  log::info!(
+    "searching {} with {} thread{}",
+    config.filenames.join(", "),
+    threads,
+    if threads == 1 { "" } else { "s" }
+  );
+  let with_filename = config.prints_filenames();
+  let matched = AtomicBool::new(false);
+  let totals = Mutex::new(Stats::default());
+  let per_file = Mutex::new(Vec::new());
+  // Only shown when someone is watching, and results are not piped to the
+  // same place
+  let progress = (show_progress() && !config.quiet).then(Progress::new);
+  // Each file is numbered in the order it was found, for the results to be
+  // printed in that order
+  let (jobs, queue) = mpsc::channel::<(usize, PathBuf)>();
+  let queue = Mutex::new(queue);
+  let ordered = !config.unordered || config.sort.is_some();
+  let turns = Turns::new(
+    out,
+    ordered,
+    printer::input_separator(config),
+    progress.as_ref(),
+  );
+  // In quiet mode, the first match anywhere settles the outcome, and once
+  // the results cannot be written nothing else is worth finding
+  let done = || {
+    (config.quiet && matched.load(Ordering::Relaxed)) || config.limit_reached() || turns.failed()
+  };
+  // Only there to tell the status line when every worker is gone
+  let (alive, gone) = mpsc::channel::<()>();
+  let filtered = thread::scope(|scope| {
+    for _ in 0..threads {
+      let (config, queue, turns, alive) = (config, &queue, &turns, alive.clone());
+      let (matched, totals, per_file, progress) = (&matched, &totals, &per_file, &progress);
+      scope.spawn(move || {
+        let mut stats = Stats::default();
+        let mut files = Vec::new();
+        loop {
+          if done() {
+            break;
+          }
+          // The lock is only held while waiting for the next path
+          let (index, path) = match queue.lock().unwrap().recv() {
+            Ok(job) => job,
+            Err(_) => break,
+          };
+          if let Some(progress) = progress {
+            progress.searching(&path);
+          }
+          let (result, file) = search_timed(
+            config,
+            &path,
+            with_filename,
+            &mut turns.input(index),
+            &mut stats,
+          );
+          match result {
+            Ok(count) => {
+              if let Some(progress) = progress {
+                progress.searched(count);
+              }
+              if count > 0 {
+                matched.store(true, Ordering::Relaxed);
+              }
+              if config.per_file {
+                files.push((
+                  index,
+                  FileStats {
+                    path: path.clone(),
+                    stats: file,
+                  },
+                ));
+              }
+            }
+            // Writing the results failed, which is told once at the end
+            Err(_) if turns.failed() => {}
+            // A single unreadable file should not abort the whole search
+            Err(e) => {
+              report(config, &path, &e);
+              stats.skipped_unreadable += 1;
+            }
+          }
+        }
+        totals.lock().unwrap().add(&stats);
+        per_file.lock().unwrap().extend(files);
+        drop(alive);
+      });
+    }
+    drop(alive);
+
+    // The status line is redrawn whenever there have been no results for a
+    // while
+    if progress.is_some() {
+      let turns = &turns;
+      scope.spawn(move || {
+        while let Err(mpsc::RecvTimeoutError::Timeout) = gone.recv_timeout(progress::TICK) {
+          turns.draw();
+        }
+      });
+    }
+
+    // Sending only fails once every worker has stopped
+    let mut found = 0;
+    let filtered = feed(config, |path| {
+      found += 1;
+      !done() && jobs.send((found - 1, path)).is_ok()
+    });
+    drop(jobs);
+    filtered
+  })?;
+
+  let out = turns.finish().map_err(MinigrepError::Output)?;
+  // Every worker has added its counts once they are all gone
+  let mut stats = *totals.lock().unwrap();
+  stats.skipped_filtered += filtered;
+  stats.elapsed = start.elapsed();
+  write_end(config, out, &stats)
+    .and_then(|()| out.flush())
+    .map_err(MinigrepError::Output)?;
+  let mut files = std::mem::take(&mut *per_file.lock().unwrap());
+  files.sort_unstable_by_key(|(index, _)| *index);
+  Ok(Summary {
+    matched: matched.load(Ordering::Relaxed),
+    errors: stats.skipped_unreadable,
+    stats,
+    files: files.into_iter().map(|(_, file)| file).collect(),
+  })
+}
 
-    assert_eq!(vec!["safe, fast, productive."], search(query, contents));
+// Searches one of several inputs like `search_path`, adding what it took to
+// `stats` and returning that on its own as well
+fn search_timed<W: Write>(
+  config: &Config,
+  path: &Path,
+  with_filename: bool,
+  out: &mut W,
+  stats: &mut Stats,
+) -> (io::Result<usize>, Stats) {
+  let start = Instant::now();
+  let mut file = Stats::default();
+  let result = search_path(config, path, with_filename, out, &mut file);
+  file.elapsed = start.elapsed();
+  stats.add(&file);
+  (result, file)
+}
+
+// Tells about an input that could not be searched, unless `-s` says not to
+fn report(config: &Config, path: &Path, e: &io::Error) {
+  if let Some(on_error) = config.on_error.as_ref().filter(|_| !config.no_messages) {
+    on_error(input_name(path), e);
   }
+}
 
-  #[test]
-  fn case_insensitive() {
-    let query = "rUsT";
-    let contents = "\
-Rust:
-safe, fast, productive.
-Pick three.
-Trust me.";
+// What `path` is called in messages, standard input going by the name its
+// results are printed under
+fn input_name(path: &Path) -> &Path {
+  if path == Path::new(STDIN_FILENAME) {
+    Path::new(STDIN_LABEL)
+  } else {
+    path
+  }
+}
 
-    assert_eq!(
-      vec!["Rust:", "Trust me."],
-      search_case_insensitive(query, contents)
+// Writes the results of one input after those of another, with a context
+// separator between them when context is shown, as grep does
+#[cfg(not(feature = "parallel"))]
+struct Results<'a, W> {
+  out: &'a mut W,
+  separator: Option<String>,
+  written: bool,
+  // What went wrong writing, once something did
+  failed: Option<io::ErrorKind>,
+}
+
+#[cfg(not(feature = "parallel"))]
+impl<'a, W: Write> Results<'a, W> {
+  fn new(config: &Config, out: &'a mut W) -> Results<'a, W> {
+    Results {
+      out,
+      separator: printer::input_separator(config),
+      written: false,
+      failed: None,
+    }
+  }
+
+  // Where the results of the next input go, straight to the output
+  fn input(&mut self) -> Input<'_, 'a, W> {
+    Input {
+      results: self,
+      started: false,
+    }
+  }
+}
+
+#[cfg(not(feature = "parallel"))]
+struct Input<'r, 'a, W> {
+  results: &'r mut Results<'a, W>,
+  started: bool,
+}
+
+#[cfg(not(feature = "parallel"))]
+impl<W: Write> Input<'_, '_, W> {
+  fn write_results(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let results = &mut *self.results;
+    if !self.started && !buf.is_empty() {
+      if let Some(separator) = results.separator.as_ref().filter(|_| results.written) {
+        results.out.write_all(separator.as_bytes())?;
+      }
+      self.started = true;
+      results.written = true;
+    }
+    results.out.write(buf)
+  }
+}
+
+#[cfg(not(feature = "parallel"))]
+impl<W: Write> Write for Input<'_, '_, W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    let written = self.write_results(buf);
+    if let Err(e) = &written {
+      self.results.failed = Some(e.kind());
+    }
+    written
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.results.out.flush()
+  }
+}
+
+// Searches the inputs one after another
+#[cfg(not(feature = "parallel"))]
+fn search_all<W: Write>(
+  config: &Config,
+  out: &mut W,
+  start: Instant,
+) -> Result<Summary, MinigrepError> {
+  log::info!(
+    "searching {} one file at a time",
+    config.filenames.join(", ")
+  );
+  let with_filename = config.prints_filenames();
+  let mut stats = Stats::default();
+  let mut files = Vec::new();
+  let mut matched = false;
+  let mut results = Results::new(config, out);
+  let filtered = feed(config, |path| {
+    let (result, file) = search_timed(
+      config,
+      &path,
+      with_filename,
+      &mut results.input(),
+      &mut stats,
     );
+    match result {
+      Ok(count) => {
+        matched |= count > 0;
+        if config.per_file {
+          files.push(FileStats {
+            path: path.clone(),
+            stats: file,
+          });
+        }
+      }
+      // Writing the results failed, which is told below
+      Err(_) if results.failed.is_some() => {}
+      // A single unreadable file should not abort the whole search
+      Err(e) => {
+        report(config, &path, &e);
+        stats.skipped_unreadable += 1;
+      }
+    }
+    results.failed.is_none() && !(config.quiet && matched) && !config.limit_reached()
+  })?;
+  stats.skipped_filtered += filtered;
+  stats.elapsed = start.elapsed();
+  match results.failed {
+    Some(kind) => Err(io::Error::from(kind)),
+    None => Ok(()),
+  }
+  .and_then(|()| write_end(config, results.out, &stats))
+  .and_then(|()| results.out.flush())
+  .map_err(MinigrepError::Output)?;
+  Ok(Summary {
+    matched,
+    errors: stats.skipped_unreadable,
+    stats,
+    files,
+  })
+}
+
+// The status line belongs to the command, and needs a terminal to redraw
+#[cfg(all(feature = "parallel", feature = "cli"))]
+fn show_progress() -> bool {
+  terminal::stderr_supports_escapes()
+}
+
+#[cfg(all(feature = "parallel", not(feature = "cli")))]
+fn show_progress() -> bool {
+  false
+}
+
+// What follows every result: the total of `--total`, or the summary of
+// `--json-lines`
+fn write_end<W: Write>(config: &Config, out: &mut W, stats: &Stats) -> io::Result<()> {
+  if config.format == OutputFormat::JsonLines && !config.quiet {
+    return writeln!(out, "{}", events::summary(stats));
+  }
+  if !config.total || config.first_match_only() {
+    return Ok(());
+  }
+  let total = if config.count_matches {
+    stats.matches
+  } else {
+    stats.matched_lines
+  };
+  Printer::new(out, config, None).total(total)
+}
+
+// Whether results name their input, which they always do when listing
+// files, which makes no sense without their names, for editors to jump to a
+// match, and in output read by programs
+fn names_inputs(config: &Config, with_filename: bool) -> bool {
+  with_filename
+    || config.files_with_matches
+    || config.files_without_match
+    || matches!(
+      config.format,
+      OutputFormat::Vimgrep
+        | OutputFormat::Json
+        | OutputFormat::JsonLines
+        | OutputFormat::Csv
+        | OutputFormat::Tsv
+    )
+}
+
+fn search_path<W: Write>(
+  config: &Config,
+  path: &Path,
+  with_filename: bool,
+  out: &mut W,
+  stats: &mut Stats,
+) -> io::Result<usize> {
+  #[cfg(feature = "cli")]
+  {
+    watch::printing_new(path, || search_at(config, path, with_filename, out, stats))
+  }
+  #[cfg(not(feature = "cli"))]
+  search_at(config, path, with_filename, out, stats)
+}
+
+fn search_at<W: Write>(
+  config: &Config,
+  path: &Path,
+  with_filename: bool,
+  out: &mut W,
+  stats: &mut Stats,
+) -> io::Result<usize> {
+  let url = path.to_str().is_some_and(http::is_url);
+  if let Some(max) = config.max_filesize {
+    if path != Path::new(STDIN_FILENAME) && !url && fs::metadata(path)?.len() > max {
+      log::debug!("skipping {}: larger than {} bytes", path.display(), max);
+      stats.skipped_large += 1;
+      return Ok(0);
+    }
+  }
+  if let Some(in_place) = &config.in_place {
+    if path == Path::new(STDIN_FILENAME) {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "standard input cannot be edited in place",
+      ));
+    }
+    if url {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "URLs cannot be edited in place",
+      ));
+    }
+    let count = edit::edit_file(config, path, in_place)?;
+    stats.files_searched += 1;
+    stats.matched_lines += count;
+    return Ok(count);
+  }
+  let with_filename = names_inputs(config, with_filename);
+  log::debug!("searching {}", path.display());
+  if path == Path::new(STDIN_FILENAME) {
+    let name = if with_filename {
+      Some(Path::new(STDIN_LABEL))
+    } else {
+      None
+    };
+    let mut stdin = io::stdin().lock();
+    if config.search_zip && stdin.fill_buf()?.starts_with(&gzip::MAGIC) {
+      return search_decoded(config, GzDecoder::new(stdin), name, out, stats);
+    }
+    return search_decoded(config, stdin, name, out, stats);
+  }
+  let name = if with_filename { Some(path) } else { None };
+  if url {
+    let mut reader = http::open(&path.to_string_lossy())?;
+    if config.search_zip && gzip::is_gzip(path, reader.fill_buf()?) {
+      return search_decoded(config, GzDecoder::new(reader), name, out, stats);
+    }
+    return search_decoded(config, reader, name, out, stats);
+  }
+  if let Some(pre) = config
+    .preprocessor
+    .as_ref()
+    .filter(|pre| pre.applies_to(path))
+  {
+    log::trace!("reading {} through `{}`", path.display(), pre.command());
+    return pre.run(path, |reader| {
+      search_decoded(config, reader, name, out, stats)
+    });
+  }
+  let file = File::open(path)?;
+  if let Some(kind) = Archive::for_path(path).filter(|_| config.search_archives) {
+    return search_archive(config, kind, file, path, out, stats);
+  }
+  #[cfg(feature = "documents")]
+  {
+    if let Some(kind) = document::Document::for_path(path) {
+      log::trace!("reading the text of {} as {:?}", path.display(), kind);
+      return document::with_text(kind, BufReader::new(file), |reader| {
+        search_decoded(config, reader, name, out, stats)
+      });
+    }
+  }
+  if config.search_zip {
+    // Looking for the magic bytes takes a buffer, so the file is not mapped
+    let mut reader = BufReader::new(file);
+    if gzip::is_gzip(path, reader.fill_buf()?) {
+      log::trace!("decompressing {}", path.display());
+      return search_decoded(config, GzDecoder::new(reader), name, out, stats);
+    }
+    return search_decoded(config, reader, name, out, stats);
+  }
+  if config.mmap.should_map(&file) {
+    // Fall back to reading the file when it cannot be mapped after all
+    if let Ok(map) = unsafe { Mmap::map(&file) } {
+      log::trace!("mapping {} into memory", path.display());
+      return search_decoded(config, &map[..], name, out, stats);
+    }
+  }
+  search_decoded(config, BufReader::new(file), name, out, stats)
+}
+
+// Searches each file inside an archive as `archive!path/inside`, always
+// naming it since an archive usually holds several
+fn search_archive<W: Write>(
+  config: &Config,
+  kind: Archive,
+  file: File,
+  path: &Path,
+  out: &mut W,
+  stats: &mut Stats,
+) -> io::Result<usize> {
+  let mut count = 0;
+  archive::for_each_entry(kind, BufReader::new(file), |entry, reader| {
+    let name = PathBuf::from(format!("{}!{}", path.display(), entry));
+    count += search_decoded(config, reader, Some(&name), out, stats)?;
+    Ok(())
+  })?;
+  Ok(count)
+}
+
+// Transcodes the input first when it is not UTF-8
+fn search_decoded<R: BufRead, W: Write>(
+  config: &Config,
+  reader: R,
+  name: Option<&Path>,
+  out: &mut W,
+  stats: &mut Stats,
+) -> io::Result<usize> {
+  match config.encoding {
+    Encoding::Utf8 => search_counted(config, reader, name, out, stats),
+    encoding => search_counted(
+      config,
+      DecodeReader::new(reader, encoding),
+      name,
+      out,
+      stats,
+    ),
+  }
+}
+
+/// Searches `reader` one line at a time and writes the results to `out`,
+/// labelled with `name` when one is given. Returns how many lines matched.
+///
+/// Only the current line and the lines kept for `before_context` are held in
+/// memory, so inputs of any size can be searched, and reading stops once
+/// `config.max_count` matching lines have been found, or the first one when
+/// only listing files or in quiet mode. Inputs that look binary are not
+/// printed: a notice is written at their first match instead, unless
+/// `config.binary` is set. Bytes that are not UTF-8 are printed as they
+/// were, and counted as they are in byte offsets.
+pub fn search_input<R: BufRead, W: Write>(
+  config: &Config,
+  reader: R,
+  name: Option<&Path>,
+  out: &mut W,
+) -> io::Result<usize> {
+  search_counted(config, reader, name, out, &mut Stats::default())
+}
+
+// `search_input`, adding what it went through to `stats`
+fn search_counted<R: BufRead, W: Write>(
+  config: &Config,
+  reader: R,
+  name: Option<&Path>,
+  out: &mut W,
+  stats: &mut Stats,
+) -> io::Result<usize> {
+  let start = Instant::now();
+  let first_match_only = config.first_match_only();
+  let counting = config.count || config.count_matches;
+  // Each match is looked at, but only to be counted
+  let occurrences = config.count_matches && !first_match_only;
+  let sorted = config.sort_by_score && !counting && !first_match_only;
+  let with_context = config.shows_context();
+  let options = StreamOptions {
+    max_count: match config.max_count {
+      Some(max) if first_match_only => Some(max.min(1)),
+      None if first_match_only => Some(1),
+      max => max,
+    },
+    before_context: if with_context {
+      config.before_context
+    } else {
+      0
+    },
+    after_context: if with_context {
+      config.after_context
+    } else {
+      0
+    },
+    count_only: (config.count && !occurrences) || first_match_only,
+    first_match_only,
+    passthru: config.passthru && !counting && !first_match_only && !config.only_matching && !sorted,
+    // NUL bytes are what separates the records then, not a sign of binary.
+    // Counting goes through binary inputs all the same.
+    detect_binary: !config.binary && config.records != Records::Null && !occurrences,
+    records: config.records.clone(),
+    prefilter: prefilter(config),
+    limit: config.max_results.as_ref(),
+  };
+  let mut reader = LineCounter::new(reader);
+  let binary = options.detect_binary && is_binary(reader.fill_buf()?);
+  let mut printer = Printer::new(out, config, name);
+  let mut matches = 0;
+  let count = if occurrences {
+    let mut tally = |m: &Match| {
+      matches += occurrences_in(m);
+      Ok(true)
+    };
+    stream_config(config, &options, &mut reader, &mut tally)?
+  } else if sorted {
+    let mut ranked = Ranked::new(&config.patterns);
+    let mut tallied = Tallied::new(&mut ranked, &mut matches);
+    let count = stream_config(config, &options, &mut reader, &mut tallied)?;
+    ranked.finish(&mut printer)?;
+    count
+  } else {
+    stream_printed(config, &options, &mut reader, &mut printer, &mut matches)?
+  };
+  let mut searched = Stats {
+    files_matched: usize::from(count > 0),
+    lines_scanned: reader.lines_read(),
+    matched_lines: count,
+    matches,
+    elapsed: start.elapsed(),
+    ..Stats::default()
+  };
+  if binary && count > 0 && !options.count_only {
+    // Its matches were not printed, so nothing of it counts but the skip
+    searched = Stats {
+      skipped_binary: 1,
+      elapsed: searched.elapsed,
+      ..Stats::default()
+    };
+  } else {
+    searched.files_searched = 1;
+  }
+  stats.add(&searched);
+
+  if config.quiet {
+    return Ok(count);
+  }
+  printer.end(&searched)?;
+  if config.files_with_matches || config.files_without_match {
+    if (count > 0) == config.files_with_matches {
+      printer.filename()?;
+    }
+  } else if config.count_matches {
+    printer.count(matches)?;
+  } else if config.count {
+    printer.count(count)?;
+  }
+  Ok(count)
+}
+
+// Streams the results into `printer`, leaving out the lines `watch` printed
+// before when it searches an input again
+fn stream_printed<R: BufRead, W: Write>(
+  config: &Config,
+  options: &StreamOptions,
+  reader: R,
+  printer: &mut Printer<W>,
+  matches: &mut usize,
+) -> io::Result<usize> {
+  #[cfg(feature = "cli")]
+  {
+    if let Some(printed) = watch::take_printed() {
+      let (before, after) = (options.before_context, options.after_context);
+      let mut fresh = watch::Fresh::new(printer, printed, before, after);
+      let count = stream_config(
+        config,
+        options,
+        reader,
+        &mut Tallied::new(&mut fresh, matches),
+      );
+      watch::put_printed(fresh.finish());
+      return count;
+    }
+  }
+  stream_config(config, options, reader, &mut Tallied::new(printer, matches))
+}
+
+fn stream_config<R: BufRead, S: Sink>(
+  config: &Config,
+  options: &StreamOptions,
+  reader: R,
+  sink: &mut S,
+) -> io::Result<usize> {
+  if config.json_input {
+    stream_documents(config, options, reader, sink)
+  } else if config.multiline {
+    stream_multiline(config, options, reader, sink)
+  } else if config.records == Records::Csv {
+    stream_rows(config, options, reader, sink)
+  } else {
+    // Where the matches are only matters when the sink is shown them
+    stream(
+      options,
+      line_matcher(config, !options.count_only),
+      reader,
+      sink,
+    )
+  }
+}
+
+// Streams CSV rows past the header, matching in the field of each that is
+// in `config.column`, but handing the whole row to `sink`
+fn stream_rows<R: BufRead, S: Sink>(
+  config: &Config,
+  options: &StreamOptions,
+  reader: R,
+  sink: &mut S,
+) -> io::Result<usize> {
+  let find = line_matcher(config, !options.count_only);
+  let rows = csv::Rows::new(config.column.as_ref());
+  let find_in_field = |row: &str| {
+    let field = rows.field(row)?;
+    let mut hit = find(&field.text(row))?;
+    for span in &mut hit.spans {
+      *span = field.span_in(row, span.clone());
+    }
+    Some(hit)
+  };
+  let count = stream(options, find_in_field, reader, sink)?;
+  match rows.missing() {
+    Some(column) => Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("no column is named `{}`", column),
+    )),
+    None => Ok(count),
+  }
+}
+
+// Matches the string values of each JSON document in the input, handing
+// `sink` the documents that match on a single line each, or the strings
+// themselves with `json_pointers`
+fn stream_documents<R: BufRead, S: Sink>(
+  config: &Config,
+  options: &StreamOptions,
+  mut reader: R,
+  sink: &mut S,
+) -> io::Result<usize> {
+  let mut bytes = Vec::new();
+  reader.read_to_end(&mut bytes)?;
+  let binary = options.detect_binary && is_binary(&bytes);
+  let contents = String::from_utf8_lossy(&bytes);
+  let documents = json_input::documents(&contents)
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("not JSON: {}", e)))?;
+  // Inverting is up to the whole document, not each of its strings
+  let find = pattern_finder(config, !options.count_only);
+  let mut count = 0;
+  for document in &documents {
+    let compact = json_input::compact(&document.value, config.json_path.as_ref());
+    let mut found = Vec::new();
+    if config.json_pointers {
+      for string in &compact.strings {
+        let hit = find(string.text);
+        if hit.is_some() == config.invert_match {
+          continue;
+        }
+        let line = format!("{}:{}", string.pointer, Value::from(string.text));
+        // Past the pointer, its colon and the opening quote
+        let start = string.pointer.len() + 2;
+        let hit = hit.map_or(Hit::default(), |hit| Hit {
+          spans: hit
+            .spans
+            .into_iter()
+            .map(|span| {
+              let span = json_input::escaped_span(string.text, span);
+              start + span.start..start + span.end
+            })
+            .collect(),
+          pattern: hit.pattern,
+        });
+        found.push((line, hit));
+      }
+    } else {
+      let mut matched = Hit::default();
+      let mut any = false;
+      for string in &compact.strings {
+        if let Some(hit) = find(string.text) {
+          any = true;
+          matched.pattern = matched.pattern.or(hit.pattern);
+          let spans = hit.spans.into_iter().map(|span| string.span_in_line(span));
+          matched.spans.extend(spans);
+        }
+      }
+      if any != config.invert_match {
+        if config.invert_match {
+          matched = Hit::default();
+        }
+        found.push((compact.line, matched));
+      }
+    }
+    for (line, hit) in found {
+      let limit_reached = options.max_count.is_some_and(|max| count >= max)
+        || !options.limit.is_none_or(ResultLimit::take);
+      if limit_reached {
+        return Ok(count);
+      }
+      count += 1;
+      if options.count_only {
+        continue;
+      }
+      if binary {
+        sink.binary_matched()?;
+        return Ok(count);
+      }
+      let more = sink.matched(&Match {
+        line_number: document.line_number,
+        byte_offset: document.offset,
+        line: &line,
+        terminator: "\n",
+        spans: hit.spans,
+        pattern: hit.pattern,
+      })?;
+      if !more {
+        return Ok(count);
+      }
+    }
+  }
+  Ok(count)
+}
+
+// Reads the whole input so that matches may cross line boundaries, then
+// hands the lines they touch to `sink` the same way `stream` does
+fn stream_multiline<R: BufRead, S: Sink>(
+  config: &Config,
+  options: &StreamOptions,
+  mut reader: R,
+  sink: &mut S,
+) -> io::Result<usize> {
+  let mut bytes = Vec::new();
+  reader.read_to_end(&mut bytes)?;
+  let binary = options.detect_binary && is_binary(&bytes);
+  let contents = escape::decode(&bytes);
+  if options.first_match_only {
+    let matched =
+      has_multiline_match(config, &contents) && options.limit.is_none_or(ResultLimit::take);
+    return Ok(usize::from(matched));
+  }
+  let mut matches = search_multiline(config, &contents);
+  if let Some(max) = options.max_count {
+    matches.truncate(max);
+  }
+  if let Some(limit) = options.limit {
+    let allowed = matches.iter().take_while(|_| limit.take()).count();
+    matches.truncate(allowed);
+  }
+  let count = matches.len();
+  if options.count_only || count == 0 {
+    return Ok(count);
+  }
+  if binary {
+    sink.binary_matched()?;
+    return Ok(count);
+  }
+
+  if options.before_context == 0 && options.after_context == 0 {
+    for m in &matches {
+      if !sink.matched(m)? {
+        break;
+      }
+    }
+    return Ok(count);
+  }
+  let lines = context(
+    &contents,
+    &matches,
+    options.before_context,
+    options.after_context,
+  );
+  for line in lines {
+    match line {
+      ContextLine::Match(m) => {
+        if !sink.matched(&m)? {
+          break;
+        }
+      }
+      ContextLine::Context(m) => sink.context(&m)?,
+      ContextLine::Separator => sink.separator()?,
+    }
+  }
+  Ok(count)
+}
+
+// Whether `search_multiline` finds anything in `contents`, which only takes
+// looking for the first match
+fn has_multiline_match(config: &Config, contents: &str) -> bool {
+  if config.invert_match {
+    return !search_multiline(config, contents).is_empty();
+  }
+  // An empty match at the very end is not on any line
+  let on_a_line = |span: &Range<usize>| span.start < contents.len();
+  let bounds = Bounds::of(config);
+  config.patterns.iter().any(|pattern| {
+    let matcher = pattern_matcher(pattern, config);
+    if bounds != Bounds::Anywhere {
+      matcher
+        .find_all(contents)
+        .iter()
+        .any(|span| on_a_line(span) && bounds.allow(contents, span))
+    } else {
+      matcher.find(contents).is_some_and(|span| on_a_line(&span))
+    }
+  })
+}
+
+/// Searches all of `contents` at once, so that matches may span several
+/// lines, as `--multiline` does. Every line a match touches is reported, with
+/// the part of the match on that line as its span.
+pub fn search_multiline<'a>(config: &Config, contents: &'a str) -> Vec<Match<'a>> {
+  let mut found = Vec::new();
+  for (i, pattern) in config.patterns.iter().enumerate() {
+    let matcher = pattern_matcher(pattern, config);
+    found.extend(
+      matcher
+        .find_all(contents)
+        .into_iter()
+        .filter(|span| Bounds::of(config).allow(contents, span))
+        .map(|span| (span, i)),
+    );
+  }
+  found.sort_by(|(a, _), (b, _)| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+  let mut spans: Vec<(Range<usize>, usize)> = Vec::with_capacity(found.len());
+  for (span, i) in found {
+    if spans.last().is_none_or(|(last, _)| span.start >= last.end) {
+      spans.push((span, i));
+    }
+  }
+
+  let mut matches = Vec::new();
+  let mut first = 0;
+  for (i, line) in contents.lines().enumerate() {
+    let start = offset_in(contents, line);
+    let end = start + line.len();
+    // Spans are sorted and don't overlap, so those that ended on earlier
+    // lines are all at the front
+    while spans
+      .get(first)
+      .is_some_and(|(span, _)| span.end < start || (span.end == start && span.start < start))
+    {
+      first += 1;
+    }
+    let mut line_spans = Vec::new();
+    let mut pattern = None;
+    for (span, p) in spans[first..]
+      .iter()
+      .take_while(|(span, _)| span.start <= end)
+    {
+      line_spans.push(span.start.max(start) - start..span.end.min(end) - start);
+      pattern.get_or_insert(*p);
+    }
+    if line_spans.is_empty() != config.invert_match {
+      continue;
+    }
+    if config.invert_match {
+      line_spans.clear();
+    }
+    matches.push(Match {
+      line_number: i + 1,
+      byte_offset: start,
+      line,
+      terminator: records::line_ending(&contents.as_bytes()[end..]),
+      spans: line_spans,
+      pattern,
+    });
+  }
+  matches
+}
+
+// How many occurrences of the patterns a matching line holds, a line without
+// anything to point at, as with `-v`, holding one
+fn occurrences_in(m: &Match) -> usize {
+  m.spans
+    .iter()
+    .filter(|span| !span.is_empty())
+    .count()
+    .max(1)
+}
+
+// Passes lines on to `sink`, counting the occurrences in those that match
+struct Tallied<'a, S> {
+  sink: &'a mut S,
+  matches: &'a mut usize,
+}
+
+impl<'a, S: Sink> Tallied<'a, S> {
+  fn new(sink: &'a mut S, matches: &'a mut usize) -> Tallied<'a, S> {
+    Tallied { sink, matches }
+  }
+}
+
+impl<S: Sink> Sink for Tallied<'_, S> {
+  fn matched(&mut self, m: &Match) -> io::Result<bool> {
+    *self.matches += occurrences_in(m);
+    self.sink.matched(m)
+  }
+
+  fn context(&mut self, m: &Match) -> io::Result<()> {
+    self.sink.context(m)
+  }
+
+  fn separator(&mut self) -> io::Result<()> {
+    self.sink.separator()
+  }
+
+  fn binary_matched(&mut self) -> io::Result<()> {
+    self.sink.binary_matched()
+  }
+}
+
+// Holds the matching lines of an input back so that they can be passed on
+// best scoring first, lines that score the same keeping their order
+struct Ranked<'a> {
+  patterns: &'a [Pattern],
+  // Score, line number, byte offset, text and ending of each line
+  lines: Vec<(i64, usize, usize, String, &'static str, Hit)>,
+  binary: bool,
+}
+
+impl<'a> Ranked<'a> {
+  fn new(patterns: &'a [Pattern]) -> Ranked<'a> {
+    Ranked {
+      patterns,
+      lines: Vec::new(),
+      binary: false,
+    }
+  }
+
+  fn finish<S: Sink>(mut self, sink: &mut S) -> io::Result<()> {
+    if self.binary {
+      return sink.binary_matched();
+    }
+    self.lines.sort_by_key(|line| std::cmp::Reverse(line.0));
+    for (_, line_number, byte_offset, line, terminator, hit) in self.lines {
+      let m = Match {
+        line_number,
+        byte_offset,
+        line: &line,
+        terminator,
+        spans: hit.spans,
+        pattern: hit.pattern,
+      };
+      if !sink.matched(&m)? {
+        break;
+      }
+    }
+    Ok(())
+  }
+}
+
+impl Sink for Ranked<'_> {
+  fn matched(&mut self, m: &Match) -> io::Result<bool> {
+    // The best score of any fuzzy pattern
+    let score = self
+      .patterns
+      .iter()
+      .filter_map(|pattern| match pattern {
+        Pattern::Fuzzy(fuzzy) => fuzzy.score(m.line),
+        _ => None,
+      })
+      .max()
+      .unwrap_or(0);
+    self.lines.push((
+      score,
+      m.line_number,
+      m.byte_offset,
+      m.line.to_string(),
+      m.terminator,
+      Hit {
+        spans: m.spans.clone(),
+        pattern: m.pattern,
+      },
+    ));
+    Ok(true)
+  }
+
+  fn binary_matched(&mut self) -> io::Result<()> {
+    self.binary = true;
+    Ok(())
+  }
+}
+
+/// Searches `reader` one line at a time, handing every line `matcher` finds
+/// something in to `sink`, and returns how many lines matched.
+///
+/// This works with any source of lines, such as sockets, decompressors or
+/// in-memory cursors, and stops early when `sink` asks it to. Invalid UTF-8
+/// is replaced with `U+FFFD`.
+///
+/// ```
+/// use minigrep::matcher::LiteralMatcher;
+///
+/// let mut lines = Vec::new();
+/// let input = std::io::Cursor::new("a frog\na bog\nfrog\n");
+/// minigrep::search_reader(&LiteralMatcher::new("frog"), input, &mut |m: &minigrep::Match| {
+///   lines.push(m.line_number);
+///   Ok(true)
+/// })
+/// .unwrap();
+/// assert_eq!(vec![1, 3], lines);
+/// ```
+pub fn search_reader<M: Matcher, R: BufRead, S: Sink>(
+  matcher: &M,
+  reader: R,
+  sink: &mut S,
+) -> io::Result<usize> {
+  stream(&StreamOptions::default(), single(matcher), reader, sink)
+}
+
+// How `stream` goes through its input
+#[derive(Debug, Default)]
+struct StreamOptions<'a> {
+  max_count: Option<usize>,
+  before_context: usize,
+  after_context: usize,
+  /// Count the matching lines without handing them to the sink.
+  count_only: bool,
+  /// Stop at the first match of an input that looks binary.
+  detect_binary: bool,
+  records: Records,
+  /// A string every matching record contains, so that the records before
+  /// the next place it turns up can be skipped without looking at them.
+  prefilter: Option<Finder>,
+  /// Only whether the input matches at all matters, as with `-q` and `-l`,
+  /// so nothing is looked for past its first match.
+  first_match_only: bool,
+  /// Hand every record that does not match to the sink as context.
+  passthru: bool,
+  /// Matching lines left for the whole search, which other threads may be
+  /// using up at the same time.
+  limit: Option<&'a ResultLimit>,
+}
+
+// The fixed string every matching line has in it, when there is one
+fn prefilter(config: &Config) -> Option<Finder> {
+  if config.invert_match || !config.case_sensitive || config.ignore_accents {
+    return None;
+  }
+  let required = match config.patterns.as_slice() {
+    [Pattern::Literal(query)] => query.as_str(),
+    [Pattern::Regex(re)] => re.required_literal()?,
+    _ => return None,
+  };
+  // Bytes that are not UTF-8 are only read as characters of their own
+  // after the prefilter has run
+  if required.is_empty() || required.chars().any(|c| escape::kept_byte(c).is_some()) {
+    return None;
+  }
+  Some(Finder::new(required.as_bytes()))
+}
+
+// Consumes the whole records in `reader`'s buffer that come before the next
+// occurrence of `finder`, none of which can match, counting them in
+// `records` and `offset`
+fn skip_to_candidate<R: BufRead>(
+  reader: &mut R,
+  finder: &Finder,
+  terminator: u8,
+  records: &mut usize,
+  offset: &mut usize,
+) -> io::Result<()> {
+  loop {
+    let buffer = reader.fill_buf()?;
+    let found = finder.find(buffer);
+    let before = &buffer[..found.unwrap_or(buffer.len())];
+    let skip = before
+      .iter()
+      .rposition(|&b| b == terminator)
+      .map_or(0, |i| i + 1);
+    if skip == 0 {
+      return Ok(());
+    }
+    *records += count_byte(&buffer[..skip], terminator);
+    *offset += skip;
+    reader.consume(skip);
+    if found.is_some() {
+      return Ok(());
+    }
+  }
+}
+
+fn stream<F, R, S>(
+  options: &StreamOptions,
+  find: F,
+  mut reader: R,
+  sink: &mut S,
+) -> io::Result<usize>
+where
+  F: Fn(&str) -> Option<Hit>,
+  R: BufRead,
+  S: Sink,
+{
+  let with_context = !options.count_only
+    && (options.before_context > 0 || options.after_context > 0 || options.passthru);
+  // Line number, byte offset, text and ending of the lines kept for
+  // `before_context`
+  let mut before: VecDeque<(usize, usize, String, &'static str)> =
+    VecDeque::with_capacity(options.before_context);
+  let mut after_remaining = 0;
+  let mut last_printed: Option<usize> = None;
+  let mut count = 0;
+  let binary = options.detect_binary && is_binary(reader.fill_buf()?);
+  let mut buffer = Vec::new();
+  let mut line_number = 0;
+  let mut next_offset = 0;
+  // The length of the record looked at where the reader buffered it, which is
+  // consumed once done with
+  let mut in_place = 0;
+
+  loop {
+    reader.consume(std::mem::take(&mut in_place));
+    // Past the match limit, only the trailing context is left to print
+    let limit_reached = options.max_count.is_some_and(|max| count >= max)
+      || options.limit.is_some_and(ResultLimit::reached);
+    if limit_reached && after_remaining == 0 {
+      break;
+    }
+    if let (Some(finder), Some(terminator)) =
+      (&options.prefilter, options.records.terminator_byte())
+    {
+      if !with_context {
+        skip_to_candidate(
+          &mut reader,
+          finder,
+          terminator,
+          &mut line_number,
+          &mut next_offset,
+        )?;
+      }
+    }
+    let (record, text) = match records::next_record(&mut reader, &options.records, &mut buffer)? {
+      Some(Record::Buffered { len, text }) => {
+        in_place = len;
+        (&reader.fill_buf()?[..len], text)
+      }
+      Some(Record::Copied(text)) => (&buffer[..], text),
+      None => break,
+    };
+    line_number += 1;
+    let byte_offset = next_offset + text.start;
+    next_offset += record.len();
+    let terminator = match options.records {
+      Records::Lines => records::line_ending(&record[text.end..]),
+      _ => "",
+    };
+    // Bytes that are not UTF-8 are kept to be printed as they were
+    let text = escape::decode(&record[text]);
+    let line: &str = &text;
+
+    // Claiming a result comes last, since it cannot be handed back
+    match find(line).filter(|_| !limit_reached && options.limit.is_none_or(ResultLimit::take)) {
+      Some(hit) => {
+        count += 1;
+        if options.count_only {
+          continue;
+        }
+        if binary {
+          sink.binary_matched()?;
+          break;
+        }
+        if with_context {
+          let first = before.front().map_or(line_number, |(n, _, _, _)| *n);
+          if last_printed.is_some_and(|last| first > last + 1) {
+            sink.separator()?;
+          }
+          for (n, offset, context, terminator) in before.drain(..) {
+            sink.context(&Match {
+              line_number: n,
+              byte_offset: offset,
+              line: &context,
+              terminator,
+              spans: Vec::new(),
+              pattern: None,
+            })?;
+          }
+          after_remaining = options.after_context;
+        }
+        let more = sink.matched(&Match {
+          line_number,
+          byte_offset,
+          line,
+          terminator,
+          spans: hit.spans,
+          pattern: hit.pattern,
+        })?;
+        if !more {
+          break;
+        }
+        last_printed = Some(line_number);
+      }
+      None if !with_context => {}
+      None if after_remaining > 0 => {
+        after_remaining -= 1;
+        sink.context(&Match {
+          line_number,
+          byte_offset,
+          line,
+          terminator,
+          spans: Vec::new(),
+          pattern: None,
+        })?;
+        last_printed = Some(line_number);
+      }
+      None if options.passthru && !binary => {
+        sink.context(&Match {
+          line_number,
+          byte_offset,
+          line,
+          terminator,
+          spans: Vec::new(),
+          pattern: None,
+        })?;
+        last_printed = Some(line_number);
+      }
+      None if options.before_context > 0 => {
+        // Reuse the oldest buffered line's allocation once the window is full
+        let mut kept = if before.len() == options.before_context {
+          before.pop_front().unwrap().2
+        } else {
+          String::new()
+        };
+        kept.clear();
+        kept.push_str(line);
+        before.push_back((line_number, byte_offset, kept, terminator));
+      }
+      None => {}
+    }
+  }
+  reader.consume(in_place);
+  Ok(count)
+}
+
+/// Whether `contents` is likely binary rather than text, judging by NUL bytes
+/// in its first `BINARY_CHECK_LEN` bytes.
+pub fn is_binary(contents: &[u8]) -> bool {
+  contents[..contents.len().min(BINARY_CHECK_LEN)].contains(&0)
+}
+
+// Strips the `\n` or `\r\n` a line was read with, like `str::lines` does.
+// A `\r` that isn't followed by `\n` is part of the line.
+fn trim_line_ending(line: &str) -> &str {
+  match line.strip_suffix('\n') {
+    Some(line) => line.strip_suffix('\r').unwrap_or(line),
+    None => line,
+  }
+}
+
+// Where the groups of the match at `span` of `line` are, for `--replace`,
+// from the first regex that matches just there. Only the whole match is
+// known otherwise, as it is for literal patterns.
+fn match_groups(config: &Config, line: &str, span: &Range<usize>) -> Groups {
+  if config.replace.as_ref().is_some_and(|t| t.max_group() > 0) {
+    for pattern in &config.patterns {
+      let groups = match pattern {
+        Pattern::Regex(re) => re.captures_at(line, span.start),
+        _ => None,
+      };
+      if let Some(groups) = groups.filter(|groups| groups[0].as_ref() == Some(span)) {
+        return groups;
+      }
+    }
+  }
+  vec![Some(span.clone())]
+}
+
+/// How much of an input is checked when deciding whether it is binary.
+pub const BINARY_CHECK_LEN: usize = 8 * 1024;
+
+/// An owned matching line found while searching a directory tree, with the
+/// same positions as a `Match`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FileMatch {
+  pub path: PathBuf,
+  pub line_number: usize,
+  /// Where the line starts within the file, once decoded.
+  pub byte_offset: usize,
+  pub line: String,
+  pub spans: Vec<Range<usize>>,
+  pub pattern: Option<usize>,
+}
+
+/// Walks `root` the way `config` says recursive searches should.
+pub(crate) fn walker(config: &Config, root: &Path) -> Walker {
+  Walker::new(root)
+    .path_filter(config.path_filter.clone())
+    .git_ignore(config.git_ignore)
+    .dot_ignore(config.dot_ignore)
+    .git_tracked(config.git_tracked)
+    .follow(config.follow)
+    .max_depth(config.max_depth)
+    .newer_than(config.newer_than)
+    .older_than(config.older_than)
+    .strip_dot(config.strip_dot)
+    .sort(config.sort)
+}
+
+/// Searches every file below `root` and returns each matching line along with
+/// the file it was found in. Binary files are skipped unless `config.binary`
+/// is set.
+pub fn search_dir(config: &Config, root: &Path) -> Result<Vec<FileMatch>, MinigrepError> {
+  let mut results = Vec::new();
+  let walker = walker(config, root);
+  for path in walker {
+    let path = path.map_err(|e| MinigrepError::Io {
+      path: root.to_path_buf(),
+      source: e,
+    })?;
+    results.extend(search_file(config, &path)?);
+  }
+  Ok(results)
+}
+
+/// Searches the file at `path` like `search_dir` does, finding nothing in it
+/// if it is binary.
+pub fn search_file(config: &Config, path: &Path) -> Result<Vec<FileMatch>, MinigrepError> {
+  let bytes = fs::read(path).map_err(|e| MinigrepError::Io {
+    path: path.to_path_buf(),
+    source: e,
+  })?;
+  if !config.binary && is_binary(&bytes) {
+    return Ok(Vec::new());
+  }
+  let contents = config.encoding.decode(&bytes);
+  let results = search_contents(config, &contents)
+    .into_iter()
+    .map(|m| FileMatch {
+      path: path.to_path_buf(),
+      line_number: m.line_number,
+      byte_offset: m.byte_offset,
+      line: m.line.to_string(),
+      spans: m.spans,
+      pattern: m.pattern,
+    })
+    .collect();
+  Ok(results)
+}
+
+fn search_contents<'a>(config: &Config, contents: &'a str) -> Vec<Match<'a>> {
+  search_lines(contents, line_matcher(config, true)).collect()
+}
+
+type LineMatcher<'a> = Box<dyn Fn(&str) -> Option<Hit> + 'a>;
+
+/// What a matcher found in a single line.
+#[derive(Default)]
+struct Hit {
+  spans: Vec<Range<usize>>,
+  pattern: Option<usize>,
+}
+
+/// Builds the per-line test described by `config`, taking inversion into
+/// account so that every output mode agrees on what counts as a match.
+///
+/// The returned closure yields the spans of every match within a line, or
+/// `None` when the line should not be reported. Inverted matches have no
+/// spans to report. Without `spans`, a line is only searched until something
+/// is found in it, and no spans are returned.
+fn line_matcher(config: &Config, spans: bool) -> LineMatcher<'_> {
+  let find = pattern_finder(config, spans);
+  if config.invert_match {
+    Box::new(move |line| match find(line) {
+      Some(_) => None,
+      None => Some(Hit {
+        spans: Vec::new(),
+        pattern: None,
+      }),
+    })
+  } else {
+    find
+  }
+}
+
+// `line_matcher`, leaving inversion out
+fn pattern_finder(config: &Config, spans: bool) -> LineMatcher<'_> {
+  let bounds = Bounds::of(config);
+  match &config.literal_set {
+    // One pass finds every string, which is as quick as it gets either way
+    Some(set) => {
+      let count = config.patterns.len();
+      Box::new(move |line| find_in_set(set, count, line, bounds))
+    }
+    None => {
+      let matchers: Vec<Box<dyn Matcher + '_>> = config
+        .patterns
+        .iter()
+        .map(|pattern| pattern_matcher(pattern, config))
+        .collect();
+      if spans {
+        Box::new(move |line| find_any(&matchers, line, bounds))
+      } else {
+        Box::new(move |line| find_first(&matchers, line, bounds))
+      }
+    }
+  }
+}
+
+fn pattern_matcher<'a>(pattern: &'a Pattern, config: &Config) -> Box<dyn Matcher + 'a> {
+  match pattern {
+    Pattern::Regex(re) => Box::new(re),
+    Pattern::Fuzzy(fuzzy) => Box::new(fuzzy),
+    Pattern::Literal(query) if config.case_sensitive && !config.ignore_accents => {
+      Box::new(LiteralMatcher::new(query))
+    }
+    Pattern::Literal(query) => {
+      let folding = Folding {
+        case: (!config.case_sensitive).then_some(config.case_fold),
+        accents: config.ignore_accents,
+      };
+      Box::new(FoldingMatcher::new(query, folding))
+    }
+  }
+}
+
+// Runs every matcher over `line`, remembering the first pattern that hit and
+// merging all spans into a sorted, non-overlapping list.
+fn find_any<M: Matcher + ?Sized>(matchers: &[Box<M>], line: &str, bounds: Bounds) -> Option<Hit> {
+  let mut spans = Vec::new();
+  let mut pattern = None;
+  for (i, matcher) in matchers.iter().enumerate() {
+    let mut found = matcher.find_all(line);
+    if bounds != Bounds::Anywhere {
+      found.retain(|span| bounds.allow(line, span));
+    }
+    if found.is_empty() {
+      continue;
+    }
+    pattern.get_or_insert(i);
+    spans.extend(found);
+  }
+  Some(merge_spans(spans, pattern?))
+}
+
+// Which pattern matches `line`, stopping at the first that does, for when it
+// doesn't matter where
+fn find_first<M: Matcher + ?Sized>(matchers: &[Box<M>], line: &str, bounds: Bounds) -> Option<Hit> {
+  let pattern = matchers.iter().position(|matcher| {
+    if bounds != Bounds::Anywhere {
+      matcher
+        .find_all(line)
+        .iter()
+        .any(|span| bounds.allow(line, span))
+    } else {
+      matcher.is_match(line)
+    }
+  })?;
+  Some(Hit {
+    spans: Vec::new(),
+    pattern: Some(pattern),
+  })
+}
+
+// Finds the same spans as `find_any` would with a `LiteralMatcher` for each
+// of the `count` strings in `set`, in one pass over `line`
+fn find_in_set(set: &AhoCorasick, count: usize, line: &str, bounds: Bounds) -> Option<Hit> {
+  let mut spans = Vec::new();
+  let mut pattern: Option<usize> = None;
+  // Where the last occurrence of each string ended, since every string
+  // only matches where it doesn't overlap itself
+  let mut ends = Vec::new();
+  set.find_overlapping(line.as_bytes(), |i, span| {
+    if ends.is_empty() {
+      ends.resize(count, 0);
+    }
+    if span.start < ends[i] {
+      return;
+    }
+    ends[i] = span.end;
+    if !bounds.allow(line, &span) {
+      return;
+    }
+    pattern = Some(pattern.map_or(i, |first| first.min(i)));
+    spans.push(span);
+  });
+  Some(merge_spans(spans, pattern?))
+}
+
+// Sorts `spans` and drops those that overlap an earlier one
+fn merge_spans(mut spans: Vec<Range<usize>>, pattern: usize) -> Hit {
+  spans.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+  let mut merged: Vec<Range<usize>> = Vec::with_capacity(spans.len());
+  for span in spans {
+    if merged.last().is_none_or(|last| span.start >= last.end) {
+      merged.push(span);
+    }
+  }
+  Hit {
+    spans: merged,
+    pattern: Some(pattern),
+  }
+}
+
+/// Where a match must start and end to count, from `-w` and `-x`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Bounds {
+  Anywhere,
+  Word,
+  Line,
+}
+
+impl Bounds {
+  fn of(config: &Config) -> Bounds {
+    if config.line_regexp {
+      Bounds::Line
+    } else if config.word_regexp {
+      Bounds::Word
+    } else {
+      Bounds::Anywhere
+    }
+  }
+
+  // Whether `span` of `text`, which may be several lines, is within bounds
+  fn allow(self, text: &str, span: &Range<usize>) -> bool {
+    match self {
+      Bounds::Anywhere => true,
+      Bounds::Word => is_whole_word(text, span),
+      Bounds::Line => is_whole_line(text, span),
+    }
+  }
+}
+
+/// Whether `span` is bounded by non-word characters or the edges of `line`.
+pub fn is_whole_word(line: &str, span: &Range<usize>) -> bool {
+  let is_word = |c: char| c.is_alphanumeric() || c == '_';
+  let before = line[..span.start].chars().next_back();
+  let after = line[span.end..].chars().next();
+  !span.is_empty() && !before.is_some_and(is_word) && !after.is_some_and(is_word)
+}
+
+/// Whether `span` starts and ends a line of `text`.
+pub fn is_whole_line(text: &str, span: &Range<usize>) -> bool {
+  let rest = &text[span.end..];
+  (span.start == 0 || text[..span.start].ends_with('\n'))
+    && (rest.is_empty() || rest.starts_with('\n') || rest.starts_with("\r\n"))
+}
+
+/// A matching line along with its 1-based line number and the byte range of
+/// each match within the line.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Match<'a> {
+  pub line_number: usize,
+  /// Where the line starts within the searched text.
+  pub byte_offset: usize,
+  pub line: &'a str,
+  /// What the line ended in, `\n`, `\r\n` or nothing for a last line
+  /// without an ending, which is not part of `line`. Other records leave it
+  /// to their kind.
+  pub terminator: &'static str,
+  pub spans: Vec<Range<usize>>,
+  /// Index of the first pattern that matched, `None` for inverted matches
+  /// and context lines.
+  pub pattern: Option<usize>,
+}
+
+fn search_lines<'a, F>(contents: &'a str, find: F) -> impl Iterator<Item = Match<'a>>
+where
+  F: Fn(&str) -> Option<Hit>,
+{
+  contents.lines().enumerate().filter_map(move |(i, line)| {
+    find(line).map(|hit| Match {
+      line_number: i + 1,
+      byte_offset: offset_in(contents, line),
+      line,
+      terminator: ending_in(contents, line),
+      spans: hit.spans,
+      pattern: hit.pattern,
+    })
+  })
+}
+
+// `line` must be a slice of `contents`
+fn offset_in(contents: &str, line: &str) -> usize {
+  line.as_ptr() as usize - contents.as_ptr() as usize
+}
+
+// What ends `line`, a line of `contents`
+fn ending_in(contents: &str, line: &str) -> &'static str {
+  records::line_ending(&contents.as_bytes()[offset_in(contents, line) + line.len()..])
+}
+
+fn single<M: Matcher>(matcher: M) -> impl Fn(&str) -> Option<Hit> {
+  move |line| {
+    let spans = matcher.find_all(line);
+    if spans.is_empty() {
+      None
+    } else {
+      Some(Hit {
+        spans,
+        pattern: Some(0),
+      })
+    }
+  }
+}
+
+/// Returns the lines in which `matcher` finds something, for searching with
+/// matchers of your own.
+pub fn search_matcher<'a, M: Matcher>(matcher: &M, contents: &'a str) -> Vec<Match<'a>> {
+  search_lines(contents, single(matcher)).collect()
+}
+
+/// Like `search`, but finds each match only when the iterator gets to it, so
+/// callers that want just the first few can stop early.
+///
+/// Rather than testing every line, all of `contents` is scanned for `query`
+/// and only the lines it turns up in are looked at on their own.
+pub fn search_iter<'a>(query: &str, contents: &'a str) -> impl Iterator<Item = Match<'a>> {
+  let matcher = LiteralMatcher::new(query);
+  search_found(matcher.finder().clone(), matcher, contents)
+}
+
+// The lines in which `finder` turns up something that `matcher` then finds
+// in the line on its own
+fn search_found<'a, M: Matcher + 'a>(
+  finder: Finder,
+  matcher: M,
+  contents: &'a str,
+) -> impl Iterator<Item = Match<'a>> {
+  let bytes = contents.as_bytes();
+  // Where the next line starts, and how many lines come before it
+  let mut pos = 0;
+  let mut line_number = 0;
+  std::iter::from_fn(move || {
+    while pos < bytes.len() {
+      let found = pos + finder.find(&bytes[pos..])?;
+      let start = bytes[pos..found]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(pos, |i| pos + i + 1);
+      let end = bytes[found..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(bytes.len(), |i| found + i);
+      line_number += 1 + count_byte(&bytes[pos..start], b'\n');
+      pos = end + 1;
+      let line = &contents[start..end];
+      // Like `str::lines`, a `\r` is only part of the ending before a `\n`
+      let line = match line.strip_suffix('\r') {
+        Some(stripped) if end < bytes.len() => stripped,
+        _ => line,
+      };
+      // The query may have been found across the end of the line
+      let spans = matcher.find_all(line);
+      if !spans.is_empty() {
+        return Some(Match {
+          line_number,
+          byte_offset: start,
+          line,
+          terminator: ending_in(contents, line),
+          spans,
+          pattern: Some(0),
+        });
+      }
+    }
+    None
+  })
+}
+
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+  search_iter(query, contents).collect()
+}
+
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<Match<'a>> {
+  let matcher = CaseInsensitiveMatcher::new(query);
+  // ASCII contents are scanned whole, the same as a fixed string
+  match matcher.ascii_finder().filter(|_| contents.is_ascii()) {
+    Some(finder) => search_found(finder.clone(), matcher, contents).collect(),
+    None => search_matcher(&matcher, contents),
+  }
+}
+
+pub fn search_regex<'a>(re: &Regex, contents: &'a str) -> Vec<Match<'a>> {
+  search_matcher(re, contents)
+}
+
+/// Returns the lines containing any of `queries`. Each match records the
+/// index of the first query that was found in its line.
+pub fn search_any<'a>(queries: &[&str], contents: &'a str) -> Vec<Match<'a>> {
+  if queries.iter().any(|query| query.is_empty()) {
+    let matchers: Vec<Box<LiteralMatcher>> = queries
+      .iter()
+      .map(|query| Box::new(LiteralMatcher::new(query)))
+      .collect();
+    return search_lines(contents, |line| find_any(&matchers, line, Bounds::Anywhere)).collect();
+  }
+  let set = AhoCorasick::new(queries);
+  search_lines(contents, |line| {
+    find_in_set(&set, queries.len(), line, Bounds::Anywhere)
+  })
+  .collect()
+}
+
+pub fn search_any_case_insensitive<'a>(queries: &[&str], contents: &'a str) -> Vec<Match<'a>> {
+  let matchers: Vec<Box<CaseInsensitiveMatcher>> = queries
+    .iter()
+    .map(|query| Box::new(CaseInsensitiveMatcher::new(query)))
+    .collect();
+  search_lines(contents, |line| find_any(&matchers, line, Bounds::Anywhere)).collect()
+}
+
+/// Counts the lines of `contents` that contain `query`, without collecting
+/// them.
+pub fn count(query: &str, contents: &str) -> usize {
+  search_iter(query, contents).count()
+}
+
+pub fn count_case_insensitive(query: &str, contents: &str) -> usize {
+  let matcher = CaseInsensitiveMatcher::new(query);
+  match matcher.ascii_finder().filter(|_| contents.is_ascii()) {
+    Some(finder) => search_found(finder.clone(), matcher, contents).count(),
+    None => contents
+      .lines()
+      .filter(|line| matcher.is_match(line))
+      .count(),
+  }
+}
+
+pub fn count_regex(re: &Regex, contents: &str) -> usize {
+  contents.lines().filter(|line| re.is_match(line)).count()
+}
+
+/// A line of output when surrounding context is requested.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ContextLine<'a> {
+  Match(Match<'a>),
+  Context(Match<'a>),
+  /// Marks a gap between two non-adjacent groups of lines.
+  Separator,
+}
+
+/// Expands `matches` with up to `before` and `after` lines of surrounding
+/// context. Overlapping or touching windows are merged into a single group.
+pub fn context<'a>(
+  contents: &'a str,
+  matches: &[Match<'a>],
+  before: usize,
+  after: usize,
+) -> Vec<ContextLine<'a>> {
+  let lines: Vec<&str> = contents.lines().collect();
+  let mut shown = vec![false; lines.len()];
+  let mut matched = vec![None; lines.len()];
+  for m in matches {
+    let i = m.line_number - 1;
+    matched[i] = Some(m);
+    let end = (i + after).min(lines.len() - 1);
+    for flag in &mut shown[i.saturating_sub(before)..=end] {
+      *flag = true;
+    }
+  }
+
+  let mut output = Vec::new();
+  let mut last_shown = None;
+  for (i, line) in lines.into_iter().enumerate() {
+    if !shown[i] {
+      continue;
+    }
+    if last_shown.is_some_and(|last| last + 1 != i) {
+      output.push(ContextLine::Separator);
+    }
+    last_shown = Some(i);
+    match matched[i] {
+      Some(m) => output.push(ContextLine::Match(m.clone())),
+      None => output.push(ContextLine::Context(Match {
+        line_number: i + 1,
+        byte_offset: offset_in(contents, line),
+        line,
+        terminator: ending_in(contents, line),
+        spans: Vec::new(),
+        pattern: None,
+      })),
+    }
+  }
+  output
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+  use super::*;
+  use fixture::Fixture;
+
+  pub(crate) fn config(query: &str) -> Config {
+    Config {
+      patterns: vec![Pattern::Literal(query.to_string())],
+      color: ColorChoice::Never,
+      threads: 1,
+      mmap: MmapChoice::Never,
+      ..Config::default()
+    }
+  }
+
+  #[test]
+  fn case_sensitive() {
+    let query = "duct";
+    let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+    assert_eq!(
+      vec![Match {
+        line_number: 2,
+        byte_offset: 6,
+        line: "safe, fast, productive.",
+        terminator: "\n",
+        spans: vec![15..19],
+        pattern: Some(0),
+      }],
+      search(query, contents)
+    );
+  }
+
+  #[test]
+  fn searches_lazily() {
+    let contents = "frog\nbog\nfrog\nfrog";
+    let mut matches = search_iter("frog", contents);
+
+    assert_eq!(Some(1), matches.next().map(|m| m.line_number));
+    assert_eq!(Some(3), matches.next().map(|m| m.line_number));
+    assert_eq!(
+      search("frog", contents),
+      search_iter("frog", contents).collect::<Vec<_>>()
+    );
+  }
+
+  #[test]
+  fn case_insensitive() {
+    let query = "rUsT";
+    let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+    assert_eq!(
+      vec![
+        Match {
+          line_number: 1,
+          byte_offset: 0,
+          line: "Rust:",
+          terminator: "\n",
+          spans: vec![0..4],
+          pattern: Some(0),
+        },
+        Match {
+          line_number: 4,
+          byte_offset: 42,
+          line: "Trust me.",
+          terminator: "",
+          spans: vec![1..5],
+          pattern: Some(0),
+        }
+      ],
+      search_case_insensitive(query, contents)
+    );
+  }
+
+  #[test]
+  fn regex() {
+    let re = Regex::new(r"^fn \w+\(").unwrap();
+    let contents = "\
+fn main() {
+pub fn run(config: Config) {
+fn search(query: &str) {";
+
+    let lines: Vec<&str> = search_regex(&re, contents).iter().map(|m| m.line).collect();
+    assert_eq!(vec!["fn main() {", "fn search(query: &str) {"], lines);
+  }
+
+  #[test]
+  fn merges_context_windows() {
+    let contents = "a\nfoo\nb\nc\nfoo\nd\ne\nf\nfoo";
+    let matches = search("foo", contents);
+    let line = |line_number, byte_offset, line| Match {
+      line_number,
+      byte_offset,
+      line,
+      terminator: "\n",
+      spans: Vec::new(),
+      pattern: None,
+    };
+    // The last line has no ending
+    let hit = |line_number, byte_offset| Match {
+      line_number,
+      byte_offset,
+      line: "foo",
+      terminator: if line_number == 9 { "" } else { "\n" },
+      spans: vec![0..3],
+      pattern: Some(0),
+    };
+
+    assert_eq!(
+      vec![
+        ContextLine::Context(line(1, 0, "a")),
+        ContextLine::Match(hit(2, 2)),
+        ContextLine::Context(line(3, 6, "b")),
+        ContextLine::Context(line(4, 8, "c")),
+        ContextLine::Match(hit(5, 10)),
+        ContextLine::Context(line(6, 14, "d")),
+        ContextLine::Separator,
+        ContextLine::Context(line(8, 18, "f")),
+        ContextLine::Match(hit(9, 20)),
+      ],
+      context(contents, &matches, 1, 1)
+    );
+  }
+
+  #[test]
+  fn searches_whole_contents_like_each_line() {
+    let contents = "frog\r\n\nfrogfrog frog\nfro\ng\rfrog\r\nfrog\r";
+    for query in ["frog", "g\r", "o\nf", ""].iter() {
+      let expected: Vec<Match> =
+        search_lines(contents, single(LiteralMatcher::new(query))).collect();
+      assert_eq!(expected, search(query, contents), "query {:?}", query);
+    }
+  }
+
+  #[test]
+  fn skips_ahead_to_candidate_lines() {
+    let mut config = config("frog");
+    config.line_number = true;
+    config.byte_offset = true;
+    let input = "a toad\nfrogs\nno\nnope\na frog\n".repeat(2);
+
+    // A small buffer makes lines and matches cross the end of it
+    let mut out = Vec::new();
+    let reader = BufReader::with_capacity(8, input.as_bytes());
+    search_input(&config, reader, None, &mut out).unwrap();
+    assert_eq!(
+      "2:7:frogs\n5:21:a frog\n7:35:frogs\n10:49:a frog\n",
+      String::from_utf8(out).unwrap()
+    );
+  }
+
+  #[test]
+  fn counts_matching_lines() {
+    let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+    assert_eq!(1, count("Rust", contents));
+    assert_eq!(2, count_case_insensitive("rust", contents));
+    assert_eq!(0, count("Go", contents));
+  }
+
+  #[test]
+  fn invert_match() {
+    let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+    let mut config = config("rust");
+    config.invert_match = true;
+
+    let lines: Vec<&str> = search_contents(&config, contents)
+      .iter()
+      .map(|m| m.line)
+      .collect();
+    assert_eq!(
+      vec!["Rust:", "safe, fast, productive.", "Pick three."],
+      lines
+    );
+
+    config.case_sensitive = false;
+    assert_eq!(2, search_contents(&config, contents).len());
+  }
+
+  #[test]
+  fn case_insensitive_spans_map_to_original_line() {
+    // `İ` lowercases to two characters, shifting every later byte offset
+    let matches = search_case_insensitive("stanbul", "İSTANBUL istanbul");
+
+    assert_eq!(vec![2..9, 11..18], matches[0].spans);
+  }
+
+  #[test]
+  fn whole_word() {
+    let contents = "\
+the latest test
+testing
+Test_case
+a TEST.";
+    let mut config = config("test");
+    config.word_regexp = true;
+
+    let matches = search_contents(&config, contents);
+    assert_eq!(1, matches.len());
+    assert_eq!(vec![11..15], matches[0].spans);
+
+    config.case_sensitive = false;
+    let lines: Vec<&str> = search_contents(&config, contents)
+      .iter()
+      .map(|m| m.line)
+      .collect();
+    assert_eq!(vec!["the latest test", "a TEST."], lines);
+  }
+
+  #[test]
+  fn whole_line() {
+    let contents = "test\ntesting\na test\nTEST";
+    let mut config = config("test");
+    config.line_regexp = true;
+    config.case_sensitive = false;
+    let lines: Vec<&str> = search_contents(&config, contents)
+      .iter()
+      .map(|m| m.line)
+      .collect();
+    assert_eq!(vec!["test", "TEST"], lines);
+    config.multiline = true;
+    assert_eq!(2, search_multiline(&config, contents).len());
+  }
+
+  #[test]
+  fn any_of_several_queries() {
+    let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+    let matches = search_any(&["fast", "Pick", "st"], contents);
+    let found: Vec<(&str, Option<usize>)> = matches.iter().map(|m| (m.line, m.pattern)).collect();
+    assert_eq!(
+      vec![
+        ("Rust:", Some(2)),
+        ("safe, fast, productive.", Some(0)),
+        ("Pick three.", Some(1)),
+        ("Trust me.", Some(2))
+      ],
+      found
+    );
+    // Overlapping hits from different queries are merged
+    assert_eq!(vec![6..10], matches[1].spans);
+
+    let matches = search_any_case_insensitive(&["PICK", "rust"], contents);
+    assert_eq!(3, matches.len());
+  }
+
+  #[test]
+  fn literal_set_agrees_with_each_matcher() {
+    let queries = ["xab", "aba", "a", "ab ab"];
+    let set = AhoCorasick::new(&queries);
+    let matchers: Vec<Box<LiteralMatcher>> = queries
+      .iter()
+      .map(|query| Box::new(LiteralMatcher::new(query)))
+      .collect();
+    for line in ["xababa", "ab ab aba", "a a", "none", "ab_a xab"].iter() {
+      for &bounds in [Bounds::Anywhere, Bounds::Word, Bounds::Line].iter() {
+        let expected = find_any(&matchers, line, bounds);
+        let found = find_in_set(&set, queries.len(), line, bounds);
+        assert_eq!(
+          expected.map(|hit| (hit.spans, hit.pattern)),
+          found.map(|hit| (hit.spans, hit.pattern)),
+          "line {:?}",
+          line
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn reads_pattern_files() {
+    let contents = "\
+foo
+# a comment
+
+  bar
+// another comment";
+
+    assert_eq!(
+      vec!["foo", "  bar", "// another comment"],
+      read_patterns(contents, Some("#"))
+    );
+    assert_eq!(
+      vec!["foo", "# a comment", "  bar"],
+      read_patterns(contents, Some("//"))
+    );
+  }
+
+  #[test]
+  fn searches_any_reader_with_a_matcher() {
+    let input = io::Cursor::new("frog\nbog\nfrog\nfrog\n");
+    let mut seen = Vec::new();
+    let count = search_reader(&LiteralMatcher::new("frog"), input, &mut |m: &Match| {
+      seen.push(m.line_number);
+      Ok(seen.len() < 2)
+    })
+    .unwrap();
+
+    assert_eq!(2, count);
+    assert_eq!(vec![1, 3], seen);
+  }
+
+  #[test]
+  fn sorts_fuzzy_matches_by_score() {
+    let mut config = config("sr");
+    config.patterns = vec![Pattern::Fuzzy(FuzzyMatcher::new("sr", true))];
+    config.sort_by_score = true;
+    let contents = "sometimes never\nno\nsrc\nsearch_reader\n";
+    let mut out = Vec::new();
+    search_input(&config, contents.as_bytes(), None, &mut out).unwrap();
+
+    assert_eq!(
+      "src\nsearch_reader\nsometimes never\n",
+      String::from_utf8(out).unwrap()
+    );
+  }
+
+  #[test]
+  fn matches_across_lines() {
+    let mut config = config("");
+    config.patterns = vec![Pattern::Regex(Regex::new(r"fn \w+\(\s*\w+").unwrap())];
+    let contents = "fn search(\n  query: &str,\n) {}\nfn main() {}\n";
+    let matches = search_multiline(&config, contents);
+
+    assert_eq!(
+      vec![(1, vec![0..10]), (2, vec![0..7])],
+      matches
+        .iter()
+        .map(|m| (m.line_number, m.spans.clone()))
+        .collect::<Vec<_>>()
+    );
+    assert_eq!(11, matches[1].byte_offset);
+
+    config.invert_match = true;
+    let lines: Vec<_> = search_multiline(&config, contents)
+      .iter()
+      .map(|m| m.line)
+      .collect();
+    assert_eq!(vec![") {}", "fn main() {}"], lines);
+  }
+
+  #[test]
+  fn handles_crlf_line_endings() {
+    let mut config = config("");
+    config.multiline = true;
+    config.patterns = vec![Pattern::Regex(Regex::new("o$").unwrap().crlf(true))];
+
+    let mut out = Vec::new();
+    search_input(&config, "foo\r\nbar\r\nbaz\r".as_bytes(), None, &mut out).unwrap();
+    assert_eq!("foo\r\n", String::from_utf8(out).unwrap());
+
+    config.multiline = false;
+    config.patterns = vec![Pattern::Literal("z\r".to_string())];
+    let mut out = Vec::new();
+    search_input(&config, "foo\r\nbaz\r\nbaz\r".as_bytes(), None, &mut out).unwrap();
+    assert_eq!("baz\r\n", String::from_utf8(out).unwrap());
+
+    // Lines are matched without their `\r\n`, but printed with it
+    config.patterns = vec![Pattern::Regex(Regex::new("o$").unwrap())];
+    let mut out = Vec::new();
+    search_input(&config, "foo\r\nbar\nfoo".as_bytes(), None, &mut out).unwrap();
+    assert_eq!("foo\r\nfoo\n", String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn gives_json_lines_the_ending_they_had() {
+    let mut config = config("foo");
+    config.format = OutputFormat::JsonLines;
+    let mut out = Vec::new();
+    search_input(&config, "foo\r\nbar\nfoo".as_bytes(), None, &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(r#""lines":{"text":"foo\r\n"}"#));
+    assert!(out.contains(r#""lines":{"text":"foo"}"#));
+  }
+
+  #[test]
+  fn customizes_context_separators() {
+    // The groups around the first two matches touch, so only the third is
+    // set apart
+    let contents = "foo\na\nb\nfoo\nc\nd\ne\nfoo\n";
+    let mut config = config("foo");
+    config.before_context = 1;
+    config.after_context = 1;
+    config.context_separator = Some("@@".to_string());
+
+    let mut out = Vec::new();
+    search_input(&config, contents.as_bytes(), None, &mut out).unwrap();
+    assert_eq!(
+      "foo\na\nb\nfoo\nc\n@@\ne\nfoo\n",
+      String::from_utf8(out).unwrap()
+    );
+
+    config.context_separator = None;
+    let mut out = Vec::new();
+    search_input(&config, contents.as_bytes(), None, &mut out).unwrap();
+    assert_eq!(
+      "foo\na\nb\nfoo\nc\ne\nfoo\n",
+      String::from_utf8(out).unwrap()
+    );
+  }
+
+  #[test]
+  fn streams_context_from_a_reader() {
+    let contents = "a\nfoo\nb\nc\nfoo\nd\ne\nf\nfoo\r\ng\n";
+    let mut config = config("foo");
+    config.line_number = true;
+    config.before_context = 1;
+    config.after_context = 1;
+
+    let mut out = Vec::new();
+    search_input(&config, contents.as_bytes(), None, &mut out).unwrap();
+    assert_eq!(
+      "1-a\n2:foo\n3-b\n4-c\n5:foo\n6-d\n--\n8-f\n9:foo\r\n10-g\n",
+      String::from_utf8(out).unwrap()
+    );
+
+    config.count = true;
+    let mut out = Vec::new();
+    search_input(&config, contents.as_bytes(), None, &mut out).unwrap();
+    assert_eq!("3\n", String::from_utf8(out).unwrap());
+  }
+
+  #[cfg(feature = "cli")]
+  #[test]
+  fn prints_only_the_new_matches_when_watching() {
+    let mut config = config("foo");
+    config.line_number = true;
+    config.before_context = 1;
+    config.after_context = 1;
+    let search = |contents: &str, printed| {
+      let mut out = Vec::new();
+      watch::put_printed(printed);
+      search_input(&config, contents.as_bytes(), None, &mut out).unwrap();
+      (
+        String::from_utf8(out).unwrap(),
+        watch::take_printed().unwrap(),
+      )
+    };
+
+    let (out, printed) = search("a\nfoo\nb\n", watch::Printed::new());
+    assert_eq!("1-a\n2:foo\n3-b\n", out);
+    let (out, printed) = search("a\nfoo\nb\nc\nd\nfoo\ne\n", printed);
+    assert_eq!("5-d\n6:foo\n7-e\n", out);
+    let (out, _) = search("a\nfoo\nb\nc\nd\nfoo\ne\nfoo\nbar\nfoo\n", printed);
+    assert_eq!("7-e\n8:foo\n9-bar\n10:foo\n", out);
+  }
+
+  #[test]
+  fn searches_a_directory_with_positions() {
+    let root = Fixture::new("search-dir", &[("sub/a.txt", "safe\nfast, fast\n")]);
+    let config = config("fast");
+
+    let found = search_dir(&config, root.path()).unwrap();
+    assert_eq!(
+      vec![FileMatch {
+        path: root.join("sub").join("a.txt"),
+        line_number: 2,
+        byte_offset: 5,
+        line: "fast, fast".to_string(),
+        spans: vec![0..4, 6..10],
+        pattern: Some(0),
+      }],
+      found
+    );
+  }
+
+  #[test]
+  fn binary_inputs() {
+    let contents = b"ELF\0\x01 safe\xff\nfast\n";
+    let mut config = config("safe");
+
+    let mut out = Vec::new();
+    search_input(&config, &contents[..], Some(Path::new("app")), &mut out).unwrap();
+    assert_eq!("Binary file app matches\n", String::from_utf8(out).unwrap());
+    // Skipped, without its lines counted as scanned or matching
+    let mut stats = Stats::default();
+    search_counted(&config, &contents[..], None, &mut io::sink(), &mut stats).unwrap();
+    assert_eq!(
+      (0, 1, 0, 0),
+      (
+        stats.files_searched,
+        stats.skipped_binary,
+        stats.lines_scanned,
+        stats.matched_lines
+      )
+    );
+
+    config.binary = true;
+    let mut out = Vec::new();
+    search_input(&config, &contents[..], None, &mut out).unwrap();
+    assert_eq!("ELF\\x00\\x01 safe\\xFF\n", String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn prints_bytes_that_are_not_utf8_as_they_were() {
+    let contents = b"caf\xe9 foo\n";
+    let mut config = config("foo");
+    let mut out = Vec::new();
+    search_input(&config, &contents[..], None, &mut out).unwrap();
+    assert_eq!(&b"caf\xe9 foo\n"[..], &out[..]);
+
+    config.only_matching = true;
+    config.byte_offset = true;
+    let mut out = Vec::new();
+    search_input(&config, &contents[..], None, &mut out).unwrap();
+    assert_eq!("5:foo\n", String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn stops_after_max_count() {
+    let contents = "foo 1\nfoo 2\nbar\nfoo 3\n";
+    let mut config = config("foo");
+    config.max_count = Some(2);
+    config.after_context = 1;
+
+    let mut out = Vec::new();
+    search_input(&config, contents.as_bytes(), None, &mut out).unwrap();
+    assert_eq!("foo 1\nfoo 2\nbar\n", String::from_utf8(out).unwrap());
+
+    config.count = true;
+    let mut out = Vec::new();
+    search_input(&config, contents.as_bytes(), None, &mut out).unwrap();
+    assert_eq!("2\n", String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn stops_reading_at_the_first_match_when_listing() {
+    // Never runs out of matching lines
+    struct Frogs;
+    impl io::Read for Frogs {
+      fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        for (i, b) in buf.iter_mut().enumerate() {
+          *b = b"frog\n"[i % 5];
+        }
+        Ok(buf.len() - buf.len() % 5)
+      }
+    }
+    let mut config = config("frog");
+    config.files_with_matches = true;
+
+    let mut out = Vec::new();
+    let count = search_input(
+      &config,
+      BufReader::new(Frogs),
+      Some(Path::new("pond")),
+      &mut out,
+    );
+    assert_eq!(1, count.unwrap());
+    assert_eq!("pond\n", String::from_utf8(out).unwrap());
+
+    config.multiline = true;
+    config.quiet = true;
+    let mut out = Vec::new();
+    assert_eq!(
+      1,
+      search_input(&config, "frog\nfrog\n".as_bytes(), None, &mut out).unwrap()
+    );
+    assert!(out.is_empty());
+  }
+
+  #[test]
+  fn counts_every_occurrence() {
+    let root = Fixture::new(
+      "count-matches",
+      &[
+        ("a.txt", "frog frog frog\ntoad\nfrog\n"),
+        ("b.txt", "frogfrog\n"),
+      ],
+    );
+    let mut config = config("frog");
+    config.filenames = vec![
+      root.join("a.txt").display().to_string(),
+      root.join("b.txt").display().to_string(),
+    ];
+    config.count_matches = true;
+    config.total = true;
+    config.threads = 1;
+
+    let mut out = Vec::new();
+    run_config(&config, &mut out).unwrap();
+    let expected = format!(
+      "{}:4\n{}:2\ntotal:6\n",
+      root.join("a.txt").display(),
+      root.join("b.txt").display()
+    );
+    assert_eq!(expected, String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn prints_files_in_order_with_threads() {
+    let root = Fixture::new("ordered", &[]);
+    let mut expected = String::new();
+    // The first files take longest to search, so threads finish them last
+    for i in 0..16 {
+      let path = root.write(&format!("{:02}.txt", i), "frog\n".repeat((16 - i) * 2000));
+      expected.push_str(&format!("{}:{}\n", path.display(), (16 - i) * 2000));
+    }
+    let mut config = config("frog");
+    config.filenames = vec![root.path().display().to_string()];
+    config.recursive = true;
+    config.count = true;
+    config.threads = 4;
+
+    let mut out = Vec::new();
+    run_config(&config, &mut out).unwrap();
+    assert_eq!(expected, String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn stops_once_the_output_is_closed() {
+    struct Closed;
+    impl Write for Closed {
+      fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+      }
+      fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+      }
+    }
+    let root = Fixture::new("closed", &[("b.txt", "frog\n")]);
+    root.write("a.txt", "frog\n".repeat(10_000));
+    let mut config = config("frog");
+    config.filenames = vec![root.join("a.txt").display().to_string()];
+
+    let single = run_config(&config, &mut Closed).unwrap_err();
+    config.filenames = vec![root.path().display().to_string()];
+    config.recursive = true;
+    for threads in [1, 4] {
+      config.threads = threads;
+      assert!(run_config(&config, &mut Closed)
+        .unwrap_err()
+        .is_broken_pipe());
+    }
+    assert!(single.is_broken_pipe());
+  }
+
+  #[test]
+  fn hands_unreadable_inputs_to_the_hook() {
+    use std::sync::{Arc, Mutex};
+    let reported = std::sync::Arc::new(Mutex::new(Vec::new()));
+    let mut config = config("frog");
+    config.filenames = vec!["no-such-input".to_string(), "another-missing".to_string()];
+    let hook = Arc::clone(&reported);
+    config.on_error = Some(Box::new(move |path, e| {
+      hook.lock().unwrap().push((path.to_path_buf(), e.kind()));
+    }));
+
+    let summary = run_config(&config, &mut Vec::new()).unwrap();
+    assert_eq!(2, summary.errors);
+    let mut reported = reported.lock().unwrap().clone();
+    reported.sort();
+    assert_eq!(
+      vec![
+        (PathBuf::from("another-missing"), io::ErrorKind::NotFound),
+        (PathBuf::from("no-such-input"), io::ErrorKind::NotFound),
+      ],
+      reported
+    );
+  }
+
+  #[test]
+  fn stops_the_whole_search_at_max_results() {
+    let root = Fixture::new(
+      "max-results",
+      &[
+        ("a.txt", "frog\ntoad\nfrog\n"),
+        ("b.txt", "frog\nfrog\n"),
+        ("c.txt", "frog\n"),
+      ],
+    );
+    let mut config = config("frog");
+    config.filenames = vec![root.path().display().to_string()];
+    config.recursive = true;
+    config.count = true;
+    config.max_results = Some(ResultLimit::new(3));
+
+    let mut out = Vec::new();
+    let summary = run_config(&config, &mut out).unwrap();
+    let expected = format!(
+      "{}:2\n{}:1\n",
+      root.join("a.txt").display(),
+      root.join("b.txt").display()
+    );
+    assert_eq!(expected, String::from_utf8(out).unwrap());
+    assert_eq!(2, summary.stats.files_searched);
+  }
+
+  #[test]
+  fn collects_statistics_per_file() {
+    let root = Fixture::new(
+      "per-file",
+      &[("a.txt", "frog frog\ntoad\nfrog\n"), ("b.txt", "toad\n")],
+    );
+    let mut config = config("frog");
+    config.filenames = vec![root.path().display().to_string()];
+    config.recursive = true;
+    config.stats = true;
+    config.per_file = true;
+
+    let summary = run_config(&config, &mut Vec::new()).unwrap();
+    let files: Vec<(PathBuf, usize, usize)> = summary
+      .files
+      .iter()
+      .map(|file| {
+        (
+          file.path.clone(),
+          file.stats.matched_lines,
+          file.stats.matches,
+        )
+      })
+      .collect();
+    assert_eq!(
+      vec![(root.join("a.txt"), 2, 3), (root.join("b.txt"), 0, 0)],
+      files
+    );
+    assert_eq!(3, summary.stats.matches);
+  }
+
+  #[test]
+  fn quiet_stops_at_first_match() {
+    let mut config = config("foo");
+    config.quiet = true;
+    config.count = true;
+
+    let mut out = Vec::new();
+    let count = search_input(&config, "foo\nfoo\n".as_bytes(), None, &mut out).unwrap();
+    assert_eq!(1, count);
+    assert!(out.is_empty());
+  }
+
+  #[test]
+  fn lists_files_with_and_without_matches() {
+    let mut config = config("foo");
+    config.files_with_matches = true;
+    let name = Some(Path::new("notes.txt"));
+
+    let mut out = Vec::new();
+    let count = search_input(&config, "foo\nfoo\n".as_bytes(), name, &mut out).unwrap();
+    assert_eq!(1, count);
+    assert_eq!("notes.txt\n", String::from_utf8(out).unwrap());
+
+    let mut out = Vec::new();
+    search_input(&config, "bar\n".as_bytes(), name, &mut out).unwrap();
+    assert!(out.is_empty());
+
+    config.files_with_matches = false;
+    config.files_without_match = true;
+    let mut out = Vec::new();
+    search_input(&config, "foo\n".as_bytes(), name, &mut out).unwrap();
+    assert!(out.is_empty());
+
+    let mut out = Vec::new();
+    search_input(&config, "bar\n".as_bytes(), name, &mut out).unwrap();
+    assert_eq!("notes.txt\n", String::from_utf8(out).unwrap());
+
+    config.null = true;
+    let mut out = Vec::new();
+    search_input(&config, "bar\n".as_bytes(), name, &mut out).unwrap();
+    assert_eq!("notes.txt\0", String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn prints_only_matching_parts() {
+    let mut config = config("o+");
+    config.patterns = vec![Pattern::Regex(Regex::new("o+").unwrap())];
+    config.only_matching = true;
+    config.line_number = true;
+    config.before_context = 1;
+
+    let mut out = Vec::new();
+    search_input(&config, "x\nfoo boo\nbar\n".as_bytes(), None, &mut out).unwrap();
+    assert_eq!("2:oo\n2:oo\n", String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn reports_standard_input_by_its_label() {
+    use std::sync::{Arc, Mutex};
+
+    let reported = Arc::new(Mutex::new(Vec::new()));
+    let mut config = config("frog");
+    let sink = reported.clone();
+    config.on_error = Some(Box::new(move |path, _| {
+      sink.lock().unwrap().push(path.to_path_buf())
+    }));
+
+    let e = io::Error::new(io::ErrorKind::InvalidData, "not JSON");
+    report(&config, Path::new(STDIN_FILENAME), &e);
+    report(&config, Path::new("poem.txt"), &e);
+    assert_eq!(
+      vec![PathBuf::from(STDIN_LABEL), PathBuf::from("poem.txt")],
+      *reported.lock().unwrap()
+    );
+  }
+
+  #[test]
+  fn prints_fuzzy_matches_whole_when_only_matching() {
+    let mut config = config("cfg");
+    config.patterns = vec![Pattern::Fuzzy(FuzzyMatcher::new("cfg", true))];
+    config.syntax = Syntax::Fuzzy;
+    config.only_matching = true;
+
+    let mut out = Vec::new();
+    search_input(
+      &config,
+      "let config = 1;
+"
+      .as_bytes(),
+      None,
+      &mut out,
+    )
+    .unwrap();
+    assert_eq!("config\n", String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn prints_byte_offsets() {
+    let mut config = config("foo");
+    config.byte_offset = true;
+
+    let mut out = Vec::new();
+    search_input(&config, "bar\nfoo foo\n".as_bytes(), None, &mut out).unwrap();
+    assert_eq!("4:foo foo\n", String::from_utf8(out).unwrap());
+
+    config.only_matching = true;
+    let mut out = Vec::new();
+    search_input(&config, "bar\nfoo foo\n".as_bytes(), None, &mut out).unwrap();
+    assert_eq!("4:foo\n8:foo\n", String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn prints_vimgrep_locations() {
+    let mut config = config("foo");
+    config.format = OutputFormat::Vimgrep;
+    config.after_context = 1;
+
+    let mut out = Vec::new();
+    let path = Path::new("a.txt");
+    search_input(
+      &config,
+      "bar\nfoo foo\nbaz\n".as_bytes(),
+      Some(path),
+      &mut out,
+    )
+    .unwrap();
+    assert_eq!(
+      "a.txt:2:1:foo foo\na.txt:2:5:foo foo\n",
+      String::from_utf8(out).unwrap()
+    );
+  }
+
+  #[test]
+  fn prints_replaced_lines() {
+    let mut config = config("frog");
+    config.replace = Some(Template::new("(&)"));
+
+    let mut out = Vec::new();
+    search_input(&config, "a frog\na bog\n".as_bytes(), None, &mut out).unwrap();
+    assert_eq!("a (frog)\n", String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn replaces_with_groups() {
+    let mut config = config("");
+    config.patterns = vec![Pattern::Regex(Regex::new(r"(\w+)@(\w+)").unwrap())];
+    config.replace = Some(Template::new(r"\2 at $1"));
+    assert_eq!(Ok(()), config.validate().map_err(|e| e.to_string()));
+
+    let mut out = Vec::new();
+    search_input(&config, "mail bob@home now\n".as_bytes(), None, &mut out).unwrap();
+    assert_eq!("mail home at bob now\n", String::from_utf8(out).unwrap());
+    config.only_matching = true;
+    let mut out = Vec::new();
+    search_input(&config, "mail bob@home now\n".as_bytes(), None, &mut out).unwrap();
+    assert_eq!("home at bob\n", String::from_utf8(out).unwrap());
+
+    config.replace = Some(Template::new("$3"));
+    assert!(config.validate().is_err());
+    config.patterns = vec![Pattern::Literal("bob".to_string())];
+    config.replace = Some(Template::new(r"\1"));
+    assert!(config.validate().is_err());
+  }
+
+  #[test]
+  fn passes_every_line_through() {
+    let mut config = config("frog");
+    config.passthru = true;
+    config.line_number = true;
+
+    let mut out = Vec::new();
+    let count = search_input(&config, "a\nfrog\nb\n".as_bytes(), None, &mut out).unwrap();
+    assert_eq!(1, count);
+    assert_eq!("1-a\n2:frog\n3-b\n", String::from_utf8(out).unwrap());
+
+    config.count = true;
+    let mut out = Vec::new();
+    search_input(&config, "a\nfrog\n".as_bytes(), None, &mut out).unwrap();
+    assert_eq!("1\n", String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  fn matches_in_one_column_of_csv_rows() {
+    let mut config = config("frog");
+    config.records = Records::Csv;
+    config.column = Some(Column::parse("note"));
+    config.only_matching = true;
+    config.byte_offset = true;
+    let input = "name,note\nfrog,\"a \"\"frog\"\"\nhere\"\npond,frog\n";
+
+    let mut out = Vec::new();
+    search_input(&config, input.as_bytes(), None, &mut out).unwrap();
+    assert_eq!("20:frog\n38:frog\n", String::from_utf8(out).unwrap());
+
+    config.only_matching = false;
+    config.byte_offset = false;
+    config.invert_match = true;
+    let mut out = Vec::new();
+    search_input(&config, input.as_bytes(), None, &mut out).unwrap();
+    assert_eq!("", String::from_utf8(out).unwrap());
+
+    config.column = Some(Column::parse("size"));
+    let err = search_input(&config, input.as_bytes(), None, &mut Vec::new()).unwrap_err();
+    assert_eq!("no column is named `size`", err.to_string());
+  }
+
+  #[test]
+  fn matches_the_strings_of_json_documents() {
+    let mut config = config("frog");
+    config.json_input = true;
+    config.line_number = true;
+    let input = "{\"frog\": 1, \"pond\": \"a frog\"}\n{\"frog\": \"toad\"}\n";
+
+    let mut out = Vec::new();
+    search_input(&config, input.as_bytes(), None, &mut out).unwrap();
+    assert_eq!(
+      "1:{\"frog\":1,\"pond\":\"a frog\"}\n",
+      String::from_utf8(out).unwrap()
+    );
+
+    config.json_pointers = true;
+    config.invert_match = true;
+    let mut out = Vec::new();
+    search_input(&config, input.as_bytes(), None, &mut out).unwrap();
+    assert_eq!("2:/frog:\"toad\"\n", String::from_utf8(out).unwrap());
+
+    let err = search_input(&config, "{".as_bytes(), None, &mut Vec::new()).unwrap_err();
+    assert!(err.to_string().starts_with("not JSON"));
+  }
+
+  #[test]
+  #[cfg(feature = "cli")]
+  fn parses_arguments_from_any_iterator() {
+    let parse = |args: &[&str]| Config::parse(args.iter().map(|arg| arg.to_string()), true);
+    let config = match parse(&["minigrep", "--no-config", "-nC2", "frog", "poem.txt"]) {
+      Ok(Parsed::Search(config)) => *config,
+      _ => panic!("expected a search"),
+    };
+
+    assert_eq!("frog", config.patterns[0].as_str());
+    assert_eq!(vec!["poem.txt".to_string()], config.filenames);
+    assert!(config.line_number);
+    assert_eq!((2, 2), (config.before_context, config.after_context));
+
+    assert!(matches!(
+      Config::parse(Vec::new(), true),
+      Err(MinigrepError::MissingQuery)
+    ));
+    assert!(matches!(
+      parse(&["minigrep", "--no-config", "frog"]),
+      Err(MinigrepError::MissingFile)
+    ));
+    let args = ["minigrep", "--no-config", "frog"].map(String::from);
+    match Config::parse(args, false) {
+      Ok(Parsed::Search(config)) => assert_eq!(vec![STDIN_FILENAME], config.filenames),
+      _ => panic!("expected a search of standard input"),
+    }
+    assert!(matches!(
+      parse(&["minigrep", "--no-config", "frog", "--help"]),
+      Ok(Parsed::Help)
+    ));
+    assert!(matches!(
+      parse(&["minigrep", "--no-config", "-V"]),
+      Ok(Parsed::Version)
+    ));
+
+    let new = |args: &[&str]| Config::new(args.iter().map(|arg| arg.to_string()));
+    assert_eq!(vec!["-"], new(&["minigrep", "frog"]).unwrap().filenames);
+    assert!(matches!(
+      new(&["minigrep", "--help"]),
+      Err(MinigrepError::IncompatibleOptions(_))
+    ));
+    let color = |args: &[&str]| new(args).unwrap().color;
+    assert_eq!(ColorChoice::Auto, color(&["minigrep", "--color", "frog"]));
+    assert_eq!(
+      ColorChoice::Always,
+      color(&["minigrep", "--color=always", "frog"])
+    );
+  }
+
+  #[test]
+  #[cfg(feature = "cli")]
+  fn skips_files_over_the_size_limit() {
+    let size = |value: &str| parse_size("--max-filesize", Some(value.to_string())).ok();
+    assert_eq!(Some(10), size("10"));
+    assert_eq!(Some(10 << 20), size("10M"));
+    assert_eq!(Some(2 << 30), size("2g"));
+    assert_eq!(None, size("M"));
+    assert_eq!(None, size("-1K"));
+
+    let fixture = Fixture::new("max-filesize", &[]);
+    let path = fixture.write("poem.txt", "frog\n".repeat(10));
+    let mut config = config("frog");
+    config.max_filesize = Some(49);
+    let mut stats = Stats::default();
+    let mut out = Vec::new();
+    assert_eq!(
+      0,
+      search_path(&config, &path, false, &mut out, &mut stats).unwrap()
+    );
+    assert_eq!(1, stats.skipped_large);
+    config.max_filesize = Some(50);
+    assert_eq!(
+      10,
+      search_path(&config, &path, false, &mut out, &mut stats).unwrap()
+    );
+  }
+
+  #[test]
+  fn names_a_single_file_in_json() {
+    let fixture = Fixture::new("json-name", &[]);
+    let path = fixture.write("poem.txt", "frog\n");
+    let mut config = config("frog");
+    config.format = OutputFormat::Json;
+    config.filenames = vec![path.display().to_string()];
+
+    let mut out = Vec::new();
+    run_config(&config, &mut out).unwrap();
+    let record = Value::parse(&String::from_utf8(out).unwrap()).unwrap();
+    assert_eq!(
+      Some(&Value::from(path.display().to_string().as_str())),
+      record.get("file")
+    );
+  }
+
+  #[test]
+  fn names_a_single_file_in_csv_rows() {
+    let fixture = Fixture::new("csv-name", &[]);
+    let path = fixture.write("poem.txt", "toad\nfrog\n");
+    let mut config = config("frog");
+    config.format = OutputFormat::Tsv;
+    config.filenames = vec![path.display().to_string()];
+
+    let mut out = Vec::new();
+    run_config(&config, &mut out).unwrap();
+    let expected = format!(
+      "file\tline\tcolumn\tmatch\ttext\n{}\t2\t1\tfrog\tfrog\n",
+      path.display()
+    );
+    assert_eq!(expected, String::from_utf8(out).unwrap());
+  }
+
+  #[test]
+  #[cfg(feature = "cli")]
+  fn smart_case_follows_the_query() {
+    let parse = |args: &[&str]| {
+      let args = ["minigrep", "--no-config"]
+        .iter()
+        .chain(args)
+        .chain(&["poem.txt"]);
+      match Config::parse(args.map(|arg| arg.to_string()), true) {
+        Ok(Parsed::Search(config)) => *config,
+        _ => panic!("expected a search"),
+      }
+    };
+
+    assert!(!parse(&["--smart-case", "frog"]).case_sensitive);
+    assert!(parse(&["-S", "Frog"]).case_sensitive);
+    assert!(!parse(&["-S", "-i", "Frog"]).case_sensitive);
+    assert!(!parse(&["-i", "-S", "-E", r"\Wfrog"]).case_sensitive);
+    assert!(has_uppercase(&[r"\W".to_string()], Syntax::Literal));
   }
 }
@@ -0,0 +1,65 @@
+//! A cap on how many matching lines a whole search prints, shared by every
+//! thread searching, as `--max-results` sets.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug)]
+pub struct ResultLimit {
+  max: usize,
+  found: AtomicUsize,
+}
+
+impl ResultLimit {
+  pub fn new(max: usize) -> ResultLimit {
+    ResultLimit {
+      max,
+      found: AtomicUsize::new(0),
+    }
+  }
+
+  pub fn max(&self) -> usize {
+    self.max
+  }
+
+  /// Counts one more result, unless there are already `max` of them.
+  pub fn take(&self) -> bool {
+    self
+      .found
+      .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |found| {
+        (found < self.max).then_some(found + 1)
+      })
+      .is_ok()
+  }
+
+  /// Whether there is no room left for another result, so that searching
+  /// can stop.
+  pub fn reached(&self) -> bool {
+    self.found.load(Ordering::Relaxed) >= self.max
+  }
+
+  /// Makes room for `max` results again.
+  pub fn reset(&self) {
+    self.found.store(0, Ordering::Relaxed);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::thread;
+
+  #[test]
+  fn shares_the_limit_between_threads() {
+    let limit = ResultLimit::new(100);
+    let taken: usize = thread::scope(|scope| {
+      let threads: Vec<_> = (0..4)
+        .map(|_| scope.spawn(|| (0..50).filter(|_| limit.take()).count()))
+        .collect();
+      threads.into_iter().map(|t| t.join().unwrap()).sum()
+    });
+    assert_eq!(100, taken);
+    assert!(limit.reached());
+    limit.reset();
+    assert!(!limit.reached());
+  }
+}
@@ -0,0 +1,121 @@
+//! Messages about what a search is doing, such as which files it opens and
+//! why it skips others, written to standard error for `--verbose` and
+//! `--log-level`.
+//!
+//! Nothing is logged unless asked for. Each message is one line naming its
+//! level, as in `minigrep: debug: skipping target: ignored by .gitignore`.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much is logged, each level including the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+  Error = 1,
+  Warn,
+  /// What is searched overall, once per search.
+  Info,
+  /// Every file opened or skipped.
+  Debug,
+  /// How each file is read.
+  Trace,
+}
+
+impl Level {
+  pub fn from_name(name: &str) -> Option<Level> {
+    match name {
+      "error" => Some(Level::Error),
+      "warn" => Some(Level::Warn),
+      "info" => Some(Level::Info),
+      "debug" => Some(Level::Debug),
+      "trace" => Some(Level::Trace),
+      _ => None,
+    }
+  }
+
+  /// The level after `--verbose` was given `times` times, starting at
+  /// `info`.
+  pub fn verbose(times: usize) -> Level {
+    match times {
+      0 | 1 => Level::Info,
+      2 => Level::Debug,
+      _ => Level::Trace,
+    }
+  }
+
+  fn name(self) -> &'static str {
+    match self {
+      Level::Error => "error",
+      Level::Warn => "warn",
+      Level::Info => "info",
+      Level::Debug => "debug",
+      Level::Trace => "trace",
+    }
+  }
+}
+
+// Zero while logging is off
+static LEVEL: AtomicU8 = AtomicU8::new(0);
+
+/// Logs messages up to `level`, or none at all.
+pub fn set_level(level: Option<Level>) {
+  LEVEL.store(level.map_or(0, |level| level as u8), Ordering::Relaxed);
+}
+
+pub fn enabled(level: Level) -> bool {
+  level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+#[doc(hidden)]
+pub fn write(level: Level, message: fmt::Arguments) {
+  if enabled(level) {
+    // A message that cannot be written is not worth failing the search for
+    let _ = writeln!(
+      io::stderr().lock(),
+      "{}: {}: {}",
+      env!("CARGO_PKG_NAME"),
+      level.name(),
+      message
+    );
+  }
+}
+
+macro_rules! info {
+  ($($arg:tt)*) => {
+    $crate::log::write($crate::log::Level::Info, format_args!($($arg)*))
+  };
+}
+
+macro_rules! debug {
+  ($($arg:tt)*) => {
+    $crate::log::write($crate::log::Level::Debug, format_args!($($arg)*))
+  };
+}
+
+macro_rules! trace {
+  ($($arg:tt)*) => {
+    $crate::log::write($crate::log::Level::Trace, format_args!($($arg)*))
+  };
+}
+
+pub(crate) use {debug, info, trace};
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn logs_up_to_the_level() {
+    assert_eq!(Some(Level::Warn), Level::from_name("warn"));
+    assert_eq!(None, Level::from_name("loud"));
+    assert_eq!(Level::Debug, Level::verbose(2));
+    assert_eq!(Level::Trace, Level::verbose(5));
+
+    set_level(Some(Level::Info));
+    assert!(enabled(Level::Error) && enabled(Level::Info));
+    assert!(!enabled(Level::Debug));
+    set_level(None);
+    assert!(!enabled(Level::Error));
+  }
+}
@@ -1,15 +1,163 @@
-use minigrep::Config;
+use minigrep::completions::Shell;
+use minigrep::error::{self, MinigrepError};
+use minigrep::pager::Pager;
+use minigrep::{args, config, Config, Parsed, Summary};
 use std::env;
+use std::io::{self, IsTerminal, Write};
 use std::process;
 
+// Like grep: a match was found, nothing was found, or something went wrong
+const EXIT_MATCH: i32 = 0;
+const EXIT_NO_MATCH: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
 fn main() {
-    let config = Config::new(env::args()).unwrap_or_else(|err| {
-        eprintln!("{}", err);
-        process::exit(1);
-    });
-
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application error: {}", e);
-        process::exit(1);
+    let programname = env::args().next().unwrap_or_else(|| "minigrep".to_string());
+    // Only `completions` and the name of a shell, so that `completions
+    // notes.txt` still searches for the word
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let [command, shell] = args.as_slice() {
+        if let Some(shell) = Shell::from_name(shell).filter(|_| command == "completions") {
+            print!("{}", shell.script());
+            process::exit(EXIT_MATCH);
+        }
+    }
+    let parsed = with_defaults(env::args())
+        .and_then(|args| Config::parse(args, io::stdin().is_terminal()))
+        .unwrap_or_else(|err| {
+            eprintln!("{}: {}", programname, err);
+            if err.is_usage() {
+                eprintln!("{}", args::usage(&programname));
+            }
+            process::exit(EXIT_ERROR);
+        });
+    let mut config = match parsed {
+        Parsed::Search(config) => *config,
+        Parsed::Help => {
+            print!("{}", args::help(&programname));
+            process::exit(EXIT_MATCH);
+        }
+        Parsed::Version => {
+            println!("{}", args::version());
+            process::exit(EXIT_MATCH);
+        }
+    };
+    // Inputs that cannot be read are told about like any other error
+    let name = programname.clone();
+    config.on_error = Some(Box::new(move |path, e| {
+        eprintln!("{}: {}: {}", name, path.display(), error::describe(e));
+    }));
+    minigrep::log::set_level(config.log_level);
+
+    if config.tui {
+        if let Err(e) = minigrep::tui::run(config) {
+            eprintln!("{}: {}", programname, e);
+            process::exit(EXIT_ERROR);
+        }
+        process::exit(EXIT_MATCH);
+    }
+    if config.tail {
+        if let Err(e) = minigrep::tail(config) {
+            eprintln!("{}: {}", programname, e);
+            process::exit(EXIT_ERROR);
+        }
+        process::exit(EXIT_MATCH);
+    }
+    if config.watch {
+        match minigrep::watch(config) {
+            Err(e) if e.is_broken_pipe() => process::exit(EXIT_MATCH),
+            Err(e) => eprintln!("{}: {}", programname, e),
+            Ok(()) => {}
+        }
+        process::exit(EXIT_ERROR);
+    }
+
+    let report = Report {
+        programname: &programname,
+        stats: config.stats,
+        quiet: config.quiet,
+        no_messages: config.no_messages,
+    };
+    let code = if config.pager {
+        let mut pager = Pager::new();
+        let result = match minigrep::run_with(&config, &mut pager) {
+            // The pager only starts once there is a screenful of results, so
+            // quitting it early is no error
+            Err(MinigrepError::Output(_)) if pager.has_quit() => Ok(Summary {
+                matched: true,
+                ..Summary::default()
+            }),
+            result => result,
+        };
+        let code = report.finish(result, &mut pager);
+        let _ = pager.finish();
+        code
+    } else {
+        report.finish(minigrep::run(config), &mut io::stdout())
+    };
+    process::exit(code);
+}
+
+// How the end of a search is told, from the parts of the config it takes
+struct Report<'a> {
+    programname: &'a str,
+    stats: bool,
+    quiet: bool,
+    no_messages: bool,
+}
+
+impl Report<'_> {
+    // Prints the statistics when asked for, or the error, and picks the exit
+    // code
+    fn finish(&self, result: Result<Summary, MinigrepError>, out: &mut dyn Write) -> i32 {
+        match result {
+            Ok(summary) => {
+                if self.stats && !self.quiet {
+                    let _ = write_stats(out, &summary);
+                }
+                exit_code(summary, self.quiet)
+            }
+            // Whatever reads the results, such as `head`, has seen enough
+            // of them, so the search ends there as if it had found them all
+            Err(e) if e.is_broken_pipe() => EXIT_MATCH,
+            // `-s` only silences inputs that cannot be read
+            Err(MinigrepError::Io { .. }) if self.no_messages => EXIT_ERROR,
+            Err(e) => {
+                eprintln!("{}: {}", self.programname, e);
+                EXIT_ERROR
+            }
+        }
+    }
+}
+
+// Puts the defaults from config files and the environment between the
+// program name and the command line, so that the command line overrides them
+fn with_defaults(mut args: env::Args) -> Result<Vec<String>, MinigrepError> {
+    let mut all: Vec<String> = args.next().into_iter().collect();
+    let cli: Vec<String> = args.collect();
+    if config::wanted(&cli) {
+        all.extend(config::file_args()?);
+    }
+    all.extend(config::env_args());
+    all.extend(cli);
+    Ok(all)
+}
+
+fn write_stats(out: &mut dyn Write, summary: &Summary) -> io::Result<()> {
+    writeln!(out)?;
+    for file in &summary.files {
+        write!(out, "{}", file)?;
+    }
+    write!(out, "{}", summary.stats)
+}
+
+fn exit_code(summary: Summary, quiet: bool) -> i32 {
+    // In quiet mode a match settles it, even if some files were unreadable
+    if summary.errors > 0 && !(quiet && summary.matched) {
+        EXIT_ERROR
+    } else if summary.matched {
+        EXIT_MATCH
+    } else {
+        EXIT_NO_MATCH
     }
 }
@@ -0,0 +1,476 @@
+use crate::accents;
+use crate::fold::{CaseFold, Folding};
+use crate::memmem::{self, Finder};
+use crate::regex::Regex;
+use std::cell::RefCell;
+use std::ops::Range;
+
+/// Finds matches within a single line.
+///
+/// Implementing `find` is enough to plug a new kind of pattern into every
+/// search: the other methods are built on top of it, and can be overridden
+/// when a matcher knows a faster way.
+pub trait Matcher {
+  /// The byte range of the leftmost match within `line`, if any.
+  fn find(&self, line: &str) -> Option<Range<usize>>;
+
+  /// The leftmost match starting at or after byte offset `start`.
+  ///
+  /// By default this looks at `line[start..]` alone, which is wrong for
+  /// matchers that care about the text before a match, such as regular
+  /// expressions with `\b`.
+  fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+    self
+      .find(&line[start..])
+      .map(|span| span.start + start..span.end + start)
+  }
+
+  fn is_match(&self, line: &str) -> bool {
+    self.find(line).is_some()
+  }
+
+  /// Every non-overlapping match within `line`, from left to right.
+  fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut start = 0;
+    while start <= line.len() {
+      let span = match self.find_at(line, start) {
+        Some(span) => span,
+        None => break,
+      };
+      // Step past empty matches so that the scan always makes progress
+      start = if span.is_empty() {
+        span.end + line[span.end..].chars().next().map_or(1, char::len_utf8)
+      } else {
+        span.end
+      };
+      spans.push(span);
+    }
+    spans
+  }
+}
+
+impl<M: Matcher + ?Sized> Matcher for &M {
+  fn find(&self, line: &str) -> Option<Range<usize>> {
+    (**self).find(line)
+  }
+
+  fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+    (**self).find_at(line, start)
+  }
+
+  fn is_match(&self, line: &str) -> bool {
+    (**self).is_match(line)
+  }
+
+  fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+    (**self).find_all(line)
+  }
+}
+
+/// Matches a fixed string.
+#[derive(Debug, Clone)]
+pub struct LiteralMatcher {
+  finder: Finder,
+}
+
+impl LiteralMatcher {
+  pub fn new(query: &str) -> LiteralMatcher {
+    LiteralMatcher {
+      finder: Finder::new(query.as_bytes()),
+    }
+  }
+
+  pub fn finder(&self) -> &Finder {
+    &self.finder
+  }
+}
+
+// A match of a UTF-8 needle in a UTF-8 haystack always starts and ends on
+// character boundaries, so byte offsets can be used as they are
+impl Matcher for LiteralMatcher {
+  fn find(&self, line: &str) -> Option<Range<usize>> {
+    let len = self.finder.needle().len();
+    self
+      .finder
+      .find(line.as_bytes())
+      .map(|start| start..start + len)
+  }
+
+  fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+    let len = self.finder.needle().len();
+    if len == 0 {
+      // An empty query matches every line, just without anything to highlight
+      return std::iter::once(0..0).collect();
+    }
+    self
+      .finder
+      .find_iter(line.as_bytes())
+      .map(|start| start..start + len)
+      .collect()
+  }
+}
+
+/// Matches a fixed string once both it and the line are folded, to ignore
+/// case, accents or both. Lines that are all ASCII have their bytes compared
+/// as they are, and only the others are folded into a copy.
+#[derive(Debug, Clone)]
+pub struct FoldingMatcher {
+  folded: Finder,
+  folding: Folding,
+  // For lines that are all ASCII, when the folded query is too
+  ascii: Option<Finder>,
+  // A byte of the folded query that only comes of itself, the rarest, which
+  // a line that is not all ASCII must hold before it is worth folding
+  sure: Option<u8>,
+}
+
+impl FoldingMatcher {
+  pub fn new(query: &str, folding: Folding) -> FoldingMatcher {
+    let folded: String = query.chars().flat_map(|c| folding.fold(c)).collect();
+    // Turkic folding turns the ASCII `I` into `ı`, so bytes can't be
+    // compared on their own
+    let ascii = Some(&folded)
+      .filter(|folded| folded.is_ascii() && folding.case != Some(CaseFold::Turkic))
+      .map(|folded| match folding.case {
+        Some(_) => Finder::ascii_case_insensitive(folded.as_bytes()),
+        None => Finder::new(folded.as_bytes()),
+      });
+    let sure = folded
+      .bytes()
+      .filter(|&b| only_from_ascii(b, folding))
+      .min_by_key(|&b| memmem::rank(b));
+    FoldingMatcher {
+      folded: Finder::new(folded.as_bytes()),
+      folding,
+      ascii,
+      sure,
+    }
+  }
+
+  /// What finds the folded query in lines that are all ASCII, if it can be
+  /// found in them at all.
+  pub fn ascii_finder(&self) -> Option<&Finder> {
+    self.ascii.as_ref()
+  }
+
+  // Folds `line` from byte offset `start` on into `folded`, and notes in
+  // `origins`, if asked to, where in `line` the character each byte of it
+  // came from starts.
+  fn fold_into(
+    &self,
+    line: &str,
+    start: usize,
+    folded: &mut Vec<u8>,
+    mut origins: Option<&mut Vec<usize>>,
+  ) {
+    let turkic = self.folding.case == Some(CaseFold::Turkic);
+    let mut i = start;
+    while i < line.len() {
+      // Most of a line tends to be ASCII all the same, which folds a run at
+      // a time without going through both case mappings
+      let run = line.as_bytes()[i..]
+        .iter()
+        .take_while(|&&b| b.is_ascii() && !(turkic && b == b'I'))
+        .count();
+      let ascii = &line.as_bytes()[i..i + run];
+      match self.folding.case {
+        Some(_) => folded.extend(ascii.iter().map(u8::to_ascii_lowercase)),
+        None => folded.extend_from_slice(ascii),
+      }
+      if let Some(origins) = origins.as_deref_mut() {
+        origins.extend(i..i + run);
+      }
+      i += run;
+      if let Some(c) = line[i..].chars().next() {
+        for c in self.folding.fold(c) {
+          folded.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+        if let Some(origins) = origins.as_deref_mut() {
+          origins.resize(folded.len(), i);
+        }
+        i += c.len_utf8();
+      }
+    }
+  }
+
+  // The matches in `line` from byte offset `start` on, or only the first
+  // unless `all`, found in a folded copy of it. A match only starts where
+  // the folded form of a character does, and one that ends partway through
+  // the folded form of a character takes in the whole character, along with
+  // the combining marks after it when accents are ignored.
+  fn find_folded(&self, line: &str, start: usize, all: bool) -> Vec<Range<usize>> {
+    if let Some(sure) = self.sure {
+      let holds = |&b: &u8| match self.folding.case {
+        Some(_) => b.to_ascii_lowercase() == sure,
+        None => b == sure,
+      };
+      if !line.as_bytes()[start..].iter().any(holds) {
+        return Vec::new();
+      }
+    }
+    FOLDED.with(|folded| {
+      let mut folded = folded.borrow_mut();
+      folded.clear();
+      // Most lines hold no match, and are done with before their origins
+      // are worked out
+      self.fold_into(line, start, &mut folded, None);
+      let first = match self.folded.find(&folded) {
+        Some(found) => found,
+        None => return Vec::new(),
+      };
+      let mut origins = Vec::with_capacity(folded.len());
+      folded.clear();
+      self.fold_into(line, start, &mut folded, Some(&mut origins));
+      let len = self.folded.needle().len();
+      let mut spans = Vec::new();
+      let mut pos = first;
+      while let Some(found) = self.folded.find(&folded[pos..]).map(|i| pos + i) {
+        pos = found + 1;
+        if found > 0 && origins[found - 1] == origins[found] {
+          continue;
+        }
+        let last = origins[found + len - 1];
+        let end = last + line[last..].chars().next().map_or(0, char::len_utf8);
+        let end = end
+          + line[end..]
+            .chars()
+            .take_while(|&c| self.folding.accents && accents::base(c).is_none())
+            .map(char::len_utf8)
+            .sum::<usize>();
+        spans.push(origins[found]..end);
+        if !all {
+          break;
+        }
+        // The next match starts past this one
+        pos = found
+          + len
+          + origins[found + len..]
+            .iter()
+            .take_while(|&&o| o < end)
+            .count();
+      }
+      spans
+    })
+  }
+}
+
+// Whether no character outside ASCII folds to anything holding the ASCII
+// byte `b`. Some fold to a few letters, `ſ` to `s` and `ﬁ` to `fi`, and any
+// letter can come of dropping accents, but nothing else comes of folding.
+fn only_from_ascii(b: u8, folding: Folding) -> bool {
+  b.is_ascii()
+    && (!b.is_ascii_alphabetic()
+      || !folding.accents && !b"afhijklnstwy".contains(&b.to_ascii_lowercase()))
+}
+
+thread_local! {
+  // What lines that are not all ASCII are folded into, kept from one line to
+  // the next
+  static FOLDED: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+impl Matcher for FoldingMatcher {
+  fn find(&self, line: &str) -> Option<Range<usize>> {
+    self.find_at(line, 0)
+  }
+
+  fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+    if self.folded.needle().is_empty() {
+      return Some(start..start);
+    }
+    // Both the query and the line being ASCII, which is most searches, only
+    // their bytes need comparing
+    if line.is_ascii() && self.folding.case != Some(CaseFold::Turkic) {
+      // Nothing ASCII folds to anything else
+      let finder = self.ascii.as_ref()?;
+      let len = finder.needle().len();
+      return finder
+        .find(&line.as_bytes()[start..])
+        .map(|i| start + i..start + i + len);
+    }
+    self.find_folded(line, start, false).pop()
+  }
+
+  fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+    if self.folded.needle().is_empty() {
+      // An empty query matches every line, just without anything to highlight
+      return std::iter::once(0..0).collect();
+    }
+    if line.is_ascii() && self.folding.case != Some(CaseFold::Turkic) {
+      let mut spans = Vec::new();
+      let mut start = 0;
+      while let Some(span) = self.find_at(line, start) {
+        start = span.end;
+        spans.push(span);
+      }
+      return spans;
+    }
+    // Folding the line once for all of them
+    self.find_folded(line, 0, true)
+  }
+}
+
+/// Matches a fixed string regardless of case.
+#[derive(Debug, Clone)]
+pub struct CaseInsensitiveMatcher {
+  inner: FoldingMatcher,
+}
+
+impl CaseInsensitiveMatcher {
+  /// Matches `query` with full case folding.
+  pub fn new(query: &str) -> CaseInsensitiveMatcher {
+    CaseInsensitiveMatcher::with_fold(query, CaseFold::Full)
+  }
+
+  pub fn with_fold(query: &str, mode: CaseFold) -> CaseInsensitiveMatcher {
+    let folding = Folding {
+      case: Some(mode),
+      accents: false,
+    };
+    CaseInsensitiveMatcher {
+      inner: FoldingMatcher::new(query, folding),
+    }
+  }
+
+  pub fn ascii_finder(&self) -> Option<&Finder> {
+    self.inner.ascii_finder()
+  }
+}
+
+impl Matcher for CaseInsensitiveMatcher {
+  fn find(&self, line: &str) -> Option<Range<usize>> {
+    self.inner.find(line)
+  }
+
+  fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+    self.inner.find_at(line, start)
+  }
+
+  fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+    self.inner.find_all(line)
+  }
+}
+
+impl Matcher for Regex {
+  fn find(&self, line: &str) -> Option<Range<usize>> {
+    Regex::find(self, line)
+  }
+
+  fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+    Regex::find_at(self, line, start)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_literals() {
+    let matcher = LiteralMatcher::new("frog");
+
+    assert_eq!(Some(2..6), matcher.find("a frog, a frog"));
+    assert_eq!(vec![2..6, 10..14], matcher.find_all("a frog, a frog"));
+    assert!(!matcher.is_match("a bog"));
+
+    let matcher = CaseInsensitiveMatcher::new("FROG");
+    assert_eq!(Some(3..7), matcher.find("İ frog"));
+    assert_eq!(vec![0..4, 5..9], matcher.find_all("Frog FROG"));
+    // Characters whose lowercase form is longer keep their own length
+    assert_eq!(Some(0..3), CaseInsensitiveMatcher::new("i̇x").find("İx"));
+    assert_eq!(
+      Some(1..4),
+      CaseInsensitiveMatcher::new("k").find("a\u{212A}")
+    );
+    assert_eq!(None, CaseInsensitiveMatcher::new("é").find("e"));
+    // A match starts where the folded form of a character does
+    assert_eq!(
+      vec![0..2, 4..5],
+      CaseInsensitiveMatcher::new("S").find_all("ßés")
+    );
+
+    assert_eq!(
+      Some(0..7),
+      CaseInsensitiveMatcher::new("STRASSE").find("straße")
+    );
+    let simple = CaseInsensitiveMatcher::with_fold("STRASSE", CaseFold::Simple);
+    assert_eq!(None, simple.find("straße"));
+    assert_eq!(Some(6..10), CaseInsensitiveMatcher::new("όσ").find("ΣΟΦΌς"));
+    let turkic = CaseInsensitiveMatcher::with_fold("istanbul", CaseFold::Turkic);
+    assert_eq!(Some(0..9), turkic.find("İSTANBUL"));
+    assert_eq!(None, turkic.find("ISTANBUL"));
+  }
+
+  #[test]
+  fn ignores_accents() {
+    let accents = Folding {
+      case: None,
+      accents: true,
+    };
+    let matcher = FoldingMatcher::new("facade", accents);
+    assert_eq!(Some(4..11), matcher.find("the façade"));
+    assert_eq!(None, matcher.find("the Façade"));
+    // A decomposed `ü` takes its mark along into the match
+    let matcher = FoldingMatcher::new("uber", accents);
+    assert_eq!(Some(0..6), matcher.find("u\u{308}ber"));
+    assert_eq!(vec![0..5, 6..12], matcher.find_all("über u\u{308}ber"));
+    let matcher = FoldingMatcher::new(
+      "ECOLE",
+      Folding {
+        case: Some(CaseFold::Full),
+        ..accents
+      },
+    );
+    assert_eq!(Some(2..8), matcher.find("l'école"));
+  }
+
+  #[test]
+  fn knows_what_only_comes_of_ascii() {
+    let cases = [
+      None,
+      Some(CaseFold::Simple),
+      Some(CaseFold::Full),
+      Some(CaseFold::Turkic),
+    ];
+    for case in cases {
+      for accents in [false, true] {
+        let folding = Folding { case, accents };
+        for c in '\u{80}'..=char::MAX {
+          for folded in folding.fold(c).filter(char::is_ascii) {
+            assert!(
+              !only_from_ascii(folded as u8, folding),
+              "{:?} folds to {:?}",
+              c,
+              folded
+            );
+          }
+        }
+      }
+    }
+    // Only lines holding `!` are folded, and any may hold an `s` as `ſ`
+    let matcher = CaseInsensitiveMatcher::new("SÁFE, FAST!");
+    assert_eq!(Some(0..12), matcher.find("sáfe, fast!"));
+    assert_eq!(None, matcher.find("sáfe, fast."));
+    assert_eq!(Some(3..5), CaseInsensitiveMatcher::new("s").find("ǅ ſ"));
+  }
+
+  #[test]
+  fn builds_on_find() {
+    // Matches runs of digits, relying on the provided methods for the rest
+    struct Digits;
+
+    impl Matcher for Digits {
+      fn find(&self, line: &str) -> Option<Range<usize>> {
+        let start = line.find(|c: char| c.is_ascii_digit())?;
+        let len = line[start..]
+          .find(|c: char| !c.is_ascii_digit())
+          .unwrap_or(line.len() - start);
+        Some(start..start + len)
+      }
+    }
+
+    assert_eq!(vec![4..6, 8..9, 10..13], Digits.find_all("bed 12, 3 456"));
+    assert_eq!(Some(8..9), Digits.find_at("bed 12, 3 456", 7));
+  }
+}
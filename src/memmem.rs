@@ -0,0 +1,252 @@
+//! Substring search for fixed strings.
+//!
+//! Two bytes of the needle that are likely to be rare in text are looked for
+//! at once, sixteen haystack positions at a time with SSE2 on x86_64, and
+//! only the positions where both line up are compared in full. On other
+//! targets the same candidate filter runs one position at a time.
+//!
+//! Ignoring ASCII case only means masking out the bit that tells upper and
+//! lower case letters apart before comparing.
+
+/// Searches for one needle in many haystacks.
+#[derive(Debug, Clone)]
+pub struct Finder {
+  needle: Vec<u8>,
+  // Offsets within the needle of the two bytes used to find candidates
+  rare1: usize,
+  rare2: usize,
+  ignore_ascii_case: bool,
+}
+
+impl Finder {
+  pub fn new(needle: &[u8]) -> Finder {
+    Finder::with_ascii_case(needle, false)
+  }
+
+  /// Finds the needle whatever the case of its ASCII letters.
+  pub fn ascii_case_insensitive(needle: &[u8]) -> Finder {
+    Finder::with_ascii_case(&needle.to_ascii_lowercase(), true)
+  }
+
+  fn with_ascii_case(needle: &[u8], ignore_ascii_case: bool) -> Finder {
+    let mut rare1 = 0;
+    let mut rare2 = 0;
+    for (i, &b) in needle.iter().enumerate().skip(1) {
+      if rank(b) < rank(needle[rare1]) {
+        rare2 = rare1;
+        rare1 = i;
+      } else if rare2 == rare1 || rank(b) < rank(needle[rare2]) {
+        rare2 = i;
+      }
+    }
+    Finder {
+      needle: needle.to_vec(),
+      rare1,
+      rare2,
+      ignore_ascii_case,
+    }
+  }
+
+  // What is or'ed into the haystack byte compared with `needle[i]`
+  fn case_mask(&self, i: usize) -> u8 {
+    if self.ignore_ascii_case && self.needle[i].is_ascii_lowercase() {
+      0x20
+    } else {
+      0
+    }
+  }
+
+  fn is_match_at(&self, haystack: &[u8], i: usize) -> bool {
+    let candidate = &haystack[i..i + self.needle.len()];
+    if self.ignore_ascii_case {
+      candidate.eq_ignore_ascii_case(&self.needle)
+    } else {
+      candidate == &self.needle[..]
+    }
+  }
+
+  pub fn needle(&self) -> &[u8] {
+    &self.needle
+  }
+
+  /// The offset of the first occurrence of the needle in `haystack`.
+  pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+    let n = self.needle.len();
+    if n == 0 {
+      return Some(0);
+    }
+    if haystack.len() < n {
+      return None;
+    }
+    #[cfg(target_arch = "x86_64")]
+    {
+      // SSE2 is part of the x86_64 baseline, so it is always available
+      unsafe { self.find_sse2(haystack) }
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+      self.find_from(haystack, 0)
+    }
+  }
+
+  /// The offsets of every non-overlapping occurrence of the needle.
+  pub fn find_iter<'h>(&'h self, haystack: &'h [u8]) -> impl Iterator<Item = usize> + 'h {
+    let step = self.needle.len().max(1);
+    let mut start = 0;
+    std::iter::from_fn(move || {
+      let found = start + self.find(haystack.get(start..)?)?;
+      start = found + step;
+      Some(found)
+    })
+  }
+
+  // Checks each possible start from `start` on
+  fn find_from(&self, haystack: &[u8], start: usize) -> Option<usize> {
+    let n = self.needle.len();
+    let (b1, b2) = (self.needle[self.rare1], self.needle[self.rare2]);
+    let (m1, m2) = (self.case_mask(self.rare1), self.case_mask(self.rare2));
+    (start..=haystack.len() - n).find(|&i| {
+      haystack[i + self.rare1] | m1 == b1
+        && haystack[i + self.rare2] | m2 == b2
+        && self.is_match_at(haystack, i)
+    })
+  }
+
+  #[cfg(target_arch = "x86_64")]
+  unsafe fn find_sse2(&self, haystack: &[u8]) -> Option<usize> {
+    use std::arch::x86_64::*;
+
+    let n = self.needle.len();
+    let last_start = haystack.len() - n;
+    let v1 = _mm_set1_epi8(self.needle[self.rare1] as i8);
+    let v2 = _mm_set1_epi8(self.needle[self.rare2] as i8);
+    let m1 = _mm_set1_epi8(self.case_mask(self.rare1) as i8);
+    let m2 = _mm_set1_epi8(self.case_mask(self.rare2) as i8);
+    let ptr = haystack.as_ptr();
+    let mut start = 0;
+    // Each round tests the sixteen starts `start..start + 16`, reading at
+    // most up to `last_start + 15 + rare < haystack.len()`
+    while start + 15 <= last_start {
+      let a = _mm_loadu_si128(ptr.add(start + self.rare1) as *const __m128i);
+      let b = _mm_loadu_si128(ptr.add(start + self.rare2) as *const __m128i);
+      let (a, b) = (_mm_or_si128(a, m1), _mm_or_si128(b, m2));
+      let eq = _mm_and_si128(_mm_cmpeq_epi8(a, v1), _mm_cmpeq_epi8(b, v2));
+      let mut mask = _mm_movemask_epi8(eq) as u32;
+      while mask != 0 {
+        let candidate = start + mask.trailing_zeros() as usize;
+        if self.is_match_at(haystack, candidate) {
+          return Some(candidate);
+        }
+        mask &= mask - 1;
+      }
+      start += 16;
+    }
+    self.find_from(haystack, start)
+  }
+}
+
+/// How many times `byte` occurs in `haystack`, such as the number of lines
+/// in a stretch of text.
+pub fn count_byte(haystack: &[u8], byte: u8) -> usize {
+  // Summing each chunk in a `u8` that cannot overflow lets the compiler
+  // compare and add many bytes at a time
+  haystack
+    .chunks(u8::MAX as usize)
+    .map(|chunk| chunk.iter().map(|&b| (b == byte) as u8).sum::<u8>() as usize)
+    .sum()
+}
+
+/// Where `byte` first occurs in `haystack`, such as the end of a line.
+pub fn find_byte(haystack: &[u8], byte: u8) -> Option<usize> {
+  #[cfg(target_arch = "x86_64")]
+  {
+    unsafe { find_byte_sse2(haystack, byte) }
+  }
+  #[cfg(not(target_arch = "x86_64"))]
+  {
+    haystack.iter().position(|&b| b == byte)
+  }
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn find_byte_sse2(haystack: &[u8], byte: u8) -> Option<usize> {
+  use std::arch::x86_64::*;
+
+  let v = _mm_set1_epi8(byte as i8);
+  let ptr = haystack.as_ptr();
+  let mut start = 0;
+  while start + 16 <= haystack.len() {
+    let chunk = _mm_loadu_si128(ptr.add(start) as *const __m128i);
+    let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(chunk, v)) as u32;
+    if mask != 0 {
+      return Some(start + mask.trailing_zeros() as usize);
+    }
+    start += 16;
+  }
+  haystack[start..]
+    .iter()
+    .position(|&b| b == byte)
+    .map(|i| start + i)
+}
+
+// Roughly how common a byte is in source code and prose, lower being rarer
+pub(crate) fn rank(b: u8) -> u8 {
+  match b {
+    b' ' => 255,
+    b'e' | b't' | b'a' | b'o' | b'i' | b'n' | b's' | b'r' => 240,
+    b'a'..=b'z' => 200,
+    b'\t' | b'(' | b')' | b'.' | b',' | b';' | b'_' | b'=' => 180,
+    b'0'..=b'9' => 150,
+    b'A'..=b'Z' => 120,
+    0x80..=0xff => 60,
+    _ => 100,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_needles() {
+    let haystack = "a frog, a bog and a frog on a log by the Frogmore road".as_bytes();
+    let finder = Finder::new(b"frog");
+
+    assert_eq!(Some(2), finder.find(haystack));
+    assert_eq!(vec![2, 20], finder.find_iter(haystack).collect::<Vec<_>>());
+    assert_eq!(Some(41), Finder::new(b"Frogmore").find(haystack));
+    assert_eq!(None, Finder::new(b"toad").find(haystack));
+    assert_eq!(Some(0), Finder::new(b"").find(haystack));
+    assert_eq!(None, finder.find(b"fro"));
+    assert_eq!(2, count_byte(haystack, b'f'));
+    let finder = Finder::ascii_case_insensitive(b"FROGMORE road");
+    assert_eq!(Some(41), finder.find(haystack));
+    assert_eq!(
+      None,
+      Finder::ascii_case_insensitive(b"frog{").find(b"frog[")
+    );
+    assert_eq!(600, count_byte(&[b'\n'; 600], b'\n'));
+    assert_eq!(Some(6), find_byte(haystack, b','));
+    assert_eq!(
+      Some(199),
+      find_byte(&[&[0; 199][..], &[1; 2][..]].concat(), 1)
+    );
+    assert_eq!(None, find_byte(haystack, b'!'));
+  }
+
+  #[test]
+  fn agrees_with_a_naive_search() {
+    let haystack: Vec<u8> = (0..2000u32)
+      .map(|i| b"abcab"[(i * 7 % 5) as usize])
+      .collect();
+    let naive = |haystack: &[u8], needle: &[u8]| {
+      (0..=haystack.len() - needle.len()).find(|&i| haystack[i..].starts_with(needle))
+    };
+    for needle in [&b"b"[..], b"ab", b"cab", b"bcaba", b"aaaa"].iter() {
+      let finder = Finder::new(needle);
+      assert_eq!(naive(&haystack, needle), finder.find(&haystack));
+      let at_end = [&b"xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx"[..], needle].concat();
+      assert_eq!(Some(32), finder.find(&at_end));
+    }
+  }
+}
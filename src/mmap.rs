@@ -0,0 +1,134 @@
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+
+/// A read-only memory map of a whole file.
+///
+/// The contents are paged in by the OS as they are read instead of being
+/// copied through a buffer, which pays off for large files on local disks.
+pub struct Mmap {
+  ptr: *mut u8,
+  len: usize,
+}
+
+// The mapping is private and read-only, so it can be shared between threads
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+  /// Maps the whole of `file` into memory. Empty files cannot be mapped.
+  ///
+  /// # Safety
+  ///
+  /// The file must not be truncated or modified while it is mapped: the
+  /// returned slice would change under the caller, and reading past a new end
+  /// of file raises `SIGBUS`.
+  pub unsafe fn map(file: &File) -> io::Result<Mmap> {
+    let len = file.metadata()?.len();
+    if len == 0 {
+      return Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        "cannot map an empty file",
+      ));
+    }
+    let len = usize::try_from(len)
+      .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file is too large to map"))?;
+    sys::map(file, len).map(|ptr| Mmap { ptr, len })
+  }
+}
+
+impl Deref for Mmap {
+  type Target = [u8];
+
+  fn deref(&self) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+  }
+}
+
+impl Drop for Mmap {
+  fn drop(&mut self) {
+    unsafe { sys::unmap(self.ptr, self.len) }
+  }
+}
+
+#[cfg(unix)]
+mod sys {
+  use std::fs::File;
+  use std::io;
+  use std::os::raw::{c_int, c_void};
+  use std::os::unix::io::AsRawFd;
+
+  const PROT_READ: c_int = 1;
+  const MAP_PRIVATE: c_int = 2;
+
+  extern "C" {
+    fn mmap(
+      addr: *mut c_void,
+      len: usize,
+      prot: c_int,
+      flags: c_int,
+      fd: c_int,
+      offset: i64,
+    ) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+  }
+
+  pub unsafe fn map(file: &File, len: usize) -> io::Result<*mut u8> {
+    let ptr = mmap(
+      std::ptr::null_mut(),
+      len,
+      PROT_READ,
+      MAP_PRIVATE,
+      file.as_raw_fd(),
+      0,
+    );
+    // MAP_FAILED
+    if ptr as isize == -1 {
+      return Err(io::Error::last_os_error());
+    }
+    Ok(ptr as *mut u8)
+  }
+
+  pub unsafe fn unmap(ptr: *mut u8, len: usize) {
+    munmap(ptr as *mut c_void, len);
+  }
+}
+
+#[cfg(not(unix))]
+mod sys {
+  use std::fs::File;
+  use std::io;
+
+  pub unsafe fn map(_file: &File, _len: usize) -> io::Result<*mut u8> {
+    Err(io::Error::new(
+      io::ErrorKind::Other,
+      "memory maps are not supported on this platform",
+    ))
+  }
+
+  pub unsafe fn unmap(_ptr: *mut u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::Fixture;
+  use std::fs;
+
+  #[cfg(unix)]
+  #[test]
+  fn maps_file_contents() {
+    let fixture = Fixture::new("mmap", &[]);
+    let path = fixture.write("poem.txt", "safe, fast, productive.\n");
+    let file = File::open(&path).unwrap();
+
+    let map = unsafe { Mmap::map(&file) }.unwrap();
+    assert_eq!(b"safe, fast, productive.\n", &map[..]);
+    drop(map);
+
+    fs::write(&path, "").unwrap();
+    let empty = File::open(&path).unwrap();
+    assert!(unsafe { Mmap::map(&empty) }.is_err());
+  }
+}
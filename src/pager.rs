@@ -0,0 +1,301 @@
+//! Paging results that do not fit on the screen, as `git` does, through
+//! `$PAGER` or `less`, with `$LESS` set to `FRX` unless it is set already:
+//! the colors are kept, and output that turns out to fit on the screen after
+//! all is printed and left there.
+//!
+//! Output is held back until it fills more lines than the terminal has, so
+//! that a short search prints as it would without a pager, or until it has
+//! waited a second, so that a slow one is not silent. Lines that wrap are
+//! counted once. Once the pager quits, whatever is written after fails
+//! as a closed pipe would, which ends the search.
+
+use crate::log;
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const DEFAULT_PAGER: &str = "less";
+// When the terminal cannot tell
+const DEFAULT_ROWS: usize = 24;
+// How long output is held back for at most, so that a slow search shows
+// what it found in the meantime
+const HOLD: Duration = Duration::from_secs(1);
+
+pub struct Pager {
+  // Shared with the thread that stops holding the output back in time
+  inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+  command: Vec<String>,
+  rows: usize,
+  state: State,
+}
+
+enum State {
+  // Not yet a screenful, which is the `held` output, in `lines` lines,
+  // first written `since`
+  Holding {
+    held: Vec<u8>,
+    lines: usize,
+    since: Option<Instant>,
+  },
+  Paging {
+    child: Child,
+    stdin: ChildStdin,
+  },
+  // The pager could not be started, or `$PAGER` is empty
+  Direct(io::Stdout),
+  Quit,
+}
+
+impl Default for Pager {
+  fn default() -> Pager {
+    Pager::new()
+  }
+}
+
+impl Pager {
+  /// A pager for standard output, which must be a terminal.
+  pub fn new() -> Pager {
+    let command = env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_string());
+    Pager::with(
+      command.split_whitespace().map(String::from).collect(),
+      screen_rows(),
+      HOLD,
+    )
+  }
+
+  fn with(command: Vec<String>, rows: usize, hold: Duration) -> Pager {
+    let holding = !command.is_empty();
+    let state = if holding {
+      State::Holding {
+        held: Vec::new(),
+        lines: 0,
+        since: None,
+      }
+    } else {
+      State::Direct(io::stdout())
+    };
+    let inner = Arc::new(Mutex::new(Inner {
+      command,
+      rows,
+      state,
+    }));
+    if holding {
+      let inner = Arc::clone(&inner);
+      thread::spawn(move || hold_for(&inner, hold));
+    }
+    Pager { inner }
+  }
+
+  /// Whether the pager was quit before all of the output was written to
+  /// it.
+  pub fn has_quit(&self) -> bool {
+    matches!(self.lock().state, State::Quit)
+  }
+
+  /// Prints what was held back, or waits for the pager to be quit.
+  pub fn finish(self) -> io::Result<()> {
+    let state = std::mem::replace(&mut self.lock().state, State::Quit);
+    match state {
+      State::Holding { held, .. } => {
+        let mut stdout = io::stdout();
+        stdout.write_all(&held)?;
+        stdout.flush()
+      }
+      State::Paging { mut child, stdin } => {
+        // The pager only sees the end of its input once this is closed
+        drop(stdin);
+        child.wait().map(|_| ())
+      }
+      State::Direct(mut stdout) => stdout.flush(),
+      State::Quit => Ok(()),
+    }
+  }
+
+  fn lock(&self) -> MutexGuard<'_, Inner> {
+    self.inner.lock().unwrap()
+  }
+}
+
+// Starts the pager with what is held once it has waited `hold`, until the
+// output is no longer held back
+fn hold_for(inner: &Mutex<Inner>, hold: Duration) {
+  let mut wait = hold;
+  loop {
+    thread::sleep(wait);
+    let mut inner = inner.lock().unwrap();
+    wait = match &inner.state {
+      State::Holding {
+        since: Some(since), ..
+      } if since.elapsed() >= hold => {
+        // A pager that fails is told about by the next write
+        let _ = inner.start();
+        return;
+      }
+      State::Holding {
+        since: Some(since), ..
+      } => hold.saturating_sub(since.elapsed()),
+      State::Holding { since: None, .. } => hold,
+      _ => return,
+    };
+  }
+}
+
+impl Inner {
+  // Hands what was held back to the pager, or to standard output if there
+  // is no pager to be had
+  fn start(&mut self) -> io::Result<()> {
+    let held = match &mut self.state {
+      State::Holding { held, .. } => std::mem::take(held),
+      _ => Vec::new(),
+    };
+    let mut command = Command::new(&self.command[0]);
+    command.args(&self.command[1..]).stdin(Stdio::piped());
+    if env::var_os("LESS").is_none() {
+      command.env("LESS", "FRX");
+    }
+    let spawned = command.spawn();
+    self.state = match spawned {
+      Ok(mut child) => {
+        let stdin = child.stdin.take().expect("stdin is piped");
+        State::Paging { child, stdin }
+      }
+      Err(e) => {
+        log::info!("not paging the results: {}: {}", self.command[0], e);
+        State::Direct(io::stdout())
+      }
+    };
+    self.write_all(&held)
+  }
+
+  fn quit(&mut self) {
+    if let State::Paging { mut child, .. } = std::mem::replace(&mut self.state, State::Quit) {
+      let _ = child.wait();
+    }
+  }
+}
+
+impl Write for Inner {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    match &mut self.state {
+      State::Holding { held, lines, since } => {
+        held.extend_from_slice(buf);
+        *lines += buf.iter().filter(|&&b| b == b'\n').count();
+        since.get_or_insert_with(Instant::now);
+        if *lines >= self.rows {
+          self.start()?;
+        }
+        Ok(buf.len())
+      }
+      State::Paging { stdin, .. } => match stdin.write(buf) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+          self.quit();
+          Err(e)
+        }
+        written => written,
+      },
+      State::Direct(stdout) => stdout.write(buf),
+      State::Quit => Err(io::ErrorKind::BrokenPipe.into()),
+    }
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    match &mut self.state {
+      // Holding back is the point, until there is a screenful
+      State::Holding { .. } | State::Quit => Ok(()),
+      State::Paging { stdin, .. } => stdin.flush(),
+      State::Direct(stdout) => stdout.flush(),
+    }
+  }
+}
+
+impl Write for Pager {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.lock().write(buf)
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    self.lock().flush()
+  }
+}
+
+// How many lines the terminal shows, from `$LINES` or `stty`
+fn screen_rows() -> usize {
+  if let Some(rows) = env::var("LINES").ok().and_then(|rows| rows.parse().ok()) {
+    return rows;
+  }
+  File::open("/dev/tty")
+    .and_then(|tty| Command::new("stty").arg("size").stdin(tty).output())
+    .ok()
+    .and_then(|output| {
+      let size = String::from_utf8_lossy(&output.stdout).into_owned();
+      size.split_whitespace().next()?.parse().ok()
+    })
+    .filter(|&rows| rows > 0)
+    .unwrap_or(DEFAULT_ROWS)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::Fixture;
+  use std::fs;
+
+  #[test]
+  fn pages_once_there_is_a_screenful() {
+    let fixture = Fixture::new("pager", &[]);
+    let path = fixture.join("paged.txt");
+    let command = format!("cat > {}", path.display());
+    let hold = Duration::from_secs(60);
+    let mut pager = Pager::with(vec!["sh".into(), "-c".into(), command], 3, hold);
+    write!(pager, "frog\ntoad\n").unwrap();
+    pager.flush().unwrap();
+    assert!(matches!(
+      pager.lock().state,
+      State::Holding { lines: 2, .. }
+    ));
+    write!(pager, "newt\npond\n").unwrap();
+    assert!(matches!(pager.lock().state, State::Paging { .. }));
+    pager.finish().unwrap();
+    assert_eq!(
+      "frog\ntoad\nnewt\npond\n",
+      fs::read_to_string(&path).unwrap()
+    );
+  }
+
+  #[test]
+  fn pages_what_was_held_for_too_long() {
+    let fixture = Fixture::new("pager-held", &[]);
+    let path = fixture.join("paged.txt");
+    let command = format!("cat > {}", path.display());
+    let hold = Duration::from_millis(50);
+    let mut pager = Pager::with(vec!["sh".into(), "-c".into(), command], 30, hold);
+    thread::sleep(2 * hold);
+    assert!(matches!(pager.lock().state, State::Holding { .. }));
+    writeln!(pager, "frog").unwrap();
+    while matches!(pager.lock().state, State::Holding { .. }) {
+      thread::sleep(hold);
+    }
+    writeln!(pager, "toad").unwrap();
+    pager.finish().unwrap();
+    assert_eq!("frog\ntoad\n", fs::read_to_string(&path).unwrap());
+  }
+
+  #[test]
+  fn lets_less_quit_when_it_all_fits() {
+    let fixture = Fixture::new("pager-less", &[]);
+    let path = fixture.join("less.txt");
+    let command = format!("cat > /dev/null; printf %s \"$LESS\" > {}", path.display());
+    let mut pager = Pager::with(vec!["sh".into(), "-c".into(), command], 1, HOLD);
+    write!(pager, "frog\ntoad\n").unwrap();
+    pager.finish().unwrap();
+    let expected = env::var("LESS").unwrap_or_else(|_| "FRX".to_string());
+    assert_eq!(expected, fs::read_to_string(&path).unwrap());
+  }
+}
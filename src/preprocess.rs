@@ -0,0 +1,94 @@
+//! Searching what an external command prints for a file rather than the
+//! file itself, as with `--pre pdftotext`, for formats that are not text.
+//!
+//! The command is run with the path of the file as its only argument and the
+//! file on its standard input, so that it may read either.
+
+use crate::filter::PathFilter;
+use crate::glob::GlobError;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::process::{ChildStdout, Command, Stdio};
+
+/// The command given to `--pre`, and which files it is run for.
+#[derive(Debug, Clone)]
+pub struct Preprocessor {
+  command: String,
+  globs: PathFilter,
+}
+
+impl Preprocessor {
+  pub fn new(command: &str) -> Preprocessor {
+    Preprocessor {
+      command: command.to_string(),
+      globs: PathFilter::new(),
+    }
+  }
+
+  /// Only runs the command for files matching this glob or another one
+  /// given, from `--pre-glob`. Without any, it runs for every file.
+  pub fn glob(&mut self, pattern: &str) -> Result<&mut Preprocessor, GlobError> {
+    self.globs.include(pattern)?;
+    Ok(self)
+  }
+
+  pub fn command(&self) -> &str {
+    &self.command
+  }
+
+  pub fn applies_to(&self, path: &Path) -> bool {
+    self.globs.is_match(path)
+  }
+
+  /// Runs the command for `path` and hands its output to `search`. The
+  /// command failing counts as an error, unless it was only cut short by
+  /// the search no longer reading its output.
+  pub(crate) fn run<T, F>(&self, path: &Path, search: F) -> io::Result<T>
+  where
+    F: FnOnce(BufReader<ChildStdout>) -> io::Result<T>,
+  {
+    let mut child = Command::new(&self.command)
+      .arg(path)
+      .stdin(File::open(path)?)
+      .stdout(Stdio::piped())
+      .spawn()
+      .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", self.command, e)))?;
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let found = search(BufReader::new(stdout));
+    // Whatever is left unread closes the pipe, which ends the command
+    let status = child.wait()?;
+    let found = found?;
+    match status.code() {
+      Some(code) if code != 0 => Err(io::Error::other(format!(
+        "{} exited with status {}",
+        self.command, code
+      ))),
+      _ => Ok(found),
+    }
+  }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+  use super::*;
+  use crate::fixture::Fixture;
+  use std::io::Read;
+
+  #[test]
+  fn searches_the_command_output() {
+    let dir = Fixture::new("pre", &[]);
+    let path = dir.write("frog.txt", "frog\n");
+    let mut pre = Preprocessor::new("rev");
+    pre.glob("*.txt").unwrap();
+
+    assert!(pre.applies_to(&path));
+    assert!(!pre.applies_to(Path::new("frog.pdf")));
+    let mut output = String::new();
+    pre
+      .run(&path, |mut reader| reader.read_to_string(&mut output))
+      .unwrap();
+    assert_eq!("gorf\n", output);
+    assert!(Preprocessor::new("false").run(&path, |_| Ok(())).is_err());
+  }
+}
@@ -0,0 +1,491 @@
+use crate::escape;
+use crate::events;
+use crate::hyperlink;
+use crate::json::MatchRecord;
+use crate::records::Records;
+use crate::sink::Sink;
+use crate::stats::Stats;
+use crate::theme::{self, Style, Theme};
+use crate::{csv, match_groups, Config, Match, OutputFormat, Pattern, Syntax};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Formats the results of searching a single input.
+pub struct Printer<'a, W: Write> {
+  out: &'a mut W,
+  config: &'a Config,
+  path: Option<&'a Path>,
+  theme: &'a Theme,
+  // The file that `path` links to, when links are made
+  target: Option<PathBuf>,
+  // Whether a `begin` message was printed for `--json-lines`
+  begun: bool,
+}
+
+impl<'a, W: Write> Printer<'a, W> {
+  /// Results are labelled with `path` when one is given.
+  pub fn new(out: &'a mut W, config: &'a Config, path: Option<&'a Path>) -> Printer<'a, W> {
+    let colored = config.color.enabled();
+    Printer {
+      out,
+      config,
+      path,
+      theme: if colored {
+        &config.theme
+      } else {
+        &theme::PLAIN
+      },
+      target: path
+        .filter(|_| colored && config.hyperlink_format.is_some())
+        .and_then(hyperlink::target),
+      begun: false,
+    }
+  }
+
+  /// Finishes the results of the input, which only `--json-lines` marks,
+  /// with its statistics.
+  pub fn end(&mut self, stats: &Stats) -> io::Result<()> {
+    if !self.begun {
+      return Ok(());
+    }
+    self.begun = false;
+    writeln!(self.out, "{}", events::end(self.path, stats))
+  }
+
+  // A `match` or `context` message, after a `begin` one for the first line
+  // of the input
+  fn event(&mut self, kind: &str, m: &Match) -> io::Result<()> {
+    if !self.begun {
+      self.begun = true;
+      writeln!(self.out, "{}", events::begin(self.path))?;
+    }
+    // Lines end in what they ended in, or nothing at the end of the input
+    let terminator = match self.config.records {
+      Records::Lines => m.terminator,
+      _ => self.config.records.terminator(),
+    };
+    writeln!(self.out, "{}", events::line(kind, self.path, m, terminator))
+  }
+
+  /// Writes just the name of the input, as listed by `-l`.
+  pub fn filename(&mut self) -> io::Result<()> {
+    match self.path {
+      Some(path) => write!(
+        self.out,
+        "{}{}",
+        self.path_text(path, 1, 1),
+        self.path_terminator('\n')
+      ),
+      None => Ok(()),
+    }
+  }
+
+  pub fn count(&mut self, count: usize) -> io::Result<()> {
+    match self.path {
+      Some(path) => writeln!(
+        self.out,
+        "{}{}{}",
+        self.path_text(path, 1, 1),
+        self.separator_text(':'),
+        count
+      ),
+      None => writeln!(self.out, "{}", count),
+    }
+  }
+
+  /// Writes the sum of the counts of every input, for `--total`.
+  pub fn total(&mut self, total: usize) -> io::Result<()> {
+    writeln!(
+      self.out,
+      "{}{}{}",
+      self.theme.path.paint("total"),
+      self.separator_text(':'),
+      total
+    )
+  }
+
+  // Matching lines use `:` after each prefix field and context lines use `-`
+  fn line(&mut self, m: &Match, separator: char) -> io::Result<()> {
+    if self.config.only_matching {
+      let windows;
+      let mut spans = &m.spans[..];
+      if self.config.syntax == Syntax::Fuzzy && !spans.is_empty() {
+        windows = fuzzy_windows(self.config, m.line);
+        spans = &windows;
+      }
+      // Every non-empty match gets a line of its own
+      for span in spans.iter().filter(|span| !span.is_empty()) {
+        let byte_offset = m.byte_offset + escape::offset(m.line, span.start);
+        self.prefix(m, byte_offset, separator)?;
+        let mut text = m.line[span.clone()].to_string();
+        if let Some(template) = &self.config.replace {
+          text = template.expand(m.line, &match_groups(self.config, m.line, span));
+        }
+        if let Some((escaped, _)) = escape::escape(&text, &[]).filter(|_| self.config.binary) {
+          text = escaped;
+        }
+        let text = format!("{}\n", self.theme.matched.paint(text));
+        self.out.write_all(&escape::encode(&text))?;
+      }
+      return Ok(());
+    }
+    self.prefix(m, m.byte_offset, separator)?;
+    // Lines that ended in `\r\n` are printed with it, as they were
+    let terminator = match m.terminator {
+      "\r\n" => "\r\n",
+      _ => self.config.records.terminator(),
+    };
+    let (mut line, mut spans) = (m.line, &m.spans[..]);
+    let replaced;
+    if let Some(template) = self.config.replace.as_ref().filter(|_| !m.spans.is_empty()) {
+      replaced = template.replace_spans(line, spans, |span| match_groups(self.config, line, span));
+      (line, spans) = (&replaced.0, &replaced.1);
+    }
+    // What a binary input has in it must not reach the terminal as it is
+    let escaped;
+    if let Some(escape) = escape::escape(line, spans).filter(|_| self.config.binary) {
+      escaped = escape;
+      (line, spans) = (&escaped.0, &escaped.1);
+    }
+    let trimmed;
+    if self.config.trim {
+      trimmed = trim(line, spans);
+      (line, spans) = (trimmed.0, &trimmed.1);
+    }
+    let clipped;
+    if let Some(max) = self.config.max_columns {
+      if let Some(clip) = clip(line, spans, max, self.config.max_columns_preview) {
+        clipped = clip;
+        (line, spans) = (&clipped.0, &clipped.1);
+      }
+    }
+    // Bytes that are not UTF-8 go out as they came in
+    let text = if !self.theme.matched.is_plain() && !spans.is_empty() {
+      format!(
+        "{}{}",
+        highlight(line, spans, &self.theme.matched),
+        terminator
+      )
+    } else {
+      format!("{}{}", line, terminator)
+    };
+    self.out.write_all(&escape::encode(&text))
+  }
+
+  // A row for each match in the line, or a single one without a column when
+  // there is nothing to point at, as with `-v`
+  fn rows(&mut self, m: &Match) -> io::Result<()> {
+    let delimiter = self.config.format.delimiter().unwrap_or(',');
+    let file = self
+      .path
+      .map_or(String::new(), |path| path.display().to_string());
+    let line_number = m.line_number.to_string();
+    let line = escape::lossy(m.line);
+    let mut spans = m.spans.iter().filter(|span| !span.is_empty()).peekable();
+    if spans.peek().is_none() {
+      return csv::write_row(self.out, &[&file, &line_number, "", "", &line], delimiter);
+    }
+    for span in spans {
+      let column = (escape::offset(m.line, span.start) + 1).to_string();
+      let text = escape::lossy(&m.line[span.clone()]);
+      let fields = [&file, &line_number, &column, text.as_ref(), &line];
+      csv::write_row(self.out, &fields, delimiter)?;
+    }
+    Ok(())
+  }
+
+  // A line for each match, starting at column 1 when there is nothing to
+  // point at
+  fn vimgrep(&mut self, m: &Match) -> io::Result<()> {
+    let columns: Vec<usize> = m
+      .spans
+      .iter()
+      .filter(|span| !span.is_empty())
+      .map(|span| escape::offset(m.line, span.start) + 1)
+      .collect();
+    let columns = if columns.is_empty() { vec![1] } else { columns };
+    // Columns still count what was trimmed
+    let line = if self.config.trim {
+      trim(m.line, &[]).0
+    } else {
+      m.line
+    };
+    for column in columns {
+      if let Some(path) = self.path {
+        let path = self.path_text(path, m.line_number, column);
+        write!(self.out, "{}:", path)?;
+      }
+      let text = format!(
+        "{}:{}:{}\n",
+        self.theme.line_number.paint(m.line_number),
+        column,
+        line
+      );
+      self.out.write_all(&escape::encode(&text))?;
+    }
+    Ok(())
+  }
+
+  // With `--null`, paths end in a NUL byte so that they may contain anything
+  fn path_terminator(&self, separator: char) -> char {
+    if self.config.null {
+      '\0'
+    } else {
+      separator
+    }
+  }
+
+  // The separator after a path, which `--null` replaces uncolored
+  fn separator_text(&self, separator: char) -> String {
+    if self.config.null {
+      '\0'.to_string()
+    } else {
+      self.theme.separator.paint(separator).to_string()
+    }
+  }
+
+  // The painted path, as a link to the line and column given when links are
+  // made
+  fn path_text(&self, path: &Path, line: usize, column: usize) -> String {
+    let painted = self.theme.path.paint(path.display());
+    match (&self.config.hyperlink_format, &self.target) {
+      (Some(format), Some(target)) => hyperlink::link(&format.url(target, line, column), painted),
+      _ => painted.to_string(),
+    }
+  }
+
+  fn prefix(&mut self, m: &Match, byte_offset: usize, separator: char) -> io::Result<()> {
+    if let Some(path) = self.path {
+      // `-o` prints each match on its own, from where it starts
+      let column = match byte_offset - m.byte_offset {
+        0 => m
+          .spans
+          .first()
+          .map_or(1, |span| escape::offset(m.line, span.start) + 1),
+        start => start + 1,
+      };
+      let path = self.path_text(path, m.line_number, column);
+      write!(self.out, "{}{}", path, self.separator_text(separator))?;
+    }
+    let separator = self.theme.separator.paint(separator);
+    if self.config.line_number {
+      write!(
+        self.out,
+        "{}{}",
+        self.theme.line_number.paint(m.line_number),
+        separator
+      )?;
+    }
+    if self.config.byte_offset {
+      write!(self.out, "{}{}", byte_offset, separator)?;
+    }
+    if let Some(pattern) = m.pattern.filter(|_| self.config.show_pattern) {
+      write!(
+        self.out,
+        "{}{}",
+        self.config.patterns[pattern].as_str(),
+        separator
+      )?;
+    }
+    Ok(())
+  }
+}
+
+impl<W: Write> Sink for Printer<'_, W> {
+  fn matched(&mut self, m: &Match) -> io::Result<bool> {
+    match self.config.format {
+      OutputFormat::Text => self.line(m, ':')?,
+      OutputFormat::Json => {
+        let record = MatchRecord {
+          file: self.path.map(|path| path.display().to_string()),
+          line_number: m.line_number,
+          line: escape::lossy(m.line).into_owned(),
+          matches: m
+            .spans
+            .iter()
+            .map(|span| escape::span(m.line, span))
+            .collect(),
+        };
+        writeln!(self.out, "{}", record)?;
+      }
+      OutputFormat::JsonLines => self.event("match", m)?,
+      OutputFormat::Csv | OutputFormat::Tsv => self.rows(m)?,
+      OutputFormat::Vimgrep => self.vimgrep(m)?,
+    }
+    Ok(true)
+  }
+
+  // Context lines are left out of structured output, other than messages
+  fn context(&mut self, m: &Match) -> io::Result<()> {
+    match self.config.format {
+      OutputFormat::Text => self.line(m, '-'),
+      OutputFormat::JsonLines => self.event("context", m),
+      _ => Ok(()),
+    }
+  }
+
+  fn separator(&mut self) -> io::Result<()> {
+    if self.config.format != OutputFormat::Text {
+      return Ok(());
+    }
+    match &self.config.context_separator {
+      Some(separator) => writeln!(self.out, "{}", self.theme.separator.paint(separator)),
+      None => Ok(()),
+    }
+  }
+
+  fn binary_matched(&mut self) -> io::Result<()> {
+    if self.config.format != OutputFormat::Text {
+      return Ok(());
+    }
+    match self.path {
+      Some(path) => writeln!(self.out, "Binary file {} matches", path.display()),
+      None => writeln!(self.out, "Binary file matches"),
+    }
+  }
+}
+
+/// What goes between the results of two inputs, when there is context
+/// around them: the separator between groups of context, as within one
+/// input.
+pub(crate) fn input_separator(config: &Config) -> Option<String> {
+  if config.format != OutputFormat::Text || !config.shows_context() {
+    return None;
+  }
+  let theme = if config.color.enabled() {
+    &config.theme
+  } else {
+    &theme::PLAIN
+  };
+  let separator = config.context_separator.as_ref()?;
+  Some(format!("{}\n", theme.separator.paint(separator)))
+}
+
+/// Draws each of the (sorted, non-overlapping) `spans` of `line` in
+/// `style`.
+pub fn highlight(line: &str, spans: &[Range<usize>], style: &Style) -> String {
+  let (start, end) = (style.start(), style.end());
+  let mut output = String::with_capacity(line.len() + spans.len() * (start.len() + end.len()));
+  let mut last = 0;
+  for span in spans.iter().filter(|span| !span.is_empty()) {
+    output.push_str(&line[last..span.start]);
+    output.push_str(&start);
+    output.push_str(&line[span.clone()]);
+    output.push_str(end);
+    last = span.end;
+  }
+  output.push_str(&line[last..]);
+  output
+}
+
+// `line` without the spaces and tabs it starts with, and `spans` moved along
+// with it
+// Where each fuzzy pattern matched `line`, from its first character to its
+// last, since the characters alone are no use printed one a line
+fn fuzzy_windows(config: &Config, line: &str) -> Vec<Range<usize>> {
+  let mut windows: Vec<Range<usize>> = config
+    .patterns
+    .iter()
+    .filter_map(|pattern| match pattern {
+      Pattern::Fuzzy(fuzzy) => fuzzy.find_fuzzy(line),
+      _ => None,
+    })
+    .filter_map(|m| Some(m.spans.first()?.start..m.spans.last()?.end))
+    .collect();
+  windows.sort_by_key(|window| window.start);
+  let mut merged: Vec<Range<usize>> = Vec::with_capacity(windows.len());
+  for window in windows {
+    match merged.last_mut() {
+      Some(last) if window.start < last.end => last.end = last.end.max(window.end),
+      _ => merged.push(window),
+    }
+  }
+  merged
+}
+
+fn trim<'l>(line: &'l str, spans: &[Range<usize>]) -> (&'l str, Vec<Range<usize>>) {
+  let trimmed = line.trim_start_matches([' ', '\t']);
+  let cut = line.len() - trimmed.len();
+  let spans = spans
+    .iter()
+    .filter(|span| span.end > cut)
+    .map(|span| span.start.max(cut) - cut..span.end - cut)
+    .collect();
+  (trimmed, spans)
+}
+
+// Cuts a line longer than `max` characters down to that many, marking
+// where it was cut with an ellipsis. What is kept is centered on the first
+// match, or is the start of the line with `preview` or nothing to show.
+fn clip(
+  line: &str,
+  spans: &[Range<usize>],
+  max: usize,
+  preview: bool,
+) -> Option<(String, Vec<Range<usize>>)> {
+  let length = line.chars().count();
+  if length <= max {
+    return None;
+  }
+  let offset = |n: usize| line.char_indices().nth(n).map_or(line.len(), |(i, _)| i);
+  let first = spans
+    .iter()
+    .find(|span| !span.is_empty())
+    .filter(|_| !preview);
+  let skipped = first.map_or(0, |span| {
+    let around = max.saturating_sub(line[span.clone()].chars().count()) / 2;
+    let before = line[..span.start].chars().count();
+    before.saturating_sub(around).min(length - max)
+  });
+  let (start, end) = (offset(skipped), offset(skipped + max));
+  let mut clipped = String::with_capacity(end - start + 2 * ELLIPSIS.len());
+  if start > 0 {
+    clipped.push_str(ELLIPSIS);
+  }
+  let shift = clipped.len();
+  clipped.push_str(&line[start..end]);
+  if end < line.len() {
+    clipped.push_str(ELLIPSIS);
+  }
+  let spans = spans
+    .iter()
+    .filter(|span| span.start < end && span.end > start)
+    .map(|span| span.start.max(start) - start + shift..span.end.min(end) - start + shift)
+    .collect();
+  Some((clipped, spans))
+}
+
+const ELLIPSIS: &str = "…";
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn highlights_every_span() {
+    assert_eq!(
+      "a\x1b[1;31mfoo\x1b[0mb\x1b[1;31mfoo\x1b[0m",
+      highlight("afoobfoo", &[1..4, 5..8], &Theme::default().matched)
+    );
+  }
+
+  #[test]
+  fn trims_leading_whitespace() {
+    assert_eq!(("frog  x", vec![0..4]), trim("\t  frog  x", &[3..7]));
+    assert_eq!(("x", vec![0..1]), trim("  x", &[1..3]));
+  }
+
+  #[test]
+  fn clips_long_lines_around_the_first_match() {
+    let line = "aaaaaaaaaa frog bbbbbbbbbb";
+    let (clipped, spans) = clip(line, &[11..15], 8, false).unwrap();
+    assert_eq!(("…a frog b…", vec![5..9]), (clipped.as_str(), spans));
+    let (clipped, spans) = clip(line, &[11..15], 8, true).unwrap();
+    assert_eq!(("aaaaaaaa…", vec![]), (clipped.as_str(), spans));
+    let (clipped, _) = clip("ééé frog", &[7..11], 4, false).unwrap();
+    assert_eq!("…frog", clipped);
+    assert_eq!(None, clip(line, &[11..15], 26, false));
+  }
+}
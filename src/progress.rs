@@ -0,0 +1,123 @@
+//! A live status line on standard error for searches that take a while.
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a search runs before progress is shown.
+pub const DELAY: Duration = Duration::from_millis(1000);
+/// How often the status line is redrawn once it is shown.
+pub const TICK: Duration = Duration::from_millis(100);
+// Longer paths keep only their end
+const MAX_PATH_LEN: usize = 60;
+const ERASE_LINE: &str = "\r\x1b[K";
+
+/// Counts what a search has gone through so far, and draws it as a single
+/// line that is erased again before any results are written.
+///
+/// Workers update the counts while one thread does all of the drawing.
+#[derive(Debug)]
+pub struct Progress {
+  start: Instant,
+  files: AtomicUsize,
+  matches: AtomicUsize,
+  current: Mutex<PathBuf>,
+  drawn: Mutex<bool>,
+}
+
+impl Default for Progress {
+  fn default() -> Progress {
+    Progress {
+      start: Instant::now(),
+      files: AtomicUsize::new(0),
+      matches: AtomicUsize::new(0),
+      current: Mutex::new(PathBuf::new()),
+      drawn: Mutex::new(false),
+    }
+  }
+}
+
+impl Progress {
+  pub fn new() -> Progress {
+    Progress::default()
+  }
+
+  pub fn searching(&self, path: &Path) {
+    path.clone_into(&mut self.current.lock().unwrap());
+  }
+
+  pub fn searched(&self, matches: usize) {
+    self.files.fetch_add(1, Ordering::Relaxed);
+    self.matches.fetch_add(matches, Ordering::Relaxed);
+  }
+
+  /// Redraws the status line, once the search has run for `DELAY`.
+  pub fn draw<W: Write>(&self, out: &mut W) -> io::Result<()> {
+    if self.start.elapsed() < DELAY {
+      return Ok(());
+    }
+    let line = self.line();
+    *self.drawn.lock().unwrap() = true;
+    write!(out, "{}{}", ERASE_LINE, line)?;
+    out.flush()
+  }
+
+  /// Erases the status line, if it is shown.
+  pub fn clear<W: Write>(&self, out: &mut W) -> io::Result<()> {
+    let mut drawn = self.drawn.lock().unwrap();
+    if *drawn {
+      *drawn = false;
+      write!(out, "{}", ERASE_LINE)?;
+      out.flush()?;
+    }
+    Ok(())
+  }
+
+  fn line(&self) -> String {
+    let path = self.current.lock().unwrap().display().to_string();
+    let chars = path.chars().count();
+    let path = if chars > MAX_PATH_LEN {
+      let tail: String = path.chars().skip(chars - MAX_PATH_LEN + 1).collect();
+      format!("…{}", tail)
+    } else {
+      path
+    };
+    format!(
+      "{} files, {} matches: {}",
+      self.files.load(Ordering::Relaxed),
+      self.matches.load(Ordering::Relaxed),
+      path
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn draws_and_erases_one_line() {
+    let progress = Progress {
+      start: Instant::now() - DELAY,
+      ..Progress::new()
+    };
+    progress.searching(Path::new(&"a/".repeat(40)));
+    progress.searched(3);
+    progress.searched(0);
+
+    let mut out = Vec::new();
+    progress.clear(&mut out).unwrap();
+    assert!(out.is_empty());
+    progress.draw(&mut out).unwrap();
+    progress.clear(&mut out).unwrap();
+    let expected = format!(
+      "{}2 files, 3 matches: …{}{}",
+      ERASE_LINE,
+      "/a".repeat(29) + "/",
+      ERASE_LINE
+    );
+    assert_eq!(expected, String::from_utf8(out).unwrap());
+  }
+}
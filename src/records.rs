@@ -0,0 +1,273 @@
+//! Splitting an input into records, the unit that is matched and printed.
+//!
+//! Records are lines unless `--paragraph`, `--record-separator`,
+//! `--null-data` or `--csv` say otherwise, so that a match can be a whole log
+//! entry, a config block, a path from `find -print0` or a row of a
+//! spreadsheet.
+
+use crate::error::MinigrepError;
+use crate::memmem::{count_byte, find_byte};
+use std::io::{self, BufRead};
+use std::ops::Range;
+
+/// Where one record ends and the next begins.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Records {
+  /// Records end in `\n` or `\r\n`.
+  #[default]
+  Lines,
+  /// Records are separated by one or more blank lines, from `--paragraph`.
+  Paragraphs,
+  /// Records end in a NUL byte, from `--null-data`.
+  Null,
+  /// Records end in a string, from `--record-separator`.
+  Separator(String),
+  /// Records are CSV rows, which end in a line break outside of quotes,
+  /// from `--csv`.
+  Csv,
+}
+
+impl Records {
+  /// Reads the separator given to `--record-separator`, where `\n`, `\r`,
+  /// `\t`, `\0` and `\\` stand for the characters they do in Rust strings.
+  pub fn separator(spec: &str) -> Result<Records, MinigrepError> {
+    let invalid = || MinigrepError::InvalidValue {
+      option: "--record-separator".to_string(),
+      value: spec.to_string(),
+    };
+    let mut separator = String::new();
+    let mut chars = spec.chars();
+    while let Some(c) = chars.next() {
+      if c != '\\' {
+        separator.push(c);
+        continue;
+      }
+      separator.push(match chars.next() {
+        Some('n') => '\n',
+        Some('r') => '\r',
+        Some('t') => '\t',
+        Some('0') => '\0',
+        Some('\\') => '\\',
+        _ => return Err(invalid()),
+      });
+    }
+    match separator.as_str() {
+      "" => Err(invalid()),
+      "\n" => Ok(Records::Lines),
+      "\0" => Ok(Records::Null),
+      _ => Ok(Records::Separator(separator)),
+    }
+  }
+
+  /// The byte each record ends in, when it is a single one.
+  pub fn terminator_byte(&self) -> Option<u8> {
+    match self {
+      Records::Lines => Some(b'\n'),
+      Records::Null => Some(0),
+      // A row may well have line breaks in its quotes
+      Records::Paragraphs | Records::Separator(_) | Records::Csv => None,
+    }
+  }
+
+  /// What is written after each record that is printed.
+  pub fn terminator(&self) -> &str {
+    match self {
+      Records::Lines | Records::Csv => "\n",
+      Records::Paragraphs => "\n\n",
+      Records::Null => "\0",
+      Records::Separator(separator) => separator,
+    }
+  }
+}
+
+/// A record read by `next_record`.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Record {
+  /// The first `len` bytes of what the reader has buffered, which are left
+  /// there until they are consumed.
+  Buffered { len: usize, text: Range<usize> },
+  /// Copied into the buffer, as returned by `read_record`.
+  Copied(Range<usize>),
+}
+
+/// Like `read_record`, but leaves a record that ends within what `reader`
+/// has buffered where it is, for it to be looked at in place and consumed
+/// after. For a memory map that is every record but maybe the last.
+pub(crate) fn next_record<R: BufRead>(
+  reader: &mut R,
+  records: &Records,
+  buffer: &mut Vec<u8>,
+) -> io::Result<Option<Record>> {
+  if let Some(terminator) = records.terminator_byte() {
+    let available = reader.fill_buf()?;
+    if let Some(i) = find_byte(available, terminator) {
+      let end = match records {
+        Records::Lines => line_end(&available[..=i]),
+        _ => i,
+      };
+      return Ok(Some(Record::Buffered {
+        len: i + 1,
+        text: 0..end,
+      }));
+    }
+  }
+  Ok(read_record(reader, records, buffer)?.map(Record::Copied))
+}
+
+/// Reads the next record into `buffer`, which is cleared first, returning
+/// where its text is once the separator around it is left out. Returns
+/// `None` at the end of the input, when only separators were left.
+pub(crate) fn read_record<R: BufRead>(
+  reader: &mut R,
+  records: &Records,
+  buffer: &mut Vec<u8>,
+) -> io::Result<Option<Range<usize>>> {
+  buffer.clear();
+  match records {
+    Records::Lines => {
+      if reader.read_until(b'\n', buffer)? == 0 {
+        return Ok(None);
+      }
+      Ok(Some(0..line_end(buffer)))
+    }
+    Records::Null => {
+      if reader.read_until(0, buffer)? == 0 {
+        return Ok(None);
+      }
+      Ok(Some(0..buffer.strip_suffix(&[0]).unwrap_or(buffer).len()))
+    }
+    Records::Paragraphs => {
+      // Blank lines before a paragraph are skipped, and the first blank line
+      // after it ends it
+      let mut text: Option<Range<usize>> = None;
+      loop {
+        let start = buffer.len();
+        if reader.read_until(b'\n', buffer)? == 0 {
+          return Ok(text);
+        }
+        let end = start + line_end(&buffer[start..]);
+        let blank = buffer[start..end].iter().all(u8::is_ascii_whitespace);
+        match (&mut text, blank) {
+          (Some(_), true) => return Ok(text),
+          (Some(text), false) => text.end = end,
+          (None, false) => text = Some(start..end),
+          (None, true) => {}
+        }
+      }
+    }
+    Records::Csv => {
+      // Quotes come in pairs, even when doubled inside a field, so a row
+      // goes on while there is one left open
+      while reader.read_until(b'\n', buffer)? > 0 {
+        if count_byte(buffer, b'"').is_multiple_of(2) {
+          break;
+        }
+      }
+      if buffer.is_empty() {
+        return Ok(None);
+      }
+      Ok(Some(0..line_end(buffer)))
+    }
+    Records::Separator(separator) => {
+      let separator = separator.as_bytes();
+      let last = separator[separator.len() - 1];
+      while !buffer.ends_with(separator) {
+        if reader.read_until(last, buffer)? == 0 {
+          break;
+        }
+      }
+      if buffer.is_empty() {
+        return Ok(None);
+      }
+      let end = buffer.strip_suffix(separator).unwrap_or(buffer).len();
+      Ok(Some(0..end))
+    }
+  }
+}
+
+// Where a line ends once its `\n` or `\r\n` is left out
+/// The line ending `rest` starts with, as what comes after the text of a
+/// line does: `\n`, `\r\n`, or nothing at the end of the input.
+pub(crate) fn line_ending(rest: &[u8]) -> &'static str {
+  if rest.starts_with(b"\r\n") {
+    "\r\n"
+  } else if rest.starts_with(b"\n") {
+    "\n"
+  } else {
+    ""
+  }
+}
+
+fn line_end(line: &[u8]) -> usize {
+  match line.strip_suffix(b"\n") {
+    Some(line) => line.strip_suffix(b"\r").unwrap_or(line).len(),
+    None => line.len(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn split(records: &Records, input: &str) -> Vec<String> {
+    let mut reader = input.as_bytes();
+    let mut buffer = Vec::new();
+    let mut found = Vec::new();
+    while let Some(text) = read_record(&mut reader, records, &mut buffer).unwrap() {
+      found.push(String::from_utf8(buffer[text].to_vec()).unwrap());
+    }
+    found
+  }
+
+  #[test]
+  fn splits_into_records() {
+    assert_eq!(vec!["a", "b\r"], split(&Records::Lines, "a\r\nb\r"));
+    assert_eq!(vec!["a\nb", "c"], split(&Records::Null, "a\nb\0c"));
+    assert_eq!(
+      vec!["a\nb", "c"],
+      split(&Records::Paragraphs, "\n\na\nb\n\n \r\n\nc\n\n")
+    );
+    assert_eq!(
+      vec!["a,\"b\nc\"", "\"\"\"d\"\"\""],
+      split(&Records::Csv, "a,\"b\nc\"\r\n\"\"\"d\"\"\"\n")
+    );
+    let separator = Records::separator(r"\n--\n").unwrap();
+    assert_eq!(
+      vec!["a\nb", "c", "d-"],
+      split(&separator, "a\nb\n--\nc\n--\nd-")
+    );
+  }
+
+  #[test]
+  fn leaves_records_in_the_buffer() {
+    let mut reader = &b"a\r\nb"[..];
+    let mut buffer = Vec::new();
+    assert_eq!(
+      Some(Record::Buffered { len: 3, text: 0..1 }),
+      next_record(&mut reader, &Records::Lines, &mut buffer).unwrap()
+    );
+    reader.consume(3);
+    // The last record has no end to find, so it is read like any other
+    assert_eq!(
+      Some(Record::Copied(0..1)),
+      next_record(&mut reader, &Records::Lines, &mut buffer).unwrap()
+    );
+    assert_eq!(b"b", &buffer[..]);
+    assert_eq!(
+      None,
+      next_record(&mut reader, &Records::Lines, &mut buffer).unwrap()
+    );
+  }
+
+  #[test]
+  fn reads_separators() {
+    assert_eq!(Records::Lines, Records::separator(r"\n").unwrap());
+    assert_eq!(Records::Null, Records::separator(r"\0").unwrap());
+    assert_eq!(
+      Records::Separator("\t\\".to_string()),
+      Records::separator(r"\t\\").unwrap()
+    );
+    assert!(Records::separator("").is_err());
+    assert!(Records::separator(r"\q").is_err());
+  }
+}
@@ -0,0 +1,1088 @@
+use crate::accents;
+use crate::fold;
+use crate::memmem::Finder;
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+// Counted repetitions are expanded at compile time, so cap them to keep
+// programs like `(a{1000}){1000}` from exhausting memory.
+const MAX_REPEAT: u32 = 1000;
+const MAX_PROGRAM_SIZE: usize = 100_000;
+
+/// A compiled regular expression.
+///
+/// Supports literals, `.`, bracket classes (`[a-z]`, `[^0-9]`) and the POSIX
+/// classes inside them (`[[:alpha:]]`, in ASCII as in the C locale), the `\d`,
+/// `\w` and `\s` shorthands (and their negations), the `^`, `$`, `\b` and
+/// `\B` assertions, groups, alternation and the `*`, `+`, `?` and `{n,m}`
+/// quantifiers, each optionally followed by `?` to make it lazy. Groups are
+/// numbered from 1 in the order they open, unless they start with `?:`. In text of
+/// several lines, `.` does not match a newline, while `^` and `$` also match
+/// right after and before one. With `crlf`, lines end in `\r\n` instead.
+///
+/// Matching is done with a Pike VM, so it runs in time linear to the input
+/// and never backtracks exponentially. The fixed characters every match
+/// starts with, or has in it, are looked for first.
+#[derive(Debug, Clone)]
+pub struct Regex {
+  source: String,
+  prog: Vec<Inst>,
+  groups: usize,
+  // The longest string every match has in it, for finding candidates before
+  // running the program
+  required: Finder,
+  // What every match starts with, for the program to skip ahead to
+  prefix: Finder,
+  // Whether that is all there is to the pattern, which needs no program then
+  literal: bool,
+  case_insensitive: bool,
+  crlf: bool,
+  ignore_accents: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegexError {
+  pub message: String,
+  pub position: usize,
+}
+
+impl fmt::Display for RegexError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{} at position {}", self.message, self.position)
+  }
+}
+
+impl Error for RegexError {}
+
+impl Regex {
+  pub fn new(pattern: &str) -> Result<Regex, RegexError> {
+    Regex::with_case_insensitive(pattern, false)
+  }
+
+  pub fn with_case_insensitive(pattern: &str, case_insensitive: bool) -> Result<Regex, RegexError> {
+    let mut parser = Parser {
+      chars: pattern.chars().collect(),
+      pos: 0,
+      case_insensitive,
+      groups: 0,
+    };
+    let node = parser.parse()?;
+    let required = Finder::new(required_literal(&node).as_bytes());
+    let prefix = prefix_literal(&node);
+    let literal = match &node {
+      Node::Concat(nodes) => nodes.len() == prefix.chars().count(),
+      node => *node == Node::Char(prefix.chars().next().unwrap_or('\0')),
+    };
+    let mut compiler = Compiler { prog: Vec::new() };
+    compiler.compile(&node)?;
+    compiler.push(Inst::Match)?;
+    Ok(Regex {
+      source: pattern.to_string(),
+      prog: compiler.prog,
+      groups: parser.groups,
+      required,
+      prefix: Finder::new(prefix.as_bytes()),
+      literal,
+      case_insensitive,
+      crlf: false,
+      ignore_accents: false,
+    })
+  }
+
+  /// Treats `\r\n` as a line ending: `$` matches before it, and neither `.`
+  /// nor `$` see the `\r` as part of the line.
+  pub fn crlf(mut self, yes: bool) -> Regex {
+    self.crlf = yes;
+    self
+  }
+
+  /// Compares letters without their accents, so that `e` matches `é`, and
+  /// passes over the combining marks in the text.
+  pub fn ignore_accents(mut self, yes: bool) -> Regex {
+    self.ignore_accents = yes;
+    self
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.source
+  }
+
+  /// How many numbered groups the pattern has.
+  pub fn groups(&self) -> usize {
+    self.groups
+  }
+
+  /// A string that every match has in it as it is, byte for byte, when
+  /// there is one. Text without it cannot match, so it can be looked for
+  /// much faster than the pattern itself to rule most of the text out.
+  pub fn required_literal(&self) -> Option<&str> {
+    // Matching then compares more than the bytes
+    let required = self.required.needle();
+    if !self.compares_bytes() || required.is_empty() {
+      return None;
+    }
+    std::str::from_utf8(required).ok()
+  }
+
+  // Whether text only matches characters that are the same as the ones in
+  // the pattern, so that literal parts of it can be looked for byte by byte
+  fn compares_bytes(&self) -> bool {
+    !self.case_insensitive && !self.ignore_accents
+  }
+
+  pub fn is_match(&self, text: &str) -> bool {
+    self.find(text).is_some()
+  }
+
+  pub fn find(&self, text: &str) -> Option<Range<usize>> {
+    self.find_at(text, 0)
+  }
+
+  /// Finds the leftmost match that starts at or after byte offset `start`.
+  pub fn find_at(&self, text: &str, start: usize) -> Option<Range<usize>> {
+    if self.compares_bytes() {
+      let rest = &text.as_bytes()[start..];
+      if self.literal {
+        let len = self.prefix.needle().len();
+        return self.prefix.find(rest).map(|i| start + i..start + i + len);
+      }
+      // Most text has no match at all, which is quickest to rule out
+      self.required.find(rest)?;
+    }
+    let mut current = Threads::new(self.prog.len());
+    let mut next = Threads::new(self.prog.len());
+    let mut matched = None;
+    let mut pos = start;
+
+    loop {
+      if current.is_empty() && matched.is_none() && self.compares_bytes() {
+        // With no thread left, the next match can only start where what
+        // every match starts with is next found
+        match self.prefix.find(&text.as_bytes()[pos..]) {
+          Some(skip) => pos += skip,
+          None => break,
+        }
+      }
+      if matched.is_none() {
+        self.add_thread(&mut current, 0, pos, text, pos);
+      }
+      if current.is_empty() && matched.is_some() {
+        break;
+      }
+
+      let c = text[pos..].chars().next();
+      let next_pos = pos + c.map_or(0, char::len_utf8);
+      for i in 0..current.list.len() {
+        let (pc, thread_start) = current.list[i];
+        match &self.prog[pc] {
+          Inst::Match => {
+            matched = Some(thread_start..pos);
+            // Lower priority threads can never win over this one
+            break;
+          }
+          inst => {
+            if let Some(c) = c {
+              if self.ignore_accents && accents::base(c).is_none() {
+                // A mark is skipped without moving the thread on
+                self.add_thread(&mut next, pc, thread_start, text, next_pos);
+              } else if self.step(inst, c) {
+                self.add_thread(&mut next, pc + 1, thread_start, text, next_pos);
+              }
+            }
+          }
+        }
+      }
+
+      if c.is_none() {
+        break;
+      }
+      std::mem::swap(&mut current, &mut next);
+      next.clear();
+      pos = next_pos;
+    }
+
+    matched
+  }
+
+  /// Finds the match `find_at` does, along with where each numbered group
+  /// matched in it. The whole match comes first, and groups that took no
+  /// part in the match are `None`.
+  pub fn captures_at(&self, text: &str, start: usize) -> Option<Vec<Option<Range<usize>>>> {
+    let mut current = Threads::new(self.prog.len());
+    let mut next = Threads::new(self.prog.len());
+    let mut matched: Option<Slots> = None;
+    let mut pos = start;
+
+    loop {
+      if matched.is_none() {
+        let mut slots = vec![None; 2 * (self.groups + 1)];
+        slots[0] = Some(pos);
+        self.add_capture_thread(&mut current, 0, slots, text, pos);
+      }
+      if current.is_empty() && matched.is_some() {
+        break;
+      }
+
+      let c = text[pos..].chars().next();
+      let next_pos = pos + c.map_or(0, char::len_utf8);
+      for (pc, mut slots) in std::mem::take(&mut current.list) {
+        match &self.prog[pc] {
+          Inst::Match => {
+            slots[1] = Some(pos);
+            matched = Some(slots);
+            break;
+          }
+          inst => {
+            if let Some(c) = c {
+              if self.ignore_accents && accents::base(c).is_none() {
+                self.add_capture_thread(&mut next, pc, slots, text, next_pos);
+              } else if self.step(inst, c) {
+                self.add_capture_thread(&mut next, pc + 1, slots, text, next_pos);
+              }
+            }
+          }
+        }
+      }
+
+      if c.is_none() {
+        break;
+      }
+      std::mem::swap(&mut current, &mut next);
+      next.clear();
+      pos = next_pos;
+    }
+
+    let slots = matched?;
+    Some(
+      slots
+        .chunks(2)
+        .map(|pair| match pair {
+          [Some(start), Some(end)] => Some(*start..*end),
+          _ => None,
+        })
+        .collect(),
+    )
+  }
+
+  fn step(&self, inst: &Inst, c: char) -> bool {
+    if self.ignore_accents {
+      let base = accents::base(c).unwrap_or(c);
+      if let Inst::Char(expected) = inst {
+        return self.step_char(accents::base(*expected).unwrap_or(*expected), base);
+      }
+      if base != c && self.step(inst, base) {
+        return true;
+      }
+    }
+    match inst {
+      Inst::Char(expected) => self.step_char(*expected, c),
+      Inst::Any => c != '\n' && !(self.crlf && c == '\r'),
+      Inst::Class(class) => {
+        class.matches(c)
+          || (self.case_insensitive
+            && (class.matches(simple_lowercase(c))
+              || class.matches(simple_uppercase(c))
+              || class.matches(fold::simple(c))))
+      }
+      _ => false,
+    }
+  }
+
+  fn step_char(&self, expected: char, c: char) -> bool {
+    expected == c || (self.case_insensitive && expected == fold::simple(c))
+  }
+
+  fn add_thread(
+    &self,
+    threads: &mut Threads<usize>,
+    pc: usize,
+    start: usize,
+    text: &str,
+    pos: usize,
+  ) {
+    if threads.contains(pc) {
+      return;
+    }
+    threads.mark(pc);
+    match &self.prog[pc] {
+      Inst::Jmp(target) => self.add_thread(threads, *target, start, text, pos),
+      Inst::Split(first, second) => {
+        self.add_thread(threads, *first, start, text, pos);
+        self.add_thread(threads, *second, start, text, pos);
+      }
+      Inst::Assert(assertion) => {
+        if assertion.holds(text, pos, self.crlf) {
+          self.add_thread(threads, pc + 1, start, text, pos);
+        }
+      }
+      Inst::Save(_) => self.add_thread(threads, pc + 1, start, text, pos),
+      _ => threads.list.push((pc, start)),
+    }
+  }
+
+  // Like `add_thread`, with each thread keeping where its groups are
+  fn add_capture_thread(
+    &self,
+    threads: &mut Threads<Slots>,
+    pc: usize,
+    mut slots: Slots,
+    text: &str,
+    pos: usize,
+  ) {
+    if threads.contains(pc) {
+      return;
+    }
+    threads.mark(pc);
+    match &self.prog[pc] {
+      Inst::Jmp(target) => self.add_capture_thread(threads, *target, slots, text, pos),
+      Inst::Split(first, second) => {
+        self.add_capture_thread(threads, *first, slots.clone(), text, pos);
+        self.add_capture_thread(threads, *second, slots, text, pos);
+      }
+      Inst::Assert(assertion) => {
+        if assertion.holds(text, pos, self.crlf) {
+          self.add_capture_thread(threads, pc + 1, slots, text, pos);
+        }
+      }
+      Inst::Save(slot) => {
+        slots[*slot] = Some(pos);
+        self.add_capture_thread(threads, pc + 1, slots, text, pos);
+      }
+      _ => threads.list.push((pc, slots)),
+    }
+  }
+}
+
+// Where each group of a thread started and ended, two slots a group with
+// the whole match first
+type Slots = Vec<Option<usize>>;
+
+fn simple_lowercase(c: char) -> char {
+  let mut lower = c.to_lowercase();
+  match (lower.next(), lower.next()) {
+    (Some(l), None) => l,
+    _ => c,
+  }
+}
+
+fn simple_uppercase(c: char) -> char {
+  let mut upper = c.to_uppercase();
+  match (upper.next(), upper.next()) {
+    (Some(u), None) => u,
+    _ => c,
+  }
+}
+
+fn is_word_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_'
+}
+
+// The threads at one position, each at an instruction with what it keeps,
+// which is where its match started or where its groups are
+struct Threads<T> {
+  list: Vec<(usize, T)>,
+  seen: Vec<bool>,
+}
+
+impl<T> Threads<T> {
+  fn new(size: usize) -> Threads<T> {
+    Threads {
+      list: Vec::with_capacity(size),
+      seen: vec![false; size],
+    }
+  }
+
+  fn contains(&self, pc: usize) -> bool {
+    self.seen[pc]
+  }
+
+  fn mark(&mut self, pc: usize) {
+    self.seen[pc] = true;
+  }
+
+  fn is_empty(&self) -> bool {
+    self.list.is_empty()
+  }
+
+  fn clear(&mut self) {
+    self.list.clear();
+    for seen in self.seen.iter_mut() {
+      *seen = false;
+    }
+  }
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+  Char(char),
+  Any,
+  Class(Class),
+  Assert(Assertion),
+  Split(usize, usize),
+  Jmp(usize),
+  // Where the thread is goes into one of its group slots
+  Save(usize),
+  Match,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Assertion {
+  StartLine,
+  EndLine,
+  WordBoundary,
+  NotWordBoundary,
+}
+
+impl Assertion {
+  fn holds(self, text: &str, pos: usize, crlf: bool) -> bool {
+    let before = text[..pos].chars().next_back();
+    let after = text[pos..].chars().next();
+    match self {
+      Assertion::StartLine => before.is_none_or(|c| c == '\n'),
+      Assertion::EndLine => {
+        after.is_none_or(|c| c == '\n') || (crlf && text[pos..].starts_with("\r\n"))
+      }
+      Assertion::WordBoundary | Assertion::NotWordBoundary => {
+        let boundary = before.is_some_and(is_word_char) != after.is_some_and(is_word_char);
+        boundary == (self == Assertion::WordBoundary)
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Class {
+  ranges: Vec<(char, char)>,
+  negated: bool,
+}
+
+impl Class {
+  fn matches(&self, c: char) -> bool {
+    let found = self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi);
+    found != self.negated
+  }
+}
+
+const DIGIT: &[(char, char)] = &[('0', '9')];
+const WORD: &[(char, char)] = &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')];
+const SPACE: &[(char, char)] = &[('\t', '\r'), (' ', ' ')];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+  Empty,
+  Char(char),
+  Any,
+  Class(Class),
+  Assert(Assertion),
+  Concat(Vec<Node>),
+  Alternate(Vec<Node>),
+  Group(Box<Node>, usize),
+  Repeat {
+    node: Box<Node>,
+    min: u32,
+    max: Option<u32>,
+    greedy: bool,
+  },
+}
+
+struct Parser {
+  chars: Vec<char>,
+  pos: usize,
+  case_insensitive: bool,
+  // How many numbered groups have opened so far
+  groups: usize,
+}
+
+impl Parser {
+  fn parse(&mut self) -> Result<Node, RegexError> {
+    let node = self.parse_alternate()?;
+    if self.pos < self.chars.len() {
+      // The only way to stop early is an unbalanced closing paren
+      return Err(self.error("unmatched `)`"));
+    }
+    Ok(node)
+  }
+
+  fn error(&self, message: &str) -> RegexError {
+    RegexError {
+      message: message.to_string(),
+      position: self.pos,
+    }
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).copied()
+  }
+
+  fn next(&mut self) -> Option<char> {
+    let c = self.peek();
+    self.pos += 1;
+    c
+  }
+
+  fn eat(&mut self, expected: char) -> bool {
+    if self.peek() == Some(expected) {
+      self.pos += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn parse_alternate(&mut self) -> Result<Node, RegexError> {
+    let mut branches = vec![self.parse_concat()?];
+    while self.eat('|') {
+      branches.push(self.parse_concat()?);
+    }
+    if branches.len() == 1 {
+      Ok(branches.pop().unwrap())
+    } else {
+      Ok(Node::Alternate(branches))
+    }
+  }
+
+  fn parse_concat(&mut self) -> Result<Node, RegexError> {
+    let mut nodes = Vec::new();
+    while let Some(c) = self.peek() {
+      if c == '|' || c == ')' {
+        break;
+      }
+      let atom = self.parse_atom()?;
+      nodes.push(self.parse_repeat(atom)?);
+    }
+    match nodes.len() {
+      0 => Ok(Node::Empty),
+      1 => Ok(nodes.pop().unwrap()),
+      _ => Ok(Node::Concat(nodes)),
+    }
+  }
+
+  fn parse_repeat(&mut self, mut node: Node) -> Result<Node, RegexError> {
+    loop {
+      let (min, max) = match self.peek() {
+        Some('*') => {
+          self.pos += 1;
+          (0, None)
+        }
+        Some('+') => {
+          self.pos += 1;
+          (1, None)
+        }
+        Some('?') => {
+          self.pos += 1;
+          (0, Some(1))
+        }
+        Some('{') => match self.parse_counted()? {
+          Some(bounds) => bounds,
+          None => return Ok(node),
+        },
+        _ => return Ok(node),
+      };
+      let greedy = !self.eat('?');
+      node = Node::Repeat {
+        node: Box::new(node),
+        min,
+        max,
+        greedy,
+      };
+    }
+  }
+
+  // Parses `{n}`, `{n,}` or `{n,m}`. Anything else is left alone so that a
+  // lone `{` can still be matched literally.
+  fn parse_counted(&mut self) -> Result<Option<(u32, Option<u32>)>, RegexError> {
+    let start = self.pos;
+    self.pos += 1;
+    let min = match self.parse_number() {
+      Some(min) => min,
+      None => {
+        self.pos = start;
+        return Ok(None);
+      }
+    };
+    let max = if self.eat(',') {
+      self.parse_number()
+    } else {
+      Some(min)
+    };
+    if !self.eat('}') {
+      self.pos = start;
+      return Ok(None);
+    }
+    if max.is_some_and(|max| max < min) {
+      return Err(self.error("invalid repetition range"));
+    }
+    if max.unwrap_or(min) > MAX_REPEAT {
+      return Err(self.error("repetition count too large"));
+    }
+    Ok(Some((min, max)))
+  }
+
+  fn parse_number(&mut self) -> Option<u32> {
+    let start = self.pos;
+    while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+      self.pos += 1;
+    }
+    let digits: String = self.chars[start..self.pos].iter().collect();
+    digits.parse().ok()
+  }
+
+  fn parse_atom(&mut self) -> Result<Node, RegexError> {
+    let c = self.next().unwrap();
+    match c {
+      '(' => {
+        let numbered = !self.eat('?');
+        if !numbered && !self.eat(':') {
+          return Err(self.error("unsupported group flag"));
+        }
+        let index = if numbered {
+          self.groups += 1;
+          Some(self.groups)
+        } else {
+          None
+        };
+        let node = self.parse_alternate()?;
+        if !self.eat(')') {
+          return Err(self.error("unclosed group"));
+        }
+        Ok(match index {
+          Some(index) => Node::Group(Box::new(node), index),
+          None => node,
+        })
+      }
+      '[' => self.parse_class(),
+      '.' => Ok(Node::Any),
+      '^' => Ok(Node::Assert(Assertion::StartLine)),
+      '$' => Ok(Node::Assert(Assertion::EndLine)),
+      '\\' => self.parse_escape(),
+      '*' | '+' | '?' => {
+        self.pos -= 1;
+        Err(self.error("repetition operator missing expression"))
+      }
+      c => Ok(self.literal(c)),
+    }
+  }
+
+  fn literal(&self, c: char) -> Node {
+    if self.case_insensitive {
+      Node::Char(fold::simple(c))
+    } else {
+      Node::Char(c)
+    }
+  }
+
+  fn parse_escape(&mut self) -> Result<Node, RegexError> {
+    let c = match self.next() {
+      Some(c) => c,
+      None => return Err(self.error("trailing backslash")),
+    };
+    match c {
+      'b' => Ok(Node::Assert(Assertion::WordBoundary)),
+      'B' => Ok(Node::Assert(Assertion::NotWordBoundary)),
+      _ => match shorthand_class(c) {
+        Some(class) => Ok(Node::Class(class)),
+        None => Ok(self.literal(escaped_char(c))),
+      },
+    }
+  }
+
+  fn parse_class(&mut self) -> Result<Node, RegexError> {
+    let negated = self.eat('^');
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+      let c = match self.next() {
+        Some(c) => c,
+        None => return Err(self.error("unclosed character class")),
+      };
+      if c == ']' && !first {
+        break;
+      }
+      first = false;
+
+      if c == '[' && self.peek() == Some(':') {
+        if let Some(class) = self.parse_posix_class()? {
+          ranges.extend_from_slice(class);
+          continue;
+        }
+      }
+
+      let lo = if c == '\\' {
+        let escaped = match self.next() {
+          Some(e) => e,
+          None => return Err(self.error("unclosed character class")),
+        };
+        if let Some(class) = shorthand_class(escaped) {
+          if class.negated {
+            return Err(self.error("negated shorthand inside a character class"));
+          }
+          ranges.extend(class.ranges);
+          continue;
+        }
+        escaped_char(escaped)
+      } else {
+        c
+      };
+
+      if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+        self.pos += 1;
+        let mut hi = self.next().unwrap();
+        if hi == '\\' {
+          hi = match self.next() {
+            Some(e) => escaped_char(e),
+            None => return Err(self.error("unclosed character class")),
+          };
+        }
+        if hi < lo {
+          return Err(self.error("invalid character class range"));
+        }
+        ranges.push((lo, hi));
+      } else {
+        ranges.push((lo, lo));
+      }
+    }
+    Ok(Node::Class(Class { ranges, negated }))
+  }
+  // Parses the name and closing `:]` of a class like `[:alpha:]` inside a
+  // bracket class, once past its `[`. Without the closing `:]`, the `[` is
+  // left to be taken literally.
+  fn parse_posix_class(&mut self) -> Result<Option<&'static [(char, char)]>, RegexError> {
+    let rest = &self.chars[self.pos + 1..];
+    let len = match rest.windows(2).position(|pair| pair == [':', ']']) {
+      Some(len) => len,
+      None => return Ok(None),
+    };
+    let name: String = rest[..len].iter().collect();
+    let ranges = match posix_class(&name) {
+      Some(ranges) => ranges,
+      None => return Err(self.error(&format!("unknown POSIX class `{}`", name))),
+    };
+    self.pos += len + 3;
+    Ok(Some(ranges))
+  }
+}
+
+// The ASCII characters in each POSIX class, as GNU grep has them in the C
+// locale
+fn posix_class(name: &str) -> Option<&'static [(char, char)]> {
+  Some(match name {
+    "alnum" => &[('0', '9'), ('A', 'Z'), ('a', 'z')],
+    "alpha" => &[('A', 'Z'), ('a', 'z')],
+    "blank" => &[('\t', '\t'), (' ', ' ')],
+    "cntrl" => &[('\0', '\x1f'), ('\x7f', '\x7f')],
+    "digit" => DIGIT,
+    "graph" => &[('!', '~')],
+    "lower" => &[('a', 'z')],
+    "print" => &[(' ', '~')],
+    "punct" => &[('!', '/'), (':', '@'), ('[', '`'), ('{', '~')],
+    "space" => SPACE,
+    "upper" => &[('A', 'Z')],
+    "xdigit" => &[('0', '9'), ('A', 'F'), ('a', 'f')],
+    _ => return None,
+  })
+}
+
+// The longest string that every match of `node` has in it. Characters in
+// a row make up one, and otherwise the longest from any part that has to
+// match is taken.
+fn required_literal(node: &Node) -> String {
+  match node {
+    Node::Char(c) => c.to_string(),
+    Node::Group(node, _) => required_literal(node),
+    Node::Repeat { node, min, .. } if *min > 0 => required_literal(node),
+    Node::Concat(nodes) => {
+      let mut longest = String::new();
+      let mut run = String::new();
+      for node in nodes {
+        match node {
+          Node::Char(c) => run.push(*c),
+          // An assertion matches no characters, so those around it are
+          // still next to each other
+          Node::Assert(_) => {}
+          node => {
+            let required = required_literal(node);
+            for found in [std::mem::take(&mut run), required] {
+              if found.len() > longest.len() {
+                longest = found;
+              }
+            }
+          }
+        }
+      }
+      if run.len() > longest.len() {
+        longest = run;
+      }
+      longest
+    }
+    // Any branch may match, and the rest need not match anything fixed
+    _ => String::new(),
+  }
+}
+
+// The characters every match of `node` starts with
+fn prefix_literal(node: &Node) -> String {
+  let nodes = match node {
+    Node::Concat(nodes) => nodes.as_slice(),
+    node => std::slice::from_ref(node),
+  };
+  nodes
+    .iter()
+    // Assertions at the start are checked where the characters are found
+    .skip_while(|node| matches!(node, Node::Assert(_)))
+    .map_while(|node| match node {
+      Node::Char(c) => Some(*c),
+      _ => None,
+    })
+    .collect()
+}
+
+fn shorthand_class(c: char) -> Option<Class> {
+  let (ranges, negated) = match c {
+    'd' => (DIGIT, false),
+    'D' => (DIGIT, true),
+    'w' => (WORD, false),
+    'W' => (WORD, true),
+    's' => (SPACE, false),
+    'S' => (SPACE, true),
+    _ => return None,
+  };
+  Some(Class {
+    ranges: ranges.to_vec(),
+    negated,
+  })
+}
+
+fn escaped_char(c: char) -> char {
+  match c {
+    'n' => '\n',
+    't' => '\t',
+    'r' => '\r',
+    c => c,
+  }
+}
+
+struct Compiler {
+  prog: Vec<Inst>,
+}
+
+impl Compiler {
+  fn push(&mut self, inst: Inst) -> Result<usize, RegexError> {
+    if self.prog.len() >= MAX_PROGRAM_SIZE {
+      return Err(RegexError {
+        message: "pattern too large".to_string(),
+        position: 0,
+      });
+    }
+    self.prog.push(inst);
+    Ok(self.prog.len() - 1)
+  }
+
+  fn compile(&mut self, node: &Node) -> Result<(), RegexError> {
+    match node {
+      Node::Empty => {}
+      Node::Char(c) => {
+        self.push(Inst::Char(*c))?;
+      }
+      Node::Any => {
+        self.push(Inst::Any)?;
+      }
+      Node::Class(class) => {
+        self.push(Inst::Class(class.clone()))?;
+      }
+      Node::Assert(assertion) => {
+        self.push(Inst::Assert(*assertion))?;
+      }
+      Node::Concat(nodes) => {
+        for node in nodes {
+          self.compile(node)?;
+        }
+      }
+      Node::Group(node, index) => {
+        self.push(Inst::Save(2 * index))?;
+        self.compile(node)?;
+        self.push(Inst::Save(2 * index + 1))?;
+      }
+      Node::Alternate(branches) => {
+        let mut jumps = Vec::new();
+        for (i, branch) in branches.iter().enumerate() {
+          if i + 1 == branches.len() {
+            self.compile(branch)?;
+          } else {
+            let split = self.push(Inst::Split(0, 0))?;
+            self.compile(branch)?;
+            jumps.push(self.push(Inst::Jmp(0))?);
+            let next = self.prog.len();
+            self.prog[split] = Inst::Split(split + 1, next);
+          }
+        }
+        let end = self.prog.len();
+        for jump in jumps {
+          self.prog[jump] = Inst::Jmp(end);
+        }
+      }
+      Node::Repeat {
+        node,
+        min,
+        max,
+        greedy,
+      } => {
+        for _ in 0..*min {
+          self.compile(node)?;
+        }
+        match max {
+          None => {
+            let split = self.push(Inst::Split(0, 0))?;
+            self.compile(node)?;
+            self.push(Inst::Jmp(split))?;
+            let end = self.prog.len();
+            self.prog[split] = self.split(split + 1, end, *greedy);
+          }
+          Some(max) => {
+            let mut splits = Vec::new();
+            for _ in *min..*max {
+              splits.push(self.push(Inst::Split(0, 0))?);
+              self.compile(node)?;
+            }
+            let end = self.prog.len();
+            for split in splits {
+              self.prog[split] = self.split(split + 1, end, *greedy);
+            }
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn split(&self, body: usize, exit: usize, greedy: bool) -> Inst {
+    if greedy {
+      Inst::Split(body, exit)
+    } else {
+      Inst::Split(exit, body)
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn find(pattern: &str, text: &str) -> Option<Range<usize>> {
+    Regex::new(pattern).unwrap().find(text)
+  }
+
+  #[test]
+  fn literals_and_classes() {
+    assert_eq!(Some(2..5), find("duc", "a duct"));
+    assert_eq!(Some(0..3), find("[a-c]+", "abcd"));
+    assert_eq!(Some(1..4), find(r"\d{2,3}", "x1234"));
+    assert_eq!(Some(0..2), find("[^a]b", "cbab"));
+    assert_eq!(None, find("a.c", "ac"));
+    assert_eq!(Some(3..8), find("[[:alpha:]]+", "42 frogs!"));
+    assert_eq!(Some(0..3), find("[[:digit:][:space:]]+", "4 2x"));
+    assert_eq!(Some(8..9), find("[^[:alnum:] ]", "42 frogs!"));
+  }
+
+  #[test]
+  fn finds_literals_first() {
+    let literal = |pattern| {
+      Regex::new(pattern)
+        .unwrap()
+        .required_literal()
+        .map(str::to_string)
+    };
+    assert_eq!(Some("line".to_string()), literal("line"));
+    assert_eq!(Some(" error".to_string()), literal(r"\d+ errors?"));
+    assert_eq!(Some("ab".to_string()), literal("x?(ab)+"));
+    assert_eq!(None, literal("foo|bar"));
+    assert_eq!(None, literal("a*"));
+    let re = Regex::with_case_insensitive("line", true).unwrap();
+    assert_eq!(None, re.required_literal());
+    // Matches start at the characters every match starts with, but the
+    // assertions before them still hold
+    assert_eq!(Some(12..16), find(r"\bfrog\b", "frogs and a frog"));
+    assert_eq!(Some(7..11), find(r"^frog", "a frog\nfrog"));
+    assert_eq!(None, find(r"^frog", "a frog"));
+  }
+
+  #[test]
+  fn anchors_and_boundaries() {
+    let re = Regex::new(r"^fn \w+\(").unwrap();
+    assert!(re.is_match("fn search(query: &str)"));
+    assert!(!re.is_match("pub fn search(query: &str)"));
+    assert_eq!(Some(4..8), find(r"\btest\b", "the test is latest"));
+    assert_eq!(Some(3..6), find("end$", "endend"));
+  }
+
+  #[test]
+  fn alternation_is_leftmost_first() {
+    assert_eq!(Some(0..1), find("a|ab", "ab"));
+    assert_eq!(Some(0..2), find("(ab|a)", "ab"));
+    assert_eq!(Some(0..1), find("a+?", "aaa"));
+    assert_eq!(Some(0..3), find("a+", "aaa"));
+  }
+
+  #[test]
+  fn captures_groups() {
+    let re = Regex::new(r"(\w+)@((?:\w+)\.(com|org))?|(x)").unwrap();
+    assert_eq!(4, re.groups());
+    assert_eq!(
+      Some(vec![
+        Some(3..15),
+        Some(3..6),
+        Some(7..15),
+        Some(12..15),
+        None
+      ]),
+      re.captures_at("to bob@mail.com please", 0)
+    );
+    assert_eq!(
+      Some(vec![Some(6..10), Some(6..9), None, None, None]),
+      re.captures_at("x@y.z bob@", 2)
+    );
+    let re = Regex::new("(a|b)+c").unwrap();
+    assert_eq!(
+      Some(vec![Some(0..4), Some(2..3)]),
+      re.captures_at("abbc", 0)
+    );
+    assert_eq!(re.find_at("zabc", 1), re.captures_at("zabc", 1).unwrap()[0]);
+  }
+
+  #[test]
+  fn case_insensitive() {
+    let re = Regex::with_case_insensitive("rUsT[A-Z]", true).unwrap();
+    assert_eq!(Some(1..6), re.find("trusty"));
+  }
+
+  #[test]
+  fn ignores_accents() {
+    let re = Regex::new("fa[cd]ade$").unwrap().ignore_accents(true);
+    assert_eq!(Some(0..7), re.find("façade"));
+    assert_eq!(Some(0..8), re.find("fac\u{327}ade"));
+    let re = Regex::with_case_insensitive("été", true)
+      .unwrap()
+      .ignore_accents(true);
+    assert_eq!(Some(0..3), re.find("ETE"));
+  }
+
+  #[test]
+  fn crlf_line_endings() {
+    let text = "one\r\ntwo\r\n";
+    assert_eq!(None, find("e$", text));
+    let re = Regex::new("e$").unwrap().crlf(true);
+    assert_eq!(Some(2..3), re.find(text));
+    let re = Regex::new("^t.*$").unwrap().crlf(true);
+    assert_eq!(Some(5..8), re.find(text));
+  }
+
+  #[test]
+  fn invalid_patterns() {
+    assert!(Regex::new("(abc").is_err());
+    assert!(Regex::new("abc)").is_err());
+    assert!(Regex::new("*a").is_err());
+    assert!(Regex::new("[a-").is_err());
+    assert!(Regex::new(r"a{3,1}").is_err());
+    assert!(Regex::new("[[:alpah:]]").is_err());
+  }
+}
@@ -0,0 +1,197 @@
+use std::ops::Range;
+
+/// A sed-style replacement for matched text, as given to `--replace`.
+///
+/// `&` and `\0` in the template stand for the whole match, and `\1` to `\9`,
+/// `$1` or `${12}` for what a group of the pattern matched, which is nothing
+/// for groups that took no part in the match. `\&`, `\$` and `\\` give a
+/// literal `&`, `$` and `\`. Everything else is copied as is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+  source: String,
+  parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+  Text(String),
+  // The whole match is group 0
+  Group(usize),
+}
+
+/// Where the groups of a match are in its line, the whole match first, as
+/// `Regex::captures_at` finds them.
+pub type Groups = Vec<Option<Range<usize>>>;
+
+impl Template {
+  pub fn new(template: &str) -> Template {
+    let mut parts = Vec::new();
+    let mut text = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+      let group = match c {
+        '&' => Some(0),
+        '\\' => match chars.next() {
+          Some(digit @ '0'..='9') => digit.to_digit(10).map(|n| n as usize),
+          Some(escaped @ ('&' | '$' | '\\')) => {
+            text.push(escaped);
+            None
+          }
+          Some(other) => {
+            text.push('\\');
+            text.push(other);
+            None
+          }
+          None => {
+            text.push('\\');
+            None
+          }
+        },
+        '$' => {
+          let braced = chars.next_if_eq(&'{').is_some();
+          let mut digits = String::new();
+          while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            digits.push(digit);
+          }
+          match digits.parse() {
+            Ok(n) if !braced || chars.next_if_eq(&'}').is_some() => Some(n),
+            // Not a group after all, so it stays as it was written
+            _ => {
+              text.push('$');
+              if braced {
+                text.push('{');
+              }
+              text.push_str(&digits);
+              None
+            }
+          }
+        }
+        c => {
+          text.push(c);
+          None
+        }
+      };
+      if let Some(group) = group {
+        if !text.is_empty() {
+          parts.push(Part::Text(std::mem::take(&mut text)));
+        }
+        parts.push(Part::Group(group));
+      }
+    }
+    if !text.is_empty() {
+      parts.push(Part::Text(text));
+    }
+    Template {
+      source: template.to_string(),
+      parts,
+    }
+  }
+
+  pub fn as_str(&self) -> &str {
+    &self.source
+  }
+
+  /// The highest group the template refers to, 0 when it only has the whole
+  /// match.
+  pub fn max_group(&self) -> usize {
+    self
+      .parts
+      .iter()
+      .map(|part| match part {
+        Part::Group(group) => *group,
+        Part::Text(_) => 0,
+      })
+      .max()
+      .unwrap_or(0)
+  }
+
+  /// The replacement for a single match, whose `groups` are in `line`.
+  pub fn expand(&self, line: &str, groups: &[Option<Range<usize>>]) -> String {
+    let mut output = String::new();
+    for part in &self.parts {
+      match part {
+        Part::Text(text) => output.push_str(text),
+        Part::Group(group) => {
+          if let Some(Some(span)) = groups.get(*group) {
+            output.push_str(&line[span.clone()]);
+          }
+        }
+      }
+    }
+    output
+  }
+
+  /// Replaces every non-empty span of `line`, `groups` telling where the
+  /// groups of the match at a span are.
+  pub fn replace<G>(&self, line: &str, spans: &[Range<usize>], groups: G) -> String
+  where
+    G: Fn(&Range<usize>) -> Groups,
+  {
+    self.replace_spans(line, spans, groups).0
+  }
+
+  /// Like `replace`, also returning where each replacement ended up in the
+  /// new line.
+  pub fn replace_spans<G>(
+    &self,
+    line: &str,
+    spans: &[Range<usize>],
+    groups: G,
+  ) -> (String, Vec<Range<usize>>)
+  where
+    G: Fn(&Range<usize>) -> Groups,
+  {
+    let mut output = String::with_capacity(line.len());
+    let mut replaced = Vec::with_capacity(spans.len());
+    let mut last = 0;
+    for span in spans.iter().filter(|span| !span.is_empty()) {
+      output.push_str(&line[last..span.start]);
+      let start = output.len();
+      output.push_str(&self.expand(line, &groups(span)));
+      replaced.push(start..output.len());
+      last = span.end;
+    }
+    output.push_str(&line[last..]);
+    (output, replaced)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // Only the whole match, as a literal pattern has it
+  fn whole(span: &Range<usize>) -> Groups {
+    vec![Some(span.clone())]
+  }
+
+  #[test]
+  fn expands_matches() {
+    let template = Template::new("[&] \\& \\\\ \\n");
+
+    assert_eq!("[frog] & \\ \\n", template.expand("frog", &whole(&(0..4))));
+    assert_eq!(0, template.max_group());
+  }
+
+  #[test]
+  fn expands_groups() {
+    let template = Template::new(r"\2-$1 ${1}0 \0 $x ${2 \$1 $3");
+    let groups = vec![Some(0..9), Some(0..4), Some(5..9), None];
+
+    assert_eq!(
+      "toad-frog frog0 frog toad $x ${2 $1 ",
+      template.expand("frog toad", &groups)
+    );
+    assert_eq!(3, template.max_group());
+  }
+
+  #[test]
+  fn replaces_spans() {
+    let template = Template::new("<&>");
+    let (line, spans) = template.replace_spans("a frog, a bog", &[2..6, 10..13], whole);
+
+    assert_eq!("a <frog>, a <bog>", line);
+    assert_eq!(vec![2..8, 12..17], spans);
+    assert_eq!("a frog", template.replace("a frog", &[], whole));
+  }
+}
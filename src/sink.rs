@@ -0,0 +1,32 @@
+use crate::Match;
+use std::io;
+
+/// Receives the lines found by `search_reader` as they come.
+///
+/// Only `matched` has to be implemented, and any
+/// `FnMut(&Match) -> io::Result<bool>` closure will do.
+pub trait Sink {
+  /// Takes a matching line. Returning `false` stops the search.
+  fn matched(&mut self, m: &Match) -> io::Result<bool>;
+
+  /// Takes a line shown around a match.
+  fn context(&mut self, _m: &Match) -> io::Result<()> {
+    Ok(())
+  }
+
+  /// Marks a gap between two non-adjacent groups of context.
+  fn separator(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+
+  /// Stands in for the matches of a binary input, which end the search.
+  fn binary_matched(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+impl<F: FnMut(&Match) -> io::Result<bool>> Sink for F {
+  fn matched(&mut self, m: &Match) -> io::Result<bool> {
+    self(m)
+  }
+}
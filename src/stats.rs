@@ -0,0 +1,209 @@
+use crate::memmem::count_byte;
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Counters collected over a whole search, printed at the end by `--stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Stats {
+  /// Inputs that were read through, including each file inside an archive.
+  pub files_searched: usize,
+  /// Inputs with at least one matching line.
+  pub files_matched: usize,
+  /// Paths left out by globs, file types, hidden file rules or ignore files.
+  /// A directory that was skipped counts once, however much it holds.
+  pub skipped_filtered: usize,
+  /// Files larger than `--max-filesize`, which were not opened.
+  pub skipped_large: usize,
+  /// Inputs that looked binary, so that their matches were not printed.
+  pub skipped_binary: usize,
+  /// Inputs that could not be read.
+  pub skipped_unreadable: usize,
+  /// Lines read, which stops short of the end of an input once reading more
+  /// could not change the results.
+  pub lines_scanned: usize,
+  pub matched_lines: usize,
+  /// Occurrences of the patterns in the matching lines, which are not
+  /// looked for when only the lines are counted, or only whether there are
+  /// any.
+  pub matches: usize,
+  pub elapsed: Duration,
+}
+
+impl Stats {
+  /// Adds the counters of `other`, keeping the elapsed time of `self`.
+  pub fn add(&mut self, other: &Stats) {
+    self.files_searched += other.files_searched;
+    self.files_matched += other.files_matched;
+    self.skipped_filtered += other.skipped_filtered;
+    self.skipped_large += other.skipped_large;
+    self.skipped_binary += other.skipped_binary;
+    self.skipped_unreadable += other.skipped_unreadable;
+    self.lines_scanned += other.lines_scanned;
+    self.matched_lines += other.matched_lines;
+    self.matches += other.matches;
+  }
+
+  pub fn files_skipped(&self) -> usize {
+    self.skipped_filtered + self.skipped_large + self.skipped_binary + self.skipped_unreadable
+  }
+}
+
+impl fmt::Display for Stats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(
+      f,
+      "{} searched",
+      counted(self.files_searched, "file", "files")
+    )?;
+    writeln!(
+      f,
+      "{} skipped ({} filtered out, {} too large, {} binary, {} unreadable)",
+      counted(self.files_skipped(), "file", "files"),
+      self.skipped_filtered,
+      self.skipped_large,
+      self.skipped_binary,
+      self.skipped_unreadable
+    )?;
+    writeln!(
+      f,
+      "{} scanned",
+      counted(self.lines_scanned, "line", "lines")
+    )?;
+    writeln!(
+      f,
+      "{}",
+      counted(self.matched_lines, "matching line", "matching lines")
+    )?;
+    writeln!(f, "{:.3} seconds", self.elapsed.as_secs_f64())
+  }
+}
+
+/// The statistics of a single input, printed by `--stats --per-file`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStats {
+  pub path: PathBuf,
+  pub stats: Stats,
+}
+
+impl fmt::Display for FileStats {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(
+      f,
+      "{}: {}, {}, {:.3} seconds",
+      self.path.display(),
+      counted(self.stats.matched_lines, "matching line", "matching lines"),
+      counted(self.stats.matches, "match", "matches"),
+      self.stats.elapsed.as_secs_f64()
+    )
+  }
+}
+
+// `n` things, named `one` or `many` depending on how many there are
+fn counted(n: usize, one: &str, many: &str) -> String {
+  format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+// Counts the lines taken from the reader it wraps
+pub(crate) struct LineCounter<R> {
+  inner: R,
+  newlines: usize,
+  // Whether bytes were read since the last newline
+  partial: bool,
+}
+
+impl<R: BufRead> LineCounter<R> {
+  pub(crate) fn new(inner: R) -> LineCounter<R> {
+    LineCounter {
+      inner,
+      newlines: 0,
+      partial: false,
+    }
+  }
+
+  // A last line without a newline counts too
+  pub(crate) fn lines_read(&self) -> usize {
+    self.newlines + self.partial as usize
+  }
+}
+
+fn count(bytes: &[u8], newlines: &mut usize, partial: &mut bool) {
+  if let Some(&last) = bytes.last() {
+    *newlines += count_byte(bytes, b'\n');
+    *partial = last != b'\n';
+  }
+}
+
+impl<R: BufRead> Read for LineCounter<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    let n = self.inner.read(buf)?;
+    count(&buf[..n], &mut self.newlines, &mut self.partial);
+    Ok(n)
+  }
+}
+
+impl<R: BufRead> BufRead for LineCounter<R> {
+  fn fill_buf(&mut self) -> io::Result<&[u8]> {
+    self.inner.fill_buf()
+  }
+
+  fn consume(&mut self, amt: usize) {
+    // The buffer is still filled, so this does not read anything
+    if let Ok(buf) = self.inner.fill_buf() {
+      let consumed = &buf[..amt.min(buf.len())];
+      count(consumed, &mut self.newlines, &mut self.partial);
+    }
+    self.inner.consume(amt);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn counts_lines_read() {
+    let mut counter = LineCounter::new("one\ntwo\nthree".as_bytes());
+    let mut line = String::new();
+    counter.read_line(&mut line).unwrap();
+    assert_eq!(1, counter.lines_read());
+    counter.read_to_string(&mut line).unwrap();
+    assert_eq!(3, counter.lines_read());
+
+    let mut stats = Stats {
+      skipped_binary: 1,
+      ..Stats::default()
+    };
+    stats.add(&Stats {
+      skipped_filtered: 2,
+      skipped_large: 1,
+      lines_scanned: 3,
+      ..Stats::default()
+    });
+    assert_eq!(4, stats.files_skipped());
+    assert_eq!(3, stats.lines_scanned);
+  }
+
+  #[test]
+  fn counts_in_the_singular_and_the_plural() {
+    let stats = Stats {
+      files_searched: 1,
+      lines_scanned: 2,
+      matched_lines: 1,
+      matches: 1,
+      ..Stats::default()
+    };
+    let printed = stats.to_string();
+    assert!(printed.starts_with("1 file searched\n0 files skipped"));
+    assert!(printed.contains("\n2 lines scanned\n1 matching line\n"));
+
+    let file = FileStats {
+      path: PathBuf::from("poem.txt"),
+      stats,
+    };
+    assert!(file
+      .to_string()
+      .starts_with("poem.txt: 1 matching line, 1 match, "));
+  }
+}
@@ -0,0 +1,221 @@
+//! Reading files as they grow, for `--tail`, the way `tail -F` does.
+//!
+//! A file is read to its end like any other, and then looked at again on
+//! each poll for what was written since. A file that got shorter was
+//! truncated and is read again from its start, and so is a new file that
+//! took the name of the old one, as logs do when they are rotated. Either
+//! way, reading ends there for a new search to start on what follows.
+
+use crate::log;
+use crate::watch::POLL_INTERVAL;
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// A file read as it grows, which only ends when it starts over.
+pub struct Follow {
+  path: PathBuf,
+  file: File,
+  position: u64,
+  id: Option<FileId>,
+  restarted: bool,
+  stop: Option<Arc<AtomicBool>>,
+}
+
+impl Follow {
+  pub fn open(path: &Path) -> io::Result<Follow> {
+    let file = File::open(path)?;
+    let id = file_id(&file.metadata()?);
+    Ok(Follow {
+      path: path.to_path_buf(),
+      file,
+      position: 0,
+      id,
+      restarted: false,
+      stop: None,
+    })
+  }
+
+  /// Ends reading, as if the file was over, once `stop` is set.
+  pub fn until(self, stop: Arc<AtomicBool>) -> Follow {
+    Follow {
+      stop: Some(stop),
+      ..self
+    }
+  }
+
+  fn stopped(&self) -> bool {
+    self
+      .stop
+      .as_ref()
+      .is_some_and(|stop| stop.load(Ordering::Relaxed))
+  }
+
+  /// Whether reading ended because the file was truncated or replaced,
+  /// and may go on from its start.
+  pub fn restarted(&mut self) -> bool {
+    std::mem::take(&mut self.restarted)
+  }
+
+  // Waits until there is more to read, the file starts over, or following
+  // it stops
+  fn wait(&mut self) -> io::Result<()> {
+    loop {
+      thread::sleep(POLL_INTERVAL);
+      if self.stopped() {
+        return Ok(());
+      }
+      // A rotated file may be missing for a moment before the new one is
+      // created
+      let metadata = match fs::metadata(&self.path) {
+        Ok(metadata) => metadata,
+        Err(_) => continue,
+      };
+      if file_id(&metadata) != self.id {
+        log::info!(
+          "{} was replaced, reading it from the start",
+          self.path.display()
+        );
+        *self = Follow {
+          stop: self.stop.take(),
+          ..Follow::open(&self.path)?
+        };
+        self.restarted = true;
+        return Ok(());
+      }
+      if metadata.len() < self.position {
+        log::info!(
+          "{} was truncated, reading it from the start",
+          self.path.display()
+        );
+        self.position = self.file.seek(SeekFrom::Start(0))?;
+        self.restarted = true;
+        return Ok(());
+      }
+      if metadata.len() > self.position {
+        return Ok(());
+      }
+    }
+  }
+}
+
+impl Read for Follow {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    // Reading ends at the restart for as long as no one has seen it, since
+    // a reader may well look twice for the end
+    if buf.is_empty() || self.restarted || self.stopped() {
+      return Ok(0);
+    }
+    loop {
+      let read = self.file.read(buf)?;
+      if read > 0 {
+        self.position += read as u64;
+        return Ok(read);
+      }
+      self.wait()?;
+      if self.restarted || self.stopped() {
+        return Ok(0);
+      }
+    }
+  }
+}
+
+/// Hands whole lines to an output that several files are followed into,
+/// so that lines from different files never run into one another. Each is
+/// written as soon as it is complete, for someone to be watching.
+pub struct Lines<'a, W> {
+  out: &'a Mutex<W>,
+  pending: Vec<u8>,
+}
+
+impl<'a, W: Write> Lines<'a, W> {
+  pub fn new(out: &'a Mutex<W>) -> Lines<'a, W> {
+    Lines {
+      out,
+      pending: Vec::new(),
+    }
+  }
+}
+
+impl<W: Write> Write for Lines<'_, W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.pending.extend_from_slice(buf);
+    if let Some(end) = self.pending.iter().rposition(|&b| b == b'\n') {
+      let mut out = self.out.lock().unwrap();
+      out.write_all(&self.pending[..=end])?;
+      out.flush()?;
+      self.pending.drain(..=end);
+    }
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    // A line still being written goes out once it ends
+    Ok(())
+  }
+}
+
+// What tells a file from another that later took its name
+#[cfg(unix)]
+type FileId = (u64, u64);
+#[cfg(not(unix))]
+type FileId = ();
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<FileId> {
+  use std::os::unix::fs::MetadataExt;
+  Some((metadata.dev(), metadata.ino()))
+}
+
+// Without inode numbers only truncation can be told
+#[cfg(not(unix))]
+fn file_id(_: &Metadata) -> Option<FileId> {
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::Fixture;
+  use std::io::{BufRead, BufReader};
+
+  #[test]
+  fn reads_what_is_appended_and_starts_over_after_truncation() {
+    let fixture = Fixture::new("tail", &[]);
+    let path = fixture.write("log.txt", "one\n");
+    let mut follow = Follow::open(&path).unwrap();
+    let mut lines = BufReader::new(&mut follow).lines();
+    assert_eq!("one", lines.next().unwrap().unwrap());
+
+    let writer = {
+      let path = path.clone();
+      thread::spawn(move || {
+        thread::sleep(POLL_INTERVAL);
+        fs::OpenOptions::new()
+          .append(true)
+          .open(&path)
+          .unwrap()
+          .write_all(b"tw")
+          .unwrap();
+        thread::sleep(POLL_INTERVAL * 2);
+        fs::OpenOptions::new()
+          .append(true)
+          .open(&path)
+          .unwrap()
+          .write_all(b"o\n")
+          .unwrap();
+        thread::sleep(POLL_INTERVAL * 2);
+        fs::write(&path, "new\n").unwrap();
+      })
+    };
+    assert_eq!("two", lines.next().unwrap().unwrap());
+    assert!(lines.next().is_none());
+    assert!(follow.restarted());
+    let mut lines = BufReader::new(&mut follow).lines();
+    assert_eq!("new", lines.next().unwrap().unwrap());
+    writer.join().unwrap();
+  }
+}
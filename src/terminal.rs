@@ -0,0 +1,87 @@
+//! What the place output goes to can show, so that escape codes are only
+//! written where they turn into colors rather than noise.
+//!
+//! Colors are left out when piping, when `TERM` is `dumb`, and when the
+//! `NO_COLOR` environment variable is set to anything, following
+//! <https://no-color.org>. `--color=always` still turns them on. File names
+//! only become links in terminals known to support them.
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, IsTerminal};
+
+/// Whether results written to standard output should be colored unless
+/// asked otherwise.
+pub fn stdout_wants_color() -> bool {
+  wants_color(
+    io::stdout().is_terminal(),
+    env::var_os("TERM").as_deref(),
+    env::var_os("NO_COLOR").as_deref(),
+  )
+}
+
+/// Whether standard error can redraw a status line, which takes a terminal
+/// that understands escape codes.
+pub fn stderr_supports_escapes() -> bool {
+  supports_escapes(io::stderr().is_terminal(), env::var_os("TERM").as_deref())
+}
+
+/// Whether standard output is a terminal that turns OSC 8 escape codes into
+/// links, which the environment of the few that do gives away.
+pub fn stdout_supports_hyperlinks() -> bool {
+  supports_escapes(io::stdout().is_terminal(), env::var_os("TERM").as_deref())
+    && known_for_hyperlinks(|name| env::var_os(name))
+}
+
+fn known_for_hyperlinks<F: Fn(&str) -> Option<OsString>>(var: F) -> bool {
+  let one_of =
+    |name, values: &[&str]| var(name).is_some_and(|value| values.iter().any(|v| value == *v));
+  one_of("TERM_PROGRAM", &["iTerm.app", "WezTerm", "vscode", "ghostty"])
+    || one_of("TERM", &["xterm-kitty", "xterm-ghostty", "wezterm", "foot", "alacritty"])
+    || var("KITTY_WINDOW_ID").is_some()
+    || var("WT_SESSION").is_some()
+    // GNOME Terminal and the others built on VTE have had them since 0.50
+    || var("VTE_VERSION")
+      .and_then(|version| version.to_str()?.parse::<u32>().ok())
+      .is_some_and(|version| version >= 5000)
+}
+
+fn supports_escapes(is_terminal: bool, term: Option<&OsStr>) -> bool {
+  is_terminal && term != Some(OsStr::new("dumb"))
+}
+
+fn wants_color(is_terminal: bool, term: Option<&OsStr>, no_color: Option<&OsStr>) -> bool {
+  supports_escapes(is_terminal, term) && no_color.is_none_or(OsStr::is_empty)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn colors_only_capable_terminals() {
+    let os = |s| Some(OsStr::new(s));
+    assert!(wants_color(true, os("xterm-256color"), None));
+    assert!(wants_color(true, None, os("")));
+    assert!(!wants_color(false, os("xterm"), None));
+    assert!(!wants_color(true, os("dumb"), None));
+    assert!(!wants_color(true, os("xterm"), os("1")));
+    assert!(supports_escapes(true, os("xterm")));
+  }
+
+  #[test]
+  fn knows_which_terminals_link() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+      move |name: &str| {
+        vars
+          .iter()
+          .find(|(var, _)| *var == name)
+          .map(|(_, value)| OsString::from(value))
+      }
+    };
+    assert!(known_for_hyperlinks(env(&[("TERM_PROGRAM", "iTerm.app")])));
+    assert!(known_for_hyperlinks(env(&[("VTE_VERSION", "7600")])));
+    assert!(!known_for_hyperlinks(env(&[("VTE_VERSION", "4600")])));
+    assert!(!known_for_hyperlinks(env(&[("TERM", "xterm-256color")])));
+  }
+}
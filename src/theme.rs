@@ -0,0 +1,206 @@
+//! The colors used for each part of the output, and how they turn into ANSI
+//! escape codes.
+//!
+//! A style is a list of words, such as `bold red` or `underline bright-blue
+//! on black`: attributes, a foreground color, and a background color after
+//! `on`. Colors are one of the eight ANSI names, optionally prefixed with
+//! `bright-`, or a number from the 256 color palette. `none` clears a style.
+
+use crate::error::MinigrepError;
+use std::fmt;
+
+const RESET: &str = "\x1b[0m";
+const COLORS: [&str; 8] = [
+  "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// How one part of the output is drawn. The default style leaves text as
+/// it is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Style {
+  // The SGR parameters, such as `1;31`
+  codes: String,
+}
+
+impl Style {
+  const PLAIN: Style = Style {
+    codes: String::new(),
+  };
+
+  pub fn parse(spec: &str) -> Result<Style, String> {
+    let mut codes: Vec<String> = Vec::new();
+    let mut words = spec.split_whitespace();
+    while let Some(word) = words.next() {
+      let word = word.to_ascii_lowercase();
+      let code = match word.as_str() {
+        "none" => {
+          codes.clear();
+          continue;
+        }
+        "bold" => "1".to_string(),
+        "dim" => "2".to_string(),
+        "italic" => "3".to_string(),
+        "underline" => "4".to_string(),
+        "reverse" => "7".to_string(),
+        "on" => {
+          let color = words.next().ok_or("expected a color after `on`")?;
+          color_code(color, true)?
+        }
+        color => color_code(color, false)?,
+      };
+      codes.push(code);
+    }
+    Ok(Style {
+      codes: codes.join(";"),
+    })
+  }
+
+  pub fn is_plain(&self) -> bool {
+    self.codes.is_empty()
+  }
+
+  /// Wraps `text` in this style, or leaves it be when the style is plain.
+  pub fn paint<T: fmt::Display>(&self, text: T) -> Painted<'_, T> {
+    Painted { style: self, text }
+  }
+
+  pub fn start(&self) -> String {
+    if self.is_plain() {
+      String::new()
+    } else {
+      format!("\x1b[{}m", self.codes)
+    }
+  }
+
+  pub fn end(&self) -> &'static str {
+    if self.is_plain() {
+      ""
+    } else {
+      RESET
+    }
+  }
+}
+
+/// Text in a style, as written by `Display`.
+pub struct Painted<'a, T> {
+  style: &'a Style,
+  text: T,
+}
+
+impl<T: fmt::Display> fmt::Display for Painted<'_, T> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}{}{}", self.style.start(), self.text, self.style.end())
+  }
+}
+
+fn color_code(color: &str, background: bool) -> Result<String, String> {
+  let color = color.to_ascii_lowercase();
+  let (name, bright) = match color.strip_prefix("bright-") {
+    Some(name) => (name, true),
+    None => (color.as_str(), false),
+  };
+  if let Some(i) = COLORS.iter().position(|&known| known == name) {
+    let base = match (background, bright) {
+      (false, false) => 30,
+      (false, true) => 90,
+      (true, false) => 40,
+      (true, true) => 100,
+    };
+    return Ok((base + i).to_string());
+  }
+  match name.parse::<u8>() {
+    Ok(n) if !bright => Ok(format!("{};5;{}", if background { 48 } else { 38 }, n)),
+    _ => Err(format!("unknown color `{}`", color)),
+  }
+}
+
+/// Leaves every part of the output as it is, for when colors are off.
+pub static PLAIN: Theme = Theme {
+  path: Style::PLAIN,
+  line_number: Style::PLAIN,
+  separator: Style::PLAIN,
+  matched: Style::PLAIN,
+};
+
+/// The style of each part of the output, from `--colors`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+  pub path: Style,
+  pub line_number: Style,
+  /// The `:` and `-` after each prefix, and the `--` between context groups.
+  pub separator: Style,
+  pub matched: Style,
+}
+
+impl Default for Theme {
+  fn default() -> Theme {
+    Theme {
+      path: Style::default(),
+      line_number: Style::default(),
+      separator: Style::default(),
+      matched: Style {
+        codes: "1;31".to_string(),
+      },
+    }
+  }
+}
+
+impl Theme {
+  /// Sets the style of one part from a spec like `match:bold red`. The parts
+  /// are `path`, `line`, `separator` and `match`.
+  pub fn set(&mut self, spec: &str) -> Result<&mut Theme, MinigrepError> {
+    let invalid = || MinigrepError::InvalidValue {
+      option: "--colors".to_string(),
+      value: spec.to_string(),
+    };
+    let (part, style) = spec.split_once(':').ok_or_else(invalid)?;
+    let style = Style::parse(style).map_err(|_| invalid())?;
+    match part.trim() {
+      "path" => self.path = style,
+      "line" => self.line_number = style,
+      "separator" => self.separator = style,
+      "match" => self.matched = style,
+      _ => return Err(invalid()),
+    }
+    Ok(self)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_styles() {
+    assert_eq!("\x1b[1;31m", Style::parse("bold red").unwrap().start());
+    assert_eq!(
+      "\x1b[4;94;42m",
+      Style::parse("underline bright-blue on green")
+        .unwrap()
+        .start()
+    );
+    assert_eq!("\x1b[38;5;208m", Style::parse("208").unwrap().start());
+    assert!(Style::parse("bold none").unwrap().is_plain());
+    assert!(Style::parse("mauve").is_err());
+    assert!(Style::parse("on").is_err());
+    assert_eq!("x", Style::default().paint("x").to_string());
+  }
+
+  #[test]
+  fn sets_parts_of_a_theme() {
+    let mut theme = Theme::default();
+    theme
+      .set("path:magenta")
+      .unwrap()
+      .set("match:none")
+      .unwrap();
+
+    assert_eq!(
+      "\x1b[35ma.txt\x1b[0m",
+      theme.path.paint("a.txt").to_string()
+    );
+    assert!(theme.matched.is_plain());
+    assert!(theme.set("gutter:red").is_err());
+    assert!(theme.set("match").is_err());
+  }
+}
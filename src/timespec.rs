@@ -0,0 +1,155 @@
+//! Reading points in time as they are written on the command line, for
+//! `--newer-than` and `--older-than`.
+//!
+//! A time is either an age, such as `2d` or `1h30m`, which counts back from
+//! now, or a date, such as `2024-01-01` or `2024-01-01T12:30`, which is
+//! read as UTC since there is no time zone database to go by.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// Reads an age or a date, ages counting back from `now`.
+pub fn parse(spec: &str, now: SystemTime) -> Option<SystemTime> {
+  match parse_duration(spec) {
+    Some(age) => now.checked_sub(age),
+    None => parse_date(spec),
+  }
+}
+
+/// Reads numbers of weeks, days, hours, minutes and seconds, as in `2d` or
+/// `1h30m`, each with its unit: `w`, `d`, `h`, `m` or `s`.
+pub fn parse_duration(spec: &str) -> Option<Duration> {
+  if spec.is_empty() {
+    return None;
+  }
+  let mut seconds: u64 = 0;
+  let mut rest = spec;
+  while !rest.is_empty() {
+    let digits = rest
+      .find(|c: char| !c.is_ascii_digit())
+      .unwrap_or(rest.len());
+    let n: u64 = rest[..digits].parse().ok()?;
+    let mut units = rest[digits..].chars();
+    let unit = match units.next()? {
+      'w' => WEEK,
+      'd' => DAY,
+      'h' => HOUR,
+      'm' => MINUTE,
+      's' => 1,
+      _ => return None,
+    };
+    seconds = seconds.checked_add(n.checked_mul(unit)?)?;
+    rest = units.as_str();
+  }
+  Some(Duration::from_secs(seconds))
+}
+
+/// Reads a date like `2024-01-01`, which may go on with a time of day, as
+/// in `2024-01-01T12:30` or `2024-01-01 12:30:15`.
+pub fn parse_date(spec: &str) -> Option<SystemTime> {
+  let (date, time) = match spec.split_once(['T', ' ']) {
+    Some((date, time)) => (date, Some(time)),
+    None => (spec, None),
+  };
+  let mut parts = date.splitn(3, '-');
+  let year: i64 = number(parts.next()?, 4)?;
+  let month: u32 = number(parts.next()?, 2)?;
+  let day: u32 = number(parts.next()?, 2)?;
+  if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+    return None;
+  }
+  let mut seconds = days_from_epoch(year, month, day) * DAY as i64;
+  if let Some(time) = time {
+    let mut parts = time.split(':');
+    let hour: i64 = number(parts.next()?, 2)?;
+    let minute: i64 = number(parts.next()?, 2)?;
+    let second: i64 = parts.next().map_or(Some(0), |s| number(s, 2))?;
+    if parts.next().is_some() || hour > 23 || minute > 59 || second > 59 {
+      return None;
+    }
+    seconds += hour * HOUR as i64 + minute * MINUTE as i64 + second;
+  }
+  let since_epoch = Duration::from_secs(seconds.unsigned_abs());
+  if seconds < 0 {
+    UNIX_EPOCH.checked_sub(since_epoch)
+  } else {
+    UNIX_EPOCH.checked_add(since_epoch)
+  }
+}
+
+// A number written with exactly `width` digits
+fn number<T: std::str::FromStr>(digits: &str, width: usize) -> Option<T> {
+  if digits.len() != width || !digits.bytes().all(|b| b.is_ascii_digit()) {
+    return None;
+  }
+  digits.parse().ok()
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+  let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+  match month {
+    2 if leap => 29,
+    2 => 28,
+    4 | 6 | 9 | 11 => 30,
+    _ => 31,
+  }
+}
+
+// Days from 1970-01-01 to a date of the proleptic Gregorian calendar, by
+// counting in 400-year eras of years that begin in March, so that leap days
+// come last
+fn days_from_epoch(year: i64, month: u32, day: u32) -> i64 {
+  let year = if month <= 2 { year - 1 } else { year };
+  let era = year.div_euclid(400);
+  let year_of_era = year - era * 400;
+  let month_from_march = i64::from((month + 9) % 12);
+  let day_of_year = (153 * month_from_march + 2) / 5 + i64::from(day) - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn reads_ages() {
+    assert_eq!(Some(Duration::from_secs(2 * DAY)), parse_duration("2d"));
+    assert_eq!(
+      Some(Duration::from_secs(HOUR + 30 * MINUTE)),
+      parse_duration("1h30m")
+    );
+    assert_eq!(Some(Duration::from_secs(WEEK + 5)), parse_duration("1w5s"));
+    assert_eq!(None, parse_duration(""));
+    assert_eq!(None, parse_duration("2"));
+    assert_eq!(None, parse_duration("d"));
+    assert_eq!(None, parse_duration("2x"));
+    let now = UNIX_EPOCH + Duration::from_secs(10 * DAY);
+    assert_eq!(
+      Some(UNIX_EPOCH + Duration::from_secs(8 * DAY)),
+      parse("2d", now)
+    );
+  }
+
+  #[test]
+  fn reads_dates_as_utc() {
+    let at = |seconds: u64| Some(UNIX_EPOCH + Duration::from_secs(seconds));
+    assert_eq!(at(0), parse_date("1970-01-01"));
+    assert_eq!(at(1_704_067_200), parse_date("2024-01-01"));
+    assert_eq!(at(1_709_210_096), parse_date("2024-02-29T12:34:56"));
+    assert_eq!(at(951_827_400), parse_date("2000-02-29 12:30"));
+    assert_eq!(
+      UNIX_EPOCH.checked_sub(Duration::from_secs(DAY)),
+      parse_date("1969-12-31")
+    );
+    assert_eq!(None, parse_date("2023-02-29"));
+    assert_eq!(None, parse_date("2024-13-01"));
+    assert_eq!(None, parse_date("2024-1-1"));
+    assert_eq!(None, parse_date("2024-01-01T25:00"));
+    assert_eq!(None, parse_date("yesterday"));
+  }
+}
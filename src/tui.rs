@@ -0,0 +1,446 @@
+//! The interactive mode started by `--tui`, where the query is refined while
+//! the matches update below it.
+//!
+//! The terminal is driven with plain escape sequences, and put into raw mode
+//! through `stty`, so that no terminal library is needed. Keys are read from
+//! `/dev/tty`, which leaves standard input free to be searched.
+
+use crate::error::MinigrepError;
+use crate::{
+  compile_patterns, input_paths, is_binary, literal_set, open_output, search_contents, Config,
+};
+use crate::{report, STDIN_FILENAME, STDIN_LABEL};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// How many matches are kept at most, so that typing stays responsive on
+/// large trees.
+pub const MAX_RESULTS: usize = 10_000;
+const TTY: &str = "/dev/tty";
+// The query and the status line
+const HEADER_LINES: usize = 2;
+
+/// A key pressed in the interactive mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+  Char(char),
+  Backspace,
+  /// Ctrl-U, which clears the query.
+  ClearQuery,
+  Enter,
+  Up,
+  Down,
+  PageUp,
+  PageDown,
+  /// Ctrl-E, which opens the selected match in `$EDITOR`.
+  Edit,
+  /// Escape or Ctrl-C.
+  Quit,
+}
+
+/// What should happen after a key was handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+  Continue,
+  /// The query changed, so the search has to run again.
+  Search,
+  Print,
+  Edit,
+  Quit,
+}
+
+/// A matching line in the result list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Row {
+  pub input: usize,
+  pub line_number: usize,
+  pub line: String,
+}
+
+/// The query, its matches and where in them the selection is.
+#[derive(Debug, Default)]
+pub struct Session {
+  pub query: String,
+  pub rows: Vec<Row>,
+  pub selected: usize,
+  /// The first row shown.
+  pub scroll: usize,
+  /// Why the query could not be searched for, such as an unfinished regex.
+  pub error: Option<String>,
+}
+
+impl Session {
+  pub fn new(query: &str) -> Session {
+    Session {
+      query: query.to_string(),
+      ..Session::default()
+    }
+  }
+
+  /// Searches `inputs`, pairs of a name and contents, for the query, keeping
+  /// the previous matches when it is not valid.
+  pub fn search(&mut self, config: &mut Config, inputs: &[(String, String)]) {
+    match compile_patterns(
+      vec![self.query.clone()],
+      config.syntax,
+      config.case_sensitive,
+      config.crlf,
+      config.ignore_accents,
+    ) {
+      Ok(patterns) => {
+        let exact = config.case_sensitive && !config.ignore_accents;
+        config.literal_set = literal_set(&patterns, exact);
+        config.patterns = patterns;
+      }
+      Err(e) => {
+        self.error = Some(e.to_string());
+        return;
+      }
+    }
+    self.error = None;
+    self.rows.clear();
+    'inputs: for (input, (_, contents)) in inputs.iter().enumerate() {
+      for m in search_contents(config, contents) {
+        if self.rows.len() == MAX_RESULTS {
+          break 'inputs;
+        }
+        self.rows.push(Row {
+          input,
+          line_number: m.line_number,
+          line: m.line.to_string(),
+        });
+      }
+    }
+    self.selected = 0;
+    self.scroll = 0;
+  }
+
+  /// Handles a key, given how many rows fit on the screen.
+  pub fn key(&mut self, key: Key, height: usize) -> Action {
+    let last = self.rows.len().saturating_sub(1);
+    match key {
+      Key::Char(c) => {
+        self.query.push(c);
+        return Action::Search;
+      }
+      Key::Backspace => {
+        return match self.query.pop() {
+          Some(_) => Action::Search,
+          None => Action::Continue,
+        };
+      }
+      Key::ClearQuery if !self.query.is_empty() => {
+        self.query.clear();
+        return Action::Search;
+      }
+      Key::ClearQuery => {}
+      Key::Up => self.selected = self.selected.saturating_sub(1),
+      Key::Down => self.selected = (self.selected + 1).min(last),
+      Key::PageUp => self.selected = self.selected.saturating_sub(height.max(1)),
+      Key::PageDown => self.selected = (self.selected + height.max(1)).min(last),
+      Key::Enter if !self.rows.is_empty() => return Action::Print,
+      Key::Edit if !self.rows.is_empty() => return Action::Edit,
+      Key::Enter | Key::Edit => {}
+      Key::Quit => return Action::Quit,
+    }
+    // Keep the selection on screen
+    if self.selected < self.scroll {
+      self.scroll = self.selected;
+    } else if height > 0 && self.selected >= self.scroll + height {
+      self.scroll = self.selected + 1 - height;
+    }
+    Action::Continue
+  }
+
+  pub fn selection(&self) -> Option<&Row> {
+    self.rows.get(self.selected)
+  }
+}
+
+/// Decodes the bytes read from the terminal in one go into keys.
+pub fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+  let mut keys = Vec::new();
+  let mut rest = bytes;
+  while let Some(&first) = rest.first() {
+    let (key, len) = match rest {
+      [0x1b, b'[', b'A', ..] => (Some(Key::Up), 3),
+      [0x1b, b'[', b'B', ..] => (Some(Key::Down), 3),
+      [0x1b, b'[', b'5', b'~', ..] => (Some(Key::PageUp), 4),
+      [0x1b, b'[', b'6', b'~', ..] => (Some(Key::PageDown), 4),
+      // Other sequences are skipped up to their final byte
+      [0x1b, b'[', tail @ ..] => {
+        let end = tail.iter().position(|b| (0x40..=0x7e).contains(b));
+        (None, 2 + end.map_or(tail.len(), |i| i + 1))
+      }
+      [0x1b, ..] => (Some(Key::Quit), 1),
+      [0x03, ..] => (Some(Key::Quit), 1),
+      [0x05, ..] => (Some(Key::Edit), 1),
+      [0x0e, ..] => (Some(Key::Down), 1),
+      [0x10, ..] => (Some(Key::Up), 1),
+      [0x15, ..] => (Some(Key::ClearQuery), 1),
+      [b'\r', ..] | [b'\n', ..] => (Some(Key::Enter), 1),
+      [0x7f, ..] | [0x08, ..] => (Some(Key::Backspace), 1),
+      _ if first < 0x20 => (None, 1),
+      _ => {
+        let len = match first {
+          0xf0..=0xff => 4,
+          0xe0..=0xef => 3,
+          0xc0..=0xdf => 2,
+          _ => 1,
+        };
+        let len = len.min(rest.len());
+        let key = std::str::from_utf8(&rest[..len])
+          .ok()
+          .and_then(|s| s.chars().next())
+          .map(Key::Char);
+        (key, len)
+      }
+    };
+    keys.extend(key);
+    rest = &rest[len..];
+  }
+  keys
+}
+
+/// Runs the interactive mode until a match is printed or opened, or the user
+/// quits. Starts from the first query of `config`, if there is one.
+pub fn run(mut config: Config) -> Result<(), MinigrepError> {
+  let inputs = load(&config)?;
+  let query = config
+    .patterns
+    .first()
+    .map_or("", |p| p.as_str())
+    .to_string();
+  let mut session = Session::new(&query);
+  session.search(&mut config, &inputs);
+
+  let tty_error = |e| MinigrepError::Io {
+    path: PathBuf::from(TTY),
+    source: e,
+  };
+  let mut terminal = Terminal::open().map_err(tty_error)?;
+  loop {
+    let (rows, cols) = terminal.size();
+    let height = rows.saturating_sub(HEADER_LINES);
+    terminal
+      .draw(&session, &inputs, height, cols)
+      .map_err(tty_error)?;
+    for key in terminal.read_keys().map_err(tty_error)? {
+      match session.key(key, height) {
+        Action::Continue => {}
+        Action::Search => session.search(&mut config, &inputs),
+        Action::Quit => return Ok(()),
+        Action::Print => {
+          drop(terminal);
+          let row = session.selection().unwrap();
+          let mut out = open_output(&config)?;
+          writeln!(
+            out,
+            "{}:{}:{}",
+            inputs[row.input].0, row.line_number, row.line
+          )
+          .and_then(|_| out.flush())
+          .map_err(MinigrepError::Output)?;
+          return Ok(());
+        }
+        Action::Edit => {
+          drop(terminal);
+          let row = session.selection().unwrap();
+          return edit(&inputs[row.input].0, row.line_number);
+        }
+      }
+    }
+  }
+}
+
+// Reads every input up front, skipping binary files unless asked not to
+fn load(config: &Config) -> Result<Vec<(String, String)>, MinigrepError> {
+  let mut inputs = Vec::new();
+  if config.filenames.iter().any(|f| f == STDIN_FILENAME) {
+    let mut bytes = Vec::new();
+    io::stdin()
+      .read_to_end(&mut bytes)
+      .map_err(|e| MinigrepError::Io {
+        path: PathBuf::from(STDIN_LABEL),
+        source: e,
+      })?;
+    inputs.push((STDIN_LABEL.to_string(), config.encoding.decode(&bytes)));
+  }
+  for path in input_paths(config) {
+    let bytes = match fs::read(&path) {
+      Ok(bytes) => bytes,
+      Err(e) => {
+        report(config, &path, &e);
+        continue;
+      }
+    };
+    if config.binary || !is_binary(&bytes) {
+      inputs.push((path.display().to_string(), config.encoding.decode(&bytes)));
+    }
+  }
+  Ok(inputs)
+}
+
+// Opens `path` at a line with `$EDITOR`, or `vi`, the way most editors
+// accept it
+fn edit(path: &str, line_number: usize) -> Result<(), MinigrepError> {
+  let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+  let mut words = editor.split_whitespace();
+  let program = words.next().unwrap_or("vi");
+  Command::new(program)
+    .args(words)
+    .arg(format!("+{}", line_number))
+    .arg(path)
+    .status()
+    .map(|_| ())
+    .map_err(|e| MinigrepError::Io {
+      path: PathBuf::from(program),
+      source: e,
+    })
+}
+
+// The terminal in raw mode on the alternate screen, restored when dropped
+struct Terminal {
+  tty: File,
+  // The `stty` settings from before
+  saved: String,
+}
+
+impl Terminal {
+  fn open() -> io::Result<Terminal> {
+    let tty = OpenOptions::new().read(true).write(true).open(TTY)?;
+    let saved = stty(&tty, &["-g"])?;
+    stty(&tty, &["raw", "-echo"])?;
+    let mut terminal = Terminal {
+      tty,
+      saved: saved.trim().to_string(),
+    };
+    write!(terminal.tty, "\x1b[?1049h")?;
+    Ok(terminal)
+  }
+
+  // Rows and columns, falling back to the classic size
+  fn size(&self) -> (usize, usize) {
+    let size = stty(&self.tty, &["size"]).unwrap_or_default();
+    let mut numbers = size.split_whitespace().filter_map(|n| n.parse().ok());
+    match (numbers.next(), numbers.next()) {
+      (Some(rows), Some(cols)) if rows > 0 && cols > 0 => (rows, cols),
+      _ => (24, 80),
+    }
+  }
+
+  fn read_keys(&mut self) -> io::Result<Vec<Key>> {
+    let mut buffer = [0; 64];
+    let n = self.tty.read(&mut buffer)?;
+    Ok(parse_keys(&buffer[..n]))
+  }
+
+  fn draw(
+    &mut self,
+    session: &Session,
+    inputs: &[(String, String)],
+    height: usize,
+    cols: usize,
+  ) -> io::Result<()> {
+    let fit = |text: &str| text.chars().take(cols).collect::<String>();
+    // Raw mode does not turn `\n` into `\r\n`
+    let mut screen = format!("\x1b[H\x1b[2J{}\r\n", fit(&format!("> {}", session.query)));
+    let status = match &session.error {
+      Some(error) => error.clone(),
+      None if session.rows.len() == MAX_RESULTS => format!("{}+ matches", MAX_RESULTS),
+      None => format!("{} matches", session.rows.len()),
+    };
+    screen.push_str(&format!("\x1b[2m{}\x1b[0m", fit(&status)));
+    for (i, row) in session
+      .rows
+      .iter()
+      .enumerate()
+      .skip(session.scroll)
+      .take(height)
+    {
+      let line = fit(&format!(
+        "{}:{}:{}",
+        inputs[row.input].0, row.line_number, row.line
+      ));
+      if i == session.selected {
+        screen.push_str(&format!("\r\n\x1b[7m{}\x1b[0m", line));
+      } else {
+        screen.push_str(&format!("\r\n{}", line));
+      }
+    }
+    // Leave the cursor after the query
+    let column = (session.query.chars().count() + 3).min(cols);
+    screen.push_str(&format!("\x1b[1;{}H", column));
+    self.tty.write_all(screen.as_bytes())?;
+    self.tty.flush()
+  }
+}
+
+impl Drop for Terminal {
+  fn drop(&mut self) {
+    // Nothing more can be done if the terminal is gone
+    let _ = write!(self.tty, "\x1b[?1049l");
+    let _ = self.tty.flush();
+    let _ = stty(&self.tty, &[&self.saved]);
+  }
+}
+
+fn stty(tty: &File, args: &[&str]) -> io::Result<String> {
+  let output = Command::new("stty")
+    .args(args)
+    .stdin(Stdio::from(tty.try_clone()?))
+    .stderr(Stdio::null())
+    .output()?;
+  if !output.status.success() {
+    return Err(io::Error::other("stty failed"));
+  }
+  Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn decodes_keys() {
+    assert_eq!(
+      vec![
+        Key::Char('f'),
+        Key::Char('é'),
+        Key::Up,
+        Key::PageDown,
+        Key::Backspace,
+        Key::Enter,
+        Key::Quit
+      ],
+      parse_keys("fé\x1b[A\x1b[6~\x1b[1;5C\x7f\r\x1b".as_bytes())
+    );
+  }
+
+  #[test]
+  fn refines_the_query_and_moves_the_selection() {
+    let inputs = vec![
+      ("poem.txt".to_string(), "a frog\na bog\nfrogs\n".to_string()),
+      ("notes.txt".to_string(), "no frogs here\n".to_string()),
+    ];
+    let mut config = crate::tests::config("");
+    let mut session = Session::new("fro");
+    session.search(&mut config, &inputs);
+    assert_eq!(3, session.rows.len());
+
+    assert_eq!(Action::Continue, session.key(Key::Down, 1));
+    assert_eq!(Action::Continue, session.key(Key::Down, 1));
+    assert_eq!(Action::Continue, session.key(Key::Down, 1));
+    assert_eq!((2, 2), (session.selected, session.scroll));
+    assert_eq!(1, session.selection().unwrap().input);
+
+    assert_eq!(Action::Search, session.key(Key::Char('g'), 1));
+    assert_eq!(Action::Search, session.key(Key::Char('s'), 1));
+    session.search(&mut config, &inputs);
+    assert_eq!((2, 0), (session.rows.len(), session.selected));
+    assert_eq!(Action::Print, session.key(Key::Enter, 1));
+    assert_eq!(Action::Quit, session.key(Key::Quit, 1));
+  }
+}
@@ -0,0 +1,363 @@
+//! Letting the threads of a search take turns at the output, so that the
+//! results of one input never run into those of another.
+//!
+//! The input whose turn it is writes straight to the output as it is
+//! searched. The others hold their results back until their turn comes, but
+//! only so much between them: past that, the next to hold more waits for its
+//! turn, so that a large input ahead of the rest never has theirs pile up in
+//! memory. Inputs take their turns in the order they were found, or, when
+//! that order is not kept, whichever has results first.
+
+use crate::progress::Progress;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard};
+
+// What the inputs waiting for their turn hold back between them before the
+// next one to hold more waits
+const MAX_HELD: usize = 4 << 20;
+// What a thread gathers before handing it on, unless a line ends first
+const CHUNK: usize = 8 << 10;
+
+/// The output, shared by the threads searching one input each.
+pub struct Turns<'o, W> {
+  ordered: bool,
+  separator: Option<String>,
+  progress: Option<&'o Progress>,
+  state: Mutex<State<'o, W>>,
+  turn: Condvar,
+  // Whether `failed` is set in `state`, for those deciding whether to go on
+  // to check without waiting for the lock
+  failed: AtomicBool,
+}
+
+struct State<'o, W> {
+  out: &'o mut W,
+  // The input writing to the output, which in order is always the first one
+  // not yet written in full
+  current: Option<usize>,
+  held: BTreeMap<usize, Held>,
+  held_len: usize,
+  // Whether anything was written, and whether the current input wrote it,
+  // for the separator to go between inputs
+  written: bool,
+  started: bool,
+  failed: Option<io::ErrorKind>,
+}
+
+// The results of an input that is not writing yet
+#[derive(Default)]
+struct Held {
+  results: Vec<u8>,
+  done: bool,
+}
+
+impl<'o, W: Write> Turns<'o, W> {
+  /// Turns at `out` for inputs numbered from 0 in the order they were found,
+  /// with `separator` written between the results of two of them. While
+  /// `progress` is shown, it is erased before anything is written.
+  pub fn new(
+    out: &'o mut W,
+    ordered: bool,
+    separator: Option<String>,
+    progress: Option<&'o Progress>,
+  ) -> Turns<'o, W> {
+    Turns {
+      ordered,
+      separator,
+      progress,
+      state: Mutex::new(State {
+        out,
+        current: ordered.then_some(0),
+        held: BTreeMap::new(),
+        held_len: 0,
+        written: false,
+        started: false,
+        failed: None,
+      }),
+      turn: Condvar::new(),
+      failed: AtomicBool::new(false),
+    }
+  }
+
+  /// Where the results of input `index` are written. They are finished once
+  /// it is dropped.
+  pub fn input(&self, index: usize) -> Turn<'_, 'o, W> {
+    Turn {
+      turns: self,
+      index,
+      pending: Vec::new(),
+    }
+  }
+
+  /// Whether writing to the output failed, after which nothing more is
+  /// written and there is no use searching on.
+  pub fn failed(&self) -> bool {
+    self.failed.load(Ordering::Relaxed)
+  }
+
+  /// Redraws the status line, in between results.
+  pub fn draw(&self) {
+    if let Some(progress) = self.progress {
+      let _state = self.lock();
+      // A status line that cannot be drawn is no reason to stop
+      let _ = progress.draw(&mut io::stderr());
+    }
+  }
+
+  /// Writes what inputs that never finished held back, as happens when the
+  /// search stops early, and hands the output back.
+  pub fn finish(self) -> io::Result<&'o mut W> {
+    let mut state = self.state.into_inner().unwrap();
+    if let Some(progress) = self.progress {
+      progress.clear(&mut io::stderr())?;
+    }
+    for (_, held) in std::mem::take(&mut state.held) {
+      state.started = false;
+      write_out(&mut state, self.separator.as_deref(), None, &held.results);
+    }
+    match state.failed {
+      Some(kind) => Err(kind.into()),
+      None => Ok(state.out),
+    }
+  }
+
+  fn lock(&self) -> MutexGuard<'_, State<'o, W>> {
+    self.state.lock().unwrap()
+  }
+
+  fn write_out(&self, state: &mut State<'o, W>, results: &[u8]) {
+    write_out(state, self.separator.as_deref(), self.progress, results);
+    if state.failed.is_some() {
+      self.failed.store(true, Ordering::Relaxed);
+    }
+  }
+
+  // Writes what input `index` has, or holds it back if it is not its turn,
+  // waiting for the turn when too much is held already
+  fn append(&self, index: usize, results: &[u8]) -> io::Result<()> {
+    let mut state = self.lock();
+    if state.current.is_none() && !self.ordered {
+      state.current = Some(index);
+      state.started = false;
+    }
+    if state.current == Some(index) {
+      self.write_out(&mut state, results);
+    } else {
+      state
+        .held
+        .entry(index)
+        .or_default()
+        .results
+        .extend_from_slice(results);
+      state.held_len += results.len();
+      while state.current != Some(index) && state.held_len > MAX_HELD && state.failed.is_none() {
+        state = self.turn.wait(state).unwrap();
+      }
+    }
+    match state.failed {
+      Some(kind) => Err(kind.into()),
+      None => Ok(()),
+    }
+  }
+
+  // Input `index` has no more results
+  fn done(&self, index: usize) {
+    let mut state = self.lock();
+    if state.current.is_none() && !self.ordered && state.held.contains_key(&index) {
+      let held = state.held.remove(&index).unwrap_or_default();
+      state.held_len -= held.results.len();
+      state.current = Some(index);
+      state.started = false;
+      self.write_out(&mut state, &held.results);
+    }
+    if state.current != Some(index) {
+      // Without order, an input that had nothing need not wait for a turn
+      if self.ordered || state.held.contains_key(&index) {
+        state.held.entry(index).or_default().done = true;
+      }
+      return;
+    }
+    self.pass_on(&mut state, index);
+    self.turn.notify_all();
+  }
+
+  // Gives the turn on from input `index`, writing what each input held as
+  // it gets the turn and passing it on again from those that were done
+  fn pass_on(&self, state: &mut State<'o, W>, mut index: usize) {
+    loop {
+      let next = if self.ordered {
+        Some(index + 1)
+      } else {
+        // Those that are done go first, since they need the turn no longer
+        let done = state.held.iter().find(|(_, held)| held.done);
+        done
+          .or_else(|| state.held.iter().next())
+          .map(|(&next, _)| next)
+      };
+      state.current = next;
+      state.started = false;
+      let held = match next.and_then(|next| state.held.remove(&next)) {
+        Some(held) => held,
+        None => return,
+      };
+      state.held_len -= held.results.len();
+      self.write_out(state, &held.results);
+      if !held.done {
+        return;
+      }
+      index = state.current.unwrap_or(index);
+    }
+  }
+}
+
+fn write_out<W: Write>(
+  state: &mut State<'_, W>,
+  separator: Option<&str>,
+  progress: Option<&Progress>,
+  results: &[u8],
+) {
+  if results.is_empty() || state.failed.is_some() {
+    return;
+  }
+  let written = (|| {
+    if let Some(progress) = progress {
+      progress.clear(&mut io::stderr())?;
+    }
+    if !state.started {
+      if let Some(separator) = separator.filter(|_| state.written) {
+        state.out.write_all(separator.as_bytes())?;
+      }
+      state.started = true;
+      state.written = true;
+    }
+    state.out.write_all(results)?;
+    // The status line is drawn again soon after, so the results go first
+    if progress.is_some() {
+      state.out.flush()?;
+    }
+    Ok(())
+  })();
+  if let Err(e) = written {
+    state.failed = Some(io::Error::kind(&e));
+  }
+}
+
+/// The results of one input, handed on a line or so at a time.
+pub struct Turn<'t, 'o, W: Write> {
+  turns: &'t Turns<'o, W>,
+  index: usize,
+  pending: Vec<u8>,
+}
+
+impl<W: Write> Write for Turn<'_, '_, W> {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.pending.extend_from_slice(buf);
+    if self.pending.len() >= CHUNK {
+      self.flush()?;
+    } else if let Some(end) = buf.iter().rposition(|&b| b == b'\n') {
+      // Lines go on as soon as they end, for results to show up while
+      // slow inputs are still being read
+      let rest = self
+        .pending
+        .split_off(self.pending.len() - buf.len() + end + 1);
+      let lines = std::mem::replace(&mut self.pending, rest);
+      self.turns.append(self.index, &lines)?;
+    }
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    let pending = std::mem::take(&mut self.pending);
+    self.turns.append(self.index, &pending)
+  }
+}
+
+impl<W: Write> Drop for Turn<'_, '_, W> {
+  fn drop(&mut self) {
+    // A failure was recorded for `finish` to return
+    let _ = self.flush();
+    self.turns.done(self.index);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::thread;
+
+  #[test]
+  fn writes_inputs_in_turn_while_holding_the_rest_back() {
+    let mut out = Vec::new();
+    let turns = Turns::new(&mut out, true, Some("--\n".to_string()), None);
+    let mut second = turns.input(1);
+    second.write_all(b"b1\n").unwrap();
+    drop(turns.input(2));
+    {
+      let mut first = turns.input(0);
+      first.write_all(b"a1\na").unwrap();
+      assert!(turns.lock().out.starts_with(b"a1\n"));
+      first.write_all(b"2\n").unwrap();
+    }
+    second.write_all(b"b2\n").unwrap();
+    drop(second);
+    turns.input(3).write_all(b"d\n").unwrap();
+    turns.finish().unwrap();
+    assert_eq!(
+      "a1\na2\n--\nb1\nb2\n--\nd\n",
+      String::from_utf8(out).unwrap()
+    );
+  }
+
+  #[test]
+  fn waits_for_the_turn_once_too_much_is_held() {
+    let mut out = Vec::new();
+    let turns = Turns::new(&mut out, false, None, None);
+    let line = format!("{}\n", "x".repeat(1000));
+    thread::scope(|scope| {
+      let mut first = turns.input(0);
+      first.write_all(b"first\n").unwrap();
+      let turns = &turns;
+      let later = scope.spawn(move || {
+        let mut later = turns.input(1);
+        for _ in 0..2 * MAX_HELD / line.len() {
+          later.write_all(line.as_bytes()).unwrap();
+        }
+      });
+      while turns.lock().held_len <= MAX_HELD {
+        thread::yield_now();
+      }
+      assert!(!later.is_finished());
+      drop(first);
+    });
+    let out = turns.finish().unwrap();
+    assert!(out.starts_with(b"first\nxxx"));
+    assert_eq!(6 + 2 * MAX_HELD / 1001 * 1001, out.len());
+  }
+
+  #[test]
+  fn tells_once_writing_failed() {
+    struct Closed;
+    impl Write for Closed {
+      fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+      }
+      fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+      }
+    }
+
+    let mut out = Closed;
+    let turns = Turns::new(&mut out, true, None, None);
+    assert!(!turns.failed());
+    let mut first = turns.input(0);
+    assert!(first.write_all(b"a\n").is_err());
+    assert!(turns.failed());
+    drop(first);
+    assert_eq!(
+      io::ErrorKind::BrokenPipe,
+      turns.finish().err().unwrap().kind()
+    );
+  }
+}
@@ -0,0 +1,133 @@
+//! Named groups of globs for selecting files by language, as used by
+//! `--type`, `--type-not` and `--type-add`.
+
+use crate::error::MinigrepError;
+use crate::filter::PathFilter;
+
+const BUILT_IN: &[(&str, &[&str])] = &[
+  ("c", &["*.c", "*.h"]),
+  ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.hxx"]),
+  ("css", &["*.css", "*.scss"]),
+  ("go", &["*.go"]),
+  ("html", &["*.html", "*.htm"]),
+  ("java", &["*.java"]),
+  ("js", &["*.js", "*.jsx", "*.mjs", "*.cjs"]),
+  ("json", &["*.json"]),
+  ("md", &["*.md", "*.markdown"]),
+  ("py", &["*.py", "*.pyi"]),
+  ("rust", &["*.rs"]),
+  ("sh", &["*.sh", "*.bash", "*.zsh"]),
+  ("toml", &["*.toml", "Cargo.lock"]),
+  ("ts", &["*.ts", "*.tsx"]),
+  ("txt", &["*.txt"]),
+  ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// The file types known by name, the built-in ones and any added since.
+#[derive(Debug, Clone)]
+pub struct FileTypes {
+  types: Vec<(String, Vec<String>)>,
+}
+
+impl Default for FileTypes {
+  fn default() -> FileTypes {
+    FileTypes {
+      types: BUILT_IN
+        .iter()
+        .map(|(name, globs)| {
+          let globs = globs.iter().map(|glob| glob.to_string()).collect();
+          (name.to_string(), globs)
+        })
+        .collect(),
+    }
+  }
+}
+
+impl FileTypes {
+  pub fn new() -> FileTypes {
+    FileTypes::default()
+  }
+
+  /// Adds globs to a type, creating it if needed, from a definition like
+  /// `web:*.html,*.css`.
+  pub fn add(&mut self, definition: &str) -> Result<&mut FileTypes, MinigrepError> {
+    let invalid = || MinigrepError::InvalidValue {
+      option: "--type-add".to_string(),
+      value: definition.to_string(),
+    };
+    let (name, globs) = definition.split_once(':').ok_or_else(invalid)?;
+    let globs: Vec<String> = globs
+      .split(',')
+      .filter(|glob| !glob.is_empty())
+      .map(String::from)
+      .collect();
+    if name.is_empty() || globs.is_empty() {
+      return Err(invalid());
+    }
+    match self.types.iter_mut().find(|(known, _)| known == name) {
+      Some((_, known)) => known.extend(globs),
+      None => self.types.push((name.to_string(), globs)),
+    }
+    Ok(self)
+  }
+
+  pub fn globs(&self, name: &str) -> Option<&[String]> {
+    self
+      .types
+      .iter()
+      .find(|(known, _)| known == name)
+      .map(|(_, globs)| &globs[..])
+  }
+
+  /// Narrows `filter` to files of the `selected` types, if any are given,
+  /// and away from files of the `negated` ones.
+  pub fn apply(
+    &self,
+    filter: &mut PathFilter,
+    selected: &[String],
+    negated: &[String],
+  ) -> Result<(), MinigrepError> {
+    for (names, option, negate) in [(selected, "--type", false), (negated, "--type-not", true)] {
+      for name in names {
+        let globs = self
+          .globs(name)
+          .ok_or_else(|| MinigrepError::InvalidValue {
+            option: option.to_string(),
+            value: name.to_string(),
+          })?;
+        for glob in globs {
+          if negate {
+            filter.exclude_type(glob)?;
+          } else {
+            filter.include_type(glob)?;
+          }
+        }
+      }
+    }
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::path::Path;
+
+  #[test]
+  fn selects_files_by_type() {
+    let mut types = FileTypes::new();
+    types.add("web:*.html,*.css").unwrap();
+    let mut filter = PathFilter::new();
+    let names = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+    types
+      .apply(&mut filter, &names(&["rust", "web"]), &names(&["css"]))
+      .unwrap();
+
+    assert!(filter.is_match(Path::new("src/lib.rs")));
+    assert!(filter.is_match(Path::new("site/index.html")));
+    assert!(!filter.is_match(Path::new("site/style.css")));
+    assert!(!filter.is_match(Path::new("README.md")));
+    assert!(types.apply(&mut filter, &names(&["cobol"]), &[]).is_err());
+    assert!(types.add("web").is_err());
+  }
+}
@@ -0,0 +1,738 @@
+use crate::filter::PathFilter;
+use crate::ignore::{Gitignore, IgnoreStack};
+use crate::log;
+use std::collections::HashSet;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::SystemTime;
+use std::vec;
+
+const GITIGNORE: &str = ".gitignore";
+// Ignore files of no tool in particular, then of minigrep's own, which take
+// precedence over `.gitignore` in that order
+const DOT_IGNORES: [&str; 2] = [".ignore", ".minigrepignore"];
+const GIT_DIR: &str = ".git";
+
+/// Recursively yields every regular file below a root path.
+///
+/// Directory entries are visited in lexicographic order so that results are
+/// stable between runs. A root that is itself a file is yielded as-is.
+/// Symbolic links below the root are skipped unless `follow` is set.
+///
+/// Files are yielded as they are found, unless `sort` asks for another
+/// order, in which case every one is found before the first is yielded.
+pub struct Walker {
+  stack: Vec<Entry>,
+  filter: PathFilter,
+  git_ignore: bool,
+  dot_ignore: bool,
+  follow: bool,
+  max_depth: Option<usize>,
+  // Files last modified outside of these are skipped
+  newer_than: Option<SystemTime>,
+  older_than: Option<SystemTime>,
+  strip_dot: bool,
+  // What the enclosing Git repository tracks, once `git_tracked` is set
+  tracked: Option<io::Result<Tracked>>,
+  sort: Option<Sort>,
+  // Everything found, in order, once `sort` has had it all collected
+  sorted: Option<vec::IntoIter<io::Result<PathBuf>>>,
+  skipped: usize,
+  // Every directory read so far, so that links back to one are not walked
+  // again
+  visited: HashSet<DirId>,
+}
+
+struct Entry {
+  path: PathBuf,
+  // How deep below the root this path is
+  depth: usize,
+  // The ignore rules of every directory above this path
+  ignores: Arc<IgnoreStack>,
+}
+
+impl Walker {
+  pub fn new<P: AsRef<Path>>(root: P) -> Walker {
+    Walker {
+      stack: vec![Entry {
+        path: root.as_ref().to_path_buf(),
+        depth: 0,
+        ignores: IgnoreStack::new(),
+      }],
+      filter: PathFilter::new(),
+      git_ignore: false,
+      dot_ignore: false,
+      follow: false,
+      max_depth: None,
+      newer_than: None,
+      older_than: None,
+      strip_dot: false,
+      tracked: None,
+      sort: None,
+      sorted: None,
+      skipped: 0,
+      visited: HashSet::new(),
+    }
+  }
+
+  /// Only yields files accepted by `filter`, and skips the directories it
+  /// excludes without reading them. The root itself is never pruned.
+  pub fn path_filter(mut self, filter: PathFilter) -> Walker {
+    self.filter = filter;
+    self
+  }
+
+  /// Skips paths matched by `.gitignore` files found along the way, or in
+  /// the directories above the root up to the top of its repository, as
+  /// well as `.git` directories themselves.
+  pub fn git_ignore(mut self, yes: bool) -> Walker {
+    self.git_ignore = yes;
+    self
+  }
+
+  /// Skips paths matched by `.ignore` and `.minigrepignore` files, which
+  /// are written like `.gitignore` files and win over them, the
+  /// `.minigrepignore` of a directory over its `.ignore`.
+  pub fn dot_ignore(mut self, yes: bool) -> Walker {
+    self.dot_ignore = yes;
+    self
+  }
+
+  /// Only yields files that the Git repository around the root tracks, as
+  /// `git ls-files` lists them, and skips directories without any. Walking
+  /// fails if the root is not in a repository.
+  pub fn git_tracked(mut self, yes: bool) -> Walker {
+    self.tracked = None;
+    if yes {
+      let root = &self.stack[0].path;
+      // A root that is a file is yielded whatever Git thinks of it
+      if root.is_dir() {
+        self.tracked = Some(Tracked::list(root));
+      }
+    }
+    self
+  }
+
+  /// Follows symbolic links to files and directories. Each directory is
+  /// read at most once, however many links lead to it, so cycles end.
+  pub fn follow(mut self, yes: bool) -> Walker {
+    self.follow = yes;
+    self
+  }
+
+  /// Descends at most `depth` directories below the root, so `0` only
+  /// yields the root itself when it is a file.
+  pub fn max_depth(mut self, depth: Option<usize>) -> Walker {
+    self.max_depth = depth;
+    self
+  }
+
+  /// Only yields files last modified after `time`, going by their metadata
+  /// without opening them. A root that is a file is yielded whatever its
+  /// age.
+  pub fn newer_than(mut self, time: Option<SystemTime>) -> Walker {
+    self.newer_than = time;
+    self
+  }
+
+  /// Only yields files last modified before `time`, like `newer_than`.
+  pub fn older_than(mut self, time: Option<SystemTime>) -> Walker {
+    self.older_than = time;
+    self
+  }
+
+  /// Yields the files below a `.` root without the `./` in front, as in
+  /// `notes.txt` rather than `./notes.txt`.
+  pub fn strip_dot(mut self, yes: bool) -> Walker {
+    self.strip_dot = yes;
+    self
+  }
+
+  /// Yields the files in the order `sort` describes rather than as they are
+  /// found. Errors come before any file.
+  pub fn sort(mut self, sort: Option<Sort>) -> Walker {
+    self.sort = sort;
+    self
+  }
+
+  /// How many files and directories the filter and ignore rules have left
+  /// out so far.
+  pub fn skipped(&self) -> usize {
+    self.skipped
+  }
+
+  // Why a path is left out, if it is
+  fn pruned_by(&self, entry: &Entry, is_dir: bool) -> Option<&'static str> {
+    if entry.depth == 0 {
+      return None;
+    }
+    if self.filter.is_hidden(&entry.path) {
+      return Some("hidden");
+    }
+    if is_dir && self.filter.is_excluded_dir(&entry.path) {
+      return Some("excluded");
+    }
+    if self.git_ignore && is_dir && entry.path.file_name() == Some(GIT_DIR.as_ref()) {
+      return Some("a git directory");
+    }
+    // Only the ignore files asked for were read
+    if entry.ignores.is_ignored(&entry.path, is_dir) {
+      return Some("ignored by an ignore file");
+    }
+    if let Some(Ok(tracked)) = &self.tracked {
+      let listed = if is_dir {
+        &tracked.dirs
+      } else {
+        &tracked.files
+      };
+      if !listed.contains(&entry.path) {
+        return Some("not tracked by git");
+      }
+    }
+    None
+  }
+
+  // Why a file is left out for when it was last modified, if it is. Files
+  // that cannot tell are kept.
+  fn pruned_by_age(&self, entry: &Entry, metadata: &Metadata) -> Option<&'static str> {
+    if entry.depth == 0 {
+      return None;
+    }
+    let modified = metadata.modified().ok()?;
+    if self.newer_than.is_some_and(|time| modified <= time) {
+      return Some("not modified recently enough");
+    }
+    if self.older_than.is_some_and(|time| modified >= time) {
+      return Some("modified too recently");
+    }
+    None
+  }
+
+  // How a file found is yielded
+  fn name(&self, path: PathBuf) -> PathBuf {
+    match path.strip_prefix(".") {
+      Ok(relative) if self.strip_dot => relative.to_path_buf(),
+      _ => path,
+    }
+  }
+
+  fn skip(&mut self, path: &Path, reason: &str) {
+    log::debug!("skipping {}: {}", path.display(), reason);
+    self.skipped += 1;
+  }
+
+  fn collect_sorted(&mut self, sort: Sort) -> vec::IntoIter<io::Result<PathBuf>> {
+    let mut found = Vec::new();
+    let mut files = Vec::new();
+    while let Some(path) = self.walk_next() {
+      match path {
+        Ok(path) => files.push(path),
+        Err(e) => found.push(Err(e)),
+      }
+    }
+    // Files are found in path order, which the sort keeps between equals
+    match sort.by {
+      SortBy::Path => {}
+      SortBy::Modified => files.sort_by_cached_key(|path| modified(path)),
+      SortBy::Size => files.sort_by_cached_key(|path| fs::metadata(path).map_or(0, |m| m.len())),
+    }
+    if sort.reverse {
+      files.reverse();
+    }
+    found.extend(files.into_iter().map(Ok));
+    found.into_iter()
+  }
+
+  // The next file in the order it is found
+  fn walk_next(&mut self) -> Option<io::Result<PathBuf>> {
+    if let Some(Err(_)) = &self.tracked {
+      self.stack.clear();
+      return self.tracked.take().and_then(Result::err).map(Err);
+    }
+    while let Some(entry) = self.stack.pop() {
+      // Paths given to the walker are always followed
+      let metadata = if self.follow || entry.depth == 0 {
+        fs::metadata(&entry.path)
+      } else {
+        fs::symlink_metadata(&entry.path)
+      };
+      let metadata = match metadata {
+        Ok(m) => m,
+        Err(e) => return Some(Err(e)),
+      };
+      if metadata.is_file() {
+        let pruned = self.pruned_by(&entry, false);
+        match pruned.or_else(|| self.pruned_by_age(&entry, &metadata)) {
+          None if self.filter.is_match(&entry.path) => return Some(Ok(self.name(entry.path))),
+          None => self.skip(&entry.path, "not matching the filters"),
+          Some(reason) => self.skip(&entry.path, reason),
+        }
+        continue;
+      }
+      if !metadata.is_dir() || self.max_depth.is_some_and(|max| entry.depth >= max) {
+        continue;
+      }
+      if let Some(reason) = self.pruned_by(&entry, true) {
+        self.skip(&entry.path, reason);
+        continue;
+      }
+      if self.follow && !self.visited.insert(dir_id(&entry.path, &metadata)) {
+        continue;
+      }
+
+      let read_dir = match fs::read_dir(&entry.path) {
+        Ok(read_dir) => read_dir,
+        Err(e) => return Some(Err(e)),
+      };
+      let mut children = Vec::new();
+      for child in read_dir {
+        match child {
+          Ok(child) => children.push(child.path()),
+          Err(e) => return Some(Err(e)),
+        }
+      }
+      let mut ignores = if entry.depth == 0 {
+        self.parent_ignores(&entry.path)
+      } else {
+        entry.ignores.clone()
+      };
+      if self.git_ignore {
+        ignores = IgnoreStack::push(&ignores, Gitignore::from_dir(&entry.path, GITIGNORE));
+      }
+      for name in DOT_IGNORES.iter().filter(|_| self.dot_ignore) {
+        ignores = IgnoreStack::push(&ignores, Gitignore::from_dir(&entry.path, name));
+      }
+      // Pushed in reverse so that the smallest name is popped first
+      children.sort_unstable_by(|a, b| b.cmp(a));
+      self.stack.extend(children.into_iter().map(|path| Entry {
+        path,
+        depth: entry.depth + 1,
+        ignores: ignores.clone(),
+      }));
+    }
+    None
+  }
+
+  // The ignore files in the directories above `root`, up to the top of the
+  // Git repository it is in, for a search starting below the top
+  fn parent_ignores(&self, root: &Path) -> Arc<IgnoreStack> {
+    let mut ignores = IgnoreStack::new();
+    if !self.git_ignore && !self.dot_ignore {
+      return ignores;
+    }
+    let absolute = match fs::canonicalize(root) {
+      Ok(absolute) => absolute,
+      Err(_) => return ignores,
+    };
+    let mut parents = absolute.ancestors();
+    if parents
+      .next()
+      .is_some_and(|root| root.join(GIT_DIR).exists())
+    {
+      return ignores;
+    }
+    let parents: Vec<&Path> = parents.collect();
+    let top = match parents.iter().position(|dir| dir.join(GIT_DIR).exists()) {
+      Some(top) => top,
+      None => return ignores,
+    };
+    // Outermost first, so that nearer rules take precedence
+    for dir in parents[..=top].iter().rev() {
+      let below = absolute.strip_prefix(dir).unwrap_or(&absolute);
+      if self.git_ignore {
+        let ignore = Gitignore::from_parent(dir, GITIGNORE, root, below);
+        ignores = IgnoreStack::push(&ignores, ignore);
+      }
+      for name in DOT_IGNORES.iter().filter(|_| self.dot_ignore) {
+        ignores = IgnoreStack::push(&ignores, Gitignore::from_parent(dir, name, root, below));
+      }
+    }
+    ignores
+  }
+}
+
+impl Iterator for Walker {
+  type Item = io::Result<PathBuf>;
+
+  fn next(&mut self) -> Option<io::Result<PathBuf>> {
+    let sort = match self.sort {
+      Some(sort) => sort,
+      None => return self.walk_next(),
+    };
+    if self.sorted.is_none() {
+      self.sorted = Some(self.collect_sorted(sort));
+    }
+    self.sorted.as_mut().and_then(Iterator::next)
+  }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+  fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// What recursive searches order files by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+  Path,
+  /// When each file was last modified, oldest first.
+  Modified,
+  /// How large each file is, smallest first.
+  Size,
+}
+
+impl SortBy {
+  pub const NAMES: &'static str = "path, mtime or size";
+
+  pub fn from_name(name: &str) -> Option<SortBy> {
+    match name {
+      "path" => Some(SortBy::Path),
+      "mtime" => Some(SortBy::Modified),
+      "size" => Some(SortBy::Size),
+      _ => None,
+    }
+  }
+}
+
+/// The order files are searched and reported in, from `--sort` and
+/// `--sortr`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+  pub by: SortBy,
+  pub reverse: bool,
+}
+
+// The files a Git repository tracks below a directory, and the directories
+// leading to them
+struct Tracked {
+  files: HashSet<PathBuf>,
+  dirs: HashSet<PathBuf>,
+}
+
+impl Tracked {
+  fn list(root: &Path) -> io::Result<Tracked> {
+    let output = Command::new("git")
+      .arg("-C")
+      .arg(root)
+      .args(["ls-files", "-z"])
+      .output()
+      .map_err(|e| io::Error::new(e.kind(), format!("git: {}", e)))?;
+    if !output.status.success() {
+      let message = String::from_utf8_lossy(&output.stderr);
+      return Err(io::Error::other(format!(
+        "git ls-files: {}",
+        message.lines().next().unwrap_or("failed")
+      )));
+    }
+    let mut tracked = Tracked {
+      files: HashSet::new(),
+      dirs: HashSet::new(),
+    };
+    // Paths are listed relative to the root, separated by NUL bytes
+    for name in output
+      .stdout
+      .split(|&b| b == 0)
+      .filter(|name| !name.is_empty())
+    {
+      let path = root.join(String::from_utf8_lossy(name).as_ref());
+      let mut dir = path.parent();
+      while let Some(parent) = dir.filter(|dir| *dir != root) {
+        if !tracked.dirs.insert(parent.to_path_buf()) {
+          break;
+        }
+        dir = parent.parent();
+      }
+      tracked.files.insert(path);
+    }
+    Ok(tracked)
+  }
+}
+
+// Identifies a directory however it was reached
+#[cfg(unix)]
+type DirId = (u64, u64);
+#[cfg(not(unix))]
+type DirId = PathBuf;
+
+#[cfg(unix)]
+fn dir_id(_: &Path, metadata: &Metadata) -> DirId {
+  use std::os::unix::fs::MetadataExt;
+  (metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn dir_id(path: &Path, _: &Metadata) -> DirId {
+  fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::Fixture;
+
+  #[test]
+  fn walks_nested_directories_in_order() {
+    let root = Fixture::new(
+      "walk-order",
+      &[("a.txt", ""), ("b/c/d.txt", ""), ("b/e.txt", "")],
+    );
+
+    let found: Vec<PathBuf> = Walker::new(root.path()).map(|p| p.unwrap()).collect();
+
+    assert_eq!(
+      vec![
+        root.join("a.txt"),
+        root.join("b/c/d.txt"),
+        root.join("b/e.txt")
+      ],
+      found
+    );
+  }
+
+  #[test]
+  fn applies_path_filter() {
+    let root = Fixture::new(
+      "walk-filter",
+      &[
+        ("target/debug/build.rs", ""),
+        ("src/lib.rs", ""),
+        ("src/notes.md", ""),
+      ],
+    );
+
+    let mut filter = PathFilter::new();
+    filter.include("*.rs").unwrap().exclude("target").unwrap();
+    let found: Vec<PathBuf> = Walker::new(root.path())
+      .path_filter(filter)
+      .map(|p| p.unwrap())
+      .collect();
+
+    assert_eq!(vec![root.join("src/lib.rs")], found);
+  }
+
+  #[test]
+  fn respects_nested_gitignores() {
+    let root = Fixture::new(
+      "walk-ignore",
+      &[
+        (".gitignore", "build/\n*.log\n"),
+        ("src/.gitignore", "!important.log\n"),
+        ("build/out.txt", ""),
+        ("src/.git/HEAD", ""),
+        ("src/debug.log", ""),
+        ("src/important.log", ""),
+        ("src/main.rs", ""),
+      ],
+    );
+
+    let mut hidden = PathFilter::new();
+    hidden.hidden(true);
+    let found: Vec<PathBuf> = Walker::new(root.path())
+      .path_filter(hidden.clone())
+      .git_ignore(true)
+      .map(|p| p.unwrap())
+      .collect();
+    let everything = Walker::new(root.path()).path_filter(hidden).count();
+    let mut visible = Walker::new(root.path());
+    let visible = ((&mut visible).count(), visible.skipped());
+
+    assert_eq!(
+      vec![
+        root.join(".gitignore"),
+        root.join("src/.gitignore"),
+        root.join("src/important.log"),
+        root.join("src/main.rs")
+      ],
+      found
+    );
+    assert_eq!(7, everything);
+    assert_eq!((4, 3), visible);
+  }
+
+  #[test]
+  fn layers_dot_ignore_files_over_gitignores() {
+    let root = Fixture::new(
+      "walk-dot",
+      &[
+        (".gitignore", "*.log\n"),
+        (".ignore", "!keep.log\nsecret/\n"),
+        ("sub/.ignore", "!a.txt\n"),
+        ("sub/.minigrepignore", "*.txt\n"),
+      ],
+    );
+    for name in ["drop.log", "keep.log", "secret/x", "sub/a.txt", "sub/b.md"] {
+      root.write(name, "");
+    }
+
+    let found = |git, dot| {
+      Walker::new(root.path())
+        .git_ignore(git)
+        .dot_ignore(dot)
+        .map(|p| p.unwrap().strip_prefix(root.path()).unwrap().to_path_buf())
+        .collect::<Vec<_>>()
+    };
+    let layered = found(true, true);
+    let git_only = found(true, false);
+    let dot_only = found(false, true);
+
+    let paths = |names: &[&str]| names.iter().map(PathBuf::from).collect::<Vec<_>>();
+    assert_eq!(paths(&["keep.log", "sub/b.md"]), layered);
+    assert_eq!(paths(&["secret/x", "sub/a.txt", "sub/b.md"]), git_only);
+    assert_eq!(paths(&["drop.log", "keep.log", "sub/b.md"]), dot_only);
+  }
+
+  #[test]
+  fn reads_ignore_files_above_the_root_within_the_repository() {
+    let root = Fixture::new(
+      "walk-parent",
+      &[
+        (".gitignore", "*.log\n/sub/skip/\n"),
+        ("sub/.ignore", "!keep.log\n"),
+      ],
+    );
+    fs::create_dir_all(root.join(".git")).unwrap();
+    for name in ["sub/a.log", "sub/keep.log", "sub/a.txt", "sub/skip/x.txt"] {
+      root.write(name, "");
+    }
+
+    let sub = root.join("sub");
+    let found: Vec<PathBuf> = Walker::new(&sub)
+      .git_ignore(true)
+      .dot_ignore(true)
+      .map(|p| p.unwrap())
+      .collect();
+    let unignored = Walker::new(&sub).count();
+
+    assert_eq!(vec![sub.join("a.txt"), sub.join("keep.log")], found);
+    assert_eq!(4, unignored);
+  }
+
+  #[test]
+  fn stops_at_max_depth() {
+    let root = Fixture::new(
+      "walk-depth",
+      &[
+        ("top.txt", ""),
+        ("a/middle.txt", ""),
+        ("a/b/bottom.txt", ""),
+      ],
+    );
+
+    let depth = |max| Walker::new(root.path()).max_depth(max).count();
+    let found = (depth(Some(0)), depth(Some(1)), depth(Some(2)), depth(None));
+    let file = Walker::new(root.join("top.txt")).max_depth(Some(0)).count();
+
+    assert_eq!((0, 1, 2, 3), found);
+    assert_eq!(1, file);
+  }
+
+  #[test]
+  fn filters_by_modification_time() {
+    let root = Fixture::new("walk-age", &[]);
+    let day = std::time::Duration::from_secs(24 * 60 * 60);
+    let now = SystemTime::now();
+    for (name, age) in [("new.txt", 0), ("old.txt", 10)] {
+      let file = fs::File::create(root.join(name)).unwrap();
+      file.set_modified(now - day * age).unwrap();
+    }
+
+    let found = |newer, older| {
+      Walker::new(root.path())
+        .newer_than(newer)
+        .older_than(older)
+        .map(|p| p.unwrap().file_name().unwrap().to_owned())
+        .collect::<Vec<_>>()
+    };
+    let recent = found(Some(now - day * 2), None);
+    let old = found(None, Some(now - day * 2));
+    let neither = found(Some(now - day * 2), Some(now - day * 5)).len();
+    let root_file = Walker::new(root.join("old.txt"))
+      .newer_than(Some(now))
+      .count();
+
+    assert_eq!(vec!["new.txt"], recent);
+    assert_eq!(vec!["old.txt"], old);
+    assert_eq!(0, neither);
+    assert_eq!(1, root_file);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn follows_links_without_looping() {
+    use std::os::unix::fs::symlink;
+
+    let root = Fixture::new("walk-follow", &[("a/file.txt", "")]);
+    symlink(root.path(), root.join("a/loop")).unwrap();
+    symlink(root.join("a"), root.join("b")).unwrap();
+
+    let skipped: Vec<PathBuf> = Walker::new(root.path()).map(|p| p.unwrap()).collect();
+    let followed: Vec<PathBuf> = Walker::new(root.path())
+      .follow(true)
+      .map(|p| p.unwrap())
+      .collect();
+
+    assert_eq!(vec![root.join("a/file.txt")], skipped);
+    assert_eq!(vec![root.join("a/file.txt")], followed);
+  }
+
+  #[test]
+  fn sorts_when_asked() {
+    let root = Fixture::new(
+      "walk-sort",
+      &[
+        ("a.txt", "frog\n"),
+        ("b/c.txt", ""),
+        ("d.txt", "frog\ntoad\n"),
+      ],
+    );
+    let sorted = |by, reverse| {
+      Walker::new(root.path())
+        .sort(Some(Sort { by, reverse }))
+        .map(|p| p.unwrap())
+        .collect::<Vec<PathBuf>>()
+    };
+    let by_path = sorted(SortBy::Path, true);
+    let by_size = sorted(SortBy::Size, false);
+
+    assert_eq!(
+      vec![root.join("d.txt"), root.join("b/c.txt"), root.join("a.txt")],
+      by_path
+    );
+    assert_eq!(
+      vec![root.join("b/c.txt"), root.join("a.txt"), root.join("d.txt")],
+      by_size
+    );
+  }
+
+  #[test]
+  fn only_yields_tracked_files() {
+    let root = Fixture::new(
+      "walk-git",
+      &[
+        ("src/lib.rs", ""),
+        ("src/notes.txt", ""),
+        ("scratch/todo.txt", ""),
+      ],
+    );
+    let git = |args: &[&str]| {
+      Command::new("git")
+        .arg("-C")
+        .arg(root.path())
+        .args(args)
+        .output()
+    };
+    // Nothing to check without git
+    if !git(&["init", "-q"]).is_ok_and(|output| output.status.success()) {
+      return;
+    }
+    git(&["add", "src/lib.rs"]).unwrap();
+    let tracked: Vec<PathBuf> = Walker::new(root.path())
+      .git_tracked(true)
+      .map(|p| p.unwrap())
+      .collect();
+
+    assert_eq!(vec![root.join("src/lib.rs")], tracked);
+  }
+}
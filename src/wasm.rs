@@ -0,0 +1,190 @@
+//! Searching text handed over from JavaScript, so that web apps can match
+//! the way minigrep does without a server, behind the `wasm` feature.
+//!
+//! Searching text needs neither the file system nor the environment, so the
+//! library builds for `wasm32-unknown-unknown` without the command:
+//!
+//! ```text
+//! cargo rustc --lib --release --target wasm32-unknown-unknown \
+//!   --no-default-features --features wasm --crate-type cdylib
+//! ```
+//!
+//! Rather than depend on wasm-bindgen, the module exports plain functions
+//! over linear memory. Strings are passed as a pointer and a length into
+//! buffers from `minigrep_alloc`, and `minigrep_search_json` returns a buffer
+//! holding a little-endian `u32` length followed by that many bytes of JSON,
+//! to be released with `minigrep_free`.
+
+use crate::error::MinigrepError;
+use crate::json::{MatchRecord, Value};
+use crate::{search_contents, search_multiline, Config, Syntax, STDIN_FILENAME};
+
+/// Searches `contents` for `query` and returns the matching lines as a JSON
+/// array of `--json` records without a file.
+///
+/// `options` is a JSON object of any of `ignore_case`, `smart_case`,
+/// `ignore_accents`, `regex`, `fuzzy`, `word_regexp`, `invert_match` and
+/// `multiline`, which are booleans, and `max_count`, a number:
+///
+/// ```
+/// let found = minigrep::wasm::search_json("frog", "a frog\na toad\n", r#"{"ignore_case": true}"#);
+/// assert_eq!(
+///   r#"[{"file":null,"line_number":1,"line":"a frog","matches":[{"start":2,"end":6}]}]"#,
+///   found.unwrap()
+/// );
+/// ```
+pub fn search_json(query: &str, contents: &str, options: &str) -> Result<String, MinigrepError> {
+  let invalid = |option: &str, value: String| MinigrepError::InvalidValue {
+    option: option.to_string(),
+    value,
+  };
+  let options = Value::parse(options).map_err(|e| invalid("options", e.to_string()))?;
+  let members = match &options {
+    Value::Object(members) => members,
+    _ => return Err(invalid("options", options.to_string())),
+  };
+  // Nothing is read from it, but a config always names its inputs
+  let mut builder = Config::builder().query(query).path(STDIN_FILENAME);
+  let mut max_count = None;
+  for (name, value) in members {
+    if name == "max_count" {
+      max_count = Some(
+        value
+          .as_usize()
+          .ok_or_else(|| invalid(name, value.to_string()))?,
+      );
+      continue;
+    }
+    let yes = value
+      .as_bool()
+      .ok_or_else(|| invalid(name, value.to_string()))?;
+    builder = match name.as_str() {
+      "ignore_case" => builder.ignore_case(yes),
+      "smart_case" => builder.smart_case(yes),
+      "ignore_accents" => builder.ignore_accents(yes),
+      "regex" => builder.regex(yes),
+      "fuzzy" if yes => builder.syntax(Syntax::Fuzzy),
+      "fuzzy" => builder,
+      "word_regexp" => builder.word_regexp(yes),
+      "invert_match" => builder.invert_match(yes),
+      "multiline" => builder.multiline(yes),
+      _ => return Err(MinigrepError::UnknownOption(name.clone())),
+    };
+  }
+  let config = builder.build()?;
+
+  let mut found = if config.multiline {
+    search_multiline(&config, contents)
+  } else {
+    search_contents(&config, contents)
+  };
+  if let Some(max) = max_count {
+    found.truncate(max);
+  }
+  let records = found
+    .into_iter()
+    .map(|m| {
+      MatchRecord {
+        file: None,
+        line_number: m.line_number,
+        line: m.line.to_string(),
+        matches: m.spans,
+      }
+      .to_value()
+    })
+    .collect();
+  Ok(Value::Array(records).to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+mod exports {
+  use super::search_json;
+  use crate::json::Value;
+  use std::slice;
+  use std::str;
+
+  /// Makes room for `len` bytes, such as the arguments of
+  /// `minigrep_search_json`.
+  #[no_mangle]
+  pub extern "C" fn minigrep_alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr
+  }
+
+  /// Releases a buffer of `len` bytes from `minigrep_alloc`, or a result of
+  /// `minigrep_search_json` along with its length prefix.
+  ///
+  /// # Safety
+  ///
+  /// `ptr` must come from one of those functions with that same length.
+  #[no_mangle]
+  pub unsafe extern "C" fn minigrep_free(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+  }
+
+  /// `search_json` over strings in linear memory. Failures come back as a
+  /// `{"error": message}` object instead of matches.
+  ///
+  /// # Safety
+  ///
+  /// Each pointer must point to that many initialized bytes.
+  #[no_mangle]
+  pub unsafe extern "C" fn minigrep_search_json(
+    query: *const u8,
+    query_len: usize,
+    contents: *const u8,
+    contents_len: usize,
+    options: *const u8,
+    options_len: usize,
+  ) -> *mut u8 {
+    let text = |ptr: *const u8, len: usize| str::from_utf8(slice::from_raw_parts(ptr, len));
+    let result = match (
+      text(query, query_len),
+      text(contents, contents_len),
+      text(options, options_len),
+    ) {
+      (Ok(query), Ok(contents), Ok(options)) => {
+        search_json(query, contents, options).map_err(|e| e.to_string())
+      }
+      _ => Err("arguments must be UTF-8".to_string()),
+    };
+    let json = result.unwrap_or_else(|message| {
+      Value::Object(vec![("error".to_string(), Value::from(message.as_str()))]).to_string()
+    });
+    let mut buffer = (json.len() as u32).to_le_bytes().to_vec();
+    buffer.extend_from_slice(json.as_bytes());
+    let mut buffer = buffer.into_boxed_slice();
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+    ptr
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn searches_with_json_options() {
+    let contents = "Frog\nfrog pond\ntoad\n";
+    let lines = |options: &str| {
+      let found = Value::parse(&search_json("frog", contents, options).unwrap()).unwrap();
+      found
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|record| MatchRecord::from_value(record).unwrap().line_number)
+        .collect::<Vec<_>>()
+    };
+
+    assert_eq!(vec![2], lines("{}"));
+    assert_eq!(vec![1, 2], lines(r#"{"ignore_case": true}"#));
+    assert_eq!(vec![1], lines(r#"{"ignore_case": true, "max_count": 1}"#));
+    assert_eq!(vec![1, 3], lines(r#"{"invert_match": true}"#));
+    assert!(search_json("frog", contents, r#"{"loud": true}"#).is_err());
+    assert!(search_json("frog", contents, r#"{"regex": 1}"#).is_err());
+    assert!(search_json("(", contents, r#"{"regex": true}"#).is_err());
+  }
+}
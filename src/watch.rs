@@ -0,0 +1,238 @@
+//! Noticing changes to the searched files, for `--watch`.
+//!
+//! There is no portable way to be told about changes without extra
+//! dependencies, so the inputs are listed again and their modification
+//! times and sizes compared on each poll.
+//!
+//! A file that changed is searched again in full, but only the matching
+//! lines that were not printed for it before are printed, along with their
+//! context: a line that was there before and was printed once is left out
+//! once, so that a line logged again is printed again.
+
+use crate::sink::Sink;
+use crate::{input_paths, Config, Match};
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// How long to wait between looking for changes.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Remembers what each input looked like when it was last seen.
+#[derive(Debug, Default)]
+pub struct Watcher {
+  seen: HashMap<PathBuf, (SystemTime, u64)>,
+}
+
+impl Watcher {
+  pub fn new() -> Watcher {
+    Watcher::default()
+  }
+
+  /// Lists the inputs of `config` that are new or have changed since the
+  /// last call. Inputs that disappeared are forgotten, so they count as new
+  /// should they come back.
+  pub fn changed(&mut self, config: &Config) -> Vec<PathBuf> {
+    let mut changed = Vec::new();
+    let mut seen = HashMap::new();
+    for path in input_paths(config) {
+      let metadata = match fs::metadata(&path) {
+        Ok(metadata) => metadata,
+        Err(_) => continue,
+      };
+      let stamp = (
+        metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        metadata.len(),
+      );
+      if self.seen.get(&path) != Some(&stamp) {
+        changed.push(path.clone());
+      }
+      seen.insert(path, stamp);
+    }
+    self.seen = seen;
+    changed
+  }
+}
+
+/// How many times each matching line was printed for an input.
+pub(crate) type Printed = HashMap<String, usize>;
+
+// What was printed for each input while watching, which only `watch` does
+static WATCHED: Mutex<Option<HashMap<PathBuf, Printed>>> = Mutex::new(None);
+
+thread_local! {
+  // What was printed for the input being searched on this thread, for the
+  // search to leave out
+  static PRINTED: RefCell<Option<Printed>> = const { RefCell::new(None) };
+}
+
+/// Makes every search from now on remember what it printed for each input,
+/// and leave that out the next time the input is searched.
+pub(crate) fn start() {
+  *WATCHED.lock().unwrap() = Some(HashMap::new());
+}
+
+/// Runs `search` of the input at `path`, which prints only what was not
+/// printed for it before once `start` was called.
+pub(crate) fn printing_new<T>(path: &Path, search: impl FnOnce() -> T) -> T {
+  let printed = match WATCHED.lock().unwrap().as_mut() {
+    Some(watched) => watched.remove(path).unwrap_or_default(),
+    None => return search(),
+  };
+  PRINTED.with(|slot| *slot.borrow_mut() = Some(printed));
+  let result = search();
+  let printed = PRINTED.with(|slot| slot.borrow_mut().take());
+  if let Some(watched) = WATCHED.lock().unwrap().as_mut() {
+    watched.insert(path.to_path_buf(), printed.unwrap_or_default());
+  }
+  result
+}
+
+/// What the search that `printing_new` runs is to leave out, which it hands
+/// back with `put_printed`.
+pub(crate) fn take_printed() -> Option<Printed> {
+  PRINTED.with(|slot| slot.borrow_mut().take())
+}
+
+pub(crate) fn put_printed(printed: Printed) {
+  PRINTED.with(|slot| *slot.borrow_mut() = Some(printed));
+}
+
+/// Hands the matching lines that were not printed before on to `sink`,
+/// with the context around them.
+pub(crate) struct Fresh<'a, S> {
+  sink: &'a mut S,
+  // What is left of what was printed before, and everything found since
+  before: Printed,
+  found: Printed,
+  // Context that may come before the next new match, as `before_context`
+  // keeps it
+  pending: VecDeque<(usize, usize, String, &'static str)>,
+  before_context: usize,
+  after_context: usize,
+  // How much context there is left to pass on after a new match, and
+  // whether lines were left out since the last ones passed on
+  after_new: usize,
+  gap: bool,
+  passed: bool,
+}
+
+impl<'a, S: Sink> Fresh<'a, S> {
+  pub(crate) fn new(
+    sink: &'a mut S,
+    before: Printed,
+    before_context: usize,
+    after_context: usize,
+  ) -> Fresh<'a, S> {
+    Fresh {
+      sink,
+      before,
+      found: Printed::new(),
+      pending: VecDeque::new(),
+      before_context,
+      after_context,
+      after_new: 0,
+      gap: false,
+      passed: false,
+    }
+  }
+
+  /// Every matching line found, to leave out the next time.
+  pub(crate) fn finish(self) -> Printed {
+    self.found
+  }
+
+  // A separator first when lines were left out in between
+  fn pass_gap(&mut self) -> io::Result<()> {
+    if mem::take(&mut self.gap) && self.passed {
+      self.sink.separator()?;
+    }
+    self.passed = true;
+    Ok(())
+  }
+}
+
+impl<S: Sink> Sink for Fresh<'_, S> {
+  fn matched(&mut self, m: &Match) -> io::Result<bool> {
+    *self.found.entry(m.line.to_string()).or_insert(0) += 1;
+    if let Some(left) = self.before.get_mut(m.line).filter(|left| **left > 0) {
+      *left -= 1;
+      self.after_new = 0;
+      self.gap = true;
+      self.pending.clear();
+      return Ok(true);
+    }
+    self.pass_gap()?;
+    for (line_number, byte_offset, line, terminator) in mem::take(&mut self.pending) {
+      self.sink.context(&Match {
+        line_number,
+        byte_offset,
+        line: &line,
+        terminator,
+        spans: Vec::new(),
+        pattern: None,
+      })?;
+    }
+    self.after_new = self.after_context;
+    self.sink.matched(m)
+  }
+
+  fn context(&mut self, m: &Match) -> io::Result<()> {
+    if self.after_new > 0 {
+      self.after_new -= 1;
+      self.pass_gap()?;
+      return self.sink.context(m);
+    }
+    if self.pending.len() == self.before_context {
+      self.pending.pop_front();
+      self.gap = true;
+    }
+    if self.before_context > 0 {
+      let line = m.line.to_string();
+      self
+        .pending
+        .push_back((m.line_number, m.byte_offset, line, m.terminator));
+    }
+    Ok(())
+  }
+
+  fn separator(&mut self) -> io::Result<()> {
+    self.after_new = 0;
+    self.gap = true;
+    Ok(())
+  }
+
+  fn binary_matched(&mut self) -> io::Result<()> {
+    self.sink.binary_matched()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::fixture::Fixture;
+
+  #[test]
+  fn reports_new_and_changed_files() {
+    let root = Fixture::new("watch", &[("a.txt", "one")]);
+    let mut config = crate::tests::config("one");
+    config.filenames = vec![root.path().display().to_string()];
+    config.recursive = true;
+
+    let mut watcher = Watcher::new();
+    let first = watcher.changed(&config);
+    let unchanged = watcher.changed(&config);
+    root.write("a.txt", "one two");
+    root.write("b.txt", "");
+    let second = watcher.changed(&config);
+
+    assert_eq!(vec![root.join("a.txt")], first);
+    assert!(unchanged.is_empty());
+    assert_eq!(vec![root.join("a.txt"), root.join("b.txt")], second);
+  }
+}
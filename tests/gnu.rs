@@ -0,0 +1,172 @@
+//! The flags minigrep shares with GNU grep, run against what GNU grep 3.8
+//! prints for the same arguments, so that scripts written for one keep
+//! working with the other.
+//!
+//! Every case searches the same few files from the directory holding them.
+//! Error messages are left out, since each program names itself in them.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+const FILES: &[(&str, &str)] = &[
+  ("a.txt", "The frog sat\nfrogs leap\nno toads here\nFROG\n"),
+  ("b.txt", "toad\nfrog\n"),
+  ("d/e/c.txt", "frog in d\n"),
+];
+
+// The arguments, then the exit status and output of GNU grep
+#[rustfmt::skip]
+const CASES: &[(&[&str], i32, &str)] = &[
+  (&["frog", "a.txt"], 0, "The frog sat\nfrogs leap\n"),
+  (&["-i", "frog", "a.txt", "b.txt"], 0, "a.txt:The frog sat\na.txt:frogs leap\na.txt:FROG\nb.txt:frog\n"),
+  (&["-v", "frog", "a.txt"], 0, "no toads here\nFROG\n"),
+  (&["-n", "frog", "a.txt", "b.txt"], 0, "a.txt:1:The frog sat\na.txt:2:frogs leap\nb.txt:2:frog\n"),
+  (&["-c", "frog", "a.txt", "b.txt"], 0, "a.txt:2\nb.txt:1\n"),
+  (&["-l", "frog", "a.txt", "b.txt"], 0, "a.txt\nb.txt\n"),
+  (&["-L", "frog", "a.txt", "b.txt"], 0, ""),
+  (&["-L", "toad", "a.txt", "b.txt"], 0, ""),
+  (&["-w", "frog", "a.txt"], 0, "The frog sat\n"),
+  (&["-w", "fro", "a.txt"], 1, ""),
+  (&["-x", "frog", "a.txt", "b.txt"], 0, "b.txt:frog\n"),
+  (&["-xi", "frog", "a.txt", "b.txt"], 0, "a.txt:FROG\nb.txt:frog\n"),
+  (&["-x", "The frog sat", "a.txt"], 0, "The frog sat\n"),
+  (&["-x", "", "a.txt"], 1, ""),
+  (&["-H", "frog", "a.txt"], 0, "a.txt:The frog sat\na.txt:frogs leap\n"),
+  (&["-Hc", "frog", "a.txt"], 0, "a.txt:2\n"),
+  (&["-hc", "frog", "a.txt", "b.txt"], 0, "2\n1\n"),
+  (&["-hl", "frog", "a.txt", "b.txt"], 0, "a.txt\nb.txt\n"),
+  (&["-r", "frog", "d"], 0, "d/e/c.txt:frog in d\n"),
+  (&["-rn", "frog", "d", "a.txt"], 0, "d/e/c.txt:1:frog in d\na.txt:1:The frog sat\na.txt:2:frogs leap\n"),
+  (&["-rh", "frog", "d"], 0, "frog in d\n"),
+  (&["-rl", "frog", "d"], 0, "d/e/c.txt\n"),
+  (&["-r", "frog", "a.txt"], 0, "The frog sat\nfrogs leap\n"),
+  (&["-rc", "frog", "a.txt", "b.txt"], 0, "a.txt:2\nb.txt:1\n"),
+  (&["-q", "frog", "a.txt"], 0, ""),
+  (&["-q", "newt", "a.txt"], 1, ""),
+  (&["-q", "frog", "a.txt", "nope"], 0, ""),
+  (&["-s", "frog", "nope", "a.txt"], 2, "a.txt:The frog sat\na.txt:frogs leap\n"),
+  (&["-s", "frog", "nope"], 2, ""),
+  (&["-m1", "frog", "a.txt", "b.txt"], 0, "a.txt:The frog sat\nb.txt:frog\n"),
+  (&["-m1", "-c", "frog", "a.txt"], 0, "1\n"),
+  (&["-m1", "-v", "frog", "a.txt"], 0, "no toads here\n"),
+  (&["-m2", "-cv", "frog", "a.txt"], 0, "2\n"),
+  (&["-m0", "frog", "a.txt"], 1, ""),
+  (&["-m1", "-A1", "frog", "a.txt"], 0, "The frog sat\nfrogs leap\n"),
+  (&["-n", "-C1", "frog", "a.txt", "b.txt"], 0, "a.txt:1:The frog sat\na.txt:2:frogs leap\na.txt-3-no toads here\n--\nb.txt-1-toad\nb.txt:2:frog\n"),
+  (&["-A2", "-B1", "toad", "a.txt", "b.txt"], 0, "a.txt-frogs leap\na.txt:no toads here\na.txt-FROG\n--\nb.txt:toad\nb.txt-frog\n"),
+  (&["-B2", "-C1", "toad", "a.txt"], 0, "The frog sat\nfrogs leap\nno toads here\nFROG\n"),
+  (&["-C1", "-B2", "toad", "a.txt"], 0, "The frog sat\nfrogs leap\nno toads here\nFROG\n"),
+  (&["-o", "-A1", "frog", "b.txt", "a.txt"], 0, "b.txt:frog\n--\na.txt:frog\na.txt:frog\n"),
+  (&["-cv", "frog", "a.txt"], 0, "2\n"),
+  (&["-nv", "frog", "a.txt"], 0, "3:no toads here\n4:FROG\n"),
+  (&["-wv", "frog", "a.txt"], 0, "frogs leap\nno toads here\nFROG\n"),
+  (&["-ci", "frog", "a.txt"], 0, "3\n"),
+  (&["-e", "frog", "-e", "toad", "a.txt"], 0, "The frog sat\nfrogs leap\nno toads here\n"),
+  (&["-E", "-x", "frogs?( leap)?", "a.txt"], 0, "frogs leap\n"),
+  (&["-inw", "FROG", "a.txt", "b.txt"], 0, "a.txt:1:The frog sat\na.txt:4:FROG\nb.txt:2:frog\n"),
+  (&["newt", "a.txt"], 1, ""),
+];
+
+// The files of `FILES` in a directory of their own, removed once dropped
+struct Fixture(PathBuf);
+
+impl Fixture {
+  fn new(name: &str) -> Fixture {
+    let root = std::env::temp_dir().join(format!("minigrep-gnu-{}-{}", name, std::process::id()));
+    for (path, contents) in FILES {
+      let path = root.join(path);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, contents).unwrap();
+    }
+    Fixture(root)
+  }
+
+  fn minigrep(&self, args: &[&str], stdin: Stdio) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_minigrep"))
+      .args(args)
+      .current_dir(&self.0)
+      // No config file or variable of the one running the tests applies
+      .env("HOME", &self.0)
+      .env_remove("CASE_INSENSITIVE")
+      .stdin(stdin)
+      .output()
+      .unwrap()
+  }
+}
+
+impl Drop for Fixture {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.0);
+  }
+}
+
+#[test]
+fn prints_what_gnu_grep_prints() {
+  let fixture = Fixture::new("cases");
+  for (args, status, stdout) in CASES {
+    let output = fixture.minigrep(args, Stdio::null());
+    assert_eq!(
+      (Some(*status), *stdout),
+      (
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout).as_ref()
+      ),
+      "minigrep {}",
+      args.join(" ")
+    );
+  }
+}
+
+#[test]
+fn searches_the_working_directory_like_gnu_grep() {
+  let fixture = Fixture::new("recursive");
+  let output = fixture.minigrep(&["-r", "frog"], Stdio::null());
+  // GNU grep lists each directory in whatever order it is read
+  let mut lines: Vec<&str> = std::str::from_utf8(&output.stdout)
+    .unwrap()
+    .lines()
+    .collect();
+  lines.sort_unstable();
+  assert_eq!(
+    vec![
+      "a.txt:The frog sat",
+      "a.txt:frogs leap",
+      "b.txt:frog",
+      "d/e/c.txt:frog in d"
+    ],
+    lines
+  );
+}
+
+#[test]
+fn reads_standard_input_like_gnu_grep() {
+  let fixture = Fixture::new("stdin");
+  let a = || Stdio::from(fs::File::open(Path::new(&fixture.0).join("a.txt")).unwrap());
+  let output = fixture.minigrep(&["-c", "frog"], a());
+  assert_eq!("2\n", String::from_utf8_lossy(&output.stdout));
+  let output = fixture.minigrep(&["-r", "frog", "-"], a());
+  assert_eq!(
+    "The frog sat\nfrogs leap\n",
+    String::from_utf8_lossy(&output.stdout)
+  );
+  let output = fixture.minigrep(&["-Hn", "frog", "-"], a());
+  assert_eq!(
+    "(standard input):1:The frog sat\n(standard input):2:frogs leap\n",
+    String::from_utf8_lossy(&output.stdout)
+  );
+}
+
+#[test]
+fn reports_unreadable_files_like_gnu_grep() {
+  let fixture = Fixture::new("errors");
+  let output = fixture.minigrep(&["frog", "nope", "b.txt"], Stdio::null());
+  assert_eq!(Some(2), output.status.code());
+  assert_eq!("b.txt:frog\n", String::from_utf8_lossy(&output.stdout));
+  assert!(String::from_utf8_lossy(&output.stderr).ends_with(": nope: No such file or directory\n"));
+  let output = fixture.minigrep(&["-s", "frog", "nope", "b.txt"], Stdio::null());
+  assert_eq!(
+    (Some(2), &[][..]),
+    (output.status.code(), &output.stderr[..])
+  );
+}